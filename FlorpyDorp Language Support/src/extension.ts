@@ -106,7 +106,33 @@ function getLSPIC10Configurations(): any {
         },
         suppressHashDiagnostics: config.get('suppressHashDiagnostics'),
         enableControlFlowAnalysis: config.get('enableControlFlowAnalysis'),
-        suppressRegisterWarnings: config.get('suppressRegisterWarnings')
+        maxCallDepth: config.get('maxCallDepth'),
+        analysisLineBudget: config.get('analysisLineBudget'),
+        invalidInstructionSeverity: config.get('invalidInstructionSeverity'),
+        allowedInstructions: config.get('allowedInstructions'),
+        showLabelLineHints: config.get('showLabelLineHints'),
+        showCycleHints: config.get('showCycleHints'),
+        suppressRegisterWarnings: config.get('suppressRegisterWarnings'),
+        diagnosticsOnSaveOnly: config.get('diagnosticsOnSaveOnly'),
+        warnLoneDeviceWrite: config.get('warnings.lone_device_write'),
+        warnAccidentalDbAccess: config.get('warnings.accidental_db_access'),
+        warnLogicTypeRoundtrip: config.get('warnings.logictype_roundtrip'),
+        verboseCompletionDetail: config.get('completion.verbose_detail'),
+        enumsRequirePrefix: config.get('completion.enums_require_prefix'),
+        customDocsPath: config.get('customDocsPath'),
+        customDevices: config.get('customDevices'),
+        completionCategories: {
+            enums: config.get('completion.enums'),
+            defines: config.get('completion.defines'),
+            aliases: config.get('completion.aliases'),
+            labels: config.get('completion.labels'),
+            registers: config.get('completion.registers'),
+            devices: config.get('completion.devices')
+        },
+        aliasNaming: {
+            register: config.get('aliasNaming.register'),
+            device: config.get('aliasNaming.device')
+        }
     };
 }
 
@@ -286,7 +312,10 @@ export function activate(context: vscode.ExtensionContext) {
         ],
         // Use UTF-8 encoding for proper handling of special characters
         outputChannelName: 'IC10 Language Server',
-        initializationOptions: getLSPIC10Configurations(),
+        initializationOptions: {
+            ...getLSPIC10Configurations(),
+            diagnosticsEnabled: (vscode.workspace.getConfiguration().get('ic10.diagnostics.enabled') as boolean | undefined) ?? true
+        },
         // Add completion trigger characters - space bar triggers parameter completions
         synchronize: {
             configurationSection: 'ic10'
@@ -719,6 +748,17 @@ export function activate(context: vscode.ExtensionContext) {
         lc.sendRequest('workspace/executeCommand', options);
     }   ));
 
+    // Go to definition on a device hash opens a generated ic10-device: document
+    context.subscriptions.push(vscode.workspace.registerTextDocumentContentProvider('ic10-device', {
+        provideTextDocumentContent: async (uri: vscode.Uri) => {
+            const content = await lc.sendRequest('workspace/executeCommand', {
+                command: 'ic10.deviceDocument',
+                arguments: [uri.toString()]
+            }) as string | null;
+            return content ?? `Unknown device: ${uri.path}`;
+        }
+    }));
+
     // Register global benchmarking commands
     context.subscriptions.push(vscode.commands.registerCommand('ic10.toggleGlobalBenchmarking', () => {
         globalBenchmarkingEnabled = !globalBenchmarkingEnabled;
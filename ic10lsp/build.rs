@@ -158,12 +158,15 @@ fn main() {
     let mut enum_value_by_name = ::phf_codegen::Map::new();
     let mut enum_desc_by_name = ::phf_codegen::Map::new();
     let mut enum_deprecated = ::phf_codegen::Set::new();
+    let mut enum_family_deprecated = ::phf_codegen::Map::new();
     let mut logic_name_to_value = ::phf_codegen::Map::new();
 
     // Stage entries first to avoid borrow/lifetime issues
     let mut enum_value_by_name_entries: Vec<(String, String)> = Vec::new();
     let mut enum_desc_by_name_entries: Vec<(String, String)> = Vec::new();
     let mut enum_deprecated_entries: Vec<String> = Vec::new();
+    // family -> replacement family ("" when none is recorded)
+    let mut enum_family_deprecated_entries: Vec<(String, String)> = Vec::new();
     let mut logic_name_to_value_entries: Vec<(String, String)> = Vec::new();
 
     let mut seen_qnames: HashSet<String> = HashSet::new();
@@ -172,6 +175,24 @@ fn main() {
     // let _seen_logic_vals: HashSet<i32> = HashSet::new();
 
     let mut process_family = |family_name: &str, family_obj: &Value| {
+        let family_deprecated = family_obj
+            .get("deprecated")
+            .and_then(|x| x.as_bool())
+            .unwrap_or(false);
+        if family_deprecated
+            && !enum_family_deprecated_entries
+                .iter()
+                .any(|(name, _)| name == family_name)
+        {
+            let replacement = family_obj
+                .get("replacedBy")
+                .and_then(|x| x.as_str())
+                .unwrap_or("");
+            enum_family_deprecated_entries.push((
+                family_name.to_string(),
+                format!("\"{}\"", escape_str(replacement)),
+            ));
+        }
         if let Some(values) = family_obj.get("values").and_then(|x| x.as_object()) {
             for (member_name, member) in values.iter() {
                 let val = member.get("value").and_then(|x| x.as_i64()).unwrap_or(0) as i32;
@@ -230,6 +251,9 @@ fn main() {
     for (k, v) in logic_name_to_value_entries.iter() {
         logic_name_to_value.entry(k, v);
     }
+    for (k, v) in enum_family_deprecated_entries.iter() {
+        enum_family_deprecated.entry(k, v);
+    }
 
     let mut w =
         BufWriter::new(File::create(enums_out_path).expect("Failed to create enums_generated.rs"));
@@ -251,6 +275,12 @@ fn main() {
         enum_deprecated.build()
     )
     .unwrap();
+    writeln!(
+        &mut w,
+        "pub(crate) const ENUM_FAMILY_DEPRECATED: phf::Map<&'static str, &'static str> = {};",
+        enum_family_deprecated.build()
+    )
+    .unwrap();
     writeln!(
         &mut w,
         "pub(crate) const LOGIC_TYPE_NAME_TO_VALUE: phf::Map<&'static str, i32> = {};",
@@ -485,6 +515,67 @@ fn main() {
     ).unwrap();
     
     println!("cargo:rerun-if-changed=../data/game-sources/english.xml");
+
+    // =========================
+    // Generate per-device logic access from Stationpedia.json
+    // =========================
+    let stationpedia_json = fs::read_to_string("../data/game-sources/Stationpedia.json")
+        .expect("Failed to read Stationpedia.json");
+    let stationpedia: Value =
+        serde_json::from_str(&stationpedia_json).expect("Failed to parse Stationpedia.json");
+
+    // Build PHF map: prefab_name -> [(logic_type, readable, writable)]
+    let mut device_logic_builder = ::phf_codegen::Map::new();
+    let mut seen_prefabs = HashSet::new();
+
+    for page in stationpedia["pages"].as_array().into_iter().flatten() {
+        let Some(prefab_name) = page["PrefabName"].as_str() else {
+            continue;
+        };
+        let Some(logic) = page["LogicInsert"].as_array() else {
+            continue;
+        };
+        if logic.is_empty() || !seen_prefabs.insert(prefab_name.to_string()) {
+            continue;
+        }
+        let entries: Vec<String> = logic
+            .iter()
+            .filter_map(|entry| {
+                let name = strip_rich_text(entry["LogicName"].as_str()?);
+                let access = entry["LogicAccessTypes"].as_str().unwrap_or("");
+                Some(format!(
+                    "(\"{}\", {}, {})",
+                    escape_str(&name),
+                    access.contains("Read"),
+                    access.contains("Write")
+                ))
+            })
+            .collect();
+        device_logic_builder.entry(prefab_name.to_string(), &format!("&[{}]", entries.join(", ")));
+    }
+
+    writeln!(
+        &mut desc_writer,
+        "pub(crate) const DEVICE_LOGIC_ACCESS: phf::Map<&'static str, &'static [(&'static str, bool, bool)]> = {};",
+        device_logic_builder.build()
+    ).unwrap();
+
+    println!("cargo:rerun-if-changed=../data/game-sources/Stationpedia.json");
+}
+
+// Strip Stationpedia rich-text markup, e.g. `<link=LogicTypeOn><color=orange>On</color></link>`
+fn strip_rich_text(text: &str) -> String {
+    let mut out = String::new();
+    let mut in_tag = false;
+    for ch in text.chars() {
+        match ch {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            _ if !in_tag => out.push(ch),
+            _ => {}
+        }
+    }
+    out.trim().to_string()
 }
 
 // Parse instruction signatures from ProgrammableChip.cs GetCommandExample method
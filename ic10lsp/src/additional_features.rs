@@ -1,12 +1,27 @@
 use std::collections::HashMap;
 use tower_lsp::lsp_types::{
-    CodeActionOrCommand, Diagnostic, DiagnosticSeverity, Position as LspPosition, Range as LspRange,
+    CodeActionOrCommand, Diagnostic, DiagnosticSeverity, DiagnosticTag, Position as LspPosition,
+    Range as LspRange,
 };
 use tree_sitter::{Query, QueryCursor, Tree};
 
-use crate::instructions::LOGIC_TYPES;
+use crate::instructions::{INSTRUCTIONS, LOGIC_TYPES};
 use crate::Range;
 
+/// Matches every instruction, including opcodes the grammar lexes as `invalid_instruction`
+/// (e.g. `rand`, whose leading `ra` collides with the return-address register token).
+const INSTRUCTION_QUERY: &str =
+    "(instruction [(operation) (invalid_instruction)] @op) @instruction";
+
+/// Returns the opcode text for an `@op` capture, skipping genuinely unknown instructions.
+fn operation_text<'a>(node: tree_sitter::Node, content: &'a str) -> Option<&'a str> {
+    let text = node.utf8_text(content.as_bytes()).ok()?;
+    if node.kind() == "invalid_instruction" && !INSTRUCTIONS.contains_key(text) {
+        return None;
+    }
+    Some(text)
+}
+
 #[derive(Debug, Clone)]
 pub struct OperationRecord {
     pub line_number: u32,
@@ -84,6 +99,92 @@ pub struct RegisterAnalyzer {
     register_usage: HashMap<String, RegisterUsage>,
     alias_to_register: HashMap<String, String>, // alias -> register mapping for quick lookup
     ignored_registers: std::collections::HashSet<String>, // registers to suppress diagnostics for
+    dead_stores: Vec<DeadStore>, // device loads/comparisons overwritten or dropped before any read
+    stack_imbalances: Vec<(Range, String)>, // provable stack misuse: instruction range, message
+    kind_history: HashMap<String, Vec<(u32, ValueKind)>>, // register -> (row, kind) in source order
+}
+
+/// Instructions whose dead results get their own, more specific diagnostic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DeadStoreKind {
+    DeviceRead, // `l`, `lb`, ...
+    Comparison, // `slt`, `seq`, ...
+}
+
+/// A device load or comparison whose destination is never read afterwards
+#[derive(Debug, Clone)]
+struct DeadStore {
+    kind: DeadStoreKind,
+    register: String,
+    target_range: Range,      // destination operand; matches the RegisterUsage assignment range
+    instruction_range: Range, // whole instruction, faded as unnecessary
+}
+
+/// Drop the pending stores at end of file. A store there is only dead if nothing ever reads
+/// its register; otherwise a wrap-around or earlier loop body may consume the value.
+fn drain_unread_stores(
+    register_usage: &HashMap<String, RegisterUsage>,
+    pending: &mut HashMap<String, (DeadStoreKind, Range, Range)>,
+    dead: &mut Vec<DeadStore>,
+) {
+    for (register, (kind, target_range, instruction_range)) in pending.drain() {
+        if register_usage
+            .get(&register)
+            .is_some_and(|usage| usage.reads.is_empty())
+        {
+            dead.push(DeadStore {
+                kind,
+                register,
+                target_range,
+                instruction_range,
+            });
+        }
+    }
+}
+
+/// Drop the pending stores where tracking resets (label, branch, `rr`). Device loads are
+/// forgotten there; a comparison is still dead if nothing ever reads its register.
+fn reset_pending_stores(
+    register_usage: &HashMap<String, RegisterUsage>,
+    pending: &mut HashMap<String, (DeadStoreKind, Range, Range)>,
+    dead: &mut Vec<DeadStore>,
+) {
+    pending.retain(|_, (kind, _, _)| *kind == DeadStoreKind::Comparison);
+    drain_unread_stores(register_usage, pending, dead);
+}
+
+/// Set-on-comparison instructions (`slt`, `seqz`, `sap`, ...), which write 0 or 1
+fn is_set_comparison(op: &str) -> bool {
+    matches!(
+        op,
+        "slt" | "sgt" | "sle" | "sge" | "seq" | "sne" | "sap" | "sna" | "sltz" | "sgtz" | "slez"
+            | "sgez" | "seqz" | "snez" | "sapz" | "snaz" | "snan" | "snanz" | "sdse" | "sdns"
+    )
+}
+
+/// Logic types whose readings are continuous quantities rather than whole numbers
+fn is_float_logic_type(name: &str) -> bool {
+    const PREFIXES: [&str; 11] = [
+        "Temperature", "Pressure", "Ratio", "Power", "Charge", "TotalMoles", "Horizontal",
+        "Vertical", "SolarAngle", "Velocity", "Position",
+    ];
+    PREFIXES.iter().any(|p| name.starts_with(p))
+}
+
+/// Parse a `# log <register>` debug annotation, returning the byte offset of the register
+/// name within `comment` and the name itself. Other comments are not directives.
+pub fn parse_log_directive(comment: &str) -> Option<(usize, &str)> {
+    let body = comment.strip_prefix('#')?.trim_start();
+    let rest = body.strip_prefix("log")?;
+    if !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let rest = rest.trim_start();
+    let name = rest
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .next()
+        .filter(|name| !name.is_empty())?;
+    Some((comment.len() - rest.len(), name))
 }
 
 // Helper function to recursively find identifier nodes within operands
@@ -107,6 +208,9 @@ impl RegisterAnalyzer {
             register_usage: HashMap::new(),
             alias_to_register: HashMap::new(),
             ignored_registers: std::collections::HashSet::new(),
+            dead_stores: Vec::new(),
+            stack_imbalances: Vec::new(),
+            kind_history: HashMap::new(),
         }
     }
 
@@ -117,7 +221,7 @@ impl RegisterAnalyzer {
     }
 
     /// Check if a string is a valid register name (r0-r15, ra, sp, rr0-rr15)
-    fn is_register_name(name: &str) -> bool {
+    pub(crate) fn is_register_name(name: &str) -> bool {
         if name == "ra" || name == "sp" {
             return true;
         }
@@ -229,6 +333,9 @@ impl RegisterAnalyzer {
         self.register_usage.clear();
         self.alias_to_register.clear();
         self.ignored_registers.clear();
+        self.dead_stores.clear();
+        self.stack_imbalances.clear();
+        self.kind_history.clear();
         
         // Parse ignore directives from comments
         self.parse_ignore_directives(content);
@@ -264,6 +371,172 @@ impl RegisterAnalyzer {
         self.fallback_line_scan(content, aliases); // resilience if tree-sitter patterns miss
         self.bootstrap_registers();
         self.mark_rr_as_used();
+        self.detect_dead_stores(tree, content, aliases);
+        self.detect_stack_imbalances(tree, content);
+    }
+
+    /// A subroutine (a label some `jal`/`b*al` calls) whose straight-line body runs to `j ra`
+    /// must pop everything it pushed first. Built on [`unmatched_pushes`], so nested calls are
+    /// assumed balanced and branches or writes to `sp` stop the check. Nothing is said about
+    /// the main loop: `sp` keeps its value when execution wraps back to line 0.
+    fn detect_stack_imbalances(&mut self, tree: &Tree, content: &str) {
+        let text = |n: tree_sitter::Node| n.utf8_text(content.as_bytes()).unwrap_or("");
+        let mut called = std::collections::HashSet::new();
+        let mut returns = HashMap::new(); // row -> range of a `j ra`
+        let query = Query::new(tree_sitter_ic10::language(), "(instruction) @i").unwrap();
+        let mut query_cursor = QueryCursor::new();
+        for (instruction_match, _) in query_cursor.captures(&query, tree.root_node(), content.as_bytes()) {
+            let node = instruction_match.captures[0].node;
+            let Some(op) = node
+                .child_by_field_name("operation")
+                .and_then(|op_node| operation_text(op_node, content))
+            else {
+                continue;
+            };
+            let op = op.to_ascii_lowercase();
+            let mut operand_cursor = node.walk();
+            let target = node
+                .children_by_field_name("operand", &mut operand_cursor)
+                .last()
+                .map(text)
+                .unwrap_or("");
+            if op == "jal" || (op.starts_with('b') && op.ends_with("al")) {
+                called.insert(target.to_string());
+            } else if op == "j" && target == "ra" {
+                returns.insert(node.start_position().row as u32, Range::from(node.range()));
+            }
+        }
+
+        // Open pushes per returning block: (subroutine, count)
+        let mut left_pushed: std::collections::BTreeMap<u32, (String, usize)> = Default::default();
+        for push in unmatched_pushes(tree, content) {
+            let Some(name) = push.block_label.filter(|name| called.contains(name)) else {
+                continue;
+            };
+            if returns.contains_key(&push.block_end) {
+                left_pushed.entry(push.block_end).or_insert((name, 0)).1 += 1;
+            }
+        }
+        self.stack_imbalances = left_pushed
+            .into_iter()
+            .map(|(row, (name, depth))| {
+                (
+                    returns[&row],
+                    format!(
+                        "Subroutine '{}' returns with {} value{} still pushed. Pop {} before 'j ra'.",
+                        name,
+                        depth,
+                        if depth == 1 { "" } else { "s" },
+                        if depth == 1 { "it" } else { "them" }
+                    ),
+                )
+            })
+            .collect();
+    }
+
+    /// Find device loads and comparisons whose destination is reassigned before being read, or
+    /// never read at all. Reassignment is only tracked through straight-line code: labels,
+    /// branches and `rr` indirection reset it.
+    fn detect_dead_stores(
+        &mut self,
+        tree: &Tree,
+        content: &str,
+        aliases: &HashMap<String, crate::DefinitionData<crate::AliasValue>>,
+    ) {
+        // register -> (kind, destination operand range, instruction range) of the pending store
+        let mut pending: HashMap<String, (DeadStoreKind, Range, Range)> = HashMap::new();
+        let mut dead = Vec::new();
+
+        let line_query = Query::new(tree_sitter_ic10::language(), "(line) @line").unwrap();
+        let mut query_cursor = QueryCursor::new();
+        for (line_match, _) in query_cursor.captures(&line_query, tree.root_node(), content.as_bytes()) {
+            let line = line_match.captures[0].node;
+            let mut child_cursor = line.walk();
+            for node in line.children(&mut child_cursor) {
+                if node.kind() == "label" {
+                    reset_pending_stores(&self.register_usage, &mut pending, &mut dead);
+                    continue;
+                }
+                if node.kind() != "instruction" {
+                    continue;
+                }
+                let Some(op) = node
+                    .child_by_field_name("operation")
+                    .and_then(|op_node| operation_text(op_node, content))
+                else {
+                    continue;
+                };
+                let op = op.to_ascii_lowercase();
+                let mut operand_cursor = node.walk();
+                let operands: Vec<_> = node
+                    .children_by_field_name("operand", &mut operand_cursor)
+                    .collect();
+
+                let indirect = operands.iter().any(|o| {
+                    o.child(0).is_some_and(|c| {
+                        c.kind() == "register"
+                            && c.utf8_text(content.as_bytes()).is_ok_and(|t| t.starts_with("rr"))
+                    })
+                });
+                if indirect {
+                    reset_pending_stores(&self.register_usage, &mut pending, &mut dead);
+                    continue;
+                }
+
+                let assigns = self.is_assignment_operation(&op);
+                for operand in operands.iter().skip(usize::from(assigns)) {
+                    let reg = self.get_register_from_operand(operand, content, aliases);
+                    pending.remove(&reg);
+                }
+
+                if assigns {
+                    if let Some(target) = operands.first() {
+                        let reg = self.get_register_from_operand(target, content, aliases);
+                        if !reg.is_empty() {
+                            if let Some((kind, target_range, instruction_range)) =
+                                pending.remove(&reg)
+                            {
+                                dead.push(DeadStore {
+                                    kind,
+                                    register: reg.clone(),
+                                    target_range,
+                                    instruction_range,
+                                });
+                            }
+                            let kind = if matches!(
+                                op.as_str(),
+                                "l" | "lr" | "ls" | "ld" | "lb" | "lbn" | "lbs" | "lbns"
+                            ) {
+                                Some(DeadStoreKind::DeviceRead)
+                            } else if is_set_comparison(&op) {
+                                Some(DeadStoreKind::Comparison)
+                            } else {
+                                None
+                            };
+                            if let (Some(kind), Some(target_node)) = (kind, target.child(0)) {
+                                pending.insert(
+                                    reg,
+                                    (
+                                        kind,
+                                        Range::from(target_node.range()),
+                                        Range::from(node.range()),
+                                    ),
+                                );
+                            }
+                        }
+                    }
+                }
+
+                if op.starts_with('b') || op.starts_with('j') {
+                    reset_pending_stores(&self.register_usage, &mut pending, &mut dead);
+                }
+            }
+        }
+
+        drain_unread_stores(&self.register_usage, &mut pending, &mut dead);
+
+        dead.retain(|d| d.register != "sp" && d.register != "ra");
+        self.dead_stores = dead;
     }
 
     fn detect_register_assignments(
@@ -276,7 +549,7 @@ impl RegisterAnalyzer {
         // Query for all instructions and manually check the first operand
         let instruction_query = Query::new(
             tree_sitter_ic10::language(),
-            "(instruction (operation) @op) @instruction",
+            INSTRUCTION_QUERY,
         )
         .unwrap();
 
@@ -291,7 +564,7 @@ impl RegisterAnalyzer {
 
             for cap in query_match.captures {
                 if cap.index == op_idx {
-                    operation = Some(cap.node.utf8_text(content.as_bytes()).unwrap());
+                    operation = operation_text(cap.node, content);
                 } else if cap.index == instruction_idx {
                     instruction_node = Some(cap.node);
                 }
@@ -375,7 +648,7 @@ impl RegisterAnalyzer {
         // Query for all instructions and manually check operands
         let instruction_query = Query::new(
             tree_sitter_ic10::language(),
-            "(instruction (operation) @op) @instruction",
+            INSTRUCTION_QUERY,
         )
         .unwrap();
 
@@ -390,7 +663,7 @@ impl RegisterAnalyzer {
 
             for cap in query_match.captures {
                 if cap.index == op_idx {
-                    operation = Some(cap.node.utf8_text(content.as_bytes()).unwrap());
+                    operation = operation_text(cap.node, content);
                 } else if cap.index == instruction_idx {
                     instruction_node = Some(cap.node);
                 }
@@ -469,7 +742,7 @@ impl RegisterAnalyzer {
         // (e.g. JAL, Jal) is still recognised as assigning to ra.
         let instruction_query = Query::new(
             tree_sitter_ic10::language(),
-            "(instruction (operation) @op) @instruction",
+            INSTRUCTION_QUERY,
         )
         .unwrap();
 
@@ -484,7 +757,7 @@ impl RegisterAnalyzer {
 
             for cap in query_match.captures {
                 if cap.index == op_idx {
-                    operation = Some(cap.node.utf8_text(content.as_bytes()).unwrap());
+                    operation = operation_text(cap.node, content);
                 } else if cap.index == instruction_idx {
                     instruction_node = Some(cap.node);
                 }
@@ -561,6 +834,14 @@ impl RegisterAnalyzer {
                         continue;
                     }
                     for assignment_range in &usage.assignments {
+                        // Reported below as the more specific dead-store diagnostic
+                        if self
+                            .dead_stores
+                            .iter()
+                            .any(|d| d.target_range == *assignment_range)
+                        {
+                            continue;
+                        }
                         let register_display = usage
                             .alias_name
                             .as_ref()
@@ -613,6 +894,599 @@ impl RegisterAnalyzer {
             }
         }
 
+        // `# ignore sp` silences the stack checks
+        if !self.ignored_registers.contains("sp") {
+            for (range, message) in &self.stack_imbalances {
+                diagnostics.push(Diagnostic {
+                    range: (*range).into(),
+                    severity: Some(DiagnosticSeverity::WARNING),
+                    code: Some(tower_lsp::lsp_types::NumberOrString::String("stack-imbalance".to_string())),
+                    message: message.clone(),
+                    data: Some(serde_json::json!("sp")),
+                    ..Default::default()
+                });
+            }
+        }
+
+        for dead in &self.dead_stores {
+            if self.ignored_registers.contains(&dead.register) {
+                continue;
+            }
+            let register_display = self
+                .register_usage
+                .get(&dead.register)
+                .and_then(|usage| usage.alias_name.as_ref())
+                .map(|alias| format!("'{}' ({})", alias, dead.register))
+                .unwrap_or_else(|| dead.register.clone());
+            let (code, message) = match dead.kind {
+                DeadStoreKind::DeviceRead => (
+                    "dead-device-read",
+                    format!(
+                        "Device read into {} is never used. Device loads cost a full instruction each tick; remove it or use the value.",
+                        register_display
+                    ),
+                ),
+                DeadStoreKind::Comparison => (
+                    "unused-comparison-result",
+                    format!(
+                        "Comparison result in {} is never used. Branch on it or use it, or remove the comparison.",
+                        register_display
+                    ),
+                ),
+            };
+            diagnostics.push(Diagnostic {
+                range: dead.instruction_range.into(),
+                severity: Some(DiagnosticSeverity::HINT),
+                code: Some(tower_lsp::lsp_types::NumberOrString::String(code.to_string())),
+                message,
+                tags: Some(vec![DiagnosticTag::UNNECESSARY]),
+                data: Some(serde_json::json!(dead.register)),
+                ..Default::default()
+            });
+        }
+
+        diagnostics
+    }
+
+    /// Control-flow analysis: warn when a value set before `jal sub` is read after the call,
+    /// but `sub` overwrites that register without reading it first (so it is not an argument).
+    pub fn detect_call_clobbers(
+        &self,
+        tree: &Tree,
+        content: &str,
+        aliases: &HashMap<String, crate::DefinitionData<crate::AliasValue>>,
+    ) -> Vec<Diagnostic> {
+        struct Step {
+            row: u32,
+            op: String,
+            reads: Vec<(String, Range)>,
+            write: Option<String>,
+            target: String, // last operand text, for jumps
+            after_label: bool,
+        }
+
+        let mut steps: Vec<Step> = Vec::new();
+        let mut labels: HashMap<String, usize> = HashMap::new();
+        let mut after_label = false;
+
+        let line_query = Query::new(tree_sitter_ic10::language(), "(line) @line").unwrap();
+        let mut query_cursor = QueryCursor::new();
+        for (line_match, _) in query_cursor.captures(&line_query, tree.root_node(), content.as_bytes()) {
+            let line = line_match.captures[0].node;
+            let mut child_cursor = line.walk();
+            for node in line.children(&mut child_cursor) {
+                if node.kind() == "label" {
+                    if let Some(name) = node.child(0).and_then(|n| n.utf8_text(content.as_bytes()).ok()) {
+                        labels.insert(name.to_string(), steps.len());
+                    }
+                    after_label = true;
+                    continue;
+                }
+                if node.kind() != "instruction" {
+                    continue;
+                }
+                let Some(op) = node
+                    .child_by_field_name("operation")
+                    .and_then(|op_node| operation_text(op_node, content))
+                else {
+                    continue;
+                };
+                let op = op.to_ascii_lowercase();
+                let mut operand_cursor = node.walk();
+                let operands: Vec<_> = node
+                    .children_by_field_name("operand", &mut operand_cursor)
+                    .collect();
+                let assigns = self.is_assignment_operation(&op);
+
+                let mut reads = Vec::new();
+                for operand in operands.iter().skip(usize::from(assigns)) {
+                    let reg = self.get_register_from_operand(operand, content, aliases);
+                    if !reg.is_empty() {
+                        reads.push((reg, Range::from(operand.range())));
+                    }
+                }
+                let write = if assigns {
+                    operands
+                        .first()
+                        .map(|o| self.get_register_from_operand(o, content, aliases))
+                        .filter(|r| !r.is_empty())
+                } else {
+                    None
+                };
+                let target = operands
+                    .last()
+                    .and_then(|o| o.utf8_text(content.as_bytes()).ok())
+                    .unwrap_or("")
+                    .to_string();
+
+                steps.push(Step {
+                    row: node.start_position().row as u32,
+                    op,
+                    reads,
+                    write,
+                    target,
+                    after_label,
+                });
+                after_label = false;
+            }
+        }
+
+        let tracked = |reg: &str| !(reg == "ra" || reg == "sp" || reg.starts_with("rr"));
+
+        // Registers a subroutine writes before reading (nested calls included)
+        fn clobbers(
+            steps: &[Step],
+            labels: &HashMap<String, usize>,
+            label: &str,
+            visiting: &mut std::collections::HashSet<String>,
+        ) -> std::collections::HashSet<String> {
+            let mut written = std::collections::HashSet::new();
+            let Some(&start) = labels.get(label) else {
+                return written;
+            };
+            if !visiting.insert(label.to_string()) {
+                return written;
+            }
+            let mut seen = std::collections::HashSet::new();
+            for step in &steps[start..] {
+                for (reg, _) in &step.reads {
+                    seen.insert(reg.clone());
+                }
+                if let Some(reg) = &step.write {
+                    if seen.insert(reg.clone()) {
+                        written.insert(reg.clone());
+                    }
+                }
+                if step.op == "jal" {
+                    for reg in clobbers(steps, labels, &step.target, visiting) {
+                        if seen.insert(reg.clone()) {
+                            written.insert(reg);
+                        }
+                    }
+                }
+                if step.op == "j" || step.op == "jr" {
+                    break;
+                }
+            }
+            visiting.remove(label);
+            written
+        }
+
+        let mut diagnostics = Vec::new();
+        for (i, call) in steps.iter().enumerate() {
+            if call.op != "jal" {
+                continue;
+            }
+            let callee_writes =
+                clobbers(&steps, &labels, &call.target, &mut std::collections::HashSet::new());
+            if callee_writes.is_empty() {
+                continue;
+            }
+
+            // Values the caller set in the straight-line code leading up to the call
+            let mut live = std::collections::HashSet::new();
+            if !call.after_label {
+                for k in (0..i).rev() {
+                    let step = &steps[k];
+                    if step.op == "j" || step.op == "jr" {
+                        break;
+                    }
+                    if let Some(reg) = &step.write {
+                        if tracked(reg) && callee_writes.contains(reg) {
+                            live.insert(reg.clone());
+                        }
+                    }
+                    if step.after_label {
+                        break;
+                    }
+                }
+            }
+
+            // Report the first read of each such value after the call returns
+            for step in &steps[i + 1..] {
+                if live.is_empty() || step.after_label {
+                    break;
+                }
+                for (reg, range) in &step.reads {
+                    if live.remove(reg) && !self.ignored_registers.contains(reg) {
+                        diagnostics.push(Diagnostic {
+                            range: (*range).into(),
+                            severity: Some(DiagnosticSeverity::WARNING),
+                            code: Some(tower_lsp::lsp_types::NumberOrString::String("clobbered-across-call".to_string())),
+                            message: format!(
+                                "Register {} is overwritten by subroutine '{}' (called on line {}); the value set before the call is lost.",
+                                reg,
+                                call.target,
+                                call.row + 1
+                            ),
+                            data: Some(serde_json::json!(reg)),
+                            ..Default::default()
+                        });
+                    }
+                }
+                if let Some(reg) = &step.write {
+                    live.remove(reg);
+                }
+                if step.op.starts_with('b') || step.op.starts_with('j') {
+                    break;
+                }
+            }
+        }
+        diagnostics
+    }
+
+    /// Hint when `beq`/`bne`/`seq`/`sne` compares a value that is likely fractional: a float
+    /// literal, or a register last loaded from a float-valued logic type (Temperature, ...).
+    /// Tracking is straight-line only and resets at labels.
+    pub fn detect_float_equality(
+        &self,
+        tree: &Tree,
+        content: &str,
+        aliases: &HashMap<String, crate::DefinitionData<crate::AliasValue>>,
+    ) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        let mut float_regs: std::collections::HashSet<String> = std::collections::HashSet::new();
+
+        let line_query = Query::new(tree_sitter_ic10::language(), "(line) @line").unwrap();
+        let mut query_cursor = QueryCursor::new();
+        for (line_match, _) in query_cursor.captures(&line_query, tree.root_node(), content.as_bytes()) {
+            let line = line_match.captures[0].node;
+            let mut child_cursor = line.walk();
+            for node in line.children(&mut child_cursor) {
+                if node.kind() == "label" {
+                    float_regs.clear();
+                    continue;
+                }
+                if node.kind() != "instruction" {
+                    continue;
+                }
+                let Some(op) = node
+                    .child_by_field_name("operation")
+                    .and_then(|op_node| operation_text(op_node, content))
+                else {
+                    continue;
+                };
+                let op = op.to_ascii_lowercase();
+                let mut operand_cursor = node.walk();
+                let operands: Vec<_> = node
+                    .children_by_field_name("operand", &mut operand_cursor)
+                    .collect();
+                let text = |o: &tree_sitter::Node| o.utf8_text(content.as_bytes()).unwrap_or("");
+                let is_float = |o: &tree_sitter::Node| {
+                    text(o).parse::<f64>().is_ok_and(|v| v.fract() != 0.0)
+                        || float_regs.contains(&self.get_register_from_operand(o, content, aliases))
+                };
+
+                let compared = match op.as_str() {
+                    "beq" | "bne" => operands.get(0..2),
+                    "seq" | "sne" => operands.get(1..3),
+                    _ => None,
+                };
+                if let Some(pair) = compared {
+                    if let Some(culprit) = pair.iter().find(|o| is_float(o)) {
+                        diagnostics.push(Diagnostic {
+                            range: Range::from(node.range()).into(),
+                            severity: Some(DiagnosticSeverity::HINT),
+                            code: Some(tower_lsp::lsp_types::NumberOrString::String("float-equality".to_string())),
+                            message: format!(
+                                "'{}' may hold a fractional value and never compare exactly equal; consider an approximate comparison ({}).",
+                                text(culprit),
+                                if op.starts_with('b') { "bap/bna" } else { "sap/sna" }
+                            ),
+                            ..Default::default()
+                        });
+                    }
+                }
+
+                if !self.is_assignment_operation(&op) {
+                    continue;
+                }
+                let Some(dest) = operands
+                    .first()
+                    .map(|o| self.get_register_from_operand(o, content, aliases))
+                    .filter(|r| !r.is_empty())
+                else {
+                    continue;
+                };
+                let produces_float = match op.as_str() {
+                    "l" | "ld" | "lb" | "lbn" => operands
+                        .iter()
+                        .skip(1)
+                        .any(|o| is_float_logic_type(text(o))),
+                    "move" => operands.get(1).is_some_and(is_float),
+                    _ => false,
+                };
+                if produces_float {
+                    float_regs.insert(dest);
+                } else {
+                    float_regs.remove(&dest);
+                }
+            }
+        }
+        diagnostics
+    }
+
+    /// Hint on `move rA rB` directly followed by an arithmetic instruction that writes `rA` and
+    /// reads it exactly once, e.g. `move r0 r1` + `add r0 r0 5` -> `add r0 r1 5`. A label in
+    /// between (a jump could land on the second instruction) breaks the pair. With numeric
+    /// branch targets in the script the fix blanks the `move` instead of deleting its line.
+    pub fn detect_fusible_moves(
+        &self,
+        tree: &Tree,
+        content: &str,
+        aliases: &HashMap<String, crate::DefinitionData<crate::AliasValue>>,
+    ) -> Vec<Diagnostic> {
+        const FUSIBLE: &[&str] = &[
+            "add", "sub", "mul", "div", "mod", "pow", "max", "min", "and", "or", "xor", "nor",
+            "not", "sla", "sll", "sra", "srl", "abs", "ceil", "floor", "round", "trunc", "sqrt",
+            "exp", "log", "sin", "cos", "tan", "asin", "acos", "atan", "atan2",
+        ];
+        let mut diagnostics = Vec::new();
+        let keep_line_count = !crate::lsp_diagnostics::absolute_jumps(tree, content).is_empty();
+        let register_of = |operand: &tree_sitter::Node| {
+            Some(self.get_register_from_operand(operand, content, aliases)).filter(|r| !r.is_empty())
+        };
+
+        // Previous `move`: (instruction node, destination register, source operand text)
+        let mut pending: Option<(tree_sitter::Node, String, String)> = None;
+
+        let line_query = Query::new(tree_sitter_ic10::language(), "(line) @line").unwrap();
+        let mut query_cursor = QueryCursor::new();
+        for (line_match, _) in query_cursor.captures(&line_query, tree.root_node(), content.as_bytes()) {
+            let line = line_match.captures[0].node;
+            let mut child_cursor = line.walk();
+            let children: Vec<_> = line.children(&mut child_cursor).collect();
+            if children.iter().any(|n| n.kind() == "label") {
+                pending = None;
+                continue;
+            }
+            let Some(node) = children.into_iter().find(|n| n.kind() == "instruction") else {
+                continue;
+            };
+            let operation = node
+                .child_by_field_name("operation")
+                .and_then(|op_node| operation_text(op_node, content))
+                .unwrap_or("");
+            let mut operand_cursor = node.walk();
+            let operands: Vec<_> = node.children_by_field_name("operand", &mut operand_cursor).collect();
+
+            if let Some((move_node, moved, source)) = pending.take() {
+                let reads_moved: Vec<_> = operands
+                    .iter()
+                    .skip(1)
+                    .filter(|o| register_of(o).as_deref() == Some(moved.as_str()))
+                    .collect();
+                if FUSIBLE.contains(&operation)
+                    && operands.first().and_then(register_of).as_deref() == Some(moved.as_str())
+                    && reads_moved.len() == 1
+                {
+                    let mut fused = operation.to_string();
+                    for operand in &operands {
+                        fused.push(' ');
+                        if operand.id() == reads_moved[0].id() {
+                            fused.push_str(&source);
+                        } else {
+                            fused.push_str(operand.utf8_text(content.as_bytes()).unwrap_or(""));
+                        }
+                    }
+                    let remove = if keep_line_count {
+                        LspRange::from(Range::from(move_node.range()))
+                    } else {
+                        let move_row = move_node.start_position().row as u32;
+                        LspRange::new(LspPosition::new(move_row, 0), LspPosition::new(move_row + 1, 0))
+                    };
+                    diagnostics.push(Diagnostic {
+                        range: Range::from(move_node.range()).into(),
+                        severity: Some(DiagnosticSeverity::HINT),
+                        code: Some(tower_lsp::lsp_types::NumberOrString::String(
+                            crate::LINT_FUSIBLE_MOVE.to_string(),
+                        )),
+                        message: format!("This move can be folded into the next instruction: {}", fused),
+                        data: Some(serde_json::json!({
+                            "remove": remove,
+                            "replace": LspRange::from(Range::from(node.range())),
+                            "text": fused,
+                        })),
+                        ..Default::default()
+                    });
+                }
+            }
+
+            if operation == "move" && operands.len() == 2 {
+                if let (Some(dest), Some(_)) = (register_of(&operands[0]), register_of(&operands[1])) {
+                    let source = operands[1].utf8_text(content.as_bytes()).unwrap_or("").to_string();
+                    pending = Some((node, dest, source));
+                }
+            }
+        }
+        diagnostics
+    }
+
+    /// Hint when a value loaded with `l` from one logic type is stored back with `s` to the
+    /// same device under a different logic type, with only `move`s in between. Straight-line
+    /// tracking only; labels reset it.
+    pub fn detect_logictype_roundtrips(
+        &self,
+        tree: &Tree,
+        content: &str,
+        aliases: &HashMap<String, crate::DefinitionData<crate::AliasValue>>,
+    ) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        // register -> (device, logic type) it was loaded from
+        let mut loaded: HashMap<String, (String, String)> = HashMap::new();
+
+        let device_of = |operand: &tree_sitter::Node| {
+            let text = operand.utf8_text(content.as_bytes()).unwrap_or("");
+            match aliases.get(text).map(|alias| &alias.value) {
+                Some(crate::AliasValue::Device(device)) => device.clone(),
+                _ => text.to_string(),
+            }
+        };
+
+        let line_query = Query::new(tree_sitter_ic10::language(), "(line) @line").unwrap();
+        let mut query_cursor = QueryCursor::new();
+        for (line_match, _) in query_cursor.captures(&line_query, tree.root_node(), content.as_bytes()) {
+            let line = line_match.captures[0].node;
+            let mut child_cursor = line.walk();
+            for node in line.children(&mut child_cursor) {
+                if node.kind() == "label" {
+                    loaded.clear();
+                    continue;
+                }
+                if node.kind() != "instruction" {
+                    continue;
+                }
+                let Some(op) = node
+                    .child_by_field_name("operation")
+                    .and_then(|op_node| operation_text(op_node, content))
+                else {
+                    continue;
+                };
+                let op = op.to_ascii_lowercase();
+                let mut operand_cursor = node.walk();
+                let operands: Vec<_> = node
+                    .children_by_field_name("operand", &mut operand_cursor)
+                    .collect();
+                let register = |index: usize| {
+                    operands
+                        .get(index)
+                        .map(|o| self.get_register_from_operand(o, content, aliases))
+                        .filter(|r| !r.is_empty())
+                };
+
+                if op == "s" && operands.len() == 3 {
+                    let (device, logic_type) = (device_of(&operands[0]), device_of(&operands[1]));
+                    if let Some((source_device, source_type)) =
+                        register(2).and_then(|reg| loaded.get(&reg))
+                    {
+                        if *source_device == device && *source_type != logic_type {
+                            diagnostics.push(Diagnostic {
+                                range: Range::from(node.range()).into(),
+                                severity: Some(DiagnosticSeverity::HINT),
+                                code: Some(tower_lsp::lsp_types::NumberOrString::String(
+                                    "mismatched-logictype-roundtrip".to_string(),
+                                )),
+                                message: format!(
+                                    "Value read from {} {} is written back as {} unchanged.",
+                                    device, source_type, logic_type
+                                ),
+                                data: register(2).map(|reg| serde_json::json!(reg)),
+                                ..Default::default()
+                            });
+                        }
+                    }
+                    continue;
+                }
+
+                if !self.is_assignment_operation(&op) {
+                    continue;
+                }
+                let Some(dest) = register(0) else {
+                    continue;
+                };
+                let origin = match op.as_str() {
+                    "l" if operands.len() == 3 => {
+                        Some((device_of(&operands[1]), device_of(&operands[2])))
+                    }
+                    "move" => register(1).and_then(|src| loaded.get(&src).cloned()),
+                    _ => None,
+                };
+                match origin {
+                    Some(origin) => loaded.insert(dest, origin),
+                    None => loaded.remove(&dest),
+                };
+            }
+        }
+        diagnostics
+    }
+
+    /// Info when a register holding a device id (loaded from `ReferenceId`, or just used as
+    /// the id of `ld`/`sd`/`getd`/`putd`/`clrd`) feeds arithmetic before being reassigned.
+    /// Reads the per-line value kinds, so unary rounding ops pass ids through. A kind set
+    /// before the nearest label above is not trusted: a jump may land there with anything.
+    /// `# ignore` directives apply.
+    pub fn detect_deviceid_reuse(
+        &self,
+        tree: &Tree,
+        content: &str,
+        aliases: &HashMap<String, crate::DefinitionData<crate::AliasValue>>,
+    ) -> Vec<Diagnostic> {
+        const ARITHMETIC: [&str; 20] = [
+            "add", "sub", "mul", "div", "mod", "pow", "exp", "log", "max", "min", "sin", "cos",
+            "tan", "asin", "acos", "atan", "atan2", "lerp", "sll", "srl",
+        ];
+        let mut diagnostics = Vec::new();
+        let mut last_label: Option<u32> = None;
+
+        let line_query = Query::new(tree_sitter_ic10::language(), "(line) @line").unwrap();
+        let mut query_cursor = QueryCursor::new();
+        for (line_match, _) in query_cursor.captures(&line_query, tree.root_node(), content.as_bytes()) {
+            let line = line_match.captures[0].node;
+            let row = line.start_position().row as u32;
+            let mut child_cursor = line.walk();
+            for node in line.children(&mut child_cursor) {
+                if node.kind() == "label" {
+                    last_label = Some(row);
+                    continue;
+                }
+                if node.kind() != "instruction" {
+                    continue;
+                }
+                let Some(op) = node
+                    .child_by_field_name("operation")
+                    .and_then(|op_node| operation_text(op_node, content))
+                else {
+                    continue;
+                };
+                let op = op.to_ascii_lowercase();
+                if !ARITHMETIC.contains(&op.as_str()) {
+                    continue;
+                }
+                let mut operand_cursor = node.walk();
+                for operand in node.children_by_field_name("operand", &mut operand_cursor).skip(1) {
+                    let reg = self.get_register_from_operand(&operand, content, aliases);
+                    let holds_id = self.kind_before(&reg, row).is_some_and(|(set_at, kind)| {
+                        kind == ValueKind::DeviceId && last_label.is_none_or(|label| set_at >= label)
+                    });
+                    if holds_id && !self.ignored_registers.contains(&reg) {
+                        diagnostics.push(Diagnostic {
+                            range: Range::from(operand.range()).into(),
+                            severity: Some(DiagnosticSeverity::INFORMATION),
+                            code: Some(tower_lsp::lsp_types::NumberOrString::String(
+                                "deviceid-reused-as-number".to_string(),
+                            )),
+                            message: format!(
+                                "'{}' holds a device id; using it in '{}' is probably a mistake. Load the value first (ld/getd) or add '# ignore {}'.",
+                                operand.utf8_text(content.as_bytes()).unwrap_or(""),
+                                op,
+                                reg
+                            ),
+                            ..Default::default()
+                        });
+                    }
+                }
+            }
+        }
         diagnostics
     }
 
@@ -627,7 +1501,7 @@ impl RegisterAnalyzer {
         // Query for all instructions in order
         let instruction_query = Query::new(
             tree_sitter_ic10::language(),
-            "(instruction (operation) @op) @instruction",
+            INSTRUCTION_QUERY,
         )
         .unwrap();
 
@@ -642,7 +1516,7 @@ impl RegisterAnalyzer {
 
             for cap in query_match.captures {
                 if cap.index == op_idx {
-                    operation = Some(cap.node.utf8_text(content.as_bytes()).unwrap());
+                    operation = operation_text(cap.node, content);
                 } else if cap.index == instruction_idx {
                     instruction_node = Some(cap.node);
                 }
@@ -657,12 +1531,13 @@ impl RegisterAnalyzer {
         }
     }
 
-    fn set_kind(&mut self, reg: &str, kind: ValueKind) {
+    fn set_kind(&mut self, reg: &str, row: u32, kind: ValueKind) {
         if reg.is_empty() {
             return;
         }
         let usage = self.ensure_register_entry(reg);
         usage.value_kind = kind;
+        self.kind_history.entry(reg.to_string()).or_default().push((row, kind));
     }
 
     #[allow(dead_code)]
@@ -685,7 +1560,7 @@ impl RegisterAnalyzer {
         let mut cursor = QueryCursor::new();
         let instruction_query = Query::new(
             tree_sitter_ic10::language(),
-            "(instruction (operation) @op) @instruction",
+            INSTRUCTION_QUERY,
         )
         .unwrap();
 
@@ -700,7 +1575,7 @@ impl RegisterAnalyzer {
 
             for cap in query_match.captures {
                 if cap.index == op_idx {
-                    operation = Some(cap.node.utf8_text(content.as_bytes()).unwrap());
+                    operation = operation_text(cap.node, content);
                 } else if cap.index == instruction_idx {
                     instruction_node = Some(cap.node);
                 }
@@ -725,6 +1600,18 @@ impl RegisterAnalyzer {
             // Helpers
             let target_reg = self.get_register_from_operand(&operands[0], content, aliases);
             let op_lc = op.to_lowercase();
+            let row = inst.start_position().row as u32;
+
+            // A register naming the device of `ld`/`sd`/`getd`/`putd`/`clrd` holds a device id
+            let id_operand = match op_lc.as_str() {
+                "ld" | "getd" => operands.get(1),
+                "sd" | "putd" | "clrd" => operands.first(),
+                _ => None,
+            };
+            if let Some(id_operand) = id_operand {
+                let id_reg = self.get_register_from_operand(id_operand, content, aliases);
+                self.set_kind(&id_reg, row, ValueKind::DeviceId);
+            }
 
             match op_lc.as_str() {
                 // Loads
@@ -750,56 +1637,49 @@ impl RegisterAnalyzer {
                     }
                     if !target_reg.is_empty() {
                         if saw_reference {
-                            self.set_kind(&target_reg, ValueKind::DeviceId);
+                            self.set_kind(&target_reg, row, ValueKind::DeviceId);
                         } else if saw_logic {
-                            self.set_kind(&target_reg, ValueKind::Number);
+                            self.set_kind(&target_reg, row, ValueKind::Number);
                         }
                     }
                 }
-                // Pass-through: simple unary ops copy DeviceId if operand is DeviceId
-                "move" | "alias" => { /* handled above or ignored here */ }
-                "abs" | "ceil" | "floor" | "round" | "sqrt" | "trunc" => {
-                    if operands.len() >= 2 {
-                        if let Some(src) = operands[1].child(0) {
-                            if src.kind() == "register" {
-                                let src_reg = src.utf8_text(content.as_bytes()).unwrap_or("");
-                                if let Some(kind) =
-                                    self.register_usage.get(src_reg).map(|u| u.value_kind)
-                                {
-                                    if kind == ValueKind::DeviceId || kind == ValueKind::LogicType {
-                                        // preserve device id and logictype through pure unary ops
-                                        if !target_reg.is_empty() {
-                                            self.set_kind(&target_reg, kind);
-                                        }
-                                        continue;
-                                    }
-                                }
-                            }
-                        }
-                    }
+                // Pass-through: a register copy or a pure unary op keeps a device id or logic
+                // type; any other value is left for the fallback scan to classify
+                "move" | "abs" | "ceil" | "floor" | "round" | "sqrt" | "trunc" => {
+                    let source_kind = operands
+                        .get(1)
+                        .map(|operand| self.get_register_from_operand(operand, content, aliases))
+                        .filter(|reg| !reg.is_empty())
+                        .and_then(|reg| self.register_usage.get(&reg).map(|u| u.value_kind));
+                    let kind = match (op_lc.as_str(), source_kind) {
+                        (_, Some(kind @ (ValueKind::DeviceId | ValueKind::LogicType))) => kind,
+                        ("move", Some(kind)) => kind,
+                        ("move", None) => ValueKind::Unknown,
+                        _ => ValueKind::Number,
+                    };
                     if !target_reg.is_empty() {
-                        self.set_kind(&target_reg, ValueKind::Number);
+                        self.set_kind(&target_reg, row, kind);
                     }
                 }
-                // Explicit numeric generating ops
-                // move already handled earlier; no case needed here to avoid unreachable pattern warning
+                "alias" => {}
                 // get/getd read from device slots or network channels and can return any type of data
                 // pop/peek read from the stack and can return any type of data
                 // We treat them as Unknown so they can be used as numbers, device IDs, or other values
                 "get" | "getd" | "pop" | "peek" => {
-                    // Leave as Unknown - don't set a specific kind
-                    // This allows the register to be used flexibly as the runtime value could be anything
+                    if !target_reg.is_empty() {
+                        self.set_kind(&target_reg, row, ValueKind::Unknown);
+                    }
                 }
                 "add" | "sub" | "mul" | "div" | "mod" | "max" | "min" | "and" | "or" | "xor" | "nor" => {
                     // Arithmetic/logical operations: always produce Number even if inputs include LogicType constants
                     if !target_reg.is_empty() {
-                        self.set_kind(&target_reg, ValueKind::Number);
+                        self.set_kind(&target_reg, row, ValueKind::Number);
                     }
                 }
                 _ => {
                     // Arithmetic and others -> Number
                     if !target_reg.is_empty() && self.is_assignment_operation(op) {
-                        self.set_kind(&target_reg, ValueKind::Number);
+                        self.set_kind(&target_reg, row, ValueKind::Number);
                     }
                 }
             }
@@ -1049,6 +1929,25 @@ impl RegisterAnalyzer {
         None
     }
 
+    /// The last recorded operation on a register at or before `line_number` (1-based)
+    pub fn last_operation_before(&self, register_or_alias: &str, line_number: u32) -> Option<&OperationRecord> {
+        self.get_register_info(register_or_alias)?
+            .operation_history
+            .iter()
+            .rev()
+            .find(|record| record.line_number <= line_number)
+    }
+
+    /// The latest (row, kind) recorded for a register on a line before `row`
+    fn kind_before(&self, register: &str, row: u32) -> Option<(u32, ValueKind)> {
+        self.kind_history
+            .get(register)?
+            .iter()
+            .rev()
+            .find(|(set_at, _)| *set_at < row)
+            .copied()
+    }
+
     pub fn get_register_kind(&self, register_or_alias: &str) -> ValueKind {
         if let Some(info) = self.get_register_info(register_or_alias) {
             return info.value_kind;
@@ -1057,6 +1956,342 @@ impl RegisterAnalyzer {
     }
 }
 
+/// A `push` with no `pop` before control leaves its straight-line block
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnmatchedPush {
+    pub line: u32,                   // row of the push
+    pub value: String,               // pushed operand text
+    pub block_end: u32,              // row a matching pop should be inserted at (label/branch row, or EOF row)
+    pub block_label: Option<String>, // label the block starts at, if it starts at one
+}
+
+/// Stack balance within straight-line blocks: each `pop` matches the latest open `push`, and a
+/// block ends at a label or before a branch/jump. A call (`jal`, `b*al`) returns to the next
+/// line, so it does not end the block; the callee is assumed balanced. A write to `sp` hands
+/// the stack to manual bookkeeping and drops the open pushes unreported. Pushes still open at
+/// the block end are returned in source order.
+pub fn unmatched_pushes(tree: &Tree, content: &str) -> Vec<UnmatchedPush> {
+    let mut unmatched = Vec::new();
+    let mut open: Vec<(u32, String)> = Vec::new();
+    let mut block_label: Option<String> = None;
+    let text = |n: tree_sitter::Node| n.utf8_text(content.as_bytes()).unwrap_or("");
+
+    let line_query = Query::new(tree_sitter_ic10::language(), "(line) @line").unwrap();
+    let mut query_cursor = QueryCursor::new();
+    for (line_match, _) in query_cursor.captures(&line_query, tree.root_node(), content.as_bytes()) {
+        let line = line_match.captures[0].node;
+        let row = line.start_position().row as u32;
+        let mut child_cursor = line.walk();
+        for node in line.children(&mut child_cursor) {
+            let mnemonic = crate::tree_utils::mnemonic_label(node, content.as_bytes());
+            if node.kind() == "label" || mnemonic.is_some() {
+                unmatched.extend(open.drain(..).map(|(line, value)| UnmatchedPush {
+                    line,
+                    value,
+                    block_end: row,
+                    block_label: block_label.clone(),
+                }));
+                block_label = mnemonic.or_else(|| node.child(0)).map(|n| text(n).to_string());
+                continue;
+            }
+            if node.kind() != "instruction" {
+                continue;
+            }
+            let Some(op) = node
+                .child_by_field_name("operation")
+                .and_then(|op_node| operation_text(op_node, content))
+            else {
+                continue;
+            };
+            let op = op.to_ascii_lowercase();
+            let first_operand = node.child_by_field_name("operand").map(text).unwrap_or("");
+            match op.as_str() {
+                "push" => open.push((row, first_operand.to_string())),
+                "pop" => {
+                    open.pop();
+                }
+                op if op == "jal" || (op.starts_with('b') && op.ends_with("al")) => {}
+                op if op.starts_with('b') || matches!(op, "j" | "jr") => {
+                    unmatched.extend(open.drain(..).map(|(line, value)| UnmatchedPush {
+                        line,
+                        value,
+                        block_end: row,
+                        block_label: block_label.clone(),
+                    }));
+                    block_label = None;
+                }
+                op if first_operand == "sp"
+                    && INSTRUCTIONS.get(op).is_some_and(|signature| {
+                        signature.0.first().is_some_and(|first| {
+                            first.match_type(crate::instructions::DataType::Register)
+                                && !first.match_type(crate::instructions::DataType::Number)
+                        })
+                    }) =>
+                {
+                    open.clear();
+                }
+                _ => {}
+            }
+        }
+    }
+    let end_row = tree.root_node().end_position().row as u32;
+    unmatched.extend(open.into_iter().map(|(line, value)| UnmatchedPush {
+        line,
+        value,
+        block_end: end_row,
+        block_label: block_label.clone(),
+    }));
+    unmatched.sort_by_key(|push| push.line);
+    unmatched
+}
+
+/// A basic block: a run of source lines entered only at the top and left only at the bottom
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControlFlowBlock {
+    pub start_line: u32,
+    pub end_line: u32,
+    pub label: Option<String>,             // label on the first line, if any
+    pub terminator: Option<(u32, String)>, // row and text of the block's last instruction
+}
+
+/// A transfer of control between two blocks (indices into the block list)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControlFlowEdge {
+    pub from: usize,
+    pub to: usize,
+    pub kind: &'static str, // "branch", "jump", "call" or "fallthrough"
+}
+
+/// Split the program into basic blocks and connect them. Blocks start at line 0, at labels, at
+/// branch/jump targets (labels or line numbers, relative for `br*`/`jr`) and after each branch
+/// or jump. Comment-only blocks are dropped; control reaching one continues in the next block.
+/// Register targets (`j ra`) have no edge.
+pub fn control_flow_graph(
+    tree: &Tree,
+    content: &str,
+    labels: &HashMap<String, crate::DefinitionData<u8>>,
+) -> (Vec<ControlFlowBlock>, Vec<ControlFlowEdge>) {
+    struct Step {
+        row: u32,
+        op: String,
+        target: String, // last operand text
+        text: String,
+    }
+
+    let mut steps = Vec::new();
+    let query = Query::new(tree_sitter_ic10::language(), "(instruction) @x").unwrap();
+    let mut query_cursor = QueryCursor::new();
+    for (capture, _) in query_cursor.captures(&query, tree.root_node(), content.as_bytes()) {
+        let node = capture.captures[0].node;
+        if crate::tree_utils::mnemonic_label(node, content.as_bytes()).is_some() {
+            continue;
+        }
+        let Some(op) = node
+            .child_by_field_name("operation")
+            .and_then(|op_node| operation_text(op_node, content))
+        else {
+            continue;
+        };
+        let mut operand_cursor = node.walk();
+        let target = node
+            .children_by_field_name("operand", &mut operand_cursor)
+            .last()
+            .and_then(|operand| operand.utf8_text(content.as_bytes()).ok())
+            .unwrap_or("");
+        steps.push(Step {
+            row: node.start_position().row as u32,
+            op: op.to_ascii_lowercase(),
+            target: target.to_string(),
+            text: node.utf8_text(content.as_bytes()).unwrap_or("").trim().to_string(),
+        });
+    }
+
+    let line_count = content.lines().count().max(1) as u32;
+    let is_control = |op: &str| op.starts_with('b') || matches!(op, "j" | "jal" | "jr");
+    let resolve = |step: &Step| -> Option<u32> {
+        if let Some(label) = labels.get(&step.target) {
+            return Some(label.range.0.start.line);
+        }
+        let offset = step.target.parse::<i64>().ok()?;
+        let relative = step.op.starts_with("br") || step.op == "jr";
+        let line = if relative { i64::from(step.row) + offset } else { offset };
+        (0..i64::from(line_count)).contains(&line).then_some(line as u32)
+    };
+
+    let mut leaders = std::collections::BTreeSet::from([0]);
+    leaders.extend(labels.values().map(|label| label.range.0.start.line));
+    for step in steps.iter().filter(|step| is_control(&step.op)) {
+        if step.row + 1 < line_count {
+            leaders.insert(step.row + 1);
+        }
+        leaders.extend(resolve(step));
+    }
+
+    let leaders: Vec<u32> = leaders.into_iter().collect();
+    let mut blocks = Vec::new();
+    for (i, &start_line) in leaders.iter().enumerate() {
+        let end_line = leaders.get(i + 1).map_or(line_count - 1, |next| next - 1);
+        let label = labels
+            .iter()
+            .find(|(_, label)| label.range.0.start.line == start_line)
+            .map(|(name, _)| name.clone());
+        let terminator = steps
+            .iter()
+            .rev()
+            .find(|step| (start_line..=end_line).contains(&step.row))
+            .map(|step| (step.row, step.text.clone()));
+        if label.is_some() || terminator.is_some() {
+            blocks.push(ControlFlowBlock {
+                start_line,
+                end_line,
+                label,
+                terminator,
+            });
+        }
+    }
+
+    // Control reaching `line` continues in the first remaining block that covers or follows it
+    let block_at = |line: u32| blocks.iter().position(|block| block.end_line >= line);
+    let mut edges = Vec::new();
+    for (from, block) in blocks.iter().enumerate() {
+        let last = block
+            .terminator
+            .as_ref()
+            .and_then(|(row, _)| steps.iter().find(|step| step.row == *row));
+        let op = last.map_or("", |step| step.op.as_str());
+        if let Some(step) = last.filter(|step| is_control(&step.op)) {
+            let kind = if op.ends_with("al") {
+                "call"
+            } else if op.starts_with('b') {
+                "branch"
+            } else {
+                "jump"
+            };
+            if let Some(to) = resolve(step).and_then(block_at) {
+                edges.push(ControlFlowEdge { from, to, kind });
+            }
+        }
+        if !matches!(op, "j" | "jr" | "hcf") {
+            if let Some(to) = block_at(block.end_line + 1) {
+                edges.push(ControlFlowEdge {
+                    from,
+                    to,
+                    kind: "fallthrough",
+                });
+            }
+        }
+    }
+    (blocks, edges)
+}
+
+/// A device a script loads from or stores to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReferencedDevice {
+    pub name: String,                 // pin alias, pin (`d0`, `db`) or prefab name for batch access
+    pub display_name: Option<String>, // in-game name, when the device is known
+    pub read: bool,
+    pub written: bool,
+    pub batch: bool, // reached with `lb*`/`sb*` by prefab hash rather than through a pin
+}
+
+/// The devices a script touches, in order of first use. Pin access (`l`, `ls`, `lr`, `s`, `ss`)
+/// is keyed by the alias or pin written; its display name comes from an alias named after a
+/// prefab. Batch access (`lb*`, `sb*`) is keyed by prefab name, from `HASH("...")`, a define
+/// holding one, or a known device hash number; unknown numbers are left out.
+pub fn referenced_devices(
+    tree: &Tree,
+    content: &str,
+    aliases: &HashMap<String, crate::DefinitionData<crate::AliasValue>>,
+    defines: &HashMap<String, crate::DefinitionData<crate::DefineValue>>,
+    device_hashes: &crate::hash_utils::DeviceHashes,
+) -> Vec<ReferencedDevice> {
+    use crate::hash_utils::{compute_crc32, extract_hash_argument};
+
+    let mut devices: Vec<ReferencedDevice> = Vec::new();
+    let query = Query::new(tree_sitter_ic10::language(), INSTRUCTION_QUERY).unwrap();
+    let mut query_cursor = QueryCursor::new();
+    for (capture, _) in query_cursor.captures(&query, tree.root_node(), content.as_bytes()) {
+        let instruction = capture.captures[0].node;
+        let Some(op) = capture
+            .captures
+            .iter()
+            .find(|c| c.node.kind() != "instruction")
+            .and_then(|c| operation_text(c.node, content))
+        else {
+            continue;
+        };
+        let op = op.to_ascii_lowercase();
+        let (index, written, batch) = match op.as_str() {
+            "l" | "ls" | "lr" => (1, false, false),
+            "s" | "ss" => (0, true, false),
+            "lb" | "lbn" | "lbs" | "lbns" => (1, false, true),
+            "sb" | "sbn" | "sbs" => (0, true, true),
+            _ => continue,
+        };
+        let mut operand_cursor = instruction.walk();
+        let Some(operand) = instruction.children_by_field_name("operand", &mut operand_cursor).nth(index) else {
+            continue;
+        };
+        let text = operand.utf8_text(content.as_bytes()).unwrap_or("").trim();
+
+        let device = if batch {
+            let hash = extract_hash_argument(text)
+                .map(|name| device_hashes.get_device_hash(&name).unwrap_or_else(|| compute_crc32(&name)))
+                .or_else(|| defines.get(text).and_then(|define| define.value.resolved_numeric()))
+                .or_else(|| text.parse::<i32>().ok());
+            let Some(hash) = hash else {
+                continue;
+            };
+            let display_name = device_hashes.get_device_name_for_hash(hash).map(str::to_string);
+            let name = device_hashes
+                .get_prefab_name_for_hash(hash)
+                .map(str::to_string)
+                .or_else(|| extract_hash_argument(text));
+            match name {
+                Some(name) => (name, display_name),
+                None => continue,
+            }
+        } else {
+            match aliases.get(text).map(|alias| &alias.value) {
+                Some(crate::AliasValue::Device(_)) => {
+                    let display_name = device_hashes
+                        .prefab_for_alias_name(text)
+                        .and_then(|prefab| device_hashes.get_device_hash(prefab))
+                        .and_then(|hash| device_hashes.get_device_name_for_hash(hash))
+                        .map(str::to_string);
+                    (text.to_string(), display_name)
+                }
+                Some(crate::AliasValue::Register(_)) => continue,
+                None if operand.child(0).is_some_and(|child| child.kind() == "device_spec") => {
+                    (text.to_string(), None)
+                }
+                None => continue,
+            }
+        };
+
+        let (name, display_name) = device;
+        let entry = match devices.iter().position(|device| device.name == name) {
+            Some(i) => &mut devices[i],
+            None => {
+                devices.push(ReferencedDevice {
+                    name,
+                    display_name,
+                    read: false,
+                    written: false,
+                    batch,
+                });
+                devices.last_mut().unwrap()
+            }
+        };
+        if written {
+            entry.written = true;
+        } else {
+            entry.read = true;
+        }
+    }
+    devices
+}
+
 /// Code Actions for enhanced interactivity with instructions
 pub fn get_instruction_code_actions(
     _node: &tree_sitter::Node,
@@ -1073,6 +2308,12 @@ mod tests {
     use std::collections::HashMap;
     use tree_sitter::Parser;
 
+    fn parse(source: &str) -> tree_sitter::Tree {
+        let mut parser = Parser::new();
+        parser.set_language(tree_sitter_ic10::language()).unwrap();
+        parser.parse(source, None).unwrap()
+    }
+
     fn analyze(
         source: &str,
         aliases: &HashMap<String, crate::DefinitionData<crate::AliasValue>>,
@@ -1111,6 +2352,241 @@ mod tests {
         assert_eq!(info.get_state(), RegisterState::Used);
     }
 
+    #[test]
+    fn rand_assigns_its_destination() {
+        // `rand` lexes as invalid_instruction; it must still count as a write to r0.
+        let aliases = HashMap::new();
+        let ra = analyze("rand r0\npush r0\n", &aliases);
+        assert_eq!(ra.get_register_info("r0").unwrap().get_state(), RegisterState::Used);
+        assert_eq!(ra.get_register_kind("r0"), ValueKind::Number);
+    }
+
+    #[test]
+    fn overwritten_device_read_is_dead() {
+        let aliases = HashMap::new();
+        let src = "l r0 d0 Temperature\nl r0 d0 Pressure\ns db Setting r0\n";
+        let diags = analyze(src, &aliases).generate_diagnostics();
+        let dead: Vec<_> = diags
+            .iter()
+            .filter(|d| d.code == Some(tower_lsp::lsp_types::NumberOrString::String("dead-device-read".into())))
+            .collect();
+        assert_eq!(dead.len(), 1);
+        assert_eq!(dead[0].range.start.line, 0);
+        assert_eq!(dead[0].tags, Some(vec![DiagnosticTag::UNNECESSARY]));
+    }
+
+    #[test]
+    fn device_read_across_branch_is_not_dead() {
+        let aliases = HashMap::new();
+        // The loop reads r0 at the top, so the final load is live.
+        let src = "start:\ns db Setting r0\nl r0 d0 Temperature\nbeqz r1 start\nl r0 d0 Pressure\nj start\n";
+        let diags = analyze(src, &aliases).generate_diagnostics();
+        assert!(diags.iter().all(|d| d.code
+            != Some(tower_lsp::lsp_types::NumberOrString::String("dead-device-read".into()))));
+    }
+
+    #[test]
+    fn device_read_before_a_label_is_not_dead() {
+        let aliases = HashMap::new();
+        let src = "l r0 d0 Temperature\nstart:\nyield\nj start\n";
+        let diags = analyze(src, &aliases).generate_diagnostics();
+        assert!(diags.iter().all(|d| d.code
+            != Some(tower_lsp::lsp_types::NumberOrString::String("dead-device-read".into()))));
+    }
+
+    #[test]
+    fn unused_comparison_result_is_hinted() {
+        let aliases = HashMap::new();
+        let code = Some(tower_lsp::lsp_types::NumberOrString::String("unused-comparison-result".into()));
+        let src = "l r1 d0 Temperature\nl r2 d0 Setting\nslt r0 r1 r2\nslt r3 r1 r2\nbnez r3 0\n";
+        let diags = analyze(src, &aliases).generate_diagnostics();
+        let unused: Vec<_> = diags.iter().filter(|d| d.code == code).collect();
+        assert_eq!(unused.len(), 1);
+        assert_eq!(unused[0].range.start.line, 2);
+        assert_eq!(unused[0].severity, Some(DiagnosticSeverity::HINT));
+
+        let ignored = format!("# ignore r0\n{}", src);
+        let diags = analyze(&ignored, &aliases).generate_diagnostics();
+        assert!(diags.iter().all(|d| d.code != code));
+    }
+
+    #[test]
+    fn jal_clobber_is_reported() {
+        let aliases = HashMap::new();
+        let src = "main:\nl r0 d0 Temperature\njal work\ns db Setting r0\nj main\nwork:\nmove r0 5\ns d1 On r0\nj ra\n";
+        let mut ra = analyze(src, &aliases);
+        let diags = ra.detect_call_clobbers(&parse(src), src, &aliases);
+        assert_eq!(diags.len(), 1, "{:?}", diags);
+        assert_eq!(diags[0].range.start.line, 3);
+
+        // Callee reads r0 first: it is an argument, not a clobber
+        let src = "main:\nmove r0 1\njal work\ns db Setting r0\nj main\nwork:\nadd r0 r0 1\nj ra\n";
+        ra = analyze(src, &aliases);
+        assert!(ra.detect_call_clobbers(&parse(src), src, &aliases).is_empty());
+    }
+
+    #[test]
+    fn logictype_roundtrip_is_hinted() {
+        let mut aliases = HashMap::new();
+        aliases.insert(
+            "tank".to_string(),
+            crate::DefinitionData::new(
+                Range(tower_lsp::lsp_types::Range::default()),
+                crate::AliasValue::Device("d0".to_string()),
+            ),
+        );
+        let src = "l r0 d0 Temperature\nmove r1 r0\ns tank Pressure r1\ns d1 Pressure r0\ns d0 Temperature r0\nmul r0 r0 2\ns d0 Pressure r0\n";
+        let ra = analyze(src, &aliases);
+        let diags = ra.detect_logictype_roundtrips(&parse(src), src, &aliases);
+        assert_eq!(diags.len(), 1, "{:?}", diags);
+        assert_eq!(diags[0].range.start.line, 2);
+    }
+
+    #[test]
+    fn log_directive_tracks_register_history() {
+        assert_eq!(parse_log_directive("# log r0"), Some((6, "r0")));
+        assert_eq!(parse_log_directive("#log temp, watch this"), Some((5, "temp")));
+        assert_eq!(parse_log_directive("# logger r0"), None);
+        assert_eq!(parse_log_directive("# log"), None);
+        assert_eq!(parse_log_directive("# trace r0"), None);
+
+        let ra = analyze("move r0 5\nadd r0 r0 1 # log r0\nmul r0 r0 2\n", &HashMap::new());
+        let record = ra.last_operation_before("r0", 2).unwrap();
+        assert_eq!(record.operation, "add r0 r0 1");
+        assert!(ra.last_operation_before("r0", 0).is_none());
+    }
+
+    #[test]
+    fn pushes_pair_with_pops_per_block() {
+        let src = "push r0\npush r1\npop r1\nj start\nstart:\npush ra\npop ra\npush r2\n";
+        let tree = parse(src);
+        let unmatched = unmatched_pushes(&tree, src);
+        assert_eq!(
+            unmatched,
+            vec![
+                UnmatchedPush { line: 0, value: "r0".to_string(), block_end: 3, block_label: None },
+                UnmatchedPush {
+                    line: 7,
+                    value: "r2".to_string(),
+                    block_end: 8,
+                    block_label: Some("start".to_string()),
+                },
+            ]
+        );
+
+        // A call returns to the next line: the pop after it still matches
+        let src = "push ra\njal sub\npop ra\nj end\nsub:\nj ra\nend:\n";
+        assert!(unmatched_pushes(&parse(src), src).is_empty());
+    }
+
+    #[test]
+    fn stack_imbalance_is_reported_where_certain() {
+        let stack = |src: &str| -> Vec<u32> {
+            analyze(src, &HashMap::new())
+                .generate_diagnostics()
+                .into_iter()
+                .filter(|d| d.code == Some(tower_lsp::lsp_types::NumberOrString::String("stack-imbalance".into())))
+                .map(|d| d.range.start.line)
+                .collect()
+        };
+        // A subroutine that leaves a value pushed. The stack persists across the wrap back
+        // to line 0, so a pop before anything is pushed is not an underflow.
+        let src = "push r0\npop r1\npeek r2\nmain:\njal sub\nj main\nsub:\npush ra\npush r0\npop r0\nj ra\n";
+        assert_eq!(stack(src), vec![10]);
+        // Balanced, branching or `sp`-adjusting code is left alone
+        assert!(stack("push r0\npop r0\nmain:\njal sub\nj main\nsub:\npush ra\njal other\npop ra\nj ra\nother:\nj ra\n").is_empty());
+        assert!(stack("jal sub\nsub:\npush r0\nbeqz r1 done\npop r0\ndone:\nj ra\n").is_empty());
+        assert!(stack("move sp 10\npop r0\n").is_empty());
+        assert!(stack("# ignore sp\npop r0\n").is_empty());
+    }
+
+    #[test]
+    fn control_flow_graph_splits_at_labels_and_branches() {
+        let src = "move r0 0\nloop:\nadd r0 r0 1\nblt r0 10 loop\nbeqz r0 6\nj loop\n# end\nyield\n";
+        let range = tower_lsp::lsp_types::Range::new(
+            tower_lsp::lsp_types::Position::new(1, 0),
+            tower_lsp::lsp_types::Position::new(1, 4),
+        );
+        let labels = HashMap::from([(
+            "loop".to_string(),
+            crate::DefinitionData::new(crate::Range(range), 1),
+        )]);
+        let (blocks, edges) = control_flow_graph(&parse(src), src, &labels);
+        let spans: Vec<_> = blocks.iter().map(|b| (b.start_line, b.end_line)).collect();
+        assert_eq!(spans, vec![(0, 0), (1, 3), (4, 4), (5, 5), (6, 7)]);
+        assert_eq!(blocks[1].label.as_deref(), Some("loop"));
+        assert_eq!(blocks[1].terminator, Some((3, "blt r0 10 loop".to_string())));
+        let edges: Vec<_> = edges.iter().map(|e| (e.from, e.to, e.kind)).collect();
+        assert_eq!(
+            edges,
+            vec![
+                (0, 1, "fallthrough"),
+                (1, 1, "branch"),
+                (1, 2, "fallthrough"),
+                (2, 4, "branch"),
+                (2, 3, "fallthrough"),
+                (3, 1, "jump"),
+            ]
+        );
+    }
+
+    #[test]
+    fn referenced_devices_merge_reads_and_writes() {
+        let src = "alias GasSensor d0\nalias heater d1\ndefine Pumps HASH(\"StructureVolumePump\")\n\
+                   l r0 GasSensor Temperature\ns heater On 1\nl r1 heater On\nsb Pumps On 0\n\
+                   lb r2 HASH(\"NotADevice\") On Sum\nlb r3 12345 On Sum\ns db Setting r0\n";
+        let tree = parse(src);
+        let range = crate::Range(LspRange::default());
+        let aliases = HashMap::from([
+            ("GasSensor".to_string(), crate::DefinitionData::new(range, "d0".to_string().into())),
+            ("heater".to_string(), crate::DefinitionData::new(range, "d1".to_string().into())),
+        ]);
+        let defines = HashMap::from([(
+            "Pumps".to_string(),
+            crate::DefinitionData::new(range, "HASH(\"StructureVolumePump\")".to_string().into()),
+        )]);
+        let devices = referenced_devices(&tree, src, &aliases, &defines, &Default::default());
+        let summary: Vec<_> = devices
+            .iter()
+            .map(|d| (d.name.as_str(), d.display_name.is_some(), d.read, d.written))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("GasSensor", true, true, false),
+                ("heater", false, true, true),
+                ("StructureVolumePump", true, false, true),
+                ("NotADevice", false, true, false),
+                ("db", false, false, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn float_equality_is_hinted() {
+        let aliases = HashMap::new();
+        let src = "l r0 d0 Temperature\nbeq r0 300 done\nl r1 d0 On\nseq r2 r1 1\nsne r3 r1 0.5\ndone:\nbeq r0 1 done\n";
+        let ra = analyze(src, &aliases);
+        let diags = ra.detect_float_equality(&parse(src), src, &aliases);
+        let lines: Vec<_> = diags.iter().map(|d| d.range.start.line).collect();
+        assert_eq!(lines, vec![1, 4], "{:?}", diags);
+    }
+
+    #[test]
+    fn deviceid_in_arithmetic_is_noted() {
+        let aliases = HashMap::new();
+        let src = "l r1 d0 ReferenceId\nadd r2 r1 1\ngetd r0 r3 0\nmul r4 r3 2\nmove r3 5\nsub r5 r3 1\nld r6 r1 Temperature\nstart:\nadd r2 r1 1\n";
+        let ra = analyze(src, &aliases);
+        let diags = ra.detect_deviceid_reuse(&parse(src), src, &aliases);
+        let spots: Vec<_> = diags.iter().map(|d| (d.range.start.line, d.range.start.character)).collect();
+        assert_eq!(spots, vec![(1, 7), (3, 7)], "{:?}", diags);
+        assert!(diags.iter().all(|d| d.severity == Some(DiagnosticSeverity::INFORMATION)));
+
+        let src = "l r1 d0 ReferenceId # ignore r1\nadd r2 r1 1\n";
+        let ra = analyze(src, &aliases);
+        assert!(ra.detect_deviceid_reuse(&parse(src), src, &aliases).is_empty());
+    }
+
     #[test]
     fn reference_id_load_sets_deviceid() {
         let src = "l r1 d0 ReferenceId\n";
@@ -12,4 +12,20 @@ pub(crate) struct Cli {
     /// Run diagnostics on the provided files and print results to stdout
     #[arg(long)]
     pub diagnose: Vec<std::path::PathBuf>,
+    /// With --diagnose, print one JSON object mapping each file to its diagnostics, or to an
+    /// `{"error": ...}` object when it cannot be read
+    #[arg(long, requires = "diagnose")]
+    pub json: bool,
+    /// Run diagnostics on the provided files and print a SARIF 2.1.0 log to stdout
+    #[arg(long)]
+    pub diagnose_sarif: Vec<std::path::PathBuf>,
+    /// Print the defines, aliases and labels of the provided file as a JSON array
+    #[arg(long)]
+    pub symbols: Option<std::path::PathBuf>,
+    /// Format the provided files in place, like the editor's Format Document
+    #[arg(long, num_args = 1..)]
+    pub format: Vec<std::path::PathBuf>,
+    /// With --format, print the formatted files to stdout instead of rewriting them
+    #[arg(long, requires = "format")]
+    pub stdout: bool,
 }
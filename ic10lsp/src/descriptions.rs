@@ -11,6 +11,54 @@ pub fn get_device_description(prefab_name: &str) -> Option<(&'static str, &'stat
     DEVICE_DESCRIPTIONS.get(prefab_name).copied()
 }
 
+/// Get the logic types a device exposes as `(name, readable, writable)`, from Stationpedia
+pub fn get_device_logic_types(
+    prefab_name: &str,
+) -> Option<&'static [(&'static str, bool, bool)]> {
+    DEVICE_LOGIC_ACCESS.get(prefab_name).copied()
+}
+
+/// Whether a logic type can be read with `l`, written with `s`, or both
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogicDirection {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+impl LogicDirection {
+    pub fn readable(self) -> bool {
+        matches!(self, LogicDirection::Read | LogicDirection::ReadWrite)
+    }
+
+    pub fn writable(self) -> bool {
+        matches!(self, LogicDirection::Write | LogicDirection::ReadWrite)
+    }
+}
+
+/// The access direction of a logic type across every device in the Stationpedia table:
+/// readable if any device reads it, writable if any device writes it. `None` for logic
+/// types no device lists.
+pub fn logic_type_direction(logic_type: &str) -> Option<LogicDirection> {
+    static DIRECTIONS: std::sync::OnceLock<std::collections::HashMap<&'static str, (bool, bool)>> =
+        std::sync::OnceLock::new();
+    let directions = DIRECTIONS.get_or_init(|| {
+        let mut directions = std::collections::HashMap::new();
+        for (name, readable, writable) in DEVICE_LOGIC_ACCESS.values().flat_map(|types| types.iter()) {
+            let entry = directions.entry(*name).or_insert((false, false));
+            entry.0 |= readable;
+            entry.1 |= writable;
+        }
+        directions
+    });
+    match directions.get(logic_type)? {
+        (true, true) => Some(LogicDirection::ReadWrite),
+        (true, false) => Some(LogicDirection::Read),
+        (false, true) => Some(LogicDirection::Write),
+        (false, false) => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -25,4 +73,12 @@ mod tests {
             assert!(!description.is_empty());
         }
     }
+
+    #[test]
+    fn logic_type_directions_come_from_the_device_table() {
+        assert_eq!(logic_type_direction("Temperature"), Some(LogicDirection::Read));
+        assert_eq!(logic_type_direction("ClearMemory"), Some(LogicDirection::Write));
+        assert!(logic_type_direction("On").is_some_and(|d| d.readable() && d.writable()));
+        assert_eq!(logic_type_direction("NotALogicType"), None);
+    }
 }
@@ -3,7 +3,7 @@
 //! This module provides utility functions for working with LSP diagnostics,
 //! including deduplication and identity checking.
 
-use tower_lsp::lsp_types::Diagnostic;
+use tower_lsp::lsp_types::{Diagnostic, Position};
 
 /// Create a unique identity tuple for a diagnostic
 ///
@@ -34,3 +34,70 @@ pub fn should_ignore_limits(content: &str) -> bool {
     }
     false
 }
+
+/// The lines the game keeps from pasted code: each trimmed at the end, with trailing blank
+/// lines dropped. `\r\n` and `\n` endings read the same.
+fn game_lines(content: &str) -> Vec<&str> {
+    let mut lines: Vec<&str> = content.lines().map(str::trim_end).collect();
+    while lines.last().is_some_and(|line| line.is_empty()) {
+        lines.pop();
+    }
+    lines
+}
+
+/// Script size as Stationeers counts it (`UpdateFileSize()`): the length of each kept line
+/// plus 2 bytes (CRLF) between lines, none after the last
+pub fn game_byte_size(content: &str) -> usize {
+    let lines = game_lines(content);
+    lines.iter().map(|line| line.len()).sum::<usize>() + 2 * lines.len().saturating_sub(1)
+}
+
+/// Where the game's byte count first exceeds `max_bytes`, as (line, character); `None` when it
+/// never does. A limit crossed by a line break lands at the end of the line before it.
+pub fn game_byte_overflow_position(content: &str, max_bytes: usize) -> Option<Position> {
+    let mut count = 0;
+    let mut previous_end = None;
+    for (row, line) in game_lines(content).into_iter().enumerate() {
+        if let Some(end) = previous_end {
+            count += 2;
+            if count > max_bytes {
+                return Some(end);
+            }
+        }
+        previous_end = Some(Position::new(row as u32, line.chars().count() as u32));
+        for (column, char) in line.chars().enumerate() {
+            count += char.len_utf8();
+            if count > max_bytes {
+                return Some(Position::new(row as u32, column as u32));
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn byte_size_matches_the_game() {
+        assert_eq!(game_byte_size(""), 0);
+        assert_eq!(game_byte_size("yield"), 5);
+        // The newline every saved file ends with is not a line of its own
+        assert_eq!(game_byte_size("yield\n"), 5);
+        assert_eq!(game_byte_size("yield\nj 0\n"), 10);
+        assert_eq!(game_byte_size("yield\r\nj 0\r\n"), 10);
+        // Trailing whitespace and trailing blank lines are dropped, inner blank lines count
+        assert_eq!(game_byte_size("yield  \nj 0\t\n\n  \n"), 10);
+        assert_eq!(game_byte_size("yield\n\nj 0\n"), 12);
+    }
+
+    #[test]
+    fn overflow_position_follows_the_game_count() {
+        let content = "yield\r\nj 0\r\n";
+        assert_eq!(game_byte_overflow_position(content, 10), None);
+        assert_eq!(game_byte_overflow_position(content, 9), Some(Position::new(1, 2)));
+        assert_eq!(game_byte_overflow_position(content, 6), Some(Position::new(0, 5)));
+        assert_eq!(game_byte_overflow_position(content, 4), Some(Position::new(0, 4)));
+    }
+}
@@ -7,7 +7,7 @@ use ic10lsp::instructions::DataType;
 use std::collections::HashMap;
 use std::fmt;
 use std::time::Instant;
-use tower_lsp::lsp_types::{Range as LspRange, Url};
+use tower_lsp::lsp_types::{DiagnosticSeverity, Range as LspRange, Url};
 use tree_sitter::{Parser, Tree};
 
 use crate::types::Range;
@@ -22,7 +22,149 @@ pub struct Configuration {
     pub warn_overcolumn_comment: bool,
     pub suppress_hash_diagnostics: bool,
     pub enable_control_flow_analysis: bool,
+    pub max_call_depth: usize,
+    pub analysis_line_budget: usize,
+    pub invalid_instruction_severity: DiagnosticSeverity,
+    /// When set, only these mnemonics may be used; directives are always allowed
+    pub allowed_instructions: Option<Vec<String>>,
+    pub show_label_line_hints: bool,
+    pub show_cycle_hints: bool,
     pub suppress_register_warnings: bool,
+    pub warn_lone_device_write: bool,
+    pub warn_accidental_db_access: bool,
+    pub warn_logictype_roundtrip: bool,
+    pub diagnostics_on_save_only: bool,
+    pub verbose_completion_detail: bool,
+    pub enums_require_prefix: bool,
+    pub custom_docs_path: Option<String>,
+    pub custom_devices: Vec<CustomDevice>,
+    pub completion_categories: CompletionCategories,
+    pub alias_naming: AliasNaming,
+}
+
+/// A device the game doesn't ship (e.g. from a mod), recognized by its prefab name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CustomDevice {
+    pub name: String,
+    pub display_name: Option<String>,
+}
+
+impl CustomDevice {
+    /// Parse the `customDevices` setting: `[{ "name": ..., "displayName": ... }]`.
+    /// Entries without a name are skipped.
+    pub fn list_from_json(value: &serde_json::Value) -> Option<Vec<CustomDevice>> {
+        let devices = value
+            .as_array()?
+            .iter()
+            .filter_map(|entry| {
+                let name = entry.get("name")?.as_str()?.trim();
+                (!name.is_empty()).then(|| CustomDevice {
+                    name: name.to_string(),
+                    display_name: entry
+                        .get("displayName")
+                        .and_then(serde_json::Value::as_str)
+                        .map(str::to_string),
+                })
+            })
+            .collect();
+        Some(devices)
+    }
+}
+
+/// Parse the `allowedInstructions` setting: a list of mnemonics, matched case-insensitively.
+/// A missing or empty list means every instruction is allowed.
+pub fn allowed_instructions_from_json(value: &serde_json::Value) -> Option<Vec<String>> {
+    let allowed: Vec<String> = value
+        .as_array()?
+        .iter()
+        .filter_map(serde_json::Value::as_str)
+        .map(|mnemonic| mnemonic.trim().to_ascii_lowercase())
+        .filter(|mnemonic| !mnemonic.is_empty())
+        .collect();
+    (!allowed.is_empty()).then_some(allowed)
+}
+
+/// Name templates for generated register and device aliases; `{n}` stands for the index
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AliasNaming {
+    pub register: String,
+    pub device: String,
+}
+
+impl AliasNaming {
+    /// Update from the `aliasNaming` setting: `{ "register": ..., "device": ... }`. Templates
+    /// that would not yield a valid name are skipped and returned.
+    pub fn update_from_json(&mut self, value: &serde_json::Map<String, serde_json::Value>) -> Vec<String> {
+        let mut rejected = Vec::new();
+        for (key, template) in [("register", &mut self.register), ("device", &mut self.device)] {
+            let Some(candidate) = value.get(key).and_then(serde_json::Value::as_str) else {
+                continue;
+            };
+            let candidate = candidate.trim();
+            if Self::is_valid_template(candidate) {
+                *template = candidate.to_string();
+            } else {
+                rejected.push(candidate.to_string());
+            }
+        }
+        rejected
+    }
+
+    /// A template holds `{n}` exactly once and the names it makes follow the game's symbol
+    /// rules: a letter or `_`, then letters, digits and `_`. None of them may already be a
+    /// register, pin, mnemonic or logic type (`r{n}` would make `alias r0 r0`).
+    pub fn is_valid_template(template: &str) -> bool {
+        let name = template.replacen("{n}", "0", 1);
+        let mut chars = name.chars();
+        template.matches("{n}").count() == 1
+            && chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+            && (0..=15).all(|n| {
+                let name = template.replacen("{n}", &n.to_string(), 1);
+                crate::type_classification::reserved_name_kind(&name).is_none()
+            })
+    }
+
+    pub fn register_name(&self, index: u8) -> String {
+        self.register.replacen("{n}", &index.to_string(), 1)
+    }
+
+    pub fn device_name(&self, index: u8) -> String {
+        self.device.replacen("{n}", &index.to_string(), 1)
+    }
+}
+
+impl Default for AliasNaming {
+    fn default() -> Self {
+        AliasNaming {
+            register: "value{n}".to_string(),
+            device: "device{n}".to_string(),
+        }
+    }
+}
+
+/// Which kinds of items parameter completion offers
+#[derive(Debug, Clone)]
+pub struct CompletionCategories {
+    pub enums: bool,
+    pub defines: bool,
+    pub aliases: bool,
+    pub labels: bool,
+    pub registers: bool,
+    pub devices: bool,
+}
+
+impl Default for CompletionCategories {
+    fn default() -> Self {
+        CompletionCategories {
+            enums: true,
+            defines: true,
+            aliases: true,
+            labels: true,
+            registers: true,
+            devices: true,
+        }
+    }
 }
 
 impl Default for Configuration {
@@ -35,7 +177,23 @@ impl Default for Configuration {
             warn_overcolumn_comment: true,
             suppress_hash_diagnostics: false,
             enable_control_flow_analysis: false,
+            max_call_depth: 8,
+            analysis_line_budget: 2000,
+            invalid_instruction_severity: DiagnosticSeverity::ERROR,
+            allowed_instructions: None,
+            show_label_line_hints: true,
+            show_cycle_hints: false,
             suppress_register_warnings: false,
+            warn_lone_device_write: false,
+            warn_accidental_db_access: true,
+            warn_logictype_roundtrip: false,
+            diagnostics_on_save_only: false,
+            verbose_completion_detail: false,
+            enums_require_prefix: false,
+            custom_docs_path: None,
+            custom_devices: Vec::new(),
+            completion_categories: CompletionCategories::default(),
+            alias_naming: AliasNaming::default(),
         }
     }
 }
@@ -45,32 +203,68 @@ impl Default for Configuration {
 pub enum DefineValue {
     Number(String),
     FunctionCall(String),
+    /// A `STR("...")` call: a named string constant
+    StringHash(String),
     Identifier(String),
 }
 
+/// Parse a numeric value the way IC10 writes them: any integer literal accepted by
+/// [`crate::hash_utils::parse_ic10_number`], or a decimal float (`1.5`, `1.5e3`)
+pub fn parse_ic10_value(text: &str) -> Option<f64> {
+    crate::hash_utils::parse_ic10_number(text)
+        .map(|value| value as f64)
+        .or_else(|| text.trim().parse::<f64>().ok())
+}
+
 impl DefineValue {
+    /// The value of a numeric define, whatever notation it is written in
+    pub fn resolved_value(&self) -> Option<f64> {
+        match self {
+            DefineValue::Number(s) => parse_ic10_value(s),
+            _ => None,
+        }
+    }
+
     /// Try to resolve this define to a numeric hash value
     pub fn resolved_numeric(&self) -> Option<i32> {
         match self {
-            DefineValue::Number(s) => s.parse().ok(),
+            DefineValue::Number(s) => match crate::hash_utils::parse_ic10_number(s) {
+                Some(value) => i32::try_from(value).ok(),
+                // Floats such as `1e3` still name a whole number
+                None => self
+                    .resolved_value()
+                    .filter(|value| {
+                        value.fract() == 0.0 && *value >= i32::MIN as f64 && *value <= i32::MAX as f64
+                    })
+                    .map(|value| value as i32),
+            },
             DefineValue::FunctionCall(s) => {
-                // Try to extract HASH("...") and resolve it
-                if let Some(device_name) = crate::hash_utils::extract_hash_argument(s) {
-                    crate::hash_utils::get_device_hash(&device_name)
-                } else {
-                    None
-                }
+                // HASH("...") is the CRC32 of its argument, whether or not a device goes by it
+                crate::hash_utils::extract_hash_argument(s).map(|name| crate::hash_utils::compute_crc32(&name))
+            }
+            DefineValue::StringHash(s) => {
+                crate::hash_utils::extract_str_argument(s).map(|text| crate::hash_utils::compute_crc32(&text))
             }
             DefineValue::Identifier(_) => None,
         }
     }
+
+    /// The string behind a `STR("...")` define
+    pub fn string_constant(&self) -> Option<String> {
+        match self {
+            DefineValue::StringHash(s) => crate::hash_utils::extract_str_argument(s),
+            _ => None,
+        }
+    }
 }
 
 impl From<String> for DefineValue {
     fn from(s: String) -> Self {
         if s.starts_with("HASH(") || s.starts_with("hash(") {
             DefineValue::FunctionCall(s)
-        } else if s.parse::<f64>().is_ok() {
+        } else if s.starts_with("STR(") || s.starts_with("str(") {
+            DefineValue::StringHash(s)
+        } else if parse_ic10_value(&s).is_some() {
             DefineValue::Number(s)
         } else {
             DefineValue::Identifier(s)
@@ -81,7 +275,10 @@ impl From<String> for DefineValue {
 impl fmt::Display for DefineValue {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            DefineValue::Number(s) | DefineValue::FunctionCall(s) | DefineValue::Identifier(s) => {
+            DefineValue::Number(s)
+            | DefineValue::FunctionCall(s)
+            | DefineValue::StringHash(s)
+            | DefineValue::Identifier(s) => {
                 write!(f, "{}", s)
             }
         }
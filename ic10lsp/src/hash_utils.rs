@@ -1,4 +1,5 @@
 use crate::device_hashes::{DEVICE_NAME_TO_HASH, HASH_TO_DISPLAY_NAME};
+use std::collections::{HashMap, HashSet};
 use crc32fast::Hasher;
 
 /// Computes CRC32 hash for a given string using the same algorithm as Stationeers
@@ -95,14 +96,222 @@ pub fn is_hash_function_call(input: &str) -> bool {
     extract_hash_argument(input).is_some()
 }
 
-/// Looks up device name in device registry and returns the corresponding hash
-pub fn get_device_hash(device_name: &str) -> Option<i32> {
-    DEVICE_NAME_TO_HASH.get(device_name).copied()
+/// Device hashes layered over the tables baked in at build time: supplied at runtime
+/// (`ic10/reloadDeviceHashes`) or configured as custom (modded) devices
+#[derive(Default)]
+struct DeviceHashOverlay {
+    by_name: HashMap<String, i32>,
+    by_hash: HashMap<i32, (String, String)>, // hash -> (prefab name, display name)
+    /// Built-in hashes of the prefabs this overlay gives a different hash
+    shadowed: HashSet<i32>,
 }
 
-/// Gets device name for a given hash value from the registry
-pub fn get_device_name_for_hash(hash_value: i32) -> Option<&'static str> {
-    HASH_TO_DISPLAY_NAME.get(&hash_value).copied()
+impl DeviceHashOverlay {
+    /// Build from (prefab name, hash, display name) entries. Display names default to the
+    /// built-in table where the prefab is already known.
+    fn build(entries: Vec<(String, i32, Option<String>)>) -> Self {
+        let mut overlay = DeviceHashOverlay::default();
+        for (name, hash, display) in entries {
+            let builtin = DEVICE_NAME_TO_HASH.get(name.as_str()).copied();
+            let display = display
+                .or_else(|| builtin.and_then(|old| HASH_TO_DISPLAY_NAME.get(&old)).map(|d| d.to_string()))
+                .unwrap_or_else(|| name.clone());
+            if let Some(old) = builtin.filter(|old| *old != hash) {
+                overlay.shadowed.insert(old);
+            }
+            overlay.by_name.insert(name.clone(), hash);
+            overlay.by_hash.insert(hash, (name, display));
+        }
+        overlay
+    }
+}
+
+/// The device hash tables a server answers from: the built-in tables with the runtime and
+/// custom device overlays on top. Overlays answer first in both directions, runtime hashes
+/// before custom devices, and a built-in hash an overlay moved to a new value no longer
+/// resolves.
+#[derive(Default)]
+pub struct DeviceHashes {
+    runtime: DeviceHashOverlay,
+    custom: DeviceHashOverlay,
+    /// The (prefab name, display name) list `custom` was built from
+    custom_devices: Vec<(String, Option<String>)>,
+}
+
+impl DeviceHashes {
+    fn overlays(&self) -> [&DeviceHashOverlay; 2] {
+        [&self.runtime, &self.custom]
+    }
+
+    fn overlay_entry(&self, hash_value: i32) -> Option<&(String, String)> {
+        self.overlays().into_iter().find_map(|overlay| overlay.by_hash.get(&hash_value))
+    }
+
+    /// Whether `hash_value` is a built-in hash whose prefab an overlay remapped
+    fn is_shadowed(&self, hash_value: i32) -> bool {
+        self.overlays().iter().any(|overlay| overlay.shadowed.contains(&hash_value))
+    }
+
+    /// Replace the runtime overlay with `hashes` (prefab name -> hash); an empty map clears it.
+    /// Display names carry over from the built-in table where the prefab is already known.
+    pub fn set_runtime_hashes(&mut self, hashes: HashMap<String, i32>) {
+        let entries = hashes.into_iter().map(|(name, hash)| (name, hash, None)).collect();
+        self.runtime = DeviceHashOverlay::build(entries);
+    }
+
+    /// The custom devices last passed to [`DeviceHashes::set_custom_devices`]
+    pub fn custom_devices(&self) -> &[(String, Option<String>)] {
+        &self.custom_devices
+    }
+
+    /// Replace the custom (modded) devices with `devices` (prefab name, display name); hashes are
+    /// the CRC32 of the name, as the game computes them. Returns the names that were already
+    /// built in, which the custom entry now overrides.
+    pub fn set_custom_devices(&mut self, devices: &[(String, Option<String>)]) -> Vec<String> {
+        let overridden = devices
+            .iter()
+            .filter(|(name, _)| DEVICE_NAME_TO_HASH.contains_key(name.as_str()))
+            .map(|(name, _)| name.clone())
+            .collect();
+        let entries = devices
+            .iter()
+            .map(|(name, display)| (name.clone(), compute_crc32(name), display.clone()))
+            .collect();
+        self.custom = DeviceHashOverlay::build(entries);
+        self.custom_devices = devices.to_vec();
+        overridden
+    }
+
+    /// Looks up device name in device registry and returns the corresponding hash
+    pub fn get_device_hash(&self, device_name: &str) -> Option<i32> {
+        self.overlays()
+            .into_iter()
+            .find_map(|overlay| overlay.by_name.get(device_name).copied())
+            .or_else(|| DEVICE_NAME_TO_HASH.get(device_name).copied())
+    }
+
+    /// Gets device name for a given hash value from the registry
+    pub fn get_device_name_for_hash(&self, hash_value: i32) -> Option<&str> {
+        match self.overlay_entry(hash_value) {
+            Some((_, display)) => Some(display),
+            None if self.is_shadowed(hash_value) => None,
+            None => HASH_TO_DISPLAY_NAME.get(&hash_value).copied(),
+        }
+    }
+
+    /// Gets the prefab name (the `HASH("...")` argument) for a given hash value
+    pub fn get_prefab_name_for_hash(&self, hash_value: i32) -> Option<&str> {
+        match self.overlay_entry(hash_value) {
+            Some((name, _)) => Some(name),
+            None if self.is_shadowed(hash_value) => None,
+            None => DEVICE_NAME_TO_HASH
+                .entries()
+                .find(|(_, hash)| **hash == hash_value)
+                .map(|(name, _)| *name),
+        }
+    }
+
+    /// All known devices as (prefab name, hash), overlay values taking precedence
+    pub fn device_hash_entries(&self) -> Vec<(&str, i32)> {
+        let in_runtime = |name: &str| self.runtime.by_name.contains_key(name);
+        let in_custom = |name: &str| self.custom.by_name.contains_key(name);
+        let mut entries: Vec<(&str, i32)> = DEVICE_NAME_TO_HASH
+            .entries()
+            .map(|(name, hash)| (*name, *hash))
+            .filter(|(name, _)| !in_runtime(name) && !in_custom(name))
+            .collect();
+        entries.extend(
+            self.custom
+                .by_name
+                .iter()
+                .map(|(name, hash)| (name.as_str(), *hash))
+                .filter(|(name, _)| !in_runtime(name)),
+        );
+        entries.extend(self.runtime.by_name.iter().map(|(name, hash)| (name.as_str(), *hash)));
+        entries
+    }
+
+    /// Find the prefab an alias name refers to by naming convention: the prefab name with or
+    /// without its `Structure`/`Item` prefix, ignoring case
+    pub fn prefab_for_alias_name(&self, alias: &str) -> Option<&str> {
+        self.device_hash_entries()
+            .into_iter()
+            .map(|(prefab, _)| prefab)
+            .find(|prefab| {
+                let short = prefab
+                    .strip_prefix("Structure")
+                    .or_else(|| prefab.strip_prefix("Item"))
+                    .unwrap_or(prefab);
+                prefab.eq_ignore_ascii_case(alias) || short.eq_ignore_ascii_case(alias)
+            })
+    }
+
+    /// The known prefab name closest to `name` by edit distance, ignoring case; `None` when even
+    /// the closest needs more than a third of the name's characters changed
+    pub fn closest_device_name(&self, name: &str) -> Option<&str> {
+        let lowered = name.to_ascii_lowercase();
+        self.device_hash_entries()
+            .into_iter()
+            .map(|(prefab, _)| (edit_distance(&lowered, &prefab.to_ascii_lowercase()), prefab))
+            .filter(|(distance, _)| *distance <= name.chars().count() / 3)
+            .min()
+            .map(|(_, prefab)| prefab)
+    }
+}
+
+/// Edit distance between two strings: the fewest single-character insertions, deletions,
+/// substitutions and adjacent swaps turning one into the other, so `mvoe` is one edit from `move`
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut before_previous: Vec<usize> = Vec::new();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for i in 0..a.len() {
+        let mut current = vec![i + 1];
+        for j in 0..b.len() {
+            let substitution = previous[j] + usize::from(a[i] != b[j]);
+            let mut distance = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+            if i > 0 && j > 0 && a[i] == b[j - 1] && a[i - 1] == b[j] {
+                distance = distance.min(before_previous[j - 1] + 1);
+            }
+            current.push(distance);
+        }
+        before_previous = std::mem::replace(&mut previous, current);
+    }
+    previous[b.len()]
+}
+
+/// Reads an integer literal as a 32-bit hash. Hashes are signed in the game, but scripts
+/// sometimes carry the unsigned form of a negative one (`3973563687` for `-321403609`);
+/// values in `i32::MAX + 1..=u32::MAX` are reinterpreted as their signed counterpart.
+pub fn hash_from_number_text(text: &str) -> Option<i32> {
+    let value = parse_ic10_number(text)?;
+    i32::try_from(value)
+        .ok()
+        .or_else(|| u32::try_from(value).ok().map(|unsigned| unsigned as i32))
+}
+
+/// Parses an integer literal as IC10 writes them: decimal (`-12`), hex (`$1A`, `0x1A`) or
+/// binary (`%1010`). Hex and binary digits may be grouped with `_`.
+pub fn parse_ic10_number(text: &str) -> Option<i64> {
+    let text = text.trim();
+    let (negative, unsigned) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let (digits, radix) = if let Some(hex) = unsigned.strip_prefix('$').or_else(|| unsigned.strip_prefix("0x")) {
+        (hex.replace('_', ""), 16)
+    } else if let Some(binary) = unsigned.strip_prefix('%') {
+        (binary.replace('_', ""), 2)
+    } else {
+        (unsigned.to_string(), 10)
+    };
+    // from_str_radix would accept a sign of its own after the prefix
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+        return None;
+    }
+    let value = i64::from_str_radix(&digits, radix).ok()?;
+    Some(if negative { -value } else { value })
 }
 
 /// Checks if a string contains only digits (potentially negative)
@@ -151,9 +360,50 @@ mod tests {
 
     #[test]
     fn test_get_device_hash() {
-        assert_eq!(get_device_hash("StructureVolumePump"), Some(-321403609));
-        assert_eq!(get_device_hash("StructureDaylightSensor"), Some(1076425094));
-        assert_eq!(get_device_hash("NonExistentDevice"), None);
+        let hashes = DeviceHashes::default();
+        assert_eq!(hashes.get_device_hash("StructureVolumePump"), Some(-321403609));
+        assert_eq!(hashes.get_device_hash("StructureDaylightSensor"), Some(1076425094));
+        assert_eq!(hashes.get_device_hash("NonExistentDevice"), None);
+    }
+
+    #[test]
+    fn test_parse_ic10_number() {
+        assert_eq!(parse_ic10_number("42"), Some(42));
+        assert_eq!(parse_ic10_number("-7"), Some(-7));
+        assert_eq!(parse_ic10_number("$FF"), Some(255));
+        assert_eq!(parse_ic10_number("$1a"), Some(26));
+        assert_eq!(parse_ic10_number("0x10"), Some(16));
+        assert_eq!(parse_ic10_number("-$10"), Some(-16));
+        assert_eq!(parse_ic10_number("$FF_FF"), Some(65535));
+        assert_eq!(parse_ic10_number("%1010"), Some(10));
+        assert_eq!(parse_ic10_number("%1111_0000"), Some(240));
+        assert_eq!(parse_ic10_number("$GG"), None);
+        assert_eq!(parse_ic10_number("%102"), None);
+        assert_eq!(parse_ic10_number("$"), None);
+        assert_eq!(parse_ic10_number("%"), None);
+        assert_eq!(parse_ic10_number("$-1"), None);
+        assert_eq!(parse_ic10_number("1.5"), None);
+        assert_eq!(parse_ic10_number("abc"), None);
+    }
+
+    #[test]
+    fn test_closest_device_name() {
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("mvoe", "move"), 1);
+        assert_eq!(DeviceHashes::default().closest_device_name("StructureVolumPump"), Some("StructureVolumePump"));
+        assert_eq!(DeviceHashes::default().closest_device_name("structurevolumepump"), Some("StructureVolumePump"));
+        assert_eq!(DeviceHashes::default().closest_device_name("Banana"), None);
+    }
+
+    #[test]
+    fn test_hash_from_number_text() {
+        assert_eq!(hash_from_number_text("-321403609"), Some(-321403609));
+        assert_eq!(hash_from_number_text("3973563687"), Some(-321403609));
+        assert_eq!(hash_from_number_text("1076425094"), Some(1076425094));
+        assert_eq!(hash_from_number_text("4294967296"), None);
+        assert_eq!(hash_from_number_text("-3973563687"), None);
+        assert_eq!(hash_from_number_text("1.5"), None);
     }
 
     #[test]
@@ -187,6 +187,57 @@ pub const INSTRUCTIONS: phf::Map<&'static str, InstructionSignature> = phf_map!
     "lerp" => InstructionSignature(&[REGISTER, VALUE, VALUE, VALUE]),
 };
 
+/// Number of values in an IC housing stack; valid addresses are `0..STACK_SIZE`
+pub const STACK_SIZE: u32 = 512;
+
+/// Index of the operand holding a stack address, for the stack-access instructions
+pub fn stack_address_operand(instruction: &str) -> Option<usize> {
+    match instruction {
+        "poke" => Some(0),
+        "put" | "putd" => Some(1),
+        "get" | "getd" => Some(2),
+        _ => None,
+    }
+}
+
+/// Index of the operand holding a device slot number, for the slot instructions
+pub fn slot_index_operand(instruction: &str) -> Option<usize> {
+    match instruction {
+        "ss" | "sbs" => Some(1),
+        "ls" | "lbs" => Some(2),
+        "lbns" => Some(3),
+        _ => None,
+    }
+}
+
+/// Comparison instructions grouped by form; members of a row differ only in the comparison
+pub const COMPARISON_FAMILIES: &[&[&str]] = &[
+    &["slt", "sgt", "sle", "sge", "seq", "sne"],
+    &["sltz", "sgtz", "slez", "sgez", "seqz", "snez"],
+    &["blt", "bgt", "ble", "bge", "beq", "bne"],
+    &["bltz", "bgtz", "blez", "bgez", "beqz", "bnez"],
+    &["brlt", "brgt", "brle", "brge", "breq", "brne"],
+    &["brltz", "brgtz", "brlez", "brgez", "breqz", "brnez"],
+    &["bltal", "bgtal", "bleal", "bgeal", "beqal", "bneal"],
+    &["bltzal", "bgtzal", "blezal", "bgezal", "beqzal", "bnezal"],
+];
+
+/// The other members of an instruction's comparison family, opposite comparison first
+pub fn comparison_siblings(instruction: &str) -> Vec<&'static str> {
+    let Some(family) = COMPARISON_FAMILIES
+        .iter()
+        .find(|family| family.contains(&instruction))
+    else {
+        return Vec::new();
+    };
+    let index = family.iter().position(|x| *x == instruction).unwrap();
+    // Rows pair each comparison with its opposite: lt/ge, gt/le, eq/ne
+    let opposite = family[[3, 2, 1, 0, 5, 4][index]];
+    std::iter::once(opposite)
+        .chain(family.iter().copied().filter(|x| *x != instruction && *x != opposite))
+        .collect()
+}
+
 /* MANUAL DEFINITIONS DISABLED - NOW AUTO-GENERATED FROM game-sources/Enums.json
    See module 'generated' at top of file for auto-generated LOGIC_TYPES, etc.
 
@@ -451,6 +502,29 @@ impl Display for DataType {
     }
 }
 
+impl DataType {
+    /// Spelled-out name for prose such as signature help (`Display` gives the compact `r?` form)
+    pub fn describe(&self) -> &'static str {
+        match *self {
+            DataType::Number => "number",
+            DataType::Register => "register",
+            DataType::Device => "device",
+            DataType::LogicType => "logicType",
+            DataType::SlotLogicType => "logicSlotType",
+            DataType::Name => "name",
+            DataType::BatchMode => "batchMode",
+            DataType::ReagentMode => "reagentMode",
+        }
+    }
+}
+
+impl<'a> Union<'a> {
+    /// The accepted types spelled out, e.g. `register | number`
+    pub fn describe(&self) -> String {
+        self.0.iter().map(DataType::describe).collect::<Vec<_>>().join(" | ")
+    }
+}
+
 impl Display for InstructionSignature {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         for parameter in self.0 {
@@ -913,6 +987,104 @@ pub use _stationpedia_generated::*;
 // Generated from Enums.json at build time
 include!(concat!(env!("OUT_DIR"), "/enums_generated.rs"));
 
+/// When each slot logic type is worth reading, for newcomers to `ls`/`ss`/`lbs`: the game's own
+/// descriptions say what the value is but not which occupants have it.
+pub const SLOT_TYPE_NOTES: phf::Map<&'static str, &'static str> = phf_map! {
+    "Occupied" => "Any slot. Check it first: the other slot values read 0 for an empty slot.",
+    "OccupantHash" => "Any occupied slot. Compare with HASH(\"ItemName\") to tell which item sits there.",
+    "PrefabHash" => "Any occupied slot. Same as OccupantHash for items: the hash of the item's prefab.",
+    "Quantity" => "Stackable items (ores, ingots, food). Non-stacking items read 1.",
+    "MaxQuantity" => "Stackable items; compare with Quantity to see if the stack is full.",
+    "Damage" => "Items that wear out (tools, suits, canisters): 0 is pristine.",
+    "Class" => "Any occupied slot: the item's slot class as a number (see the SlotClass enum).",
+    "Charge" => "Batteries and battery-powered items in the slot.",
+    "ChargeRatio" => "Batteries and battery-powered items: charge as 0-1, handy for charger logic.",
+    "Pressure" => "Gas canisters and tanks in the slot (e.g. a canister in a filler or suit).",
+    "Temperature" => "Gas canisters and tanks in the slot; the gas inside, in Kelvin.",
+    "PressureWaste" => "Jetpacks and suits: the waste tank.",
+    "PressureAir" => "Jetpacks and suits: the air tank.",
+    "Efficiency" => "Plants in hydroponics trays and planters.",
+    "Health" => "Plants in hydroponics trays and planters.",
+    "Growth" => "Plants in hydroponics trays and planters: the growth stage.",
+    "Mature" => "Plants: 1 when ready to harvest.",
+    "MaturityRatio" => "Plants: progress to maturity as 0-1.",
+    "Seeding" => "Plants: 1 when harvesting yields seeds.",
+    "SeedingRatio" => "Plants: progress to seeding as 0-1.",
+    "HarvestedHash" => "Plants: hash of what harvesting will produce.",
+    "Open" => "Openable items in the slot, such as a canister's valve.",
+    "Lock" => "Lockable items in the slot.",
+    "Volume" => "Containers in the slot: their internal volume.",
+    "Mode" => "Items with modes, such as tools.",
+    "FilterType" => "Gas filters in the slot: the gas they filter.",
+    "ReferenceId" => "Any occupied slot: the item's unique id, for ld/sd-style access.",
+    "LineNumber" => "Programmable items (e.g. an IC chip in a housing slot).",
+    "TotalSlots" => "Items with their own slots, such as backpacks and toolbelts.",
+    "FreeSlots" => "Items with their own slots, such as backpacks and toolbelts.",
+    "On" => "Switchable items in the slot, such as a tool or light.",
+    "SortingClass" => "Any occupied slot: the class sorters use to route the item.",
+};
+
+/// Slot logic type docs: the game's description (when it has one) followed by the note on
+/// which occupants it applies to
+pub fn slot_type_doc(name: &str) -> Option<String> {
+    let description = SLOT_TYPE_DOCS
+        .get(name)
+        .copied()
+        .filter(|doc| !doc.starts_with("No description"));
+    let note = SLOT_TYPE_NOTES.get(name).copied();
+    match (description, note) {
+        (Some(description), Some(note)) => Some(format!("{}\n\n*Applies to:* {}", description, note)),
+        (Some(description), None) => Some(description.to_string()),
+        (None, Some(note)) => Some(format!("*Applies to:* {}", note)),
+        (None, None) => SLOT_TYPE_DOCS.get(name).map(|doc| doc.to_string()),
+    }
+}
+
+/// How each batch mode combines the values of the devices a batch instruction reaches: every
+/// device on the data network with the given prefab hash (and, for `lbn`/`lbns`, name hash)
+pub const BATCH_MODE_NOTES: phf::Map<&'static str, &'static str> = phf_map! {
+    "Average" => "The mean of the value across all matching devices: their sum divided by how many there are.",
+    "Sum" => "The values of all matching devices added together, e.g. the total charge of every battery.",
+    "Minimum" => "The lowest value among all matching devices.",
+    "Maximum" => "The highest value among all matching devices.",
+};
+
+/// Batch mode docs: the game's description (when it has one) followed by how the mode
+/// aggregates across the matching devices
+pub fn batch_mode_doc(name: &str) -> Option<String> {
+    let description = BATCH_MODE_DOCS.get(name).copied().filter(|doc| !doc.is_empty());
+    let note = BATCH_MODE_NOTES.get(name).copied();
+    match (description, note) {
+        (Some(description), Some(note)) => Some(format!("{}\n\n{}", description, note)),
+        (Some(description), None) => Some(description.to_string()),
+        (None, Some(note)) => Some(note.to_string()),
+        (None, None) => BATCH_MODE_DOCS.get(name).map(|doc| doc.to_string()),
+    }
+}
+
+/// Enum families whose members are the meaningful values of a logic type, e.g. the states a
+/// `Mode` setting takes on the devices that expose it. Logic types named after a family
+/// (`Color`, `EntityState`) need no entry.
+pub const LOGIC_TYPE_ENUM_FAMILIES: phf::Map<&'static str, &'static [&'static str]> = phf_map! {
+    "SoundAlert" => &["Sound"],
+    "Mode" => &[
+        "AirControl", "AirCon", "Vent", "PowerMode", "DisplayMode", "ElevatorMode",
+        "DaylightSensorMode", "TransmitterMode", "RobotMode", "RocketMode",
+    ],
+};
+
+/// Enum families a value written to `logic_type` is drawn from; empty when it takes plain numbers
+pub fn enum_families_for_logic_type(logic_type: &str) -> Vec<&'static str> {
+    if let Some(families) = LOGIC_TYPE_ENUM_FAMILIES.get(logic_type) {
+        return families.to_vec();
+    }
+    all_enum_entries()
+        .map(|(family, ..)| family)
+        .find(|family| *family == logic_type && *family != "LogicType")
+        .into_iter()
+        .collect()
+}
+
 /// Returns (value, description, deprecated) for a fully qualified enum name such as
 /// "LogicType.Power" or "PrinterInstruction.Print" if present.
 pub fn enum_info(qname: &str) -> Option<(i32, &'static str, bool)> {
@@ -947,6 +1119,15 @@ pub fn enum_info_case_insensitive(qname: &str) -> Option<(&'static str, i32, &'s
     None
 }
 
+/// Family-level deprecation from Enums.json (`"deprecated": true` on the family object).
+/// Returns `Some(replacement)` for a deprecated family, where `replacement` is the family
+/// recorded in `"replacedBy"`, if any.
+pub fn enum_family_deprecation(family: &str) -> Option<Option<&'static str>> {
+    ENUM_FAMILY_DEPRECATED
+        .get(family)
+        .map(|replacement| Some(*replacement).filter(|r| !r.is_empty()))
+}
+
 /// Convenience: logic type simple name to numeric value if present.
 pub fn logic_type_value(name: &str) -> Option<i32> {
     LOGIC_TYPE_NAME_TO_VALUE.get(name).cloned()
@@ -1007,6 +1188,40 @@ mod test {
         }
     }
 
+    #[test]
+    fn stack_access_signatures() {
+        let peek = INSTRUCTIONS["peek"].0;
+        assert_eq!(peek.len(), 1);
+        assert_eq!(peek[0].0, REGISTER.0);
+        for instruction in ["poke", "get", "getd", "put", "putd"] {
+            let index = stack_address_operand(instruction).unwrap();
+            assert_eq!(INSTRUCTIONS[instruction].0[index].0, VALUE.0, "{instruction}");
+        }
+        assert_eq!(stack_address_operand("peek"), None);
+    }
+
+    #[test]
+    fn slot_index_signatures() {
+        for instruction in ["ls", "ss", "lbs", "sbs", "lbns"] {
+            let index = slot_index_operand(instruction).unwrap();
+            let signature = INSTRUCTIONS[instruction].0;
+            assert_eq!(signature[index].0, VALUE.0, "{instruction}");
+            assert_eq!(signature[index + 1].0, SLOT_LOGIC_TYPE.0, "{instruction}");
+        }
+        assert_eq!(slot_index_operand("sd"), None);
+    }
+
+    #[test]
+    fn comparison_families_are_instructions() {
+        for instruction in COMPARISON_FAMILIES.iter().flat_map(|family| family.iter()) {
+            assert!(INSTRUCTIONS.contains_key(instruction), "{instruction}");
+        }
+        assert_eq!(comparison_siblings("slt")[0], "sge");
+        assert_eq!(comparison_siblings("beqz")[0], "bnez");
+        assert_eq!(comparison_siblings("brgt").len(), 5);
+        assert!(comparison_siblings("add").is_empty());
+    }
+
     #[test]
     fn matching_logic_types() {
         for logictype in LOGIC_TYPES.iter() {
@@ -1031,6 +1246,50 @@ mod test {
         }
     }
 
+    #[test]
+    fn slot_types_have_notes() {
+        for slottype in SLOT_TYPE_DOCS.keys().filter(|name| **name != "None") {
+            assert!(SLOT_TYPE_NOTES.contains_key(slottype), "{slottype} has no note");
+        }
+        let doc = slot_type_doc("OccupantHash").unwrap();
+        assert!(doc.starts_with("returns the hash") && doc.contains("*Applies to:*"));
+        assert_eq!(slot_type_doc("Volume").unwrap(), format!("*Applies to:* {}", SLOT_TYPE_NOTES["Volume"]));
+    }
+
+    #[test]
+    fn batch_modes_explain_aggregation() {
+        for mode in BATCH_MODES.iter() {
+            assert!(BATCH_MODE_NOTES.contains_key(mode), "{mode}");
+            assert!(batch_mode_doc(mode).unwrap().contains("matching devices"), "{mode}");
+        }
+    }
+
+    #[test]
+    fn logic_types_map_to_enum_families() {
+        let families: std::collections::HashSet<_> = all_enum_entries().map(|(family, ..)| family).collect();
+        for (logic_type, mapped) in LOGIC_TYPE_ENUM_FAMILIES.entries() {
+            assert!(logic_type_value(logic_type).is_some(), "{logic_type}");
+            for family in mapped.iter() {
+                assert!(families.contains(family), "{family}");
+            }
+        }
+        assert_eq!(enum_families_for_logic_type("Color"), vec!["Color"]);
+        assert_eq!(enum_families_for_logic_type("SoundAlert"), vec!["Sound"]);
+        assert!(enum_families_for_logic_type("Setting").is_empty());
+    }
+
+    #[test]
+    fn destination_only_signatures() {
+        // rand takes no inputs: its only operand is the destination register.
+        assert_eq!(INSTRUCTIONS.get("rand").unwrap().0.len(), 1);
+        assert!(INSTRUCTIONS.get("rand").unwrap().0[0].match_type(DataType::Register));
+        for op in ["sqrt", "abs", "ceil", "floor", "round", "trunc", "exp", "log"] {
+            let sig = INSTRUCTIONS.get(op).unwrap();
+            assert_eq!(sig.0.len(), 2, "{op} should take a destination and one value");
+            assert!(sig.0[0].match_type(DataType::Register), "{op} must write a register");
+        }
+    }
+
     #[test]
     fn channel_constants() {
         // Verify Channel0..Channel7 numeric values via Enums.json ingestion.
@@ -8,7 +8,12 @@
 //! - HASH() function completions for device names
 //! - Context-aware completions based on parameter types
 
-use crate::document::{DefinitionData, HasType};
+use crate::document::{
+    AliasValue, CompletionCategories, Configuration, DefineValue, DefinitionData, DocumentData,
+    HasType, TypeData,
+};
+use crate::hash_utils::DeviceHashes;
+use crate::tooltip_documentation::CustomDocs;
 use crate::instructions::{self, DataType};
 use crate::performance;
 use crate::tree_utils::{get_current_parameter, NodeEx};
@@ -17,6 +22,22 @@ use std::collections::HashMap;
 use tower_lsp::jsonrpc::Result;
 use tower_lsp::lsp_types::*;
 
+// Sort keys. Editors order completions by `sort_text`, falling back to the label, and `!`
+// sorts before letters and digits while `~` sorts after them. Keys set in this module follow
+// one scheme so items from different sources interleave predictably:
+// - `!...` ahead of plain labels: `!0000` the HASH(" snippet, `!0001...` stack and slot
+//   indices, `!1...` devices already used in the project, `!2...` preferred enum families
+// - `<label>~` directly after the item labelled `<label>` (an alias's pin after the alias)
+// - `~<group>...` after every plain label: `~0<index>` related comparisons, `~1<label>` logic
+//   types going the wrong way for a load or store
+
+/// Appended to a label to sort an item directly after it
+const SORT_AFTER_LABEL: &str = "~";
+/// Prefix for related comparisons offered after the typed mnemonic's own matches
+const SORT_RELATED_COMPARISON: &str = "~0";
+/// Prefix for logic types that no device accepts in the instruction's direction
+const SORT_WRONG_DIRECTION: &str = "~1";
+
 /// Main completion handler function that processes completion requests
 /// 
 /// # Arguments
@@ -32,30 +53,88 @@ pub async fn handle_completion(
     let _timer = performance::TimingGuard::new(&backend.perf_tracker, "lsp.server.completion");
     backend.perf_tracker.increment("lsp.server.completion.calls", 1);
 
-    let mut ret = Vec::new();
+    let mut response = complete(backend, params).await?;
+    if let Some(CompletionResponse::Array(items)) = response.as_mut() {
+        let device_hashes = backend.device_hashes.read().await;
+        rank_project_devices(&backend.device_usage, &device_hashes, items);
+    }
+    Ok(response)
+}
 
-    let uri = params.text_document_position.text_document.uri;
-    let original_position = params.text_document_position.position;
-    let position = {
-        let pos = params.text_document_position.position;
-        Position::from(tower_lsp::lsp_types::Position::new(
-            pos.line,
-            pos.character.saturating_sub(1),
-        ))
-    };
+/// Move device names already used in HASH("...") across open files ahead of the
+/// alphabetical list, most widely used first
+fn rank_project_devices(
+    device_usage: &dashmap::DashMap<Url, std::collections::HashSet<String>>,
+    device_hashes: &DeviceHashes,
+    items: &mut [CompletionItem],
+) {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for file in device_usage.iter() {
+        for name in file.value() {
+            *counts.entry(name.clone()).or_default() += 1;
+        }
+    }
+    if counts.is_empty() {
+        return;
+    }
+    for item in items.iter_mut() {
+        if item.sort_text.is_some() || device_hashes.get_device_hash(&item.label).is_none() {
+            continue;
+        }
+        if let Some(count) = counts.get(&item.label) {
+            item.sort_text = Some(format!("!1{:04}{}", 9999usize.saturating_sub(*count), item.label));
+        }
+    }
+}
 
+async fn complete(
+    backend: &crate::Backend,
+    params: CompletionParams,
+) -> Result<Option<CompletionResponse>> {
+    let uri = params.text_document_position.text_document.uri;
+    let config = backend.config.read().await.clone();
+    let device_hashes = backend.device_hashes.read().await;
+    let custom_docs = backend.custom_docs.read().await;
     let files = backend.files.read().await;
     let Some(file_data) = files.get(&uri) else {
         return Err(tower_lsp::jsonrpc::Error::invalid_request());
     };
+    completions_at(
+        &file_data.document_data,
+        &file_data.type_data,
+        params.text_document_position.position,
+        &config,
+        &device_hashes,
+        &custom_docs,
+    )
+}
 
-    let document = &file_data.document_data;
+/// Completion items at `original_position`, independent of the server state; shared by the
+/// `completion` request and the `ic10.analyzeAt` command
+pub(crate) fn completions_at(
+    document: &DocumentData,
+    type_data: &TypeData,
+    original_position: tower_lsp::lsp_types::Position,
+    config: &Configuration,
+    device_hashes: &DeviceHashes,
+    custom_docs: &CustomDocs,
+) -> Result<Option<CompletionResponse>> {
+    let mut ret = Vec::new();
+
+    let position = Position::from(tower_lsp::lsp_types::Position::new(
+        original_position.line,
+        original_position.character.saturating_sub(1),
+    ));
+
+    let categories = &config.completion_categories;
+    let verbose_detail = config.verbose_completion_detail;
+    let enums_require_prefix = config.enums_require_prefix;
 
     let Some(ref tree) = document.tree else {
         return Err(tower_lsp::jsonrpc::Error::internal_error());
     };
 
-    let Some(node) = backend.node_at_position(position, tree) else {
+    let Some(node) = tree.root_node().named_descendant_for_point_range(position.into(), position.into()) else {
         // Tree-sitter hasn't parsed this position yet (common after typing space)
         // Fall back to text-based completion logic
         let actual_line = document
@@ -103,11 +182,8 @@ pub async fn handle_completion(
                         let search_text = &text_up_to_cursor[start_pos + 6..];
                         let search_lower = search_text.to_lowercase();
 
-                        for hash_name in crate::device_hashes::DEVICE_NAME_TO_HASH.keys() {
-                            let hash_value = crate::device_hashes::DEVICE_NAME_TO_HASH[hash_name];
-                            let display_name = crate::device_hashes::HASH_TO_DISPLAY_NAME
-                                .get(&hash_value)
-                                .unwrap_or(hash_name);
+                        for (hash_name, hash_value) in device_hashes.device_hash_entries() {
+                            let display_name = device_hashes.get_device_name_for_hash(hash_value).unwrap_or(hash_name);
 
                             let matches = search_text.is_empty()
                                 || hash_name.to_lowercase().contains(&search_lower)
@@ -232,11 +308,8 @@ pub async fn handle_completion(
                     // Provide device name completions
                     #[allow(unused_variables)]
                     let mut match_count = 0;
-                    for hash_name in crate::device_hashes::DEVICE_NAME_TO_HASH.keys() {
-                        let hash_value = crate::device_hashes::DEVICE_NAME_TO_HASH[hash_name];
-                        let display_name = crate::device_hashes::HASH_TO_DISPLAY_NAME
-                            .get(&hash_value)
-                            .unwrap_or(hash_name);
+                    for (hash_name, hash_value) in device_hashes.device_hash_entries() {
+                        let display_name = device_hashes.get_device_name_for_hash(hash_value).unwrap_or(hash_name);
 
                         let matches = search_text.is_empty()
                             || hash_name.to_lowercase().contains(&search_lower)
@@ -279,7 +352,7 @@ pub async fn handle_completion(
         let cursor_pos = position.0.character as usize - node.start_position().column;
         let prefix = &text[..cursor_pos + 1];
 
-        instruction_completions(prefix, &mut ret);
+        instruction_completions(prefix, custom_docs, &mut ret);
     } else if let Some(node) = node.find_parent("invalid_instruction") {
         let raw = node.utf8_text(document.content.as_bytes()).unwrap();
         let lowered;
@@ -292,7 +365,7 @@ pub async fn handle_completion(
         let cursor_pos = position.0.character as usize - node.start_position().column;
         let prefix = &text[..cursor_pos + 1];
 
-        instruction_completions(prefix, &mut ret);
+        instruction_completions(prefix, custom_docs, &mut ret);
     } else if let Some(line_node) = node.find_parent("line") {
         let text = line_node.utf8_text(document.content.as_bytes()).unwrap();
         let cursor_pos = position.0.character as usize - line_node.start_position().column;
@@ -303,7 +376,7 @@ pub async fn handle_completion(
         let at_line_start = global_prefix.chars().all(char::is_whitespace);
 
         if at_line_start {
-            instruction_completions("", &mut ret);
+            instruction_completions("", custom_docs, &mut ret);
         } else {
             // Try to find instruction node that contains cursor, fallback to querying line
             let instruction_node_opt = if let Some(inst) = node.find_parent("instruction") {
@@ -313,7 +386,7 @@ pub async fn handle_completion(
                 // This handles cases where tree-sitter parsing is incomplete
                 let result = line_node.query(
                     "(instruction)@x",
-                    file_data.document_data.content.as_bytes(),
+                    document.content.as_bytes(),
                 );
                 if let Some(ref inst) = result {
                     // Calculate cursor byte position using original_position
@@ -429,12 +502,8 @@ pub async fn handle_completion(
                             let search_lower = search_text.to_lowercase();
                             let already_complete = actual_line[start_pos..].contains("\")");
 
-                            for hash_name in crate::device_hashes::DEVICE_NAME_TO_HASH.keys() {
-                                let hash_value =
-                                    crate::device_hashes::DEVICE_NAME_TO_HASH[hash_name];
-                                let display_name = crate::device_hashes::HASH_TO_DISPLAY_NAME
-                                    .get(&hash_value)
-                                    .unwrap_or(hash_name);
+                            for (hash_name, hash_value) in device_hashes.device_hash_entries() {
+                                let display_name = device_hashes.get_device_name_for_hash(hash_value).unwrap_or(hash_name);
 
                                 let matches = search_text.is_empty()
                                     || hash_name.to_lowercase().contains(&search_lower)
@@ -487,9 +556,9 @@ pub async fn handle_completion(
                                     false
                                 };
 
-                                if should_show_labels {
+                                if should_show_labels && categories.labels {
                                     // Add label completions
-                                    for (label_name, _) in &file_data.type_data.labels {
+                                    for label_name in type_data.labels.keys() {
                                         if prefix.is_empty() || label_name.starts_with(prefix) {
                                             ret.push(CompletionItem {
                                                 label: label_name.clone(),
@@ -513,33 +582,59 @@ pub async fn handle_completion(
 
                                 // For non-label parameters, provide both builtin, alias, and static completions
                                 if !should_show_labels {
-                                    param_completions_builtin(prefix, param_type, &mut ret, None);
-                                    // Add alias completions - these should appear first for register/device parameters
-                                    param_completions_dynamic(
+                                    param_completions_builtin(
                                         prefix,
-                                        &file_data.type_data.aliases,
-                                        " alias",
                                         param_type,
                                         &mut ret,
                                         None,
+                                        categories,
+                                        &type_data.aliases,
                                     );
+                                    // Add alias completions - these should appear first for register/device parameters
+                                    if categories.aliases {
+                                        param_completions_dynamic(
+                                            prefix,
+                                            &type_data.aliases,
+                                            " alias",
+                                            param_type,
+                                            &mut ret,
+                                            None,
+                                        );
+                                    }
                                     // Add define completions for numeric parameters
-                                    param_completions_dynamic(
-                                        prefix,
-                                        &file_data.type_data.defines,
-                                        " define",
-                                        param_type,
+                                    if categories.defines {
+                                        param_completions_dynamic(
+                                            prefix,
+                                            &type_data.defines,
+                                            " define",
+                                            param_type,
+                                            &mut ret,
+                                            None,
+                                        );
+                                    }
+                                    stack_address_completion(first_word, param_count, &mut ret);
+                                    slot_index_completion(first_word, param_count, &mut ret);
+                                    let detail =
+                                        static_detail(verbose_detail, first_word, param_count);
+                                    let start = ret.len();
+                                    param_completions_static(prefix, &detail, param_type, custom_docs, &mut ret);
+                                    restrict_to_device_logic_types(
+                                        first_word,
+                                        param_count,
+                                        actual_line,
+                                        type_data,
+                                        device_hashes,
                                         &mut ret,
-                                        None,
+                                        start,
                                     );
-                                    param_completions_static(prefix, "", param_type, &mut ret);
+                                    demote_wrong_direction(first_word, &mut ret, start);
                                 }
                             }
                         }
                     }
                 } else {
                     // Not continuing an instruction - offer instruction completions
-                    instruction_completions("", &mut ret);
+                    instruction_completions("", custom_docs, &mut ret);
                 }
 
                 return Ok(Some(CompletionResponse::Array(ret)));
@@ -550,7 +645,7 @@ pub async fn handle_completion(
             };
 
             let raw = operation_node
-                .utf8_text(file_data.document_data.content.as_bytes())
+                .utf8_text(document.content.as_bytes())
                 .unwrap();
             let lowered;
             let text: &str = if instructions::INSTRUCTIONS.contains_key(raw) {
@@ -690,11 +785,8 @@ pub async fn handle_completion(
                     };
 
                     // Provide device name completions
-                    for hash_name in crate::device_hashes::DEVICE_NAME_TO_HASH.keys() {
-                        let hash_value = crate::device_hashes::DEVICE_NAME_TO_HASH[hash_name];
-                        let display_name = crate::device_hashes::HASH_TO_DISPLAY_NAME
-                            .get(&hash_value)
-                            .unwrap_or(hash_name);
+                    for (hash_name, hash_value) in device_hashes.device_hash_entries() {
+                        let display_name = device_hashes.get_device_name_for_hash(hash_value).unwrap_or(hash_name);
 
                         let matches = search_text.is_empty()
                             || hash_name.to_lowercase().contains(&search_lower)
@@ -735,7 +827,7 @@ pub async fn handle_completion(
             if let Some(hash_func_node) = node.find_parent("hash_function") {
                 if let Some(hash_string_node) = hash_func_node.child_by_field_name("argument") {
                     let string_text = hash_string_node
-                        .utf8_text(file_data.document_data.content.as_bytes())
+                        .utf8_text(document.content.as_bytes())
                         .unwrap();
 
                     // Extract content without quotes
@@ -752,11 +844,8 @@ pub async fn handle_completion(
                     let search_lower = search_text.to_lowercase();
 
                     // Provide device name completions
-                    for hash_name in crate::device_hashes::DEVICE_NAME_TO_HASH.keys() {
-                        let hash_value = crate::device_hashes::DEVICE_NAME_TO_HASH[hash_name];
-                        let display_name = crate::device_hashes::HASH_TO_DISPLAY_NAME
-                            .get(&hash_value)
-                            .unwrap_or(hash_name);
+                    for (hash_name, hash_value) in device_hashes.device_hash_entries() {
+                        let display_name = device_hashes.get_device_name_for_hash(hash_value).unwrap_or(hash_name);
 
                         let matches = search_text.is_empty()
                             || hash_name.to_lowercase().contains(&search_lower)
@@ -826,57 +915,65 @@ pub async fn handle_completion(
                 }
 
                 // Add all defined aliases and defines to used_items
-                for alias_name in file_data.type_data.aliases.keys() {
+                for alias_name in type_data.aliases.keys() {
                     used_items.insert(alias_name.clone());
                 }
-                for define_name in file_data.type_data.defines.keys() {
+                for define_name in type_data.defines.keys() {
                     used_items.insert(define_name.clone());
                 }
 
                 // 1. Show built-in registers (if valid)
-                param_completions_builtin(prefix, param_type, &mut ret, Some(&used_items));
-
-                // 2. Show aliases (if valid)
-                param_completions_dynamic(
+                param_completions_builtin(
                     prefix,
-                    &file_data.type_data.aliases,
-                    " alias",
                     param_type,
                     &mut ret,
                     Some(&used_items),
+                    categories,
+                    &type_data.aliases,
                 );
 
+                // 2. Show aliases (if valid)
+                if categories.aliases {
+                    param_completions_dynamic(
+                        prefix,
+                        &type_data.aliases,
+                        " alias",
+                        param_type,
+                        &mut ret,
+                        Some(&used_items),
+                    );
+                }
+
                 // 3. Show defines
-                param_completions_dynamic(
-                    prefix,
-                    &file_data.type_data.defines,
-                    " define",
-                    param_type,
-                    &mut ret,
-                    Some(&used_items),
-                );
+                if categories.defines {
+                    param_completions_dynamic(
+                        prefix,
+                        &type_data.defines,
+                        " define",
+                        param_type,
+                        &mut ret,
+                        Some(&used_items),
+                    );
+                }
                 return Ok(Some(CompletionResponse::Array(ret)));
             }
 
             // Legacy preproc_string support (for backwards compatibility)
             if let Some(preproc_string_node) = instruction_node.query(
                 "(preproc_string)@x",
-                file_data.document_data.content.as_bytes(),
+                document.content.as_bytes(),
             ) {
                 let string_text = preproc_string_node
-                    .utf8_text(file_data.document_data.content.as_bytes())
+                    .utf8_text(document.content.as_bytes())
                     .unwrap();
 
                 let start_entries = ret.len();
 
                 // Use comprehensive device registry with fuzzy search
-                for hash_name in crate::device_hashes::DEVICE_NAME_TO_HASH.keys() {
+                for (hash_name, hash_value) in device_hashes.device_hash_entries() {
                     // Fuzzy search: match if search text appears anywhere in device name or display name
                     let search_lower = string_text.to_lowercase();
-                    let hash_value = crate::device_hashes::DEVICE_NAME_TO_HASH[hash_name];
-                    let display_name = crate::device_hashes::HASH_TO_DISPLAY_NAME
-                        .get(&hash_value)
-                        .unwrap_or(hash_name);
+                    let display_name = device_hashes.get_device_name_for_hash(hash_value).unwrap_or(hash_name);
 
                     let matches = hash_name.to_lowercase().contains(&search_lower)
                         || display_name.to_lowercase().contains(&search_lower);
@@ -904,14 +1001,16 @@ pub async fn handle_completion(
             // Context-aware completions based on parameter type
             if !text.starts_with("br") && text.starts_with("b") || text == "j" || text == "jal" {
                 // Branch instructions - ONLY show labels
-                param_completions_dynamic(
-                    prefix,
-                    &file_data.type_data.labels,
-                    " label",
-                    param_type,
-                    &mut ret,
-                    None,
-                );
+                if categories.labels {
+                    param_completions_dynamic(
+                        prefix,
+                        &type_data.labels,
+                        " label",
+                        param_type,
+                        &mut ret,
+                        None,
+                    );
+                }
             } else {
                 // Regular instructions - prioritize script-specific items first
                 // Collect items that are actually used in the script for smart sorting
@@ -942,10 +1041,10 @@ pub async fn handle_completion(
                 }
 
                 // Add all defined aliases and defines to used_items
-                for alias_name in file_data.type_data.aliases.keys() {
+                for alias_name in type_data.aliases.keys() {
                     used_items.insert(alias_name.clone());
                 }
-                for define_name in file_data.type_data.defines.keys() {
+                for define_name in type_data.defines.keys() {
                     used_items.insert(define_name.clone());
                 }
 
@@ -962,45 +1061,82 @@ pub async fn handle_completion(
 
                 if is_static_only {
                     // For static-only parameters, ONLY show the predefined constants
-                    param_completions_static("", "", param_type, &mut ret);
+                    let detail = static_detail(verbose_detail, text, current_param);
+                    let start = ret.len();
+                    param_completions_static("", &detail, param_type, custom_docs, &mut ret);
+                    restrict_to_device_logic_types(
+                        text,
+                        current_param,
+                        actual_line,
+                        type_data,
+                        device_hashes,
+                        &mut ret,
+                        start,
+                    );
+                    demote_wrong_direction(text, &mut ret, start);
                 } else {
                     // For other parameters, show the full completion list
+                    stack_address_completion(text, current_param, &mut ret);
+                    slot_index_completion(text, current_param, &mut ret);
                     // 0. Show built-in registers and devices first (always available)
-                    param_completions_builtin(prefix, param_type, &mut ret, Some(&used_items));
-
-                    // 1. Show aliases (registers and devices) - MOST RELEVANT, script-specific
-                    param_completions_dynamic(
+                    param_completions_builtin(
                         prefix,
-                        &file_data.type_data.aliases,
-                        " alias",
                         param_type,
                         &mut ret,
                         Some(&used_items),
+                        categories,
+                        &type_data.aliases,
                     );
 
+                    // 1. Show aliases (registers and devices) - MOST RELEVANT, script-specific
+                    if categories.aliases {
+                        param_completions_dynamic(
+                            prefix,
+                            &type_data.aliases,
+                            " alias",
+                            param_type,
+                            &mut ret,
+                            Some(&used_items),
+                        );
+                    }
+
                     // 2. Show defines (often used for device hashes and constants) - script-specific
-                    param_completions_dynamic(
-                        prefix,
-                        &file_data.type_data.defines,
-                        " define",
-                        param_type,
-                        &mut ret,
-                        Some(&used_items),
-                    );
+                    if categories.defines {
+                        param_completions_dynamic(
+                            prefix,
+                            &type_data.defines,
+                            " define",
+                            param_type,
+                            &mut ret,
+                            Some(&used_items),
+                        );
+                    }
 
                     // 3. Show labels (less common for non-branch instructions) - script-specific
-                    param_completions_dynamic(
-                        prefix,
-                        &file_data.type_data.labels,
-                        " label",
-                        param_type,
-                        &mut ret,
-                        Some(&used_items),
-                    );
+                    if categories.labels {
+                        param_completions_dynamic(
+                            prefix,
+                            &type_data.labels,
+                            " label",
+                            param_type,
+                            &mut ret,
+                            Some(&used_items),
+                        );
+                    }
 
                     // 4. Show enum completions last - global numeric constants
-                    if param_type.match_type(DataType::Number) {
-                        enum_completions(prefix, param_type, &mut ret);
+                    // (optionally only once the user has started typing one)
+                    let enum_prefix_ok = !enums_require_prefix
+                        || prefix.len() >= 2
+                        || prefix.contains('.');
+                    // A slot number is never an enum member; the indices above cover it
+                    let slot_index = instructions::slot_index_operand(text) == Some(current_param);
+                    if categories.enums
+                        && enum_prefix_ok
+                        && !slot_index
+                        && param_type.match_type(DataType::Number)
+                    {
+                        enum_completions(prefix, param_type, text, current_param, actual_line, &mut ret);
                     }
 
                     // Final sort: prioritize defines for numeric/value parameters
@@ -1046,39 +1182,207 @@ pub async fn handle_completion(
 // ============================================================================
 
 /// Provides instruction completions based on prefix matching
-fn instruction_completions(prefix: &str, completions: &mut Vec<CompletionItem>) {
+fn instruction_completions(prefix: &str, custom_docs: &CustomDocs, completions: &mut Vec<CompletionItem>) {
     let start_entries = completions.len();
     for (instruction, _signature) in instructions::INSTRUCTIONS.entries() {
         if instruction.starts_with(prefix) {
-            // Use labeled syntax but only show the operand suffix in the detail
-            let full_syntax = crate::tooltip_documentation::get_instruction_syntax(instruction);
-            let operand_suffix_core = full_syntax
-                .strip_prefix(&format!("{} ", instruction))
-                .unwrap_or(full_syntax.as_str())
-                .to_string();
-            let operand_suffix = if operand_suffix_core.is_empty() {
-                String::new()
-            } else {
-                format!(" {}", operand_suffix_core)
-            };
-            completions.push(CompletionItem {
-                label: instruction.to_string(),
-                label_details: Some(CompletionItemLabelDetails {
-                    // Show only operands in detail to prevent duplicated mnemonic
-                    detail: Some(operand_suffix),
-                    description: None,
-                }),
-                kind: Some(CompletionItemKind::FUNCTION),
-                documentation: instructions::INSTRUCTION_DOCS
-                    .get(instruction)
-                    .map(|x| Documentation::String(x.to_string())),
-                deprecated: Some(*instruction == "label"),
-                ..Default::default()
-            });
+            completions.push(instruction_completion_item(instruction, custom_docs));
         }
     }
     let length = completions.len();
     completions[start_entries..length].sort_by(|x, y| x.label.cmp(&y.label));
+
+    // A fully typed comparison also offers the rest of its family, trailing the prefix matches
+    for (index, sibling) in instructions::comparison_siblings(prefix).into_iter().enumerate() {
+        if completions[start_entries..].iter().any(|item| item.label == sibling) {
+            continue;
+        }
+        let mut item = instruction_completion_item(sibling, custom_docs);
+        if let Some(details) = item.label_details.as_mut() {
+            details.description = Some("opposite/related comparison".to_string());
+        }
+        // Keep the editor from filtering these out against the typed mnemonic
+        item.filter_text = Some(prefix.to_string());
+        item.sort_text = Some(format!("{SORT_RELATED_COMPARISON}{index}"));
+        completions.push(item);
+    }
+}
+
+fn instruction_completion_item(instruction: &str, custom_docs: &CustomDocs) -> CompletionItem {
+    // Use labeled syntax but only show the operand suffix in the detail
+    let full_syntax = crate::tooltip_documentation::get_instruction_syntax(instruction);
+    let operand_suffix_core = full_syntax
+        .strip_prefix(&format!("{} ", instruction))
+        .unwrap_or(full_syntax.as_str())
+        .to_string();
+    let operand_suffix = if operand_suffix_core.is_empty() {
+        String::new()
+    } else {
+        format!(" {}", operand_suffix_core)
+    };
+    CompletionItem {
+        label: instruction.to_string(),
+        label_details: Some(CompletionItemLabelDetails {
+            // Show only operands in detail to prevent duplicated mnemonic
+            detail: Some(operand_suffix),
+            description: None,
+        }),
+        kind: Some(CompletionItemKind::FUNCTION),
+        documentation: custom_docs
+            .instruction_doc(instruction)
+            .map(|x| Documentation::String(x.to_string())),
+        deprecated: Some(instruction == "label"),
+        // Saving a register is the common case; the tab stop selects it for replacement
+        insert_text: (instruction == "push").then(|| "push ${1:r0}".to_string()),
+        insert_text_format: (instruction == "push").then_some(InsertTextFormat::SNIPPET),
+        ..Default::default()
+    }
+}
+
+/// For the address operand of stack instructions, lead with a placeholder that states the
+/// valid range; registers and defines follow from the regular completions
+fn stack_address_completion(instruction: &str, param_index: usize, completions: &mut Vec<CompletionItem>) {
+    if instructions::stack_address_operand(instruction) != Some(param_index) {
+        return;
+    }
+    completions.push(CompletionItem {
+        label: "0".to_string(),
+        label_details: Some(CompletionItemLabelDetails {
+            description: None,
+            detail: Some(format!(" stack address (0-{})", instructions::STACK_SIZE - 1)),
+        }),
+        kind: Some(CompletionItemKind::VALUE),
+        documentation: Some(Documentation::String(format!(
+            "Stack addresses run from 0 to {}. A register holding the address also works.",
+            instructions::STACK_SIZE - 1
+        ))),
+        sort_text: Some("!0001".to_string()),
+        ..Default::default()
+    });
+}
+
+/// Slot indices offered for the slot operand of `ls`/`ss`/`lbs`/`sbs`/`lbns`, with what the
+/// slot usually holds on the game's machines
+const SLOT_INDEX_MEANINGS: [&str; 6] = [
+    "first slot; the import slot on machines",
+    "second slot; the export slot on machines",
+    "third slot",
+    "fourth slot",
+    "fifth slot",
+    "sixth slot",
+];
+
+/// For the slot operand of slot instructions, lead with the literal indices; registers,
+/// aliases and defines follow from the regular completions since the slot can be dynamic
+fn slot_index_completion(instruction: &str, param_index: usize, completions: &mut Vec<CompletionItem>) {
+    if instructions::slot_index_operand(instruction) != Some(param_index) {
+        return;
+    }
+    for (index, meaning) in SLOT_INDEX_MEANINGS.iter().enumerate() {
+        completions.push(CompletionItem {
+            label: index.to_string(),
+            label_details: Some(CompletionItemLabelDetails {
+                description: None,
+                detail: Some(format!(" slot index ({meaning})")),
+            }),
+            kind: Some(CompletionItemKind::VALUE),
+            documentation: Some(Documentation::String(
+                "Slot indices start at 0. The number of slots depends on the device.".to_string(),
+            )),
+            sort_text: Some(format!("!0001{index}")),
+            ..Default::default()
+        });
+    }
+}
+
+/// Resolve a device alias to its prefab: a `HASH("...")` define on an adjacent line, or else
+/// the alias name itself matching a prefab name
+fn device_alias_prefab<'a>(alias: &str, type_data: &TypeData, device_hashes: &'a DeviceHashes) -> Option<&'a str> {
+    let data = type_data.aliases.get(alias)?;
+    if !matches!(data.value, AliasValue::Device(_)) {
+        return None;
+    }
+    let line = data.range.0.start.line;
+    type_data
+        .defines
+        .values()
+        .filter(|define| matches!(define.value, DefineValue::FunctionCall(_)))
+        .filter(|define| define.range.0.start.line.abs_diff(line) <= 1)
+        .find_map(|define| define.value.resolved_numeric())
+        .and_then(|hash| device_hashes.get_prefab_name_for_hash(hash))
+        .or_else(|| device_hashes.prefab_for_alias_name(alias))
+}
+
+/// For the logic type of `l`/`s` on a device alias whose device is known, keep only the
+/// logic types that device can read (`l`) or write (`s`). Completions from `start` onward are
+/// filtered; an unresolved device keeps the full list.
+fn restrict_to_device_logic_types(
+    instruction: &str,
+    param_index: usize,
+    line: &str,
+    type_data: &TypeData,
+    device_hashes: &DeviceHashes,
+    completions: &mut Vec<CompletionItem>,
+    start: usize,
+) {
+    let (device_index, write) = match (instruction, param_index) {
+        ("l", 2) => (1, false),
+        ("s", 1) => (0, true),
+        _ => return,
+    };
+    let Some(device) = line.split_whitespace().nth(device_index + 1) else {
+        return;
+    };
+    let Some(logic_types) = device_alias_prefab(device, type_data, device_hashes)
+        .and_then(crate::descriptions::get_device_logic_types)
+    else {
+        return;
+    };
+    let allowed: Vec<&str> = logic_types
+        .iter()
+        .filter(|(_, readable, writable)| if write { *writable } else { *readable })
+        .map(|(name, _, _)| *name)
+        .collect();
+    if allowed.is_empty() {
+        return;
+    }
+    let mut index = 0;
+    completions.retain(|item| {
+        index += 1;
+        index <= start || allowed.contains(&item.label.as_str())
+    });
+}
+
+/// For load and store instructions, move logic types that no device accepts in that direction
+/// (a read-only type for `s`, a write-only one for `l`) to the end of the completions from
+/// `start` onward, noting why. They stay listed: the device table may lag the game.
+fn demote_wrong_direction(instruction: &str, completions: &mut [CompletionItem], start: usize) {
+    let write = match instruction {
+        "l" | "lb" | "lbn" | "lbs" | "lbns" | "ls" => false,
+        "s" | "sb" | "sbn" | "sbs" | "ss" => true,
+        _ => return,
+    };
+    let wrong_way = |item: &CompletionItem| {
+        crate::descriptions::logic_type_direction(&item.label)
+            .is_some_and(|direction| if write { !direction.writable() } else { !direction.readable() })
+    };
+    for item in completions[start..].iter_mut().filter(|item| wrong_way(item)) {
+        item.sort_text = Some(format!("{SORT_WRONG_DIRECTION}{}", item.label));
+        item.label_details.get_or_insert_with(Default::default).description =
+            Some(if write { "read-only" } else { "write-only" }.to_string());
+    }
+    completions[start..]
+        .sort_by_key(|item| item.sort_text.as_deref().is_some_and(|sort| sort.starts_with(SORT_WRONG_DIRECTION)));
+}
+
+/// Detail shown next to static completions: the operand's role name when verbose detail is on
+fn static_detail(verbose: bool, instruction: &str, param_index: usize) -> String {
+    if !verbose {
+        return String::new();
+    }
+    crate::tooltip_documentation::get_parameter_name(instruction, param_index)
+        .map(|name| format!(" {}", name))
+        .unwrap_or_default()
 }
 
 /// Provides static parameter completions (LogicType, SlotLogicType, BatchMode)
@@ -1086,6 +1390,7 @@ fn param_completions_static(
     prefix: &str,
     detail: &str,
     param_type: &instructions::Union,
+    custom_docs: &CustomDocs,
     completions: &mut Vec<CompletionItem>,
 ) {
     use instructions::DataType;
@@ -1108,7 +1413,23 @@ fn param_completions_static(
 
         for entry in map.entries() {
             let name = *entry.0;
-            let docs = *entry.1;
+            let docs = match typ {
+                DataType::LogicType => {
+                    custom_docs.logic_type_doc(name).unwrap_or(entry.1).to_string()
+                }
+                DataType::SlotLogicType => {
+                    instructions::slot_type_doc(name).unwrap_or_else(|| entry.1.to_string())
+                }
+                DataType::BatchMode => {
+                    instructions::batch_mode_doc(name).unwrap_or_else(|| entry.1.to_string())
+                }
+                _ => entry.1.to_string(),
+            };
+            // Slot types say which occupants they apply to right in the list
+            let item_detail = match typ {
+                DataType::SlotLogicType => instructions::SLOT_TYPE_NOTES.get(name).map(|note| note.to_string()),
+                _ => None,
+            };
             // Case-insensitive prefix match
             if prefix_trimmed.is_empty() || name.to_ascii_lowercase().starts_with(&prefix_lower) {
                 completions.push(CompletionItem {
@@ -1118,7 +1439,8 @@ fn param_completions_static(
                         detail: Some(detail.to_string()),
                     }),
                     kind: Some(CompletionItemKind::FIELD),
-                    documentation: Some(Documentation::String(docs.to_string())),
+                    detail: item_detail,
+                    documentation: Some(Documentation::String(docs)),
                     ..Default::default()
                 });
             }
@@ -1128,20 +1450,35 @@ fn param_completions_static(
     completions[start_entries..length].sort_by(|x, y| x.label.cmp(&y.label));
 }
 
-/// Provides built-in completions (registers and devices)
+/// Provides built-in completions: the registers and device pins the parameter accepts. A pin
+/// with aliases names them in its description, and typing an alias also offers the pin it
+/// stands for.
 fn param_completions_builtin(
     prefix: &str,
     param_type: &instructions::Union,
     completions: &mut Vec<CompletionItem>,
     used_items: Option<&std::collections::HashSet<String>>,
+    categories: &CompletionCategories,
+    aliases: &HashMap<String, DefinitionData<AliasValue>>,
 ) {
     use instructions::DataType;
 
     let prefix_trimmed = prefix.trim_start();
     let start_entries = completions.len();
+    let aliased = |pin: &str| {
+        let mut names: Vec<&str> = aliases
+            .iter()
+            .filter(|(_, alias)| alias.value.to_string() == pin)
+            .map(|(name, _)| name.as_str())
+            .collect();
+        names.sort_unstable();
+        (!names.is_empty()).then(|| format!("alias {}", names.join(", ")))
+    };
 
     // Show registers if parameter accepts Register or Number
-    if param_type.match_type(DataType::Register) || param_type.match_type(DataType::Number) {
+    if categories.registers
+        && (param_type.match_type(DataType::Register) || param_type.match_type(DataType::Number))
+    {
         // Standard registers r0-r15
         for i in 0..=15 {
             let reg = format!("r{}", i);
@@ -1149,7 +1486,7 @@ fn param_completions_builtin(
                 completions.push(CompletionItem {
                     label: reg.clone(),
                     label_details: Some(CompletionItemLabelDetails {
-                        description: None,
+                        description: aliased(&reg),
                         detail: Some(" register".to_string()),
                     }),
                     kind: Some(CompletionItemKind::VARIABLE),
@@ -1165,7 +1502,7 @@ fn param_completions_builtin(
                 completions.push(CompletionItem {
                     label: special.to_string(),
                     label_details: Some(CompletionItemLabelDetails {
-                        description: None,
+                        description: aliased(special),
                         detail: Some(" register".to_string()),
                     }),
                     kind: Some(CompletionItemKind::VARIABLE),
@@ -1181,7 +1518,7 @@ fn param_completions_builtin(
     }
 
     // Show devices if parameter accepts Device
-    if param_type.match_type(DataType::Device) {
+    if categories.devices && param_type.match_type(DataType::Device) {
         // Standard devices d0-d5
         for i in 0..=5 {
             let dev = format!("d{}", i);
@@ -1189,7 +1526,7 @@ fn param_completions_builtin(
                 completions.push(CompletionItem {
                     label: dev.clone(),
                     label_details: Some(CompletionItemLabelDetails {
-                        description: None,
+                        description: aliased(&dev),
                         detail: Some(" device".to_string()),
                     }),
                     kind: Some(CompletionItemKind::VARIABLE),
@@ -1204,7 +1541,7 @@ fn param_completions_builtin(
             completions.push(CompletionItem {
                 label: "db".to_string(),
                 label_details: Some(CompletionItemLabelDetails {
-                    description: None,
+                    description: aliased("db"),
                     detail: Some(" device".to_string()),
                 }),
                 kind: Some(CompletionItemKind::VARIABLE),
@@ -1216,6 +1553,36 @@ fn param_completions_builtin(
         }
     }
 
+    // Typing an alias also offers its pin, for pins the prefix itself doesn't reach
+    if !prefix_trimmed.is_empty() {
+        for (name, alias) in aliases {
+            let accepted = match &alias.value {
+                AliasValue::Register(_) => {
+                    categories.registers
+                        && (param_type.match_type(DataType::Register)
+                            || param_type.match_type(DataType::Number))
+                }
+                AliasValue::Device(_) => categories.devices && param_type.match_type(DataType::Device),
+            };
+            let pin = alias.value.to_string();
+            if !accepted || !name.starts_with(prefix_trimmed) || pin.starts_with(prefix_trimmed) {
+                continue;
+            }
+            completions.push(CompletionItem {
+                label: pin.clone(),
+                label_details: Some(CompletionItemLabelDetails {
+                    description: Some(format!("alias {}", name)),
+                    detail: Some(" pin".to_string()),
+                }),
+                kind: Some(CompletionItemKind::VARIABLE),
+                documentation: Some(Documentation::String(format!("The pin behind alias {}", name))),
+                filter_text: Some(name.clone()),
+                sort_text: Some(format!("{}{SORT_AFTER_LABEL}", name)),
+                ..Default::default()
+            });
+        }
+    }
+
     let length = completions.len();
     // Apply usage-based sorting if provided
     if let Some(used) = used_items {
@@ -1277,16 +1644,34 @@ fn param_completions_dynamic<T>(
     }
 }
 
-/// Provides enum completions for numeric parameters
+/// The logic type named earlier on the line when `param_index` is the value it receives
+/// (`s d0 Mode |`, `sb hash Color |`)
+fn written_logic_type<'a>(instruction: &str, param_index: usize, line: &'a str) -> Option<&'a str> {
+    let signature = instructions::INSTRUCTIONS.get(instruction)?;
+    let logic_type_index = signature.0[..param_index]
+        .iter()
+        .position(|param| param.match_type(DataType::LogicType))?;
+    line.split_whitespace()
+        .nth(logic_type_index + 1)
+        .filter(|name| instructions::logic_type_value(name).is_some())
+}
+
+/// Provides enum completions for numeric parameters. Members of the families the written logic
+/// type takes (see [`instructions::enum_families_for_logic_type`]) are ranked ahead of the rest.
 fn enum_completions(
     prefix: &str,
     param_type: &instructions::Union,
+    instruction: &str,
+    param_index: usize,
+    line: &str,
     completions: &mut Vec<CompletionItem>,
 ) {
     use instructions::DataType;
     if !param_type.match_type(DataType::Number) {
         return;
     }
+    let logic_type = written_logic_type(instruction, param_index, line);
+    let preferred = logic_type.map(instructions::enum_families_for_logic_type).unwrap_or_default();
     let prefix_lower = prefix.trim_start().to_ascii_lowercase();
     let start_entries = completions.len();
     for (family, member, qualified, value, desc, deprecated) in instructions::all_enum_entries() {
@@ -1307,7 +1692,9 @@ fn enum_completions(
                 label: display_label.clone(),
                 label_details: Some(CompletionItemLabelDetails {
                     detail: Some(format!("= {}", value)),
-                    description: None,
+                    description: logic_type
+                        .filter(|_| preferred.contains(&family))
+                        .map(|logic_type| format!("{} value", logic_type)),
                 }),
                 kind: Some(CompletionItemKind::ENUM),
                 documentation: if desc.is_empty() {
@@ -1316,6 +1703,7 @@ fn enum_completions(
                     Some(Documentation::String(desc.to_string()))
                 },
                 deprecated: Some(deprecated),
+                sort_text: preferred.contains(&family).then(|| format!("!2{}", display_label)),
                 insert_text: Some(display_label),
                 ..Default::default()
             });
@@ -1324,3 +1712,242 @@ fn enum_completions(
     let length = completions.len();
     completions[start_entries..length].sort_by(|x, y| x.label.cmp(&y.label));
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Labels offered at the `|` in `script` with `config`
+    fn labels_at(script: &str, config: &Configuration) -> Vec<String> {
+        let cursor = script.find('|').unwrap();
+        let content = script.replacen('|', "", 1);
+        let mut parser = tree_sitter::Parser::new();
+        parser.set_language(tree_sitter_ic10::language()).unwrap();
+        let document = DocumentData {
+            url: Url::parse("file:///test.ic10").unwrap(),
+            tree: parser.parse(&content, None),
+            content: content.clone(),
+            parser,
+        };
+        let type_data = crate::lsp_diagnostics::collect_type_data(
+            document.tree.as_ref().unwrap(),
+            &content,
+            &mut Vec::new(),
+        );
+        let before = &script[..cursor];
+        let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+        let position = tower_lsp::lsp_types::Position::new(
+            before.matches('\n').count() as u32,
+            (cursor - line_start) as u32,
+        );
+        let response = completions_at(
+            &document,
+            &type_data,
+            position,
+            config,
+            &DeviceHashes::default(),
+            &CustomDocs::default(),
+        );
+        match response {
+            Ok(Some(CompletionResponse::Array(items))) => items.into_iter().map(|item| item.label).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    #[test]
+    fn completion_categories_gate_parameter_completions() {
+        // (script, item its category contributes, category flag)
+        type Flag = fn(&mut CompletionCategories) -> &mut bool;
+        let rows: [(&str, &str, Flag); 6] = [
+            ("add r0 C| r2\n", "Color.Red", |c| &mut c.enums),
+            ("define Max 5\nmove r0 |\n", "Max", |c| &mut c.defines),
+            ("alias pump d0\nl r0 |\n", "pump", |c| &mut c.aliases),
+            ("start:\nyield\nj |\n", "start", |c| &mut c.labels),
+            ("move r0 |\n", "r1", |c| &mut c.registers),
+            ("l r0 |\n", "d1", |c| &mut c.devices),
+        ];
+        for (script, item, flag) in rows {
+            let mut config = Configuration::default();
+            let offered = |config: &Configuration| labels_at(script, config).iter().any(|label| label == item);
+            assert!(offered(&config), "{} missing from {:?}", item, script);
+            *flag(&mut config.completion_categories) = false;
+            assert!(!offered(&config), "{} offered with its category off in {:?}", item, script);
+        }
+    }
+
+    #[test]
+    fn enums_can_wait_for_a_typed_prefix() {
+        let offers_enum = |prefix: &str, enums_require_prefix: bool| {
+            let config = Configuration { enums_require_prefix, ..Default::default() };
+            let script = format!("add r0 {}| r2\n", prefix);
+            labels_at(&script, &config).iter().any(|label| label == "Color.Red")
+        };
+        for (prefix, offered) in [("", false), ("C", false), ("Co", true), ("Color.", true)] {
+            assert_eq!(offers_enum(prefix, true), offered, "prefix {:?}", prefix);
+        }
+        assert!(offers_enum("", false));
+    }
+
+    #[test]
+    fn used_devices_rank_first() {
+        let usage = dashmap::DashMap::new();
+        let a = Url::parse("file:///a.ic10").unwrap();
+        let b = Url::parse("file:///b.ic10").unwrap();
+        usage.insert(a, ["StructureGasSensor".to_string()].into_iter().collect());
+        usage.insert(
+            b,
+            ["StructureGasSensor".to_string(), "StructureBattery".to_string()]
+                .into_iter()
+                .collect(),
+        );
+        let item = |label: &str| CompletionItem {
+            label: label.to_string(),
+            ..Default::default()
+        };
+        let mut items = vec![item("StructureBattery"), item("StructureGasSensor"), item("StructureAirlock")];
+        rank_project_devices(&usage, &DeviceHashes::default(), &mut items);
+        assert!(items[1].sort_text < items[0].sort_text);
+        assert_eq!(items[2].sort_text, None);
+    }
+
+    #[test]
+    fn logic_types_follow_the_aliased_device() {
+        let mut type_data = TypeData::default();
+        for (line, name) in [(0, "GasSensor"), (1, "thing")] {
+            let range = crate::types::Range(tower_lsp::lsp_types::Range::new(
+                tower_lsp::lsp_types::Position::new(line, 0),
+                tower_lsp::lsp_types::Position::new(line, 10),
+            ));
+            type_data.aliases.insert(
+                name.to_string(),
+                DefinitionData::new(range, AliasValue::Device("d0".to_string())),
+            );
+        }
+        let param_type = &instructions::INSTRUCTIONS.get("l").unwrap().0[2];
+        let labels = |line: &str| {
+            let mut items = Vec::new();
+            param_completions_static("", "", param_type, &CustomDocs::default(), &mut items);
+            restrict_to_device_logic_types("l", 2, line, &type_data, &DeviceHashes::default(), &mut items, 0);
+            items.into_iter().map(|item| item.label).collect::<Vec<_>>()
+        };
+
+        let sensor = labels("l r0 GasSensor ");
+        assert!(sensor.contains(&"Pressure".to_string()));
+        assert!(!sensor.contains(&"On".to_string()));
+        assert!(labels("l r0 thing ").contains(&"On".to_string()));
+    }
+
+    #[test]
+    fn logic_types_going_the_wrong_way_are_demoted() {
+        let items = |instruction: &str, index: usize| {
+            let param_type = &instructions::INSTRUCTIONS.get(instruction).unwrap().0[index];
+            let mut items = Vec::new();
+            param_completions_static("", "", param_type, &CustomDocs::default(), &mut items);
+            demote_wrong_direction(instruction, &mut items, 0);
+            items
+        };
+        let demoted = |items: &[CompletionItem]| {
+            items
+                .iter()
+                .skip_while(|item| item.sort_text.is_none())
+                .map(|item| (item.label.clone(), item.label_details.clone().unwrap().description.unwrap()))
+                .collect::<Vec<_>>()
+        };
+
+        let store = items("s", 1);
+        let store_demoted = demoted(&store);
+        assert!(store_demoted.contains(&("Temperature".to_string(), "read-only".to_string())));
+        assert!(!store_demoted.iter().any(|(label, _)| label == "On" || label == "ClearMemory"));
+        assert_eq!(store.len(), items("l", 2).len());
+
+        let load_demoted = demoted(&items("l", 2));
+        assert!(load_demoted.contains(&("ClearMemory".to_string(), "write-only".to_string())));
+        assert!(!load_demoted.iter().any(|(label, _)| label == "On" || label == "Temperature"));
+    }
+
+    #[test]
+    fn pins_and_aliases_name_each_other() {
+        let mut aliases = HashMap::new();
+        for (name, pin) in [("pump", "d0"), ("vent", "d0"), ("temp", "r3")] {
+            let range = crate::types::Range(tower_lsp::lsp_types::Range::default());
+            aliases.insert(name.to_string(), DefinitionData::new(range, AliasValue::from(pin.to_string())));
+        }
+        let categories = CompletionCategories::default();
+        let device = &instructions::INSTRUCTIONS["l"].0[1];
+        let complete = |prefix: &str| {
+            let mut items = Vec::new();
+            param_completions_builtin(prefix, device, &mut items, None, &categories, &aliases);
+            items
+        };
+        let description = |items: &[CompletionItem], label: &str| {
+            let item = items.iter().find(|item| item.label == label).unwrap();
+            item.label_details.as_ref().and_then(|details| details.description.clone())
+        };
+
+        let items = complete("");
+        assert_eq!(description(&items, "d0"), Some("alias pump, vent".to_string()));
+        assert_eq!(description(&items, "r3"), Some("alias temp".to_string()));
+        assert_eq!(description(&items, "d1"), None);
+
+        let items = complete("pu");
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].label, "d0");
+        assert_eq!(items[0].filter_text.as_deref(), Some("pump"));
+        assert!(complete("d").iter().all(|item| item.filter_text.is_none()));
+    }
+
+    #[test]
+    fn slot_operands_offer_indices() {
+        let mut items = Vec::new();
+        slot_index_completion("ls", 2, &mut items);
+        assert_eq!(items.len(), SLOT_INDEX_MEANINGS.len());
+        assert_eq!(items[0].label, "0");
+        assert!(items.windows(2).all(|pair| pair[0].sort_text < pair[1].sort_text));
+
+        let mut items = Vec::new();
+        slot_index_completion("ls", 1, &mut items);
+        slot_index_completion("s", 1, &mut items);
+        assert!(items.is_empty());
+    }
+
+    #[test]
+    fn written_logic_type_boosts_its_enum_families() {
+        let value = &instructions::INSTRUCTIONS["s"].0[2];
+        let boosted = |instruction: &str, param_index: usize, line: &str| -> Vec<String> {
+            let mut items = Vec::new();
+            enum_completions("", value, instruction, param_index, line, &mut items);
+            assert!(items.len() > 100);
+            items.into_iter().filter(|item| item.sort_text.is_some()).map(|item| item.label).collect()
+        };
+        let sounds = boosted("s", 2, "s d0 SoundAlert ");
+        assert!(!sounds.is_empty() && sounds.iter().all(|label| label.starts_with("Sound.")));
+        let modes = boosted("sb", 2, "sb -851746783 Mode ");
+        assert!(modes.contains(&"AirControl.Pressure".to_string()));
+        assert!(modes.contains(&"PowerMode.Charging".to_string()));
+        assert!(boosted("sbn", 3, "sbn hash name Color ").contains(&"Color.Red".to_string()));
+        assert!(boosted("s", 2, "s d0 Setting ").is_empty());
+        assert!(boosted("move", 1, "move r0 ").is_empty());
+    }
+
+    #[test]
+    fn comparisons_offer_their_family() {
+        let mut items = Vec::new();
+        instruction_completions("slt", &CustomDocs::default(), &mut items);
+        assert_eq!(items[0].label, "slt");
+        let related: Vec<_> = items
+            .iter()
+            .filter(|item| {
+                item.label_details.as_ref().and_then(|d| d.description.as_deref())
+                    == Some("opposite/related comparison")
+            })
+            .map(|item| item.label.as_str())
+            .collect();
+        assert_eq!(related.first(), Some(&"sge"));
+        assert!(related.contains(&"sne"));
+        assert!(!related.contains(&"sltz"));
+
+        let mut items = Vec::new();
+        instruction_completions("s", &CustomDocs::default(), &mut items);
+        assert!(items.iter().all(|item| item.filter_text.is_none()));
+    }
+}
@@ -8,29 +8,640 @@
 //! - Register usage analysis
 //! - Linting for branch instructions
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::time::Instant;
 
 use phf::phf_set;
 use sha2::{Sha256, Digest};
 use tower_lsp::lsp_types::{
-    Diagnostic, DiagnosticSeverity, NumberOrString,
-    Position as LspPosition, Range as LspRange, Url,
+    Diagnostic, DiagnosticRelatedInformation, DiagnosticSeverity, DiagnosticTag, Location,
+    NumberOrString, Position as LspPosition, Range as LspRange, Url,
 };
-use tree_sitter::{Parser, Query, QueryCursor};
+use tree_sitter::{Parser, Query, QueryCursor, Tree};
 
 use ic10lsp::instructions::{self, DataType};
 
 use crate::additional_features;
 use crate::diagnostic_helpers::diagnostic_identity;
-use crate::document::{AliasValue, DefinitionData, TypeData};
-use crate::hash_utils::{extract_hash_argument, get_device_hash, is_hash_function_call, is_numeric_string};
+use crate::document::{AliasValue, Configuration, DefineValue, DefinitionData, TypeData};
+use crate::hash_utils::{
+    edit_distance, extract_hash_argument, is_hash_function_call, is_numeric_string, DeviceHashes,
+};
+use crate::tree_utils::{is_mnemonic_label_colon, mnemonic_label, mnemonic_labels, region_markers};
 use crate::type_classification::{classify_ci_keyword, classify_exact_keyword};
 use crate::types::{Position, Range};
 use crate::Backend;
 
 // Re-use constants from main module
-use crate::{LINT_ABSOLUTE_JUMP, LINT_RELATIVE_BRANCH_TO_LABEL, NAME_ONLY};
+use crate::{
+    LINT_ABSOLUTE_JUMP, LINT_ACCIDENTAL_DB_ACCESS, LINT_ALIAS_USED_BEFORE_DECLARATION,
+    LINT_BIT_FIELD_RANGE, LINT_DECLARATIONS_ARE_POSITIONAL, LINT_DEEP_ANALYSIS_SKIPPED,
+    LINT_DEEP_CALL_NESTING, LINT_DEPRECATED_ENUM_FAMILY, LINT_ENUM_WHERE_DEVICE_EXPECTED,
+    LINT_INSTRUCTION_NOT_ALLOWED, LINT_INVALID_DESTINATION, LINT_INVALID_INSTRUCTION,
+    LINT_INVALID_NAME_HASH, LINT_INVALID_SYMBOL_NAME, LINT_LABEL_SHADOWS_INSTRUCTION,
+    LINT_LONE_DEVICE_WRITE, LINT_LONG_SLEEP, LINT_MALFORMED_REGISTER, LINT_MISSING_TRAILING_NEWLINE,
+    LINT_MULTITOKEN_DEFINE, LINT_NO_EXECUTABLE_INSTRUCTIONS, LINT_NO_OP_INSTRUCTION,
+    LINT_NUMBER_DEFINE_AS_DEVICE, LINT_POSSIBLE_MISSING_SPACE, LINT_RELATIVE_BRANCH_TO_LABEL,
+    LINT_SHADOWS_ENUM_FAMILY, LINT_SHIFT_AMOUNT_RANGE, LINT_STACK_ADDRESS_RANGE,
+    LINT_STORE_OPERAND_ORDER, LINT_UNBALANCED_REGION, LINT_UNKNOWN_DEFINE_HASH,
+    LINT_UNREACHABLE_CODE, LINT_UNSUPPORTED_OPERATOR, LINT_UNUSED_DEFINITION, LINT_UNUSED_LABEL,
+    NAME_ONLY,
+};
+
+/// For an unknown identifier like `Pump1`, find a define/alias name it starts with where the
+/// remainder is only digits (`Pump` + `1`). Deliberately narrow to keep noise down.
+fn missing_space_split<'a>(ident: &'a str, type_data: &TypeData) -> Option<(&'a str, &'a str)> {
+    let digits_start = ident.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+    if digits_start < 2 || digits_start == ident.len() {
+        return None;
+    }
+    let (name, digits) = ident.split_at(digits_start);
+    (type_data.defines.contains_key(name) || type_data.aliases.contains_key(name))
+        .then_some((name, digits))
+}
+
+fn missing_space_diagnostic(operand: tree_sitter::Node, name: &str, digits: &str) -> Diagnostic {
+    Diagnostic {
+        range: Range::from(operand.range()).into(),
+        severity: Some(DiagnosticSeverity::INFORMATION),
+        code: Some(NumberOrString::String(LINT_POSSIBLE_MISSING_SPACE.to_string())),
+        message: format!("Did you mean '{} {}'? '{}' is defined.", name, digits, name),
+        data: Some(serde_json::json!(name.len())),
+        ..Default::default()
+    }
+}
+
+/// Instructions whose first operand is the register the result is written to
+const DESTINATION_INSTRUCTIONS: phf::Set<&'static str> = phf_set!(
+    "abs", "acos", "add", "and", "asin", "atan", "atan2", "ceil", "cos", "div", "exp", "ext",
+    "floor", "get", "getd", "ins", "l", "lb", "lbn", "lbns", "lbs", "ld", "lerp", "log", "lr",
+    "ls", "max", "min", "mod", "move", "mul", "nor", "not", "or", "peek", "pop", "pow", "rand",
+    "rmap", "round", "sap", "sapz", "sdns", "sdse", "select", "seq", "seqz", "sge", "sgez",
+    "sgt", "sgtz", "sin", "sla", "sle", "slez", "sll", "slt", "sltz", "sna", "snan", "snanz",
+    "snaz", "sne", "snez", "sqrt", "sra", "srl", "sub", "tan", "trunc", "xor"
+);
+
+/// A number or device in the destination slot gets a specific error instead of the generic
+/// type mismatch
+fn invalid_destination_diagnostic(
+    operation: &str,
+    argument_count: usize,
+    operand: tree_sitter::Node,
+    operand_kind: &str,
+) -> Option<Diagnostic> {
+    if argument_count != 1
+        || !DESTINATION_INSTRUCTIONS.contains(operation)
+        || !matches!(operand_kind, "number" | "device_spec")
+    {
+        return None;
+    }
+    Some(Diagnostic {
+        range: Range::from(operand.range()).into(),
+        severity: Some(DiagnosticSeverity::ERROR),
+        code: Some(NumberOrString::String(LINT_INVALID_DESTINATION.to_string())),
+        message: format!("Destination must be a register; '{}' writes its result here.", operation),
+        ..Default::default()
+    })
+}
+
+/// The name-hash operand of `lbn`/`lbns`/`sbn` (1-based) must be a number: it is compared with
+/// the hash of each device's name. A non-number there gets a specific error instead of the
+/// generic type mismatch.
+fn invalid_name_hash_diagnostic(
+    operation: &str,
+    argument_count: usize,
+    operand: tree_sitter::Node,
+    found: &instructions::Union,
+    parameter: &instructions::Union,
+) -> Option<Diagnostic> {
+    let name_hash_position = match operation.to_ascii_lowercase().as_str() {
+        "lbn" | "lbns" => 3,
+        "sbn" => 2,
+        _ => return None,
+    };
+    if argument_count != name_hash_position || parameter.match_union(found) {
+        return None;
+    }
+    Some(Diagnostic {
+        range: Range::from(operand.range()).into(),
+        severity: Some(DiagnosticSeverity::ERROR),
+        code: Some(NumberOrString::String(LINT_INVALID_NAME_HASH.to_string())),
+        message: format!(
+            "The name hash of '{}' must be a number, found {}. Use HASH(\"Device Name\"), STR(...), a define or a register holding the hash.",
+            operation, found
+        ),
+        ..Default::default()
+    })
+}
+
+/// Shift amount of `sll`/`srl`/`sla`/`sra` (the third operand) outside 0-63. Only constants
+/// are checked; a register amount is only known at runtime.
+fn shift_amount_diagnostic(
+    operation: &str,
+    argument_count: usize,
+    operand: tree_sitter::Node,
+    content: &[u8],
+    defines: &HashMap<String, DefinitionData<DefineValue>>,
+) -> Option<Diagnostic> {
+    if argument_count != 3
+        || !matches!(operation.to_ascii_lowercase().as_str(), "sll" | "srl" | "sla" | "sra")
+    {
+        return None;
+    }
+    let value = constant_value(operand.utf8_text(content).ok()?, defines)?;
+    if (0.0..=63.0).contains(&value) {
+        return None;
+    }
+    Some(Diagnostic {
+        range: Range::from(operand.range()).into(),
+        severity: Some(DiagnosticSeverity::WARNING),
+        code: Some(NumberOrString::String(LINT_SHIFT_AMOUNT_RANGE.to_string())),
+        message: format!(
+            "Shift amount {} is outside 0-63; '{}' gives an undefined result.",
+            value, operation
+        ),
+        ..Default::default()
+    })
+}
+
+/// Widest bit field `ext`/`ins` accept: registers hold doubles, exact to 53 bits
+const BIT_FIELD_BITS: f64 = 53.0;
+
+/// Warn when a constant offset or width of `ext`/`ins` leaves the 53 usable bits: an offset
+/// outside 0-52, a width outside 1-53, or a field running past bit 52
+fn bit_field_diagnostic(
+    operation: &str,
+    argument_count: usize,
+    operand: tree_sitter::Node,
+    content: &[u8],
+    defines: &HashMap<String, DefinitionData<DefineValue>>,
+) -> Option<Diagnostic> {
+    if !matches!(operation.to_ascii_lowercase().as_str(), "ext" | "ins") {
+        return None;
+    }
+    let value = constant_value(operand.utf8_text(content).ok()?, defines)?;
+    let message = match argument_count {
+        3 if !(0.0..BIT_FIELD_BITS).contains(&value) => {
+            format!("Bit field offset {} is outside 0-{} for '{}'.", value, BIT_FIELD_BITS - 1.0, operation)
+        }
+        3 => return None,
+        4 if !(1.0..=BIT_FIELD_BITS).contains(&value) => {
+            format!("Bit field width {} is outside 1-{} for '{}'.", value, BIT_FIELD_BITS, operation)
+        }
+        4 => {
+            let offset = operand
+                .prev_named_sibling()
+                .and_then(|offset| constant_value(offset.utf8_text(content).ok()?, defines))
+                .filter(|offset| (0.0..BIT_FIELD_BITS).contains(offset))?;
+            if offset + value <= BIT_FIELD_BITS {
+                return None;
+            }
+            format!(
+                "Bit field at offset {} with width {} runs past bit {} for '{}'.",
+                offset,
+                value,
+                BIT_FIELD_BITS - 1.0,
+                operation
+            )
+        }
+        _ => return None,
+    };
+    Some(Diagnostic {
+        range: Range::from(operand.range()).into(),
+        severity: Some(DiagnosticSeverity::WARNING),
+        code: Some(NumberOrString::String(LINT_BIT_FIELD_RANGE.to_string())),
+        message,
+        ..Default::default()
+    })
+}
+
+/// Longest constant `sleep`, in seconds, before the script is noted as unresponsive
+const LONG_SLEEP_SECONDS: f64 = 60.0;
+
+/// Info when `sleep` waits a constant time longer than [`LONG_SLEEP_SECONDS`]
+fn long_sleep_diagnostic(
+    operation: &str,
+    argument_count: usize,
+    operand: tree_sitter::Node,
+    content: &[u8],
+    defines: &HashMap<String, DefinitionData<DefineValue>>,
+) -> Option<Diagnostic> {
+    if argument_count != 1 || !operation.eq_ignore_ascii_case("sleep") {
+        return None;
+    }
+    let seconds = constant_value(operand.utf8_text(content).ok()?, defines)?;
+    if seconds <= LONG_SLEEP_SECONDS {
+        return None;
+    }
+    Some(Diagnostic {
+        range: Range::from(operand.range()).into(),
+        severity: Some(DiagnosticSeverity::INFORMATION),
+        code: Some(NumberOrString::String(LINT_LONG_SLEEP.to_string())),
+        message: format!(
+            "'sleep' pauses for {} seconds; the script reads, writes and branches nothing until then.",
+            seconds
+        ),
+        ..Default::default()
+    })
+}
+
+/// Warning on the value of `define Name HASH("...")` when the device name is unknown, with the
+/// closest known prefab name as a suggestion
+fn define_hash_diagnostic(
+    operation: &str,
+    argument_count: usize,
+    operand: tree_sitter::Node,
+    content: &[u8],
+    device_hashes: &DeviceHashes,
+) -> Option<Diagnostic> {
+    if argument_count != 2 || !operation.eq_ignore_ascii_case("define") {
+        return None;
+    }
+    if operand.named_child(0)?.kind() != "hash_function" {
+        return None;
+    }
+    let name = extract_hash_argument(operand.utf8_text(content).ok()?)?;
+    if device_hashes.get_device_hash(&name).is_some() {
+        return None;
+    }
+    let suggestion = match device_hashes.closest_device_name(&name) {
+        Some(closest) => format!(" Did you mean '{}'?", closest),
+        None => String::new(),
+    };
+    Some(Diagnostic {
+        range: Range::from(operand.range()).into(),
+        severity: Some(DiagnosticSeverity::WARNING),
+        code: Some(NumberOrString::String(LINT_UNKNOWN_DEFINE_HASH.to_string())),
+        message: format!("Unknown device '{}' in HASH(...).{}", name, suggestion),
+        ..Default::default()
+    })
+}
+
+/// Error for a recognized instruction missing from the `allowedInstructions` setting.
+/// Assembler directives are never restricted.
+fn instruction_not_allowed_diagnostic(
+    operation_node: tree_sitter::Node,
+    operation: &str,
+    allowed: Option<&[String]>,
+) -> Option<Diagnostic> {
+    let allowed = allowed?;
+    let operation = operation.to_ascii_lowercase();
+    if matches!(operation.as_str(), "define" | "alias" | "label") || allowed.contains(&operation) {
+        return None;
+    }
+    Some(Diagnostic {
+        range: Range::from(operation_node.range()).into(),
+        severity: Some(DiagnosticSeverity::ERROR),
+        code: Some(NumberOrString::String(LINT_INSTRUCTION_NOT_ALLOWED.to_string())),
+        message: format!(
+            "'{}' is not allowed here. Allowed instructions: {}.",
+            operation,
+            allowed.join(", ")
+        ),
+        ..Default::default()
+    })
+}
+
+/// Info when `s`/`ss` is written in load order, value first (`s r0 d0 On`): the first operand
+/// is not a device but the second is, and the logic type sits where the device's logic type
+/// goes. `data` carries the instruction rewritten in store order for the quick fix.
+fn store_operand_order_diagnostic(
+    instruction: tree_sitter::Node,
+    operation: &str,
+    content: &[u8],
+    aliases: &HashMap<String, DefinitionData<AliasValue>>,
+) -> Option<Diagnostic> {
+    let logic_type_index = match operation.to_ascii_lowercase().as_str() {
+        "s" => 2,
+        "ss" => 3,
+        _ => return None,
+    };
+    let mut cursor = instruction.walk();
+    let operands: Vec<_> = instruction.children_by_field_name("operand", &mut cursor).collect();
+    if operands.len() != logic_type_index + 1 {
+        return None;
+    }
+    let kind = |operand: &tree_sitter::Node| operand.named_child(0).map_or("", |child| child.kind());
+    let is_device = |operand: &tree_sitter::Node| match kind(operand) {
+        "device_spec" => true,
+        "identifier" => operand
+            .utf8_text(content)
+            .ok()
+            .and_then(|name| aliases.get(name))
+            .is_some_and(|alias| matches!(alias.value, AliasValue::Device(_))),
+        _ => false,
+    };
+    let value = &operands[0];
+    if is_device(value)
+        || !matches!(kind(value), "register" | "number" | "identifier")
+        || !is_device(&operands[1])
+        || kind(&operands[logic_type_index]) != "logictype"
+    {
+        return None;
+    }
+    let text = |node: &tree_sitter::Node| node.utf8_text(content).unwrap_or("");
+    let reordered = std::iter::once(operation)
+        .chain(operands[1..].iter().map(text))
+        .chain(std::iter::once(text(value)))
+        .collect::<Vec<_>>()
+        .join(" ");
+    Some(Diagnostic {
+        range: Range::from(instruction.range()).into(),
+        severity: Some(DiagnosticSeverity::INFORMATION),
+        code: Some(NumberOrString::String(LINT_STORE_OPERAND_ORDER.to_string())),
+        message: format!(
+            "'{}' takes the device first and the value last; did you mean '{}'?",
+            operation, reordered
+        ),
+        data: Some(serde_json::Value::String(reordered)),
+        ..Default::default()
+    })
+}
+
+/// Infix operators people carry over from other languages, with the instructions to use instead
+const UNSUPPORTED_OPERATORS: [(&str, &str); 12] = [
+    ("==", "seq/beq"),
+    ("!=", "sne/bne"),
+    ("<=", "sle/ble"),
+    (">=", "sge/bge"),
+    ("+=", "add"),
+    ("-=", "sub"),
+    ("=", "move"),
+    ("+", "add"),
+    ("*", "mul"),
+    ("/", "div"),
+    ("<", "slt/blt"),
+    (">", "sgt/bgt"),
+];
+
+fn unsupported_operator(node: tree_sitter::Node, content: &[u8]) -> Option<(&'static str, &'static str)> {
+    let text = node.utf8_text(content).ok()?;
+    UNSUPPORTED_OPERATORS
+        .into_iter()
+        .find(|(operator, _)| text.contains(operator))
+}
+
+/// Diagnostic for an ERROR node. Infix operators (`r0=5`, `r1+1`) get a targeted message;
+/// an ERROR nested in one already reported that way yields `None`.
+fn syntax_error_diagnostic(node: tree_sitter::Node, content: &[u8]) -> Option<Diagnostic> {
+    let Some((operator, instead)) = unsupported_operator(node, content) else {
+        return Some(Diagnostic::new(
+            Range::from(node.range()).into(),
+            Some(DiagnosticSeverity::ERROR),
+            None,
+            None,
+            "Syntax error".to_string(),
+            None,
+            None,
+        ));
+    };
+    if node
+        .parent()
+        .is_some_and(|parent| parent.is_error() && unsupported_operator(parent, content).is_some())
+    {
+        return None;
+    }
+    Some(Diagnostic {
+        range: Range::from(node.range()).into(),
+        severity: Some(DiagnosticSeverity::ERROR),
+        code: Some(NumberOrString::String(LINT_UNSUPPORTED_OPERATOR.to_string())),
+        message: format!(
+            "IC10 has no '{}' operator; operations are instructions with operands (use {}).",
+            operator, instead
+        ),
+        ..Default::default()
+    })
+}
+
+/// Canonical spelling of a register written with leading zeros (`r07` -> `r7`, `rr01` -> `rr1`)
+fn canonical_register_name(ident: &str) -> Option<String> {
+    let digits = ident.trim_start_matches('r');
+    let prefix = &ident[..ident.len() - digits.len()];
+    if prefix.is_empty() || digits.len() < 2 || !digits.starts_with('0') {
+        return None;
+    }
+    let index = digits.parse::<u8>().ok().filter(|index| *index <= 15)?;
+    Some(format!("{}{}", prefix, index))
+}
+
+/// Warn when an alias name is used on a line above its `alias`: the chip runs top to bottom, so
+/// the name does not exist yet there. Using the register or pin itself is unaffected.
+fn alias_used_before_declaration(
+    operand: tree_sitter::Node,
+    ident: &str,
+    alias: &DefinitionData<AliasValue>,
+    first_declared: &HashMap<String, u32>,
+) -> Option<Diagnostic> {
+    let declared = first_declared.get(ident).copied().unwrap_or(alias.range.0.start.line);
+    if operand.start_position().row as u32 >= declared {
+        return None;
+    }
+    Some(Diagnostic {
+        range: Range::from(operand.range()).into(),
+        severity: Some(DiagnosticSeverity::WARNING),
+        code: Some(NumberOrString::String(LINT_ALIAS_USED_BEFORE_DECLARATION.to_string())),
+        message: format!(
+            "Alias '{}' is used before it is declared on line {}. Move the alias above, or use {} directly.",
+            ident,
+            declared + 1,
+            alias.value
+        ),
+        ..Default::default()
+    })
+}
+
+/// Row of the first `alias` line for each name. A redeclared alias is usable from its first
+/// declaration on, while the type data keeps the last one.
+fn first_alias_rows(tree: &Tree, content: &str) -> HashMap<String, u32> {
+    let mut rows = HashMap::new();
+    let mut cursor = QueryCursor::new();
+    let query = Query::new(tree_sitter_ic10::language(), "(instruction (operation \"alias\"))@alias").unwrap();
+    for (capture, _) in cursor.captures(&query, tree.root_node(), content.as_bytes()) {
+        let instruction = capture.captures[0].node;
+        if let Some(name) = instruction
+            .child_by_field_name("operand")
+            .and_then(|operand| operand.utf8_text(content.as_bytes()).ok())
+        {
+            rows.entry(name.trim().to_string())
+                .or_insert(instruction.start_position().row as u32);
+        }
+    }
+    rows
+}
+
+/// Error for an enum (`Sound.Alarm2`) in a device operand. The signature also admits numbers
+/// there, so the enum would otherwise pass silently, but it can never name a device.
+fn enum_where_device_expected(
+    operand: tree_sitter::Node,
+    canonical: &str,
+    parameter: &instructions::Union,
+) -> Option<Diagnostic> {
+    if !parameter.match_type(DataType::Device) {
+        return None;
+    }
+    Some(Diagnostic {
+        range: Range::from(operand.range()).into(),
+        severity: Some(DiagnosticSeverity::ERROR),
+        code: Some(NumberOrString::String(LINT_ENUM_WHERE_DEVICE_EXPECTED.to_string())),
+        message: format!(
+            "'{}' is an enum, which is just a number; it can't address a device pin. Use d0-d5, db or a device alias.",
+            canonical
+        ),
+        ..Default::default()
+    })
+}
+
+/// Error for a define in a device operand. A define is always a number; even one holding
+/// `HASH("...")` names a device type for batch instructions, not a pin.
+fn number_define_as_device(
+    operand: tree_sitter::Node,
+    ident: &str,
+    define: &DefinitionData<DefineValue>,
+    parameter: &instructions::Union,
+) -> Option<Diagnostic> {
+    if !parameter.match_type(DataType::Device) {
+        return None;
+    }
+    let hint = match define.value {
+        DefineValue::FunctionCall(_) => " A HASH() define is a device type hash: use it with lb/sb.",
+        _ => "",
+    };
+    Some(Diagnostic {
+        range: Range::from(operand.range()).into(),
+        severity: Some(DiagnosticSeverity::ERROR),
+        code: Some(NumberOrString::String(LINT_NUMBER_DEFINE_AS_DEVICE.to_string())),
+        message: format!(
+            "Define '{}' is a number ({}); it can't address a device pin. Use d0-d5, db or a device alias.{}",
+            ident, define.value, hint
+        ),
+        ..Default::default()
+    })
+}
+
+/// `data` carries the canonical register name for the normalize quick fix
+fn malformed_register_diagnostic(operand: tree_sitter::Node, canonical: &str) -> Diagnostic {
+    Diagnostic {
+        range: Range::from(operand.range()).into(),
+        severity: Some(DiagnosticSeverity::WARNING),
+        code: Some(NumberOrString::String(LINT_MALFORMED_REGISTER.to_string())),
+        message: format!("Register index has leading zeros; write '{}'.", canonical),
+        data: Some(serde_json::json!(canonical)),
+        ..Default::default()
+    }
+}
+
+/// Warn when a qualified enum (`Family.Member`) belongs to a family deprecated as a whole,
+/// per `family_deprecation` (normally [`instructions::enum_family_deprecation`]). `data`
+/// carries the replacement name when the replacement family has the same member.
+pub(crate) fn deprecated_enum_family_diagnostic(
+    operand: tree_sitter::Node,
+    canonical: &str,
+    family_deprecation: fn(&str) -> Option<Option<&'static str>>,
+) -> Option<Diagnostic> {
+    let (family, member) = canonical.split_once('.')?;
+    let replacement = family_deprecation(family)?;
+    let message = match replacement {
+        Some(new_family) => format!("Enum family '{}' is deprecated; use '{}' instead.", family, new_family),
+        None => format!("Enum family '{}' is deprecated.", family),
+    };
+    let replacement_name = replacement
+        .map(|new_family| format!("{}.{}", new_family, member))
+        .filter(|name| instructions::enum_info(name).is_some());
+    Some(Diagnostic {
+        range: Range::from(operand.range()).into(),
+        severity: Some(DiagnosticSeverity::WARNING),
+        code: Some(NumberOrString::String(LINT_DEPRECATED_ENUM_FAMILY.to_string())),
+        message,
+        data: replacement_name.map(|name| serde_json::json!(name)),
+        ..Default::default()
+    })
+}
+
+/// Warning for a label named like an instruction; `data` carries the name for the rename fix
+pub(crate) fn label_shadows_instruction(name_node: tree_sitter::Node, name: &str) -> Diagnostic {
+    Diagnostic {
+        range: Range::from(name_node.range()).into(),
+        severity: Some(DiagnosticSeverity::WARNING),
+        code: Some(NumberOrString::String(LINT_LABEL_SHADOWS_INSTRUCTION.to_string())),
+        message: format!("Label '{}' has the same name as the '{}' instruction.", name, name),
+        data: Some(serde_json::json!(name)),
+        ..Default::default()
+    }
+}
+
+/// Warning for a define or alias named like an enum family (`Color`), which makes `Color.Red`
+/// ambiguous; `data` carries the name for the rename fix
+pub(crate) fn shadows_enum_family(range: Range, name: &str) -> Option<Diagnostic> {
+    let (family, ..) =
+        instructions::all_enum_entries().find(|(family, ..)| family.eq_ignore_ascii_case(name))?;
+    Some(Diagnostic {
+        range: range.into(),
+        severity: Some(DiagnosticSeverity::WARNING),
+        code: Some(NumberOrString::String(LINT_SHADOWS_ENUM_FAMILY.to_string())),
+        message: format!(
+            "'{}' has the same name as the {} enum family, so '{}.<member>' becomes ambiguous.",
+            name, family, family
+        ),
+        data: Some(serde_json::json!(name)),
+        ..Default::default()
+    })
+}
+
+/// Error for a define, alias or label name outside the game's identifier rules: letters, digits
+/// and underscores only, not starting with a digit. The grammar is looser (it allows `.`), so
+/// such a script parses here but fails to load in game.
+pub(crate) fn invalid_symbol_name(range: Range, name: &str) -> Option<Diagnostic> {
+    let valid = name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && name.chars().next().is_some_and(|c| !c.is_ascii_digit());
+    if valid {
+        return None;
+    }
+    Some(Diagnostic {
+        range: range.into(),
+        severity: Some(DiagnosticSeverity::ERROR),
+        code: Some(NumberOrString::String(LINT_INVALID_SYMBOL_NAME.to_string())),
+        message: format!(
+            "'{}' is not a valid name: names may only contain letters, digits and underscores, \
+             and must not start with a digit.",
+            name
+        ),
+        ..Default::default()
+    })
+}
+
+/// Warning on a `define` whose value is more than one token (`define A 1 2`, `define B r0 + 1`):
+/// substituted where the name is used, the extra tokens would become extra operands. Quoted
+/// strings inside `HASH("...")`/`STR("...")` are one token.
+pub(crate) fn multitoken_define(instruction: tree_sitter::Node, content: &[u8]) -> Option<Diagnostic> {
+    let mut cursor = instruction.walk();
+    let mut tokens: Vec<_> = instruction.children_by_field_name("operand", &mut cursor).skip(1).collect();
+    let mut sibling = instruction.next_sibling();
+    while let Some(node) = sibling.filter(|node| !matches!(node.kind(), "comment" | "newline")) {
+        tokens.push(node);
+        sibling = node.next_sibling();
+    }
+    let (first, last) = (tokens.first()?, tokens.last()?);
+    if tokens.len() < 2 {
+        return None;
+    }
+    let range = tree_sitter::Range {
+        start_byte: first.start_byte(),
+        end_byte: last.end_byte(),
+        start_point: first.start_position(),
+        end_point: last.end_position(),
+    };
+    let value = std::str::from_utf8(&content[range.start_byte..range.end_byte]).ok()?;
+    Some(Diagnostic {
+        range: Range::from(range).into(),
+        severity: Some(DiagnosticSeverity::WARNING),
+        code: Some(NumberOrString::String(LINT_MULTITOKEN_DEFINE.to_string())),
+        message: format!(
+            "Define value '{}' is more than one token; substituted where the name is used, it would add operands.",
+            value
+        ),
+        ..Default::default()
+    })
+}
 
 /// Check types for all instructions in the document
 pub async fn check_types(backend: &Backend, uri: &Url, diagnostics: &mut Vec<Diagnostic>) {
@@ -46,7 +657,12 @@ pub async fn check_types(backend: &Backend, uri: &Url, diagnostics: &mut Vec<Dia
     };
 
     // Read config before the loop to avoid await across non-Send types
-    let suppress_hash_diagnostics = backend.config.read().await.suppress_hash_diagnostics;
+    let (suppress_hash_diagnostics, allowed_instructions) = {
+        let config = backend.config.read().await;
+        (config.suppress_hash_diagnostics, config.allowed_instructions.clone())
+    };
+    let device_hashes = backend.device_hashes.read().await;
+    let first_alias_rows = first_alias_rows(tree, &document.content);
 
     let mut cursor = QueryCursor::new();
     let query = Query::new(tree_sitter_ic10::language(), "(instruction)@a").unwrap();
@@ -59,23 +675,31 @@ pub async fn check_types(backend: &Backend, uri: &Url, diagnostics: &mut Vec<Dia
 
     for (capture, _) in captures {
         let capture = capture.captures[0].node;
+        if mnemonic_label(capture, document.content.as_bytes()).is_some() {
+            continue;
+        }
 
         if let Some(operation_node) = capture.child_by_field_name("operation") {
             let operation = operation_node
                 .utf8_text(document.content.as_bytes())
                 .unwrap();
+            // Unknown mnemonics are reported by the invalid-instruction pass, which honours
+            // prefix suppression and the configured severity
             let Some(signature) = instructions::INSTRUCTIONS.get(operation) else {
-                diagnostics.push(Diagnostic::new(
-                    Range::from(operation_node.range()).into(),
-                    Some(DiagnosticSeverity::ERROR),
-                    None,
-                    None,
-                    format!("Invalid instruction"),
-                    None,
-                    None,
-                ));
                 continue;
             };
+            diagnostics.extend(instruction_not_allowed_diagnostic(
+                operation_node,
+                operation,
+                allowed_instructions.as_deref(),
+            ));
+
+            diagnostics.extend(store_operand_order_diagnostic(
+                capture,
+                operation,
+                document.content.as_bytes(),
+                &type_data.aliases,
+            ));
 
             let mut argument_count = 0;
             let mut tree_cursor = capture.walk();
@@ -161,6 +785,11 @@ pub async fn check_types(backend: &Backend, uri: &Url, diagnostics: &mut Vec<Dia
                                         None,
                                     ));
                                 }
+                                diagnostics.extend(deprecated_enum_family_diagnostic(operand, canonical, instructions::enum_family_deprecation));
+                                if let Some(diagnostic) = enum_where_device_expected(operand, canonical, parameter) {
+                                    diagnostics.push(diagnostic);
+                                    continue;
+                                }
                                 instructions::Union(&[DataType::Number])
                             } else if type_data.defines.contains_key(ident)
                                 || type_data.labels.contains_key(ident)
@@ -189,6 +818,7 @@ pub async fn check_types(backend: &Backend, uri: &Url, diagnostics: &mut Vec<Dia
                                 }
                                 instructions::Union(&[DataType::Number])
                             } else if let Some(type_data_val) = type_data.aliases.get(ident) {
+                                diagnostics.extend(alias_used_before_declaration(operand, ident, type_data_val, &first_alias_rows));
                                 match type_data_val.value {
                                     AliasValue::Device(_) => {
                                         instructions::Union(&[DataType::Device])
@@ -207,6 +837,14 @@ pub async fn check_types(backend: &Backend, uri: &Url, diagnostics: &mut Vec<Dia
                         else if type_data.defines.contains_key(ident)
                             || type_data.labels.contains_key(ident)
                         {
+                            if let Some(diagnostic) = type_data
+                                .defines
+                                .get(ident)
+                                .and_then(|define| number_define_as_device(operand, ident, define, parameter))
+                            {
+                                diagnostics.push(diagnostic);
+                                continue;
+                            }
                             // User-defined identifier (define/label) always resolves; value may be HASH(...) or number
                             instructions::Union(&[DataType::Number])
                         } else if let Some((canonical, _)) = type_data
@@ -231,6 +869,7 @@ pub async fn check_types(backend: &Backend, uri: &Url, diagnostics: &mut Vec<Dia
                             }
                             instructions::Union(&[DataType::Number])
                         } else if let Some(type_data_val) = type_data.aliases.get(ident) {
+                            diagnostics.extend(alias_used_before_declaration(operand, ident, type_data_val, &first_alias_rows));
                             match type_data_val.value {
                                 AliasValue::Device(_) => {
                                     instructions::Union(&[DataType::Device])
@@ -262,6 +901,9 @@ pub async fn check_types(backend: &Backend, uri: &Url, diagnostics: &mut Vec<Dia
                                     None,
                                 ));
                                     ci_flags.to_union()
+                                } else if let Some(canonical) = canonical_register_name(ident) {
+                                    diagnostics.push(malformed_register_diagnostic(operand, &canonical));
+                                    instructions::Union(&[DataType::Register])
                                 } else {
                                     diagnostics.push(Diagnostic::new(
                                         Range::from(operand.range()).into(),
@@ -272,6 +914,9 @@ pub async fn check_types(backend: &Backend, uri: &Url, diagnostics: &mut Vec<Dia
                                         None,
                                         None,
                                     ));
+                                    if let Some((name, digits)) = missing_space_split(ident, &type_data) {
+                                        diagnostics.push(missing_space_diagnostic(operand, name, digits));
+                                    }
                                     continue;
                                 }
                             }
@@ -282,11 +927,12 @@ pub async fn check_types(backend: &Backend, uri: &Url, diagnostics: &mut Vec<Dia
                         let call_text =
                             operand.utf8_text(document.content.as_bytes()).unwrap();
                         if let Some(name) = extract_hash_argument(call_text) {
-                            if let Some(_) = get_device_hash(name.as_str()) {
+                            if device_hashes.get_device_hash(&name).is_some() {
                                 // Known device name
                             } else {
-                                // Unknown device string; still treat as number but nudge (unless suppressed)
-                                if !suppress_hash_diagnostics {
+                                // Unknown device string; still treat as number but nudge (unless suppressed).
+                                // Defines get a warning with a suggestion instead, below.
+                                if !suppress_hash_diagnostics && !operation.eq_ignore_ascii_case("define") {
                                     diagnostics.push(Diagnostic::new(
                                         Range::from(operand.range()).into(),
                                         Some(DiagnosticSeverity::INFORMATION),
@@ -344,7 +990,15 @@ pub async fn check_types(backend: &Backend, uri: &Url, diagnostics: &mut Vec<Dia
                     }
                 }
                 // Allow define name second operand to be register when signature expects Number|Register already (adjusted in INSTRUCTIONS)
-                if !parameter.match_union(&effective_typ) {
+                if let Some(diagnostic) =
+                    invalid_destination_diagnostic(operation, argument_count, operand, operand_kind)
+                {
+                    diagnostics.push(diagnostic);
+                } else if let Some(diagnostic) =
+                    invalid_name_hash_diagnostic(operation, argument_count, operand, &effective_typ, parameter)
+                {
+                    diagnostics.push(diagnostic);
+                } else if !parameter.match_union(&effective_typ) {
                     diagnostics.push(Diagnostic::new(
                         Range::from(operand.range()).into(),
                         Some(DiagnosticSeverity::ERROR),
@@ -359,6 +1013,37 @@ pub async fn check_types(backend: &Backend, uri: &Url, diagnostics: &mut Vec<Dia
                     ));
                 }
 
+                diagnostics.extend(shift_amount_diagnostic(
+                    operation,
+                    argument_count,
+                    operand,
+                    document.content.as_bytes(),
+                    &type_data.defines,
+                ));
+                diagnostics.extend(bit_field_diagnostic(
+                    operation,
+                    argument_count,
+                    operand,
+                    document.content.as_bytes(),
+                    &type_data.defines,
+                ));
+                diagnostics.extend(long_sleep_diagnostic(
+                    operation,
+                    argument_count,
+                    operand,
+                    document.content.as_bytes(),
+                    &type_data.defines,
+                ));
+                if !suppress_hash_diagnostics {
+                    diagnostics.extend(define_hash_diagnostic(
+                        operation,
+                        argument_count,
+                        operand,
+                        document.content.as_bytes(),
+                        &device_hashes,
+                    ));
+                }
+
                 // After processing the second operand of DEFINE, store it in the working define map
                 if operation.eq_ignore_ascii_case("define") && argument_count == 2 {
                     if let Some((define_name, define_range)) = pending_define_name.clone() {
@@ -415,6 +1100,790 @@ pub async fn check_types(backend: &Backend, uri: &Url, diagnostics: &mut Vec<Dia
     }
 }
 
+/// Minimum number of reads before a single store to the same device is considered suspicious
+const LONE_WRITE_MIN_READS: usize = 3;
+
+/// Flag a single `s`/`ss` to a device that is otherwise only read with `l`/`ls`/`lr`.
+/// Heuristic: such a store is often an inverted load.
+pub fn check_lone_device_writes(
+    tree: &Tree,
+    content: &str,
+    aliases: &HashMap<String, DefinitionData<AliasValue>>,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    // Resolved device -> (read count, write ranges)
+    let mut usage: HashMap<String, (usize, Vec<LspRange>)> = HashMap::new();
+
+    let mut cursor = QueryCursor::new();
+    let query = Query::new(tree_sitter_ic10::language(), "(instruction)@x").unwrap();
+    let mut tree_cursor = tree.walk();
+    for (capture, _) in cursor.captures(&query, tree.root_node(), content.as_bytes()) {
+        let node = capture.captures[0].node;
+        let Some(operation_node) = node.child_by_field_name("operation") else {
+            continue;
+        };
+        let operation = operation_node.utf8_text(content.as_bytes()).unwrap();
+        let (device_index, is_write) = match operation {
+            "l" | "ls" | "lr" => (1, false),
+            "s" | "ss" => (0, true),
+            _ => continue,
+        };
+
+        tree_cursor.reset(node);
+        let Some(operand) = node
+            .children_by_field_name("operand", &mut tree_cursor)
+            .nth(device_index)
+        else {
+            continue;
+        };
+        let Some(operand) = operand.child(0) else {
+            continue;
+        };
+        let text = operand.utf8_text(content.as_bytes()).unwrap();
+        let device = match operand.kind() {
+            "device_spec" => text.to_string(),
+            "identifier" => match aliases.get(text).map(|a| &a.value) {
+                Some(AliasValue::Device(device)) => device.clone(),
+                _ => continue,
+            },
+            _ => continue,
+        };
+
+        let entry = usage.entry(device).or_default();
+        if is_write {
+            entry.1.push(Range::from(node.range()).into());
+        } else {
+            entry.0 += 1;
+        }
+    }
+
+    for (device, (reads, writes)) in usage {
+        if reads >= LONE_WRITE_MIN_READS && writes.len() == 1 {
+            diagnostics.push(Diagnostic::new(
+                writes[0],
+                Some(DiagnosticSeverity::HINT),
+                Some(NumberOrString::String(LINT_LONE_DEVICE_WRITE.to_string())),
+                None,
+                format!(
+                    "Only store to {} (read {} times elsewhere). Should this be a load?",
+                    device, reads
+                ),
+                None,
+                None,
+            ));
+        }
+    }
+}
+
+/// Logic types that control a device rather than the IC housing itself; on `db` they are
+/// usually a pin mix-up (`Setting`, the housing display, is deliberately absent)
+const DEVICE_CONTROL_TYPES: phf::Set<&'static str> = phf_set!(
+    "On", "Open", "Lock", "Mode", "Activate", "Color", "Horizontal", "Vertical", "Ratio",
+    "Pressure", "Temperature", "Charge"
+);
+
+/// Note `l`/`s` on `db` with a device-control logic type; the quick fix retargets `d0`
+pub fn check_db_access(tree: &Tree, content: &str, diagnostics: &mut Vec<Diagnostic>) {
+    let mut cursor = QueryCursor::new();
+    let query = Query::new(tree_sitter_ic10::language(), "(instruction)@x").unwrap();
+    for (capture, _) in cursor.captures(&query, tree.root_node(), content.as_bytes()) {
+        let node = capture.captures[0].node;
+        let operation = node
+            .child_by_field_name("operation")
+            .and_then(|op| op.utf8_text(content.as_bytes()).ok())
+            .unwrap_or("");
+        let device_index = match operation {
+            "l" => 1,
+            "s" => 0,
+            _ => continue,
+        };
+        let mut operand_cursor = node.walk();
+        let operands: Vec<_> = node.children_by_field_name("operand", &mut operand_cursor).collect();
+        let (Some(device), Some(logic_type)) =
+            (operands.get(device_index), operands.get(device_index + 1))
+        else {
+            continue;
+        };
+        let text = |n: &tree_sitter::Node| n.utf8_text(content.as_bytes()).unwrap_or("");
+        let is_housing = text(device) == "db"
+            && device.named_child(0).is_some_and(|d| d.kind() == "device_spec");
+        if !is_housing || !DEVICE_CONTROL_TYPES.contains(text(logic_type)) {
+            continue;
+        }
+        diagnostics.push(Diagnostic {
+            range: Range::from(device.range()).into(),
+            severity: Some(DiagnosticSeverity::INFORMATION),
+            code: Some(NumberOrString::String(LINT_ACCIDENTAL_DB_ACCESS.to_string())),
+            message: format!(
+                "'db' is the IC housing itself; {} {} on it is unusual. Did you mean a device pin like d0?",
+                if operation == "s" { "writing" } else { "reading" },
+                text(logic_type)
+            ),
+            ..Default::default()
+        });
+    }
+}
+
+/// Note on the first executable instruction when an `alias`/`define` comes after it: declarations
+/// are not hoisted, so code above them runs before the names exist.
+pub fn check_declaration_order(tree: &Tree, content: &str, diagnostics: &mut Vec<Diagnostic>) {
+    let mut cursor = QueryCursor::new();
+    let query = Query::new(tree_sitter_ic10::language(), "(instruction)@x").unwrap();
+    let mut first_executable = None;
+    for (capture, _) in cursor.captures(&query, tree.root_node(), content.as_bytes()) {
+        let node = capture.captures[0].node;
+        if mnemonic_label(node, content.as_bytes()).is_some() {
+            continue;
+        }
+        let operation = node
+            .child_by_field_name("operation")
+            .and_then(|op| op.utf8_text(content.as_bytes()).ok())
+            .unwrap_or("");
+        let is_declaration = matches!(operation, "alias" | "define");
+        match first_executable {
+            None if !is_declaration => first_executable = Some(node),
+            Some(first) if is_declaration => {
+                diagnostics.push(Diagnostic {
+                    range: Range::from(first.range()).into(),
+                    severity: Some(DiagnosticSeverity::INFORMATION),
+                    code: Some(NumberOrString::String(LINT_DECLARATIONS_ARE_POSITIONAL.to_string())),
+                    message: format!(
+                        "Code runs before the {} on line {}. Aliases and defines take effect at their line, not globally.",
+                        operation,
+                        node.start_position().row + 1
+                    ),
+                    ..Default::default()
+                });
+                return;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Warn on region markers that do not pair up, matched innermost first: an `# endregion` with
+/// no open region, and each `# region` still open at the end of the file. `uri` adds a related
+/// location at the end of the file to the dangling openers.
+pub fn check_unbalanced_regions(
+    tree: &Tree,
+    content: &str,
+    uri: Option<&Url>,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let markers = region_markers(tree.root_node(), content);
+    for node in markers.unmatched_ends {
+        diagnostics.push(Diagnostic {
+            range: Range::from(node.range()).into(),
+            severity: Some(DiagnosticSeverity::WARNING),
+            code: Some(NumberOrString::String(LINT_UNBALANCED_REGION.to_string())),
+            message: "'# endregion' has no matching '# region' above it.".to_string(),
+            ..Default::default()
+        });
+    }
+
+    let end_of_file: LspPosition = Position::from(tree.root_node().end_position()).into();
+    for node in markers.unclosed {
+        diagnostics.push(Diagnostic {
+            range: Range::from(node.range()).into(),
+            severity: Some(DiagnosticSeverity::WARNING),
+            code: Some(NumberOrString::String(LINT_UNBALANCED_REGION.to_string())),
+            message: "'# region' is never closed with '# endregion'.".to_string(),
+            related_information: uri.map(|uri| {
+                vec![DiagnosticRelatedInformation {
+                    location: Location::new(uri.clone(), LspRange::new(end_of_file, end_of_file)),
+                    message: "File ends with the region still open".to_string(),
+                }]
+            }),
+            ..Default::default()
+        });
+    }
+}
+
+/// Branch instructions whose target is a line number rather than a label. Any edit that
+/// adds, removes or reorders lines silently retargets them.
+pub(crate) fn absolute_jumps<'a>(tree: &'a Tree, content: &str) -> Vec<tree_sitter::Node<'a>> {
+    const BRANCH_INSTRUCTIONS: phf::Set<&'static str> = phf_set!(
+        "bdns", "bdnsal", "bdse", "bdseal", "bap", "bapz", "bapzal", "beq", "beqal",
+        "beqz", "beqzal", "bge", "bgeal", "bgez", "bgezal", "bgt", "bgtal", "bgtz",
+        "bgtzal", "ble", "bleal", "blez", "blezal", "blt", "bltal", "bltz", "bltzal",
+        "bna", "bnaz", "bnazal", "bne", "bneal", "bnez", "bnezal", "j", "jal"
+    );
+    let mut cursor = QueryCursor::new();
+    let query = Query::new(
+        tree_sitter_ic10::language(),
+        "(instruction operand: (operand (number))) @x",
+    )
+    .unwrap();
+    let mut tree_cursor = tree.walk();
+    let mut jumps = Vec::new();
+    for (capture, _) in cursor.captures(&query, tree.root_node(), content.as_bytes()) {
+        let capture = capture.captures[0].node;
+        let Some(operation_node) = capture.child_by_field_name("operation") else {
+            continue;
+        };
+        let operation = operation_node.utf8_text(content.as_bytes()).unwrap();
+        if !BRANCH_INSTRUCTIONS.contains(operation) {
+            continue;
+        }
+
+        tree_cursor.reset(capture);
+        let Some(last_operand) = capture
+            .children_by_field_name("operand", &mut tree_cursor)
+            .last()
+        else {
+            continue;
+        };
+        if let Some(last_operand) = last_operand.child(0) {
+            // Any integer notation names a line; `$1A` is as absolute as `26`
+            let is_line_number = last_operand.kind() == "number"
+                && last_operand
+                    .utf8_text(content.as_bytes())
+                    .ok()
+                    .and_then(crate::hash_utils::parse_ic10_number)
+                    .is_some();
+            if is_line_number && !jumps.contains(&capture) {
+                jumps.push(capture);
+            }
+        }
+    }
+    jumps
+}
+
+/// Hint at the end of a non-empty script whose last line has no newline
+pub fn missing_trailing_newline(tree: &Tree, content: &str) -> Option<Diagnostic> {
+    if content.is_empty() || content.ends_with('\n') {
+        return None;
+    }
+    let end = LspPosition::from(Position::from(tree.root_node().end_position()));
+    Some(Diagnostic {
+        range: LspRange::new(end, end),
+        severity: Some(DiagnosticSeverity::HINT),
+        code: Some(NumberOrString::String(LINT_MISSING_TRAILING_NEWLINE.to_string())),
+        message: "File does not end with a newline.".to_string(),
+        ..Default::default()
+    })
+}
+
+/// Hint at the top of a non-empty script that has nothing to run: only defines, aliases,
+/// labels or comments (forgotten logic, or everything commented out)
+pub fn check_executable_instructions(tree: &Tree, content: &str, diagnostics: &mut Vec<Diagnostic>) {
+    if content.trim().is_empty() {
+        return;
+    }
+    let mut cursor = QueryCursor::new();
+    let query = Query::new(tree_sitter_ic10::language(), "(instruction)@x").unwrap();
+    let executable = cursor
+        .captures(&query, tree.root_node(), content.as_bytes())
+        .map(|(capture, _)| capture.captures[0].node)
+        .filter(|node| mnemonic_label(*node, content.as_bytes()).is_none())
+        .filter(|node| {
+            let operation = node
+                .child_by_field_name("operation")
+                .and_then(|op| op.utf8_text(content.as_bytes()).ok())
+                .unwrap_or("");
+            !matches!(operation, "alias" | "define")
+        })
+        .count();
+    if executable == 0 {
+        let first_line_end = content.lines().next().map_or(0, |line| line.encode_utf16().count() as u32);
+        diagnostics.push(Diagnostic {
+            range: LspRange::new(LspPosition::new(0, 0), LspPosition::new(0, first_line_end)),
+            severity: Some(DiagnosticSeverity::HINT),
+            code: Some(NumberOrString::String(LINT_NO_EXECUTABLE_INSTRUCTIONS.to_string())),
+            message: "Script has no executable instructions; it only declares defines, aliases or labels."
+                .to_string(),
+            ..Default::default()
+        });
+    }
+}
+
+/// Names listed in `# ignore a, b` comments, lowercased (the register analyzer's directive)
+fn ignored_names(content: &str) -> HashSet<String> {
+    content
+        .lines()
+        .filter_map(|line| line.split_once('#').map(|(_, comment)| comment.trim().to_lowercase()))
+        .filter_map(|comment| {
+            // `# ignored because ...` or `# ignorelimits` is not the directive
+            let rest = comment.strip_prefix("ignore")?;
+            (rest.is_empty() || rest.starts_with(|c: char| c.is_whitespace() || c == ':'))
+                .then(|| rest.trim_start_matches(':').to_string())
+        })
+        .flat_map(|names| names.split(',').map(|name| name.trim().to_string()).collect::<Vec<_>>())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+/// Hint (faded, `Unnecessary`) on each define and alias whose name is never used, and under a
+/// separate code on each label nothing branches to or references. Names in a `# ignore`
+/// directive are skipped. `data` carries the name.
+pub fn check_unused_definitions(
+    tree: &Tree,
+    content: &str,
+    type_data: &TypeData,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    // Every use of a name, by text; names like `Setting` lex as logic types
+    let mut uses: HashMap<&str, Vec<LspRange>> = HashMap::new();
+    let query = Query::new(tree_sitter_ic10::language(), "[(identifier) (logictype)] @name").unwrap();
+    let mut cursor = QueryCursor::new();
+    for (capture, _) in cursor.captures(&query, tree.root_node(), content.as_bytes()) {
+        let node = capture.captures[0].node;
+        if let Ok(text) = node.utf8_text(content.as_bytes()) {
+            uses.entry(text).or_default().push(Range::from(node.range()).into());
+        }
+    }
+    let ignored = ignored_names(content);
+    let unused = |name: &str, definition: LspRange| {
+        !ignored.contains(&name.to_lowercase())
+            && uses.get(name).is_none_or(|ranges| ranges.iter().all(|range| *range == definition))
+    };
+
+    let mut found: Vec<(LspRange, &str, &str, String)> = Vec::new();
+    for (name, define) in &type_data.defines {
+        if unused(name, define.range.0) {
+            found.push((define.range.0, name, LINT_UNUSED_DEFINITION, format!("Define '{}' is never used.", name)));
+        }
+    }
+    for (name, alias) in &type_data.aliases {
+        if unused(name, alias.range.0) {
+            found.push((alias.range.0, name, LINT_UNUSED_DEFINITION, format!("Alias '{}' is never used.", name)));
+        }
+    }
+    for (name, label) in &type_data.labels {
+        if unused(name, label.range.0) {
+            found.push((
+                label.range.0,
+                name,
+                LINT_UNUSED_LABEL,
+                format!("Label '{}' is never branched to or referenced.", name),
+            ));
+        }
+    }
+    found.sort_by_key(|(range, _, _, _)| (range.start.line, range.start.character));
+    for (range, name, code, message) in found {
+        diagnostics.push(Diagnostic {
+            range,
+            severity: Some(DiagnosticSeverity::HINT),
+            code: Some(NumberOrString::String(code.to_string())),
+            message,
+            tags: Some(vec![DiagnosticTag::UNNECESSARY]),
+            data: Some(serde_json::Value::String(name.to_string())),
+            ..Default::default()
+        });
+    }
+}
+
+/// The note shown instead of register and control-flow analysis on a file longer than `budget`
+/// lines (0 means no limit); `None` when the file is within budget
+pub fn deep_analysis_skipped(content: &str, budget: usize) -> Option<Diagnostic> {
+    let lines = content.lines().count();
+    if budget == 0 || lines <= budget {
+        return None;
+    }
+    Some(Diagnostic {
+        range: LspRange::default(),
+        severity: Some(DiagnosticSeverity::INFORMATION),
+        code: Some(NumberOrString::String(LINT_DEEP_ANALYSIS_SKIPPED.to_string())),
+        message: format!(
+            "Register and control-flow analysis is off for performance: this file has {} lines, over the analysis line budget of {}.",
+            lines, budget
+        ),
+        ..Default::default()
+    })
+}
+
+/// Control-flow analysis: warn on each run of instructions after an unconditional `j`/`jr`,
+/// up to the next label. `jal` returns to the next line, so it doesn't end reachability. Lines
+/// that a numeric branch target points at count as labels, whatever notation or define names
+/// the number. A jump through a register other than `j ra` could land anywhere, so files with
+/// one are left alone.
+pub fn check_unreachable_code(
+    tree: &Tree,
+    content: &str,
+    type_data: &TypeData,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    enum Item<'a> {
+        Label,
+        Instruction(tree_sitter::Node<'a>, String),
+    }
+
+    let text = |n: tree_sitter::Node| n.utf8_text(content.as_bytes()).unwrap_or("");
+    // The line a target names, if it is a whole number
+    let line_number = |target: &str| {
+        let value = match type_data.defines.get(target) {
+            Some(define) => define.value.resolved_value(),
+            None => crate::document::parse_ic10_value(target),
+        }?;
+        (value.fract() == 0.0).then_some(value as i64)
+    };
+    let is_register = |target: tree_sitter::Node| {
+        target.child(0).is_some_and(|value| value.kind() == "register")
+            || type_data
+                .aliases
+                .get(text(target))
+                .is_some_and(|alias| matches!(alias.value, AliasValue::Register(_)))
+    };
+    let mut items = Vec::new();
+    let mut numeric_targets = HashSet::new();
+    let mut cursor = QueryCursor::new();
+    let query = Query::new(tree_sitter_ic10::language(), "(line)@x").unwrap();
+    for (capture, _) in cursor.captures(&query, tree.root_node(), content.as_bytes()) {
+        let line = capture.captures[0].node;
+        let mut child_cursor = line.walk();
+        for node in line.children(&mut child_cursor) {
+            let row = node.start_position().row;
+            if node.kind() == "label" {
+                items.push(Item::Label);
+                continue;
+            }
+            if node.kind() != "instruction" {
+                continue;
+            }
+            if mnemonic_label(node, content.as_bytes()).is_some() {
+                items.push(Item::Label);
+                continue;
+            }
+            let operation = node.child_by_field_name("operation").map(text).unwrap_or("").to_ascii_lowercase();
+            let mut operand_cursor = node.walk();
+            let target = node.children_by_field_name("operand", &mut operand_cursor).last();
+            let is_control = operation.starts_with('b') || matches!(operation.as_str(), "j" | "jal" | "jr");
+            if let Some(target) = target.filter(|_| is_control) {
+                if is_register(target) && !(operation == "j" && text(target) == "ra") {
+                    return;
+                }
+                if let Some(offset) = line_number(text(target)) {
+                    let relative = operation.starts_with("br") || operation == "jr";
+                    numeric_targets.insert(if relative { row as i64 + offset } else { offset });
+                }
+            }
+            items.push(Item::Instruction(node, operation));
+        }
+    }
+
+    let mut flush = |run: &mut Vec<tree_sitter::Node>, jump: &str| {
+        if let (Some(first), Some(last)) = (run.first(), run.last()) {
+            let count = run.len();
+            diagnostics.push(Diagnostic {
+                range: LspRange::new(
+                    Position::from(first.start_position()).into(),
+                    Position::from(last.end_position()).into(),
+                ),
+                severity: Some(DiagnosticSeverity::WARNING),
+                code: Some(NumberOrString::String(LINT_UNREACHABLE_CODE.to_string())),
+                message: format!(
+                    "Unreachable code: {} never runs because the '{}' above always jumps away and no label follows it.",
+                    if count == 1 { "this instruction".to_string() } else { format!("these {} instructions", count) },
+                    jump
+                ),
+                ..Default::default()
+            });
+        }
+        run.clear();
+    };
+
+    let mut jump: Option<String> = None;
+    let mut run = Vec::new();
+    for item in items {
+        match item {
+            Item::Label => {
+                if let Some(jump) = jump.take() {
+                    flush(&mut run, &jump);
+                }
+            }
+            Item::Instruction(node, operation) => {
+                if numeric_targets.contains(&(node.start_position().row as i64)) {
+                    if let Some(jump) = jump.take() {
+                        flush(&mut run, &jump);
+                    }
+                }
+                match &jump {
+                    Some(_) => run.push(node),
+                    None if operation == "j" || operation == "jr" => jump = Some(text(node).trim().to_string()),
+                    None => {}
+                }
+            }
+        }
+    }
+    if let Some(jump) = jump {
+        flush(&mut run, &jump);
+    }
+}
+
+/// Control-flow analysis: note `jal` call sites whose chain of nested `jal`s (followed through
+/// each subroutine's straight-line body, up to `j`/`jr`) is deeper than `max_depth` or recursive.
+/// Every nested call needs `ra` saved on the stack, so deep chains can exhaust it. Only call
+/// sites outside any called subroutine are reported, once per chain.
+pub fn check_call_depth(
+    tree: &Tree,
+    content: &str,
+    max_depth: usize,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    // (operation, last operand, instruction range)
+    let mut steps: Vec<(String, String, Range)> = Vec::new();
+    let mut labels: HashMap<String, usize> = HashMap::new();
+
+    let mut cursor = QueryCursor::new();
+    let query = Query::new(tree_sitter_ic10::language(), "(line)@x").unwrap();
+    for (capture, _) in cursor.captures(&query, tree.root_node(), content.as_bytes()) {
+        let line = capture.captures[0].node;
+        let mut child_cursor = line.walk();
+        for node in line.children(&mut child_cursor) {
+            let text = |n: tree_sitter::Node| n.utf8_text(content.as_bytes()).unwrap_or("");
+            if node.kind() == "label" {
+                if let Some(name) = node.child(0) {
+                    labels.insert(text(name).to_string(), steps.len());
+                }
+                continue;
+            }
+            if node.kind() != "instruction" {
+                continue;
+            }
+            let operation = node.child_by_field_name("operation").map(text).unwrap_or("");
+            let mut operand_cursor = node.walk();
+            let target = node
+                .children_by_field_name("operand", &mut operand_cursor)
+                .last()
+                .map(text)
+                .unwrap_or("");
+            steps.push((operation.to_string(), target.to_string(), Range::from(node.range())));
+        }
+    }
+
+    // Body of a subroutine: from its label to the first `j`/`jr`
+    let body = |label: &str| {
+        let start = labels.get(label).copied()?;
+        let end = steps[start..]
+            .iter()
+            .position(|(op, _, _)| op == "j" || op == "jr")
+            .map_or(steps.len(), |i| start + i + 1);
+        Some(start..end)
+    };
+
+    // Deepest chain of labels called from `label` (itself included), or the cycle it reaches
+    fn chain(
+        label: &str,
+        steps: &[(String, String, Range)],
+        body: &dyn Fn(&str) -> Option<std::ops::Range<usize>>,
+        stack: &mut Vec<String>,
+    ) -> std::result::Result<Vec<String>, Vec<String>> {
+        if let Some(at) = stack.iter().position(|l| l == label) {
+            let mut cycle = stack[at..].to_vec();
+            cycle.push(label.to_string());
+            return Err(cycle);
+        }
+        let Some(range) = body(label) else {
+            return Ok(Vec::new());
+        };
+        stack.push(label.to_string());
+        let mut deepest = Vec::new();
+        for (op, target, _) in &steps[range] {
+            if op == "jal" {
+                let nested = chain(target, steps, body, stack)?;
+                if nested.len() > deepest.len() {
+                    deepest = nested;
+                }
+            }
+        }
+        stack.pop();
+        deepest.insert(0, label.to_string());
+        Ok(deepest)
+    }
+
+    let called: Vec<_> = steps
+        .iter()
+        .filter(|(op, _, _)| op == "jal")
+        .filter_map(|(_, target, _)| body(target))
+        .collect();
+    for (index, (op, target, range)) in steps.iter().enumerate() {
+        if op != "jal" || called.iter().any(|r| r.contains(&index)) {
+            continue;
+        }
+        let message = match chain(target, &steps, &body, &mut Vec::new()) {
+            Ok(labels) if labels.len() > max_depth => format!(
+                "Calls from here nest {} deep ({}), above the limit of {}. Each nested jal needs ra saved on the stack.",
+                labels.len(),
+                labels.join(" → "),
+                max_depth
+            ),
+            Ok(_) => continue,
+            Err(cycle) => format!(
+                "Calls from here can recurse ({}). Each nested jal needs ra saved on the stack, so unbounded recursion exhausts it.",
+                cycle.join(" → ")
+            ),
+        };
+        diagnostics.push(Diagnostic {
+            range: (*range).into(),
+            severity: Some(DiagnosticSeverity::INFORMATION),
+            code: Some(NumberOrString::String(LINT_DEEP_CALL_NESTING.to_string())),
+            message,
+            ..Default::default()
+        });
+    }
+}
+
+/// The value of an operand known before the script runs: a number literal or a numeric define
+fn constant_value(text: &str, defines: &HashMap<String, DefinitionData<DefineValue>>) -> Option<f64> {
+    match defines.get(text) {
+        Some(define) => define.value.resolved_value(),
+        None => crate::document::parse_ic10_value(text),
+    }
+}
+
+/// Warn when `poke`/`get`/`put`/... use a constant stack address (literal or define) outside
+/// `0..STACK_SIZE`. Register addresses are only known at runtime and are not checked.
+pub fn check_stack_addresses(
+    tree: &Tree,
+    content: &str,
+    defines: &HashMap<String, DefinitionData<DefineValue>>,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let mut cursor = QueryCursor::new();
+    let query = Query::new(tree_sitter_ic10::language(), "(instruction)@x").unwrap();
+    for (capture, _) in cursor.captures(&query, tree.root_node(), content.as_bytes()) {
+        let node = capture.captures[0].node;
+        let Some(index) = node
+            .child_by_field_name("operation")
+            .and_then(|op| op.utf8_text(content.as_bytes()).ok())
+            .and_then(instructions::stack_address_operand)
+        else {
+            continue;
+        };
+        let mut operand_cursor = node.walk();
+        let Some(operand) = node.children_by_field_name("operand", &mut operand_cursor).nth(index) else {
+            continue;
+        };
+        let text = operand.utf8_text(content.as_bytes()).unwrap_or("");
+        let Some(value) = constant_value(text, defines) else {
+            continue;
+        };
+        if value < 0.0 || value >= instructions::STACK_SIZE as f64 {
+            diagnostics.push(Diagnostic {
+                range: Range::from(operand.range()).into(),
+                severity: Some(DiagnosticSeverity::WARNING),
+                code: Some(NumberOrString::String(LINT_STACK_ADDRESS_RANGE.to_string())),
+                message: format!(
+                    "Stack address {} is outside the stack (0-{}).",
+                    value,
+                    instructions::STACK_SIZE - 1
+                ),
+                ..Default::default()
+            });
+        }
+    }
+}
+
+/// Fade provable no-ops: `move r0 r0`, `add r0 r0 0`, `sub r0 r0 0`, `mul r0 r0 1`,
+/// `div r0 r0 1` (and the commuted `add`/`mul` forms). Only literally identical register or
+/// register-alias names count, and the constant must be a number literal.
+pub fn check_no_op_instructions(
+    tree: &Tree,
+    content: &str,
+    aliases: &HashMap<String, DefinitionData<AliasValue>>,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let text = |node: &tree_sitter::Node| node.utf8_text(content.as_bytes()).unwrap_or("");
+    let kind = |node: &tree_sitter::Node| node.named_child(0).map_or("", |child| child.kind());
+    let is_register = |operand: &tree_sitter::Node| match kind(operand) {
+        "register" => true,
+        "identifier" => aliases
+            .get(text(operand))
+            .is_some_and(|alias| matches!(alias.value, AliasValue::Register(_))),
+        _ => false,
+    };
+    let is_constant = |operand: &tree_sitter::Node, value: f64| {
+        kind(operand) == "number" && crate::document::parse_ic10_value(text(operand)) == Some(value)
+    };
+
+    let mut cursor = QueryCursor::new();
+    let query = Query::new(tree_sitter_ic10::language(), "(instruction) @x").unwrap();
+    for (capture, _) in cursor.captures(&query, tree.root_node(), content.as_bytes()) {
+        let node = capture.captures[0].node;
+        let Some(operation) = node.child_by_field_name("operation") else {
+            continue;
+        };
+        let operation = text(&operation).to_ascii_lowercase();
+        let mut operand_cursor = node.walk();
+        let operands: Vec<_> = node.children_by_field_name("operand", &mut operand_cursor).collect();
+        let Some(destination) = operands.first().filter(|operand| is_register(operand)) else {
+            continue;
+        };
+        let same = |operand: &tree_sitter::Node| text(operand) == text(destination);
+        let no_op = match (operation.as_str(), &operands[1..]) {
+            ("move", [source]) => same(source),
+            ("add", [a, b]) => (same(a) && is_constant(b, 0.0)) || (is_constant(a, 0.0) && same(b)),
+            ("sub", [a, b]) => same(a) && is_constant(b, 0.0),
+            ("mul", [a, b]) => (same(a) && is_constant(b, 1.0)) || (is_constant(a, 1.0) && same(b)),
+            ("div", [a, b]) => same(a) && is_constant(b, 1.0),
+            _ => false,
+        };
+        if no_op {
+            diagnostics.push(Diagnostic {
+                range: Range::from(node.range()).into(),
+                severity: Some(DiagnosticSeverity::HINT),
+                code: Some(NumberOrString::String(LINT_NO_OP_INSTRUCTION.to_string())),
+                message: format!("'{}' leaves {} unchanged.", text(&node), text(destination)),
+                tags: Some(vec![DiagnosticTag::UNNECESSARY]),
+                ..Default::default()
+            });
+        }
+    }
+}
+
+/// Largest edit distance at which an unknown mnemonic still gets a "did you mean" suggestion
+const MNEMONIC_SUGGESTION_DISTANCE: usize = 2;
+
+/// The valid mnemonic nearest to `text` by edit distance, alphabetically first on a tie
+fn nearest_mnemonic(text: &str) -> Option<&'static str> {
+    let lowered = text.to_ascii_lowercase();
+    instructions::INSTRUCTIONS
+        .keys()
+        .map(|mnemonic| (edit_distance(&lowered, mnemonic), *mnemonic))
+        .filter(|(distance, _)| *distance <= MNEMONIC_SUGGESTION_DISTANCE)
+        .min()
+        .map(|(_, mnemonic)| mnemonic)
+}
+
+/// "Invalid instruction" for `operation`, naming the nearest valid mnemonic when there is one.
+/// `data` carries that mnemonic for the replace quick fix.
+fn unknown_mnemonic_diagnostic(node: tree_sitter::Node, operation: &str, severity: DiagnosticSeverity) -> Diagnostic {
+    let suggestion = nearest_mnemonic(operation);
+    Diagnostic {
+        range: Range::from(node.range()).into(),
+        severity: Some(severity),
+        code: Some(NumberOrString::String(LINT_INVALID_INSTRUCTION.to_string())),
+        message: match suggestion {
+            Some(mnemonic) => format!("Invalid instruction, did you mean `{}`?", mnemonic),
+            None => "Invalid instruction".to_string(),
+        },
+        data: suggestion.map(serde_json::Value::from),
+        ..Default::default()
+    }
+}
+
+/// "Invalid instruction" for an unknown mnemonic, unless it is a bare prefix of a real one
+/// (`ad` on its way to `add`): that is still being typed and would only flicker.
+fn invalid_instruction_diagnostic(
+    node: tree_sitter::Node,
+    content: &[u8],
+    severity: DiagnosticSeverity,
+) -> Option<Diagnostic> {
+    let instruction_text = node.utf8_text(content).ok()?;
+    if instructions::INSTRUCTIONS.contains_key(instruction_text) {
+        return None;
+    }
+    let bare = node.parent().is_some_and(|instruction| instruction.named_child_count() == 1);
+    if bare
+        && instructions::INSTRUCTIONS
+            .keys()
+            .any(|mnemonic| mnemonic.starts_with(instruction_text))
+    {
+        return None;
+    }
+    Some(unknown_mnemonic_diagnostic(node, instruction_text, severity))
+}
+
 /// Run full diagnostics on a document and publish results
 pub async fn run_diagnostics(backend: &Backend, uri: &Url) {
     let _timer = crate::performance::TimingGuard::new(&backend.perf_tracker, "lsp.server.diagnostics");
@@ -489,15 +1958,10 @@ pub async fn run_diagnostics(backend: &Backend, uri: &Url) {
         let query = Query::new(tree_sitter_ic10::language(), "(ERROR)@error").unwrap();
         let captures = cursor.captures(&query, tree.root_node(), document.content.as_bytes());
         for (capture, _) in captures {
-            diagnostics.push(Diagnostic::new(
-                Range::from(capture.captures[0].node.range()).into(),
-                Some(DiagnosticSeverity::ERROR),
-                None,
-                None,
-                "Syntax error".to_string(),
-                None,
-                None,
-            ));
+            if is_mnemonic_label_colon(capture.captures[0].node, document.content.as_bytes()) {
+                continue;
+            }
+            diagnostics.extend(syntax_error_diagnostic(capture.captures[0].node, document.content.as_bytes()));
         }
     }
 
@@ -511,19 +1975,11 @@ pub async fn run_diagnostics(backend: &Backend, uri: &Url) {
         .unwrap();
         let captures = cursor.captures(&query, tree.root_node(), document.content.as_bytes());
         for (capture, _) in captures {
-            let node = capture.captures[0].node;
-            let instruction_text = node.utf8_text(document.content.as_bytes()).unwrap();
-            if !instructions::INSTRUCTIONS.contains_key(instruction_text) {
-                diagnostics.push(Diagnostic::new(
-                    Range::from(node.range()).into(),
-                    Some(DiagnosticSeverity::ERROR),
-                    None,
-                    None,
-                    "Invalid instruction".to_string(),
-                    None,
-                    None,
-                ));
-            }
+            diagnostics.extend(invalid_instruction_diagnostic(
+                capture.captures[0].node,
+                document.content.as_bytes(),
+                config.invalid_instruction_severity,
+            ));
         }
     }
 
@@ -632,57 +2088,15 @@ pub async fn run_diagnostics(backend: &Backend, uri: &Url) {
     {
         // Check for #IgnoreLimits directive
         if !crate::diagnostic_helpers::should_ignore_limits(&document.content) {
-            let mut byte_count = 0;
-            let mut start_pos: Option<LspPosition> = None;
-            
-            // Stationeers byte counting (matches UpdateFileSize() method):
-            // After paste, each line is trimmed with TrimEnd()
-            // Then UpdateFileSize() counts: line.Length + 2 bytes (CRLF) for all except last line
-            // This matches the file loading behavior from InputSourceCode.cs lines 562-568
-            
-            // Split content by newlines, filtering out empty trailing lines
-            // This matches how Stationeers processes pasted content
-            let mut lines: Vec<&str> = document.content.lines().collect();
-            
-            // Remove trailing empty lines (Stationeers doesn't count these)
-            while lines.last().map_or(false, |l| l.trim().is_empty()) {
-                lines.pop();
-            }
-            
-            for (line_idx, line) in lines.iter().enumerate() {
-                let trimmed = line.trim_end();
-                byte_count += trimmed.len();
-                
-                // Add CRLF (2 bytes) for all lines except the last
-                // Matches C# code: if (j < this.LinesOfCode.Count - 1)
-                if line_idx < lines.len() - 1 {
-                    byte_count += 2;
-                }
-            }
+            // Stationeers byte counting (matches UpdateFileSize(), see InputSourceCode.cs)
+            let byte_count = crate::diagnostic_helpers::game_byte_size(&document.content);
 
-            // Find position where limit is exceeded (scan content for position)
+            // Find position where limit is exceeded, counted the same way
             if byte_count > config.max_bytes {
-                let mut current_line = 0;
-                let mut current_col = 0;
-                let mut running_count = 0;
-
-                for char in document.content.chars() {
-                    let char_len = char.len_utf8();
-
-                    if running_count <= config.max_bytes && running_count + char_len > config.max_bytes {
-                        if start_pos.is_none() {
-                            start_pos = Some(LspPosition::new(current_line, current_col));
-                        }
-                    }
-                    running_count += char_len;
-
-                    if char == '\n' {
-                        current_line += 1;
-                        current_col = 0;
-                    } else if char != '\r' {
-                        current_col += 1;
-                    }
-                }
+                let start_pos = crate::diagnostic_helpers::game_byte_overflow_position(
+                    &document.content,
+                    config.max_bytes,
+                );
 
                 let end_line = document.content.lines().count().saturating_sub(1) as u32;
                 let end_col = document.content.lines().last().map_or(0, |l| l.len()) as u32;
@@ -704,55 +2118,16 @@ pub async fn run_diagnostics(backend: &Backend, uri: &Url) {
     }
 
     // Absolute jump to number lint
-    {
-        const BRANCH_INSTRUCTIONS: phf::Set<&'static str> = phf_set!(
-            "bdns", "bdnsal", "bdse", "bdseal", "bap", "bapz", "bapzal", "beq", "beqal",
-            "beqz", "beqzal", "bge", "bgeal", "bgez", "bgezal", "bgt", "bgtal", "bgtz",
-            "bgtzal", "ble", "bleal", "blez", "blezal", "blt", "bltal", "bltz", "bltzal",
-            "bna", "bnaz", "bnazal", "bne", "bneal", "bnez", "bnezal", "j", "jal"
-        );
-        let mut cursor = QueryCursor::new();
-        let query = Query::new(
-            tree_sitter_ic10::language(),
-            "(instruction operand: (operand (number))) @x",
-        )
-        .unwrap();
-        let mut tree_cursor = tree.walk();
-        let captures = cursor.captures(&query, tree.root_node(), document.content.as_bytes());
-        for (capture, _) in captures {
-            let capture = capture.captures[0].node;
-            let Some(operation_node) = capture.child_by_field_name("operation") else {
-                continue;
-            };
-            let operation = operation_node
-                .utf8_text(document.content.as_bytes())
-                .unwrap();
-            if !BRANCH_INSTRUCTIONS.contains(operation) {
-                continue;
-            }
-
-            tree_cursor.reset(capture);
-            let Some(last_operand) = capture
-                .children_by_field_name("operand", &mut tree_cursor)
-                .into_iter()
-                .last()
-            else {
-                continue;
-            };
-            if let Some(last_operand) = last_operand.child(0) {
-                if last_operand.kind() == "number" {
-                    diagnostics.push(Diagnostic::new(
-                        Range::from(capture.range()).into(),
-                        Some(DiagnosticSeverity::WARNING),
-                        Some(NumberOrString::String(LINT_ABSOLUTE_JUMP.to_string())),
-                        None,
-                        "Absolute jump to line number".to_string(),
-                        None,
-                        None,
-                    ));
-                }
-            }
-        }
+    for instruction in absolute_jumps(tree, &document.content) {
+        diagnostics.push(Diagnostic::new(
+            Range::from(instruction.range()).into(),
+            Some(DiagnosticSeverity::WARNING),
+            Some(NumberOrString::String(LINT_ABSOLUTE_JUMP.to_string())),
+            None,
+            "Absolute jump to line number".to_string(),
+            None,
+            None,
+        ));
     }
 
     // Relative branch to label lint (should use absolute branch)
@@ -815,6 +2190,7 @@ pub async fn run_diagnostics(backend: &Backend, uri: &Url) {
 
     // Check for numbers inside HASH() functions
     {
+        let device_hashes = backend.device_hashes.read().await;
         let mut cursor = QueryCursor::new();
         let query = Query::new(
             tree_sitter_ic10::language(),
@@ -822,6 +2198,7 @@ pub async fn run_diagnostics(backend: &Backend, uri: &Url) {
         )
         .unwrap();
         let captures = cursor.captures(&query, tree.root_node(), document.content.as_bytes());
+        let mut used_devices = HashSet::new();
         
         for (capture, _) in captures {
             let hash_func_node = capture.captures[0].node;
@@ -846,23 +2223,99 @@ pub async fn run_diagnostics(backend: &Backend, uri: &Url) {
                             None,
                             None,
                         ));
+                    } else if device_hashes.get_device_hash(&content).is_some() {
+                        used_devices.insert(content);
                     }
                 }
             }
         }
+        backend.device_usage.insert(uri.clone(), used_devices);
+    }
+
+    // Lone device write lint (heuristic, opt-in)
+    if config.warn_lone_device_write {
+        check_lone_device_writes(
+            tree,
+            &document.content,
+            &file_data.type_data.aliases,
+            &mut diagnostics,
+        );
+    }
+
+    if config.warn_accidental_db_access {
+        check_db_access(tree, &document.content, &mut diagnostics);
+    }
+
+    check_no_op_instructions(
+        tree,
+        &document.content,
+        &file_data.type_data.aliases,
+        &mut diagnostics,
+    );
+    check_stack_addresses(
+        tree,
+        &document.content,
+        &file_data.type_data.defines,
+        &mut diagnostics,
+    );
+    check_declaration_order(tree, &document.content, &mut diagnostics);
+    check_unbalanced_regions(tree, &document.content, Some(uri), &mut diagnostics);
+    check_executable_instructions(tree, &document.content, &mut diagnostics);
+    check_unused_definitions(tree, &document.content, &file_data.type_data, &mut diagnostics);
+
+    // Very large files only get syntax and type checks; the passes below scale badly
+    let deep_analysis_note = deep_analysis_skipped(&document.content, config.analysis_line_budget);
+    let deep_analysis = deep_analysis_note.is_none();
+    diagnostics.extend(deep_analysis_note);
+
+    if deep_analysis && config.enable_control_flow_analysis {
+        check_call_depth(tree, &document.content, config.max_call_depth, &mut diagnostics);
+        check_unreachable_code(tree, &document.content, &file_data.type_data, &mut diagnostics);
     }
 
+    // Missing final newline (content is not patched; see update_content)
+    diagnostics.extend(missing_trailing_newline(tree, &document.content));
+
     // Register usage analysis
     {
         // Skip register diagnostics if globally suppressed
-        if !config.suppress_register_warnings {
+        if deep_analysis && !config.suppress_register_warnings {
             let mut register_analyzer = additional_features::RegisterAnalyzer::new();
             register_analyzer.analyze_register_usage(
                 tree,
                 &document.content,
                 &file_data.type_data.aliases,
             );
-            let register_diagnostics = register_analyzer.generate_diagnostics();
+            let mut register_diagnostics = register_analyzer.generate_diagnostics();
+            if config.enable_control_flow_analysis {
+                register_diagnostics.extend(register_analyzer.detect_call_clobbers(
+                    tree,
+                    &document.content,
+                    &file_data.type_data.aliases,
+                ));
+            }
+            register_diagnostics.extend(register_analyzer.detect_float_equality(
+                tree,
+                &document.content,
+                &file_data.type_data.aliases,
+            ));
+            register_diagnostics.extend(register_analyzer.detect_fusible_moves(
+                tree,
+                &document.content,
+                &file_data.type_data.aliases,
+            ));
+            register_diagnostics.extend(register_analyzer.detect_deviceid_reuse(
+                tree,
+                &document.content,
+                &file_data.type_data.aliases,
+            ));
+            if config.warn_logictype_roundtrip {
+                register_diagnostics.extend(register_analyzer.detect_logictype_roundtrips(
+                    tree,
+                    &document.content,
+                    &file_data.type_data.aliases,
+                ));
+            }
             let mut seen = HashSet::new();
             for existing in diagnostics.iter() {
                 seen.insert(diagnostic_identity(existing));
@@ -900,62 +2353,9 @@ pub async fn run_diagnostics(backend: &Backend, uri: &Url) {
         .await;
 }
 
-/// Compute diagnostics for a single text buffer using the same logic as the LSP diagnostics.
-/// This is a standalone function that doesn't require the Backend.
-pub fn compute_diagnostics_for_text(content: &str) -> Vec<Diagnostic> {
-    let mut diagnostics: Vec<Diagnostic> = Vec::new();
-
-    let mut parser = Parser::new();
-    parser
-        .set_language(tree_sitter_ic10::language())
-        .expect("Could not set language");
-    let tree = parser.parse(content, None).unwrap();
-
-    // Syntax errors
-    {
-        let mut cursor = QueryCursor::new();
-        let query = Query::new(tree_sitter_ic10::language(), "(ERROR)@error").unwrap();
-        let captures = cursor.captures(&query, tree.root_node(), content.as_bytes());
-        for (capture, _) in captures {
-            diagnostics.push(Diagnostic::new(
-                Range::from(capture.captures[0].node.range()).into(),
-                Some(DiagnosticSeverity::ERROR),
-                None,
-                None,
-                "Syntax error".to_string(),
-                None,
-                None,
-            ));
-        }
-    }
-
-    // Invalid instructions
-    {
-        let mut cursor = QueryCursor::new();
-        let query = Query::new(
-            tree_sitter_ic10::language(),
-            "(instruction (invalid_instruction)@error)",
-        )
-        .unwrap();
-        let captures = cursor.captures(&query, tree.root_node(), content.as_bytes());
-        for (capture, _) in captures {
-            let node = capture.captures[0].node;
-            let instruction_text = node.utf8_text(content.as_bytes()).unwrap();
-            if !instructions::INSTRUCTIONS.contains_key(instruction_text) {
-                diagnostics.push(Diagnostic::new(
-                    Range::from(node.range()).into(),
-                    Some(DiagnosticSeverity::ERROR),
-                    None,
-                    None,
-                    "Invalid instruction".to_string(),
-                    None,
-                    None,
-                ));
-            }
-        }
-    }
-
-    // Collect defines/aliases/labels
+/// Defines, aliases and labels of a whole document, as the CLI paths see them (last
+/// definition wins). Naming lints found along the way go to `diagnostics`.
+pub fn collect_type_data(tree: &Tree, content: &str, diagnostics: &mut Vec<Diagnostic>) -> TypeData {
     let mut type_data = TypeData::default();
     {
         let mut cursor = QueryCursor::new();
@@ -996,6 +2396,12 @@ pub fn compute_diagnostics_for_text(content: &str) -> Vec<Diagnostic> {
                         )
                     };
 
+                    diagnostics.extend(shadows_enum_family(name_range, &name_text));
+                    diagnostics.extend(invalid_symbol_name(name_range, &name_text));
+                    if capture_idx == define_idx {
+                        diagnostics.extend(multitoken_define(capture.captures[0].node, content.as_bytes()));
+                    }
+
                     if let Some(value_node) = capture.captures[0]
                         .node
                         .children_by_field_name("operand", &mut name_operand_node.walk())
@@ -1036,6 +2442,10 @@ pub fn compute_diagnostics_for_text(content: &str) -> Vec<Diagnostic> {
             } else if capture_idx == label_idx {
                 let name_node = capture.captures[0].node;
                 let name = name_node.utf8_text(content.as_bytes()).unwrap();
+                if instructions::INSTRUCTIONS.contains_key(name) {
+                    diagnostics.push(label_shadows_instruction(name_node, name));
+                }
+                diagnostics.extend(invalid_symbol_name(Range::from(name_node.range()), name));
                 type_data.labels.insert(
                     name.to_owned(),
                     DefinitionData {
@@ -1046,6 +2456,80 @@ pub fn compute_diagnostics_for_text(content: &str) -> Vec<Diagnostic> {
             }
         }
     }
+    for name_node in mnemonic_labels(tree.root_node(), content.as_bytes()) {
+        let name = name_node.utf8_text(content.as_bytes()).unwrap();
+        diagnostics.push(label_shadows_instruction(name_node, name));
+        type_data.labels.entry(name.to_owned()).or_insert(DefinitionData {
+            range: Range::from(name_node.range()),
+            value: name_node.start_position().row as u8,
+        });
+    }
+    type_data
+}
+
+/// Compute diagnostics for a single text buffer using the same logic as the LSP diagnostics.
+/// This is a standalone function that doesn't require the Backend.
+pub fn compute_diagnostics_for_text(content: &str) -> Vec<Diagnostic> {
+    compute_diagnostics_for_text_with_config(content, &Configuration::default())
+}
+
+/// Like [`compute_diagnostics_for_text`], honouring the opt-in lints enabled in `config`.
+pub fn compute_diagnostics_for_text_with_config(
+    content: &str,
+    config: &Configuration,
+) -> Vec<Diagnostic> {
+    compute_diagnostics_for_text_with_hashes(content, config, &DeviceHashes::default())
+}
+
+/// Like [`compute_diagnostics_for_text_with_config`], resolving device names against
+/// `device_hashes` instead of the built-in tables alone.
+pub fn compute_diagnostics_for_text_with_hashes(
+    content: &str,
+    config: &Configuration,
+    device_hashes: &DeviceHashes,
+) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = Vec::new();
+
+    let mut parser = Parser::new();
+    parser
+        .set_language(tree_sitter_ic10::language())
+        .expect("Could not set language");
+    let tree = parser.parse(content, None).unwrap();
+
+    // Syntax errors
+    {
+        let mut cursor = QueryCursor::new();
+        let query = Query::new(tree_sitter_ic10::language(), "(ERROR)@error").unwrap();
+        let captures = cursor.captures(&query, tree.root_node(), content.as_bytes());
+        for (capture, _) in captures {
+            if is_mnemonic_label_colon(capture.captures[0].node, content.as_bytes()) {
+                continue;
+            }
+            diagnostics.extend(syntax_error_diagnostic(capture.captures[0].node, content.as_bytes()));
+        }
+    }
+
+    // Invalid instructions
+    {
+        let mut cursor = QueryCursor::new();
+        let query = Query::new(
+            tree_sitter_ic10::language(),
+            "(instruction (invalid_instruction)@error)",
+        )
+        .unwrap();
+        let captures = cursor.captures(&query, tree.root_node(), content.as_bytes());
+        for (capture, _) in captures {
+            diagnostics.extend(invalid_instruction_diagnostic(
+                capture.captures[0].node,
+                content.as_bytes(),
+                config.invalid_instruction_severity,
+            ));
+        }
+    }
+
+    // Collect defines/aliases/labels
+    let mut type_data = collect_type_data(&tree, content, &mut diagnostics);
+    let first_alias_rows = first_alias_rows(&tree, content);
 
     // Type checking (simplified copy of check_types)
     {
@@ -1059,9 +2543,23 @@ pub fn compute_diagnostics_for_text(content: &str) -> Vec<Diagnostic> {
 
         for (capture, _) in captures {
             let capture = capture.captures[0].node;
+            if mnemonic_label(capture, content.as_bytes()).is_some() {
+                continue;
+            }
             if let Some(operation_node) = capture.child_by_field_name("operation") {
                 let operation = operation_node.utf8_text(content.as_bytes()).unwrap();
                 if let Some(signature) = instructions::INSTRUCTIONS.get(operation) {
+                    diagnostics.extend(instruction_not_allowed_diagnostic(
+                        operation_node,
+                        operation,
+                        config.allowed_instructions.as_deref(),
+                    ));
+                    diagnostics.extend(store_operand_order_diagnostic(
+                        capture,
+                        operation,
+                        content.as_bytes(),
+                        &type_data.aliases,
+                    ));
                     let mut argument_count = 0;
                     let mut tree_cursor = capture.walk();
                     let operands = capture.children_by_field_name("operand", &mut tree_cursor);
@@ -1138,6 +2636,11 @@ pub fn compute_diagnostics_for_text(content: &str) -> Vec<Diagnostic> {
                                                 None,
                                             ));
                                         }
+                                        diagnostics.extend(deprecated_enum_family_diagnostic(operand, canonical, instructions::enum_family_deprecation));
+                                        if let Some(diagnostic) = enum_where_device_expected(operand, canonical, parameter) {
+                                            diagnostics.push(diagnostic);
+                                            continue;
+                                        }
                                         instructions::Union(&[DataType::Number])
                                     } else if type_data.defines.contains_key(ident)
                                         || type_data.labels.contains_key(ident)
@@ -1167,6 +2670,7 @@ pub fn compute_diagnostics_for_text(content: &str) -> Vec<Diagnostic> {
                                     } else if let Some(type_data_val) =
                                         type_data.aliases.get(ident)
                                     {
+                                        diagnostics.extend(alias_used_before_declaration(operand, ident, type_data_val, &first_alias_rows));
                                         match type_data_val.value {
                                             AliasValue::Device(_) => {
                                                 instructions::Union(&[DataType::Device])
@@ -1182,6 +2686,12 @@ pub fn compute_diagnostics_for_text(content: &str) -> Vec<Diagnostic> {
                                 } else if type_data.defines.contains_key(ident)
                                     || type_data.labels.contains_key(ident)
                                 {
+                                    if let Some(diagnostic) = type_data.defines.get(ident).and_then(|define| {
+                                        number_define_as_device(operand, ident, define, parameter)
+                                    }) {
+                                        diagnostics.push(diagnostic);
+                                        continue;
+                                    }
                                     instructions::Union(&[DataType::Number])
                                 } else if let Some((canonical, _)) = type_data
                                     .defines
@@ -1207,6 +2717,7 @@ pub fn compute_diagnostics_for_text(content: &str) -> Vec<Diagnostic> {
                                 } else if let Some(type_data_val) =
                                     type_data.aliases.get(ident)
                                 {
+                                    diagnostics.extend(alias_used_before_declaration(operand, ident, type_data_val, &first_alias_rows));
                                     match type_data_val.value {
                                         AliasValue::Device(_) => {
                                             instructions::Union(&[DataType::Device])
@@ -1233,6 +2744,9 @@ pub fn compute_diagnostics_for_text(content: &str) -> Vec<Diagnostic> {
                                                 None,
                                             ));
                                             ci_flags.to_union()
+                                        } else if let Some(canonical) = canonical_register_name(ident) {
+                                            diagnostics.push(malformed_register_diagnostic(operand, &canonical));
+                                            instructions::Union(&[DataType::Register])
                                         } else {
                                             diagnostics.push(Diagnostic::new(
                                                 Range::from(operand.range()).into(),
@@ -1243,6 +2757,9 @@ pub fn compute_diagnostics_for_text(content: &str) -> Vec<Diagnostic> {
                                                 None,
                                                 None,
                                             ));
+                                            if let Some((name, digits)) = missing_space_split(ident, &type_data) {
+                                                diagnostics.push(missing_space_diagnostic(operand, name, digits));
+                                            }
                                             continue;
                                         }
                                     }
@@ -1270,7 +2787,22 @@ pub fn compute_diagnostics_for_text(content: &str) -> Vec<Diagnostic> {
                             }
                         }
 
-                        if !parameter.match_union(&effective_typ) {
+                        if let Some(diagnostic) = invalid_destination_diagnostic(
+                            operation,
+                            argument_count,
+                            operand,
+                            operand_kind,
+                        ) {
+                            diagnostics.push(diagnostic);
+                        } else if let Some(diagnostic) = invalid_name_hash_diagnostic(
+                            operation,
+                            argument_count,
+                            operand,
+                            &effective_typ,
+                            parameter,
+                        ) {
+                            diagnostics.push(diagnostic);
+                        } else if !parameter.match_union(&effective_typ) {
                             diagnostics.push(Diagnostic::new(
                                 Range::from(operand.range()).into(),
                                 Some(DiagnosticSeverity::ERROR),
@@ -1285,6 +2817,37 @@ pub fn compute_diagnostics_for_text(content: &str) -> Vec<Diagnostic> {
                             ));
                         }
 
+                        diagnostics.extend(shift_amount_diagnostic(
+                            operation,
+                            argument_count,
+                            operand,
+                            content.as_bytes(),
+                            &type_data.defines,
+                        ));
+                        diagnostics.extend(bit_field_diagnostic(
+                            operation,
+                            argument_count,
+                            operand,
+                            content.as_bytes(),
+                            &type_data.defines,
+                        ));
+                        diagnostics.extend(long_sleep_diagnostic(
+                            operation,
+                            argument_count,
+                            operand,
+                            content.as_bytes(),
+                            &type_data.defines,
+                        ));
+                        if !config.suppress_hash_diagnostics {
+                            diagnostics.extend(define_hash_diagnostic(
+                                operation,
+                                argument_count,
+                                operand,
+                                content.as_bytes(),
+                                device_hashes,
+                            ));
+                        }
+
                         if operation.eq_ignore_ascii_case("define") && argument_count == 2 {
                             if let Some((define_name, define_range)) = pending_define_name.clone() {
                                 let value_text = operand
@@ -1343,15 +2906,68 @@ pub fn compute_diagnostics_for_text(content: &str) -> Vec<Diagnostic> {
         }
     }
 
+    if config.warn_lone_device_write {
+        check_lone_device_writes(&tree, content, &type_data.aliases, &mut diagnostics);
+    }
+    if config.warn_accidental_db_access {
+        check_db_access(&tree, content, &mut diagnostics);
+    }
+    check_no_op_instructions(&tree, content, &type_data.aliases, &mut diagnostics);
+    check_stack_addresses(&tree, content, &type_data.defines, &mut diagnostics);
+    check_declaration_order(&tree, content, &mut diagnostics);
+    check_unbalanced_regions(&tree, content, None, &mut diagnostics);
+    check_executable_instructions(&tree, content, &mut diagnostics);
+    check_unused_definitions(&tree, content, &type_data, &mut diagnostics);
+    diagnostics.extend(missing_trailing_newline(&tree, content));
+
+    let deep_analysis_note = deep_analysis_skipped(content, config.analysis_line_budget);
+    let deep_analysis = deep_analysis_note.is_none();
+    diagnostics.extend(deep_analysis_note);
+
+    if deep_analysis && config.enable_control_flow_analysis {
+        check_call_depth(&tree, content, config.max_call_depth, &mut diagnostics);
+        check_unreachable_code(&tree, content, &type_data, &mut diagnostics);
+    }
+
     // Register analyzer diagnostics
-    {
+    if deep_analysis {
         let mut register_analyzer = additional_features::RegisterAnalyzer::new();
         register_analyzer.analyze_register_usage(&tree, content, &type_data.aliases);
         let mut seen = HashSet::new();
         for existing in diagnostics.iter() {
             seen.insert(diagnostic_identity(existing));
         }
-        for diag in register_analyzer.generate_diagnostics() {
+        let mut register_diagnostics = register_analyzer.generate_diagnostics();
+        if config.enable_control_flow_analysis {
+            register_diagnostics.extend(register_analyzer.detect_call_clobbers(
+                &tree,
+                content,
+                &type_data.aliases,
+            ));
+        }
+        register_diagnostics.extend(register_analyzer.detect_float_equality(
+            &tree,
+            content,
+            &type_data.aliases,
+        ));
+        register_diagnostics.extend(register_analyzer.detect_fusible_moves(
+            &tree,
+            content,
+            &type_data.aliases,
+        ));
+        register_diagnostics.extend(register_analyzer.detect_deviceid_reuse(
+            &tree,
+            content,
+            &type_data.aliases,
+        ));
+        if config.warn_logictype_roundtrip {
+            register_diagnostics.extend(register_analyzer.detect_logictype_roundtrips(
+                &tree,
+                content,
+                &type_data.aliases,
+            ));
+        }
+        for diag in register_diagnostics {
             if seen.insert(diagnostic_identity(&diag)) {
                 diagnostics.push(diag);
             }
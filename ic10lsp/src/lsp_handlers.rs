@@ -6,34 +6,92 @@
 //! - Signature help for function parameters
 //! - Code actions for quick fixes and refactors
 //! - Go-to-definition for navigation
+//! - Rename and find-references for defines, aliases and labels
+//! - Folding ranges for `# region` / `# endregion` markers
 
 use std::collections::HashMap;
 
 use phf::phf_set;
 use tower_lsp::jsonrpc::Result;
 use tower_lsp::lsp_types::{
-    CodeAction, CodeActionKind, CodeActionOrCommand, CodeActionParams,
-    Documentation, DocumentSymbolParams, DocumentSymbolResponse,
-    GotoDefinitionParams, GotoDefinitionResponse, Location, NumberOrString,
-    ParameterInformation, ParameterLabel, SemanticToken, SemanticTokens,
-    SemanticTokensParams, SemanticTokensResult, SemanticTokenType,
-    SignatureHelp, SignatureHelpParams, SignatureInformation,
-    SymbolInformation, SymbolKind, TextEdit, WorkspaceEdit,
+    CodeAction, CodeActionKind, CodeActionOrCommand, CodeActionParams, DocumentFormattingParams,
+    DocumentHighlight, DocumentHighlightKind, DocumentHighlightParams,
+    DocumentOnTypeFormattingParams, Documentation, DocumentSymbolParams, DocumentSymbolResponse,
+    FoldingRange, FoldingRangeKind, FoldingRangeParams, GotoDefinitionParams, GotoDefinitionResponse, Location, MarkupContent, MarkupKind,
+    NumberOrString, ParameterInformation, ParameterLabel, ReferenceParams, RenameParams,
+    SelectionRange, SelectionRangeParams, SemanticToken, SemanticTokens, SemanticTokensParams,
+    SemanticTokensResult, SemanticTokenType, SignatureHelp, SignatureHelpParams,
+    SignatureInformation, SymbolInformation, SymbolKind, TextEdit, Url, WorkspaceEdit,
+    WorkspaceSymbolParams,
 };
 use tree_sitter::{Query, QueryCursor};
 
 use ic10lsp::instructions;
 
+use crate::hash_utils::DeviceHashes;
+use crate::tooltip_documentation::CustomDocs;
 use crate::tree_utils::{get_current_parameter, NodeEx};
 use crate::types::{Position, Range};
-use crate::{Backend, LINT_ABSOLUTE_JUMP, LINT_RELATIVE_BRANCH_TO_LABEL, SEMANTIC_SYMBOL_LEGEND};
+use crate::{
+    Backend, LINT_ABSOLUTE_JUMP, LINT_ACCIDENTAL_DB_ACCESS, LINT_DEPRECATED_ENUM_FAMILY,
+    LINT_FUSIBLE_MOVE, LINT_INVALID_INSTRUCTION, LINT_LABEL_SHADOWS_INSTRUCTION,
+    LINT_MALFORMED_REGISTER, LINT_MISSING_TRAILING_NEWLINE, LINT_NO_OP_INSTRUCTION,
+    LINT_POSSIBLE_MISSING_SPACE, LINT_RELATIVE_BRANCH_TO_LABEL, LINT_SHADOWS_ENUM_FAMILY,
+    LINT_STORE_OPERAND_ORDER, LINT_UNUSED_DEFINITION, LINT_UNUSED_LABEL, SEMANTIC_SYMBOL_LEGEND,
+};
+
+/// Whether `node` is the label operand of a branch or jump (`beq a b label`, `beqz a label`,
+/// `j label`), judged by position so forward references count too
+pub(crate) fn is_branch_label_operand(node: tree_sitter::Node, content: &[u8]) -> bool {
+    // Classify branch/jump mnemonics for positional label operands.
+    // Two groups: (a,b,label) form and (a,label) form; plus single-operand j/jal.
+    static THREE_OPERAND_BRANCHES: phf::Set<&'static str> = phf_set!(
+        "beq", "bne", "blt", "bgt", "ble", "bge", "breq", "brne", "brlt", "brgt", "brle", "brge",
+        "beqal", "bneal", "bltal", "bgtal", "bleal", "bgeal"
+    );
+    static TWO_OPERAND_BRANCHES: phf::Set<&'static str> = phf_set!(
+        "beqz", "bnez", "bltz", "bgtz", "blez", "bgez", "breqz", "brnez", "brltz", "brgtz",
+        "brlez", "brgez", "beqzal", "bnezal", "bltzal", "bgtzal", "blezal", "bgezal"
+    );
+    static SINGLE_OPERAND_JUMPS: phf::Set<&'static str> = phf_set!("j", "jal");
+
+    let Some(operand) = node.parent().filter(|parent| parent.kind() == "operand") else {
+        return false;
+    };
+    let Some(instruction) = operand.parent().filter(|parent| parent.kind() == "instruction") else {
+        return false;
+    };
+    let Some(op_text) = instruction
+        .child_by_field_name("operation")
+        .and_then(|op_node| op_node.utf8_text(content).ok())
+    else {
+        return false;
+    };
+    // Count operand index for this identifier within the instruction.
+    let mut cursor = instruction.walk();
+    let Some(index) = instruction
+        .children_by_field_name("operand", &mut cursor)
+        .position(|o| o.id() == operand.id())
+    else {
+        return false;
+    };
+    let op_lower = op_text.to_ascii_lowercase();
+    if THREE_OPERAND_BRANCHES.contains(op_lower.as_str()) {
+        index == 2 // label is last (third) operand
+    } else if TWO_OPERAND_BRANCHES.contains(op_lower.as_str()) {
+        index == 1 // label is second operand
+    } else if SINGLE_OPERAND_JUMPS.contains(op_lower.as_str()) {
+        index == 0 // label is sole operand
+    } else {
+        false
+    }
+}
 
 /// Handle semantic tokens request for syntax highlighting
 pub async fn handle_semantic_tokens_full(
     backend: &Backend,
     params: SemanticTokensParams,
 ) -> Result<Option<SemanticTokensResult>> {
-    let mut ret = Vec::new();
     let files = backend.files.read().await;
     let uri = params.text_document.uri;
     let Some(file_data) = files.get(&uri) else {
@@ -59,8 +117,7 @@ pub async fn handle_semantic_tokens_full(
     )
     .unwrap();
 
-    let mut previous_line = 0u32;
-    let mut previous_col = 0u32;
+    let mut tokens = SemanticTokenEncoder::new(&document.content);
 
     let comment_idx = query.capture_index_for_name("comment").unwrap();
     let keyword_idx = query.capture_index_for_name("keyword").unwrap();
@@ -113,81 +170,8 @@ pub async fn handle_semantic_tokens_full(
                     }
                 }
                 // Determine if this identifier is a branch/jump label reference even if forward-declared.
-                let mut branch_label_reference = false;
-                if !file_data.type_data.labels.contains_key(ident_text) {
-                    // Only attempt contextual detection if not already a known label definition.
-                    if let Some(operand_parent) = node.parent() {
-                        if operand_parent.kind() == "operand" {
-                            if let Some(instr_parent) = operand_parent.parent() {
-                                if instr_parent.kind() == "instruction" {
-                                    if let Some(op_node) =
-                                        instr_parent.child_by_field_name("operation")
-                                    {
-                                        if let Ok(op_text) =
-                                            op_node.utf8_text(document.content.as_bytes())
-                                        {
-                                            // Classify branch/jump mnemonics for positional label operands.
-                                            // Two groups: (a,b,label) form and (a,label) form; plus single-operand j/jal.
-                                            static THREE_OPERAND_BRANCHES: phf::Set<
-                                                &'static str,
-                                            > = phf_set!(
-                                                "beq", "bne", "blt", "bgt", "ble", "bge",
-                                                "breq", "brne", "brlt", "brgt", "brle", "brge",
-                                                "beqal", "bneal", "bltal", "bgtal", "bleal",
-                                                "bgeal"
-                                            );
-                                            static TWO_OPERAND_BRANCHES: phf::Set<
-                                                &'static str,
-                                            > = phf_set!(
-                                                "beqz", "bnez", "bltz", "bgtz", "blez", "bgez",
-                                                "breqz", "brnez", "brltz", "brgtz", "brlez",
-                                                "brgez", "beqzal", "bnezal", "bltzal",
-                                                "bgtzal", "blezal", "bgezal"
-                                            );
-                                            static SINGLE_OPERAND_JUMPS: phf::Set<
-                                                &'static str,
-                                            > = phf_set!("j", "jal");
-
-                                            // Count operand index for this identifier within the instruction.
-                                            let mut w = instr_parent.walk();
-                                            let operands: Vec<_> = instr_parent
-                                                .children_by_field_name("operand", &mut w)
-                                                .collect();
-                                            let operand_index = operands
-                                                .iter()
-                                                .position(|o| o.id() == operand_parent.id());
-                                            if let Some(idx_op) = operand_index {
-                                                let op_lower = op_text.to_ascii_lowercase();
-                                                if THREE_OPERAND_BRANCHES
-                                                    .contains(op_lower.as_str())
-                                                {
-                                                    // label is last (third) operand
-                                                    if idx_op == 2 {
-                                                        branch_label_reference = true;
-                                                    }
-                                                } else if TWO_OPERAND_BRANCHES
-                                                    .contains(op_lower.as_str())
-                                                {
-                                                    // label is second operand
-                                                    if idx_op == 1 {
-                                                        branch_label_reference = true;
-                                                    }
-                                                } else if SINGLE_OPERAND_JUMPS
-                                                    .contains(op_lower.as_str())
-                                                {
-                                                    // label is sole operand
-                                                    if idx_op == 0 {
-                                                        branch_label_reference = true;
-                                                    }
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
+                let branch_label_reference = !file_data.type_data.labels.contains_key(ident_text)
+                    && is_branch_label_operand(node, document.content.as_bytes());
                 if file_data.type_data.labels.contains_key(ident_text) || branch_label_reference
                 {
                     SemanticTokenType::TYPE
@@ -214,60 +198,86 @@ pub async fn handle_semantic_tokens_full(
             }
         };
 
-        // Convert byte positions to UTF-16 code units (required by VS Code)
+        let token_type = SEMANTIC_SYMBOL_LEGEND
+            .iter()
+            .position(|x| *x == tokentype)
+            .unwrap() as u32;
+        tokens.push(start, node.range().end_point, token_type);
+    }
+    Ok(Some(SemanticTokensResult::Tokens(SemanticTokens {
+        result_id: None,
+        data: tokens.data,
+    })))
+}
+
+/// Delta-encodes semantic tokens, converting tree-sitter byte columns to UTF-16.
+///
+/// A node spanning several lines is emitted as one token per line segment, each clamped
+/// to its own line, so a malformed parse can never produce a token running past a line end.
+struct SemanticTokenEncoder<'a> {
+    lines: Vec<&'a str>,
+    data: Vec<SemanticToken>,
+    previous_line: u32,
+    previous_col: u32,
+}
+
+impl<'a> SemanticTokenEncoder<'a> {
+    fn new(content: &'a str) -> Self {
+        Self {
+            lines: content.lines().collect(),
+            data: Vec::new(),
+            previous_line: 0,
+            previous_col: 0,
+        }
+    }
+
+    fn push(&mut self, start: tree_sitter::Point, end: tree_sitter::Point, token_type: u32) {
+        for row in start.row..=end.row {
+            let Some(line_text) = self.lines.get(row).copied() else {
+                break;
+            };
+            let byte_start = if row == start.row { start.column } else { 0 };
+            let byte_end = if row == end.row { end.column } else { line_text.len() };
+            self.push_segment(row as u32, line_text, byte_start, byte_end, token_type);
+        }
+    }
+
+    fn push_segment(&mut self, row: u32, line_text: &str, byte_start: usize, byte_end: usize, token_type: u32) {
         // Tree-sitter gives us byte offsets, but LSP uses UTF-16
-        let line_text = document.content.lines().nth(start.row).unwrap_or("");
-        
-        // Calculate UTF-16 column for the start position
-        let byte_start = start.column;
-        let utf16_start = if byte_start <= line_text.len() {
-            line_text[..byte_start].encode_utf16().count() as u32
-        } else {
-            line_text.encode_utf16().count() as u32
+        let utf16_column = |byte: usize| {
+            line_text
+                .get(..byte.min(line_text.len()))
+                .unwrap_or(line_text)
+                .encode_utf16()
+                .count() as u32
         };
-        
-        // Calculate UTF-16 column for the end position
-        let byte_end = node.range().end_point.column;
-        let utf16_end = if byte_end <= line_text.len() {
-            line_text[..byte_end].encode_utf16().count() as u32
-        } else {
-            line_text.encode_utf16().count() as u32
-        };
-        
-        // Calculate token length in UTF-16 code units
-        let utf16_length = utf16_end.saturating_sub(utf16_start);
-        
-        // Skip tokens with zero length (defensive)
-        if utf16_length == 0 {
-            continue;
+        let utf16_start = utf16_column(byte_start);
+        let utf16_length = utf16_column(byte_end).saturating_sub(utf16_start);
+
+        // Skip empty segments and anything that would move the encoder backwards
+        if utf16_length == 0
+            || row < self.previous_line
+            || (row == self.previous_line && utf16_start < self.previous_col)
+        {
+            return;
         }
-        
-        // Calculate delta for LSP semantic tokens format
-        let utf16_delta_line = start.row as u32 - previous_line;
-        let utf16_delta_start = if utf16_delta_line == 0 {
-            utf16_start - previous_col
+
+        let delta_line = row - self.previous_line;
+        let delta_start = if delta_line == 0 {
+            utf16_start - self.previous_col
         } else {
             utf16_start
         };
-
-        ret.push(SemanticToken {
-            delta_line: utf16_delta_line,
-            delta_start: utf16_delta_start,
+        self.data.push(SemanticToken {
+            delta_line,
+            delta_start,
             length: utf16_length,
-            token_type: SEMANTIC_SYMBOL_LEGEND
-                .iter()
-                .position(|x| *x == tokentype)
-                .unwrap() as u32,
+            token_type,
             token_modifiers_bitset: 0,
         });
-
-        previous_line = start.row as u32;
-        previous_col = utf16_start;
+        self.previous_line = row;
+        self.previous_col = utf16_start;
     }
-    Ok(Some(SemanticTokensResult::Tokens(SemanticTokens {
-        result_id: None,
-        data: ret,
-    })))
 }
 
 /// Handle document symbol request for outline view
@@ -275,7 +285,6 @@ pub async fn handle_document_symbol(
     backend: &Backend,
     params: DocumentSymbolParams,
 ) -> Result<Option<DocumentSymbolResponse>> {
-    let mut ret = Vec::new();
     let files = backend.files.read().await;
     let uri = params.text_document.uri;
 
@@ -289,6 +298,12 @@ pub async fn handle_document_symbol(
         return Err(tower_lsp::jsonrpc::Error::internal_error());
     };
 
+    Ok(Some(DocumentSymbolResponse::Flat(document_symbols(tree, &document.content, &uri))))
+}
+
+/// Defines, aliases and labels of one document, in source order
+fn document_symbols(tree: &tree_sitter::Tree, content: &str, uri: &Url) -> Vec<SymbolInformation> {
+    let mut ret = Vec::new();
     let mut cursor = QueryCursor::new();
     let query = Query::new(
         tree_sitter_ic10::language(),
@@ -303,7 +318,7 @@ pub async fn handle_document_symbol(
     let label_idx = query.capture_index_for_name("label").unwrap();
     let name_idx = query.capture_index_for_name("name").unwrap();
 
-    let matches = cursor.matches(&query, tree.root_node(), document.content.as_bytes());
+    let matches = cursor.matches(&query, tree.root_node(), content.as_bytes());
 
     for matched in matches {
         let main_match = {
@@ -333,7 +348,7 @@ pub async fn handle_document_symbol(
             continue;
         };
 
-        let name = name_node.utf8_text(document.content.as_bytes()).unwrap();
+        let name = name_node.utf8_text(content.as_bytes()).unwrap();
         #[allow(deprecated)]
         ret.push(SymbolInformation {
             name: name.to_string(),
@@ -344,7 +359,324 @@ pub async fn handle_document_symbol(
             container_name: None,
         });
     }
-    Ok(Some(DocumentSymbolResponse::Flat(ret)))
+    ret
+}
+
+/// Handle workspace symbol request: symbols of every open document matching the query
+pub async fn handle_workspace_symbol(
+    backend: &Backend,
+    params: WorkspaceSymbolParams,
+) -> Result<Option<Vec<SymbolInformation>>> {
+    let files = backend.files.read().await;
+    let documents = files.iter().filter_map(|(uri, file_data)| {
+        let document = &file_data.document_data;
+        document.tree.as_ref().map(|tree| (uri, tree, document.content.as_str()))
+    });
+    Ok(Some(workspace_symbols(documents, &params.query)))
+}
+
+/// Symbols across `documents` whose name contains `query` (case-insensitive; an empty query
+/// matches everything), named with their file so same-named labels in two scripts can be told
+/// apart. Sorted by file, then position.
+pub(crate) fn workspace_symbols<'a>(
+    documents: impl IntoIterator<Item = (&'a Url, &'a tree_sitter::Tree, &'a str)>,
+    query: &str,
+) -> Vec<SymbolInformation> {
+    let query = query.to_lowercase();
+    let mut ret: Vec<SymbolInformation> = documents
+        .into_iter()
+        .flat_map(|(uri, tree, content)| {
+            let file_name = uri
+                .path_segments()
+                .and_then(|mut segments| segments.next_back())
+                .map(str::to_string);
+            document_symbols(tree, content, uri).into_iter().map(move |mut symbol| {
+                symbol.container_name = file_name.clone();
+                symbol
+            })
+        })
+        .filter(|symbol| symbol.name.to_lowercase().contains(&query))
+        .collect();
+    ret.sort_by(|a, b| {
+        (a.location.uri.as_str(), a.location.range.start)
+            .cmp(&(b.location.uri.as_str(), b.location.range.start))
+    });
+    ret
+}
+
+/// Handle on-type formatting: after Enter on a `label:` line, indent the new line like the
+/// file's other indented code
+pub async fn handle_on_type_formatting(
+    backend: &Backend,
+    params: DocumentOnTypeFormattingParams,
+) -> Result<Option<Vec<TextEdit>>> {
+    if params.ch != "\n" {
+        return Ok(None);
+    }
+    let files = backend.files.read().await;
+    let Some(file_data) = files.get(&params.text_document_position.text_document.uri) else {
+        return Ok(None);
+    };
+    let edit = label_indent_edit(
+        &file_data.document_data.content,
+        params.text_document_position.position.line,
+    );
+    Ok(edit.map(|edit| vec![edit]))
+}
+
+/// Edit setting the indentation of `line` when the line above is a label. The indent is taken
+/// from the first indented instruction in the file; flush-left files get no edit.
+pub(crate) fn label_indent_edit(content: &str, line: u32) -> Option<TextEdit> {
+    let lines: Vec<&str> = content.lines().collect();
+    let code = |text: &str| text.split('#').next().unwrap_or("").trim().to_string();
+    let is_label = |text: &str| {
+        let code = code(text);
+        code.strip_suffix(':').is_some_and(|name| {
+            !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+        })
+    };
+
+    let previous = lines.get((line as usize).checked_sub(1)?)?;
+    if !is_label(previous) {
+        return None;
+    }
+    let indent: String = lines
+        .iter()
+        .enumerate()
+        .filter(|(index, text)| *index != line as usize && !code(text).is_empty() && !is_label(text))
+        .map(|(_, text)| text.chars().take_while(|c| *c == ' ' || *c == '\t').collect::<String>())
+        .find(|indent| !indent.is_empty())?;
+
+    let current = lines.get(line as usize).copied().unwrap_or("");
+    let existing = current.chars().take_while(|c| *c == ' ' || *c == '\t').count();
+    if current[..existing] == indent {
+        return None;
+    }
+    Some(TextEdit::new(
+        tower_lsp::lsp_types::Range::new(
+            tower_lsp::lsp_types::Position::new(line, 0),
+            tower_lsp::lsp_types::Position::new(line, existing as u32),
+        ),
+        indent,
+    ))
+}
+
+/// The `line` nodes of the program in order, and the index of the one holding source row `row`
+fn program_lines<'a>(tree: &'a tree_sitter::Tree, row: u32) -> Option<(Vec<tree_sitter::Node<'a>>, usize)> {
+    let program = tree.root_node().child(0).filter(|node| node.kind() == "program")?;
+    let mut cursor = program.walk();
+    let lines: Vec<_> = program.children(&mut cursor).filter(|node| node.kind() == "line").collect();
+    let row = row as usize;
+    let index = lines
+        .iter()
+        .position(|line| line.start_position().row <= row && row < line.end_position().row.max(line.start_position().row + 1))?;
+    Some((lines, index))
+}
+
+/// A line's text without its line break, exactly as written, where that text ends, and the
+/// line break itself (empty for a last line without one)
+fn line_parts<'a>(line: tree_sitter::Node, content: &'a str) -> (&'a str, tree_sitter::Point, &'a str) {
+    let newline = (0..line.child_count())
+        .filter_map(|i| line.child(i))
+        .find(|child| child.kind() == "newline");
+    let (text_end, break_end, end) = match newline {
+        Some(newline) => (newline.start_byte(), newline.end_byte(), newline.start_position()),
+        None => (line.end_byte(), line.end_byte(), line.end_position()),
+    };
+    // Line nodes start at their first token; the indentation in front belongs to the line too
+    let start = line.start_byte() - line.start_position().column;
+    (&content[start..text_end], end, &content[text_end..break_end])
+}
+
+/// `ic10.duplicateLine`: copy the whole line at `row` (label, instruction and comment) below it
+pub(crate) fn duplicate_line_edit(tree: &tree_sitter::Tree, content: &str, row: u32) -> Option<TextEdit> {
+    let (lines, index) = program_lines(tree, row)?;
+    let line = lines[index];
+    let (text, text_end, line_break) = line_parts(line, content);
+    let (at, new_text) = if line_break.is_empty() {
+        // Last line without a line break: the copy goes after a new one
+        (text_end, format!("\n{}", text))
+    } else {
+        (line.end_position(), format!("{}{}", text, line_break))
+    };
+    let at = Position::from(at).into();
+    Some(TextEdit::new(tower_lsp::lsp_types::Range::new(at, at), new_text))
+}
+
+/// `ic10.moveLine`: swap the line at `row` with the one above (`up`) or below. Line breaks stay
+/// where they are, so a last line without one keeps lacking it. `None` at the file edges.
+pub(crate) fn move_line_edit(tree: &tree_sitter::Tree, content: &str, row: u32, up: bool) -> Option<TextEdit> {
+    let (lines, index) = program_lines(tree, row)?;
+    let first = if up { index.checked_sub(1)? } else { index };
+    let second = lines.get(first + 1)?;
+    let (first_text, _, separator) = line_parts(lines[first], content);
+    let (second_text, second_end, _) = line_parts(*second, content);
+    Some(TextEdit::new(
+        tower_lsp::lsp_types::Range::new(
+            Position::from(tree_sitter::Point::new(lines[first].start_position().row, 0)).into(),
+            Position::from(second_end).into(),
+        ),
+        format!("{}{}{}", second_text, separator, first_text),
+    ))
+}
+
+/// Handle whole-document formatting
+pub async fn handle_formatting(
+    backend: &Backend,
+    params: DocumentFormattingParams,
+) -> Result<Option<Vec<TextEdit>>> {
+    let files = backend.files.read().await;
+    let Some(file_data) = files.get(&params.text_document.uri) else {
+        return Ok(None);
+    };
+    let document = &file_data.document_data;
+    let Some(ref tree) = document.tree else {
+        return Ok(None);
+    };
+    Ok(format_document_edit(tree, &document.content).map(|edit| vec![edit]))
+}
+
+/// One edit replacing the document with [`format_document`]'s result. `None` when the
+/// document is already formatted.
+pub(crate) fn format_document_edit(tree: &tree_sitter::Tree, content: &str) -> Option<TextEdit> {
+    let formatted = format_document(tree, content)?;
+    Some(TextEdit::new(
+        tower_lsp::lsp_types::Range::new(
+            Position::from(tree_sitter::Point::new(0, 0)).into(),
+            Position::from(tree.root_node().end_position()).into(),
+        ),
+        formatted,
+    ))
+}
+
+/// The document with its instruction lines normalized: a single space between the opcode and
+/// each operand, one space before an inline comment, no trailing whitespace. Indentation,
+/// blank lines, labels, `define`/`alias` lines and anything that failed to parse are left
+/// alone, lines are never merged (so numeric branch targets stay valid) and the final newline
+/// is kept or left missing as it was. Shared by the `formatting` request and `--format`.
+/// `None` when the document is already formatted.
+pub(crate) fn format_document(tree: &tree_sitter::Tree, content: &str) -> Option<String> {
+    let program = tree.root_node().child(0).filter(|node| node.kind() == "program")?;
+    let text = |node: tree_sitter::Node| node.utf8_text(content.as_bytes()).unwrap_or("");
+    let mut formatted = content.to_string();
+    let mut cursor = program.walk();
+    let lines: Vec<_> = program.children(&mut cursor).filter(|node| node.kind() == "line").collect();
+    // Back to front so earlier byte offsets stay valid
+    for line in lines.into_iter().rev() {
+        let mut line_cursor = line.walk();
+        let children: Vec<_> = line.children(&mut line_cursor).collect();
+        let Some(instruction) = children.iter().find(|child| child.kind() == "instruction") else {
+            continue;
+        };
+        if children.iter().any(|child| matches!(child.kind(), "ERROR" | "label")) {
+            continue;
+        }
+        let Some(operation) = instruction.child_by_field_name("operation") else {
+            continue;
+        };
+        if matches!(text(operation).to_ascii_lowercase().as_str(), "define" | "alias") {
+            continue;
+        }
+
+        let mut operand_cursor = instruction.walk();
+        let mut new_text = std::iter::once(text(operation))
+            .chain(instruction.children_by_field_name("operand", &mut operand_cursor).map(text))
+            .collect::<Vec<_>>()
+            .join(" ");
+        if let Some(comment) = children.iter().find(|child| child.kind() == "comment") {
+            new_text.push(' ');
+            new_text.push_str(text(*comment).trim_end());
+        }
+        let end = children
+            .iter()
+            .find(|child| child.kind() == "newline")
+            .map_or(line.end_byte(), |newline| newline.start_byte());
+        formatted.replace_range(line.start_byte()..end, &new_text);
+    }
+
+    (formatted != content).then_some(formatted)
+}
+
+/// Handle selection range request for expand/shrink selection
+pub async fn handle_selection_range(
+    backend: &Backend,
+    params: SelectionRangeParams,
+) -> Result<Option<Vec<SelectionRange>>> {
+    let files = backend.files.read().await;
+    let Some(file_data) = files.get(&params.text_document.uri) else {
+        return Err(tower_lsp::jsonrpc::Error::invalid_request());
+    };
+    let Some(ref tree) = file_data.document_data.tree else {
+        return Err(tower_lsp::jsonrpc::Error::internal_error());
+    };
+    Ok(Some(
+        params
+            .positions
+            .into_iter()
+            .map(|position| selection_range_at(tree, Position::from(position).into()))
+            .collect(),
+    ))
+}
+
+/// Handle folding range request: one fold per matched `# region` / `# endregion` pair
+pub async fn handle_folding_range(
+    backend: &Backend,
+    params: FoldingRangeParams,
+) -> Result<Option<Vec<FoldingRange>>> {
+    let files = backend.files.read().await;
+    let Some(file_data) = files.get(&params.text_document.uri) else {
+        return Err(tower_lsp::jsonrpc::Error::invalid_request());
+    };
+    let document = &file_data.document_data;
+    let Some(ref tree) = document.tree else {
+        return Err(tower_lsp::jsonrpc::Error::internal_error());
+    };
+    Ok(Some(region_folding_ranges(tree, &document.content)))
+}
+
+/// Folds from each `# region` line through its `# endregion` line
+pub(crate) fn region_folding_ranges(tree: &tree_sitter::Tree, content: &str) -> Vec<FoldingRange> {
+    let mut ranges: Vec<_> = crate::tree_utils::region_markers(tree.root_node(), content)
+        .regions
+        .into_iter()
+        .map(|(opener, closer)| FoldingRange {
+            start_line: opener.start_position().row as u32,
+            end_line: closer.start_position().row as u32,
+            kind: Some(FoldingRangeKind::Region),
+            ..Default::default()
+        })
+        .collect();
+    ranges.sort_by_key(|range| range.start_line);
+    ranges
+}
+
+/// The chain of enclosing syntax nodes at `point`, innermost first: e.g. string → `HASH(...)`
+/// call → operand → instruction → line → whole program. Ancestors covering exactly the same
+/// span as their child are skipped, so every step grows the selection.
+pub(crate) fn selection_range_at(tree: &tree_sitter::Tree, point: tree_sitter::Point) -> SelectionRange {
+    let mut ranges: Vec<tower_lsp::lsp_types::Range> = Vec::new();
+    let mut node = tree.root_node().named_descendant_for_point_range(point, point);
+    while let Some(current) = node {
+        let range = Range::from(current.range()).into();
+        if ranges.last() != Some(&range) {
+            ranges.push(range);
+        }
+        node = current.parent();
+    }
+    let mut selection: Option<SelectionRange> = None;
+    for range in ranges.into_iter().rev() {
+        selection = Some(SelectionRange {
+            range,
+            parent: selection.map(Box::new),
+        });
+    }
+    selection.unwrap_or_else(|| {
+        let at = Position::from(point).into();
+        SelectionRange {
+            range: tower_lsp::lsp_types::Range::new(at, at),
+            parent: None,
+        }
+    })
 }
 
 /// Handle signature help request for function parameter hints
@@ -353,20 +685,36 @@ pub async fn handle_signature_help(
     params: SignatureHelpParams,
 ) -> Result<Option<SignatureHelp>> {
     let uri = params.text_document_position_params.text_document.uri;
-    let position = Position::from(params.text_document_position_params.position);
 
+    let custom_docs = backend.custom_docs.read().await;
     let files = backend.files.read().await;
     let Some(file_data) = files.get(&uri) else {
         return Err(tower_lsp::jsonrpc::Error::invalid_request());
     };
+    signature_help_at(
+        &file_data.document_data,
+        params.text_document_position_params.position,
+        &custom_docs,
+    )
+}
 
-    let document = &file_data.document_data;
+/// Signature of the instruction at `position` with its active parameter; shared by the
+/// `signatureHelp` request and the `ic10.analyzeAt` command
+pub(crate) fn signature_help_at(
+    document: &crate::DocumentData,
+    position: tower_lsp::lsp_types::Position,
+    custom_docs: &CustomDocs,
+) -> Result<Option<SignatureHelp>> {
+    let position = Position::from(position);
 
     let Some(ref tree) = document.tree else {
         return Err(tower_lsp::jsonrpc::Error::internal_error());
     };
 
-    let Some(node) = backend.node_at_position(position, tree) else {
+    let Some(node) = tree
+        .root_node()
+        .named_descendant_for_point_range(position.into(), position.into())
+    else {
         return Ok(None);
     };
 
@@ -411,7 +759,7 @@ pub async fn handle_signature_help(
         document.content.as_bytes(),
     );
 
-    let Some(_signature) = instructions::INSTRUCTIONS.get(text) else {
+    let Some(signature) = instructions::INSTRUCTIONS.get(text) else {
         return Ok(None);
     };
 
@@ -465,15 +813,22 @@ pub async fn handle_signature_help(
     Ok(Some(SignatureHelp {
         signatures: vec![SignatureInformation {
             label,
-            documentation: instructions::INSTRUCTION_DOCS
-                .get(text)
+            documentation: custom_docs
+                .instruction_doc(text)
                 .map(|x| Documentation::String(x.to_string())),
             parameters: Some(
                 parameters
                     .iter()
-                    .map(|offset| ParameterInformation {
+                    .enumerate()
+                    .map(|(index, offset)| ParameterInformation {
                         label: ParameterLabel::LabelOffsets(offset.to_owned()),
-                        documentation: None,
+                        // What the operand accepts, shown by clients for the active parameter
+                        documentation: signature.0.get(index).map(|accepted| {
+                            Documentation::MarkupContent(MarkupContent {
+                                kind: MarkupKind::Markdown,
+                                value: format!("Accepts **{}**", accepted.describe()),
+                            })
+                        }),
                     })
                     .collect(),
             ),
@@ -484,6 +839,47 @@ pub async fn handle_signature_help(
     }))
 }
 
+/// Payload of `ic10.analyzeAt`: everything an editor would show at `position`, in one object.
+///
+/// ```json
+/// {
+///   "hover": Hover | null,
+///   "completions": ["label", ...],
+///   "signature": SignatureHelp | null
+/// }
+/// ```
+///
+/// `hover` and `signature` are the LSP objects the `hover` and `signatureHelp` requests return;
+/// `completions` lists the completion labels in server order (empty when nothing applies).
+pub(crate) fn analyze_at(
+    document: &crate::DocumentData,
+    type_data: &crate::TypeData,
+    position: tower_lsp::lsp_types::Position,
+    config: &crate::Configuration,
+    device_hashes: &DeviceHashes,
+    custom_docs: &CustomDocs,
+) -> Result<serde_json::Value> {
+    let hover = crate::lsp_hover::hover_at(document, type_data, position, device_hashes, custom_docs)?;
+    let completions = match crate::lsp_completion::completions_at(
+        document,
+        type_data,
+        position,
+        config,
+        device_hashes,
+        custom_docs,
+    )? {
+        Some(tower_lsp::lsp_types::CompletionResponse::Array(items)) => items,
+        Some(tower_lsp::lsp_types::CompletionResponse::List(list)) => list.items,
+        None => Vec::new(),
+    };
+    let signature = signature_help_at(document, position, custom_docs)?;
+    Ok(serde_json::json!({
+        "hover": hover,
+        "completions": completions.into_iter().map(|item| item.label).collect::<Vec<_>>(),
+        "signature": signature,
+    }))
+}
+
 /// Handle code action request for quick fixes and refactors
 pub async fn handle_code_action(
     backend: &Backend,
@@ -491,6 +887,7 @@ pub async fn handle_code_action(
 ) -> Result<Option<Vec<CodeActionOrCommand>>> {
     let mut ret = Vec::new();
 
+    let device_hashes = backend.device_hashes.read().await;
     let files = backend.files.read().await;
     let Some(file_data) = files.get(&params.text_document.uri) else {
         return Err(tower_lsp::jsonrpc::Error::invalid_request());
@@ -503,8 +900,41 @@ pub async fn handle_code_action(
         return Err(tower_lsp::jsonrpc::Error::invalid_request());
     };
 
+    // Source action: group the header's aliases/defines under a comment block.
+    // Only offered on explicit request (never part of format-on-save).
+    let wants_source = params.context.only.as_ref().is_none_or(|kinds| {
+        kinds.iter().any(|k| CodeActionKind::SOURCE.as_str().starts_with(k.as_str()))
+    });
+    if wants_source {
+        if let Some(edit) = group_header_definitions(tree, &document.content) {
+            ret.push(CodeActionOrCommand::CodeAction(CodeAction {
+                title: "Group definitions into a documented block".to_string(),
+                kind: Some(CodeActionKind::SOURCE),
+                edit: Some(WorkspaceEdit::new(HashMap::from([(uri.clone(), vec![edit])]))),
+                ..Default::default()
+            }));
+        }
+    }
+
+    // The end-of-file position may not sit inside any line node, so handle this one up front
+    for diagnostic in &params.context.diagnostics {
+        if diagnostic.code == Some(NumberOrString::String(LINT_MISSING_TRAILING_NEWLINE.to_string())) {
+            ret.push(CodeActionOrCommand::CodeAction(CodeAction {
+                title: "Add trailing newline".to_string(),
+                kind: Some(CodeActionKind::QUICKFIX),
+                diagnostics: Some(vec![diagnostic.clone()]),
+                edit: Some(WorkspaceEdit::new(HashMap::from([(
+                    uri.clone(),
+                    vec![TextEdit::new(diagnostic.range, "\n".to_string())],
+                )]))),
+                is_preferred: Some(true),
+                ..Default::default()
+            }));
+        }
+    }
+
     let Some(node) = backend.node_at_range(params.range.into(), tree) else {
-        return Ok(None);
+        return Ok(Some(ret));
     };
 
     'diagnostics: for diagnostic in params.context.diagnostics {
@@ -539,6 +969,21 @@ pub async fn handle_code_action(
                     "j" => "jr",
                 };
 
+                if let Some((label, edits)) = line_node
+                    .query("(instruction)@x", document.content.as_bytes())
+                    .and_then(|instruction| {
+                        branch_target_label_edits(tree, &document.content, &file_data.type_data, instruction)
+                    })
+                {
+                    ret.push(CodeActionOrCommand::CodeAction(CodeAction {
+                        title: format!("Branch to label {label}"),
+                        kind: Some(CodeActionKind::QUICKFIX),
+                        diagnostics: Some(vec![diagnostic.clone()]),
+                        edit: Some(WorkspaceEdit::new(HashMap::from([(uri.clone(), edits)]))),
+                        ..Default::default()
+                    }));
+                }
+
                 if let Some(node) =
                     line_node.query("(instruction (operation)@x)", document.content.as_bytes())
                 {
@@ -635,7 +1080,268 @@ pub async fn handle_code_action(
                     break;
                 }
             }
-            "register_assigned_not_read" | "register_read_before_assign" => {
+            LINT_LABEL_SHADOWS_INSTRUCTION => {
+                let Some(name) = diagnostic.data.as_ref().and_then(|d| d.as_str()) else {
+                    continue 'diagnostics;
+                };
+                let mut new_name = format!("{}_label", name);
+                let mut suffix = 2;
+                while file_data.type_data.labels.contains_key(&new_name)
+                    || file_data.type_data.defines.contains_key(&new_name)
+                    || file_data.type_data.aliases.contains_key(&new_name)
+                {
+                    new_name = format!("{}_label{}", name, suffix);
+                    suffix += 1;
+                }
+
+                // Definition plus every operand that refers to it
+                let mut edits = vec![TextEdit::new(diagnostic.range, new_name.clone())];
+                let query = Query::new(tree_sitter_ic10::language(), "(operand (identifier) @id)").unwrap();
+                let mut cursor = QueryCursor::new();
+                for (capture, _) in cursor.captures(&query, tree.root_node(), document.content.as_bytes()) {
+                    let id = capture.captures[0].node;
+                    if id.utf8_text(document.content.as_bytes()) == Ok(name) {
+                        edits.push(TextEdit::new(Range::from(id.range()).into(), new_name.clone()));
+                    }
+                }
+                ret.push(CodeActionOrCommand::CodeAction(CodeAction {
+                    title: format!("Rename label to {}", new_name),
+                    kind: Some(CodeActionKind::QUICKFIX),
+                    diagnostics: Some(vec![diagnostic.clone()]),
+                    edit: Some(WorkspaceEdit::new(HashMap::from([(uri.clone(), edits)]))),
+                    is_preferred: Some(true),
+                    ..Default::default()
+                }));
+            }
+            LINT_STORE_OPERAND_ORDER => {
+                let Some(reordered) = diagnostic.data.as_ref().and_then(|d| d.as_str()) else {
+                    continue 'diagnostics;
+                };
+                let edit = TextEdit::new(diagnostic.range, reordered.to_string());
+                ret.push(CodeActionOrCommand::CodeAction(CodeAction {
+                    title: format!("Reorder to '{}'", reordered),
+                    kind: Some(CodeActionKind::QUICKFIX),
+                    diagnostics: Some(vec![diagnostic.clone()]),
+                    edit: Some(WorkspaceEdit::new(HashMap::from([(uri.clone(), vec![edit])]))),
+                    is_preferred: Some(true),
+                    ..Default::default()
+                }));
+            }
+            LINT_SHADOWS_ENUM_FAMILY => {
+                let Some(name) = diagnostic.data.as_ref().and_then(|d| d.as_str()) else {
+                    continue 'diagnostics;
+                };
+                let kind = if file_data.type_data.defines.contains_key(name) { "define" } else { "alias" };
+                let mut new_name = format!("{}_{}", name, kind);
+                let mut suffix = 2;
+                while file_data.type_data.labels.contains_key(&new_name)
+                    || file_data.type_data.defines.contains_key(&new_name)
+                    || file_data.type_data.aliases.contains_key(&new_name)
+                {
+                    new_name = format!("{}_{}{}", name, kind, suffix);
+                    suffix += 1;
+                }
+                let point = Position::from(diagnostic.range.start).into();
+                let Some(node) = tree.root_node().named_descendant_for_point_range(point, point) else {
+                    continue 'diagnostics;
+                };
+                let Ok(Some(mut edits)) = rename_edits(node, &document.content, &file_data.type_data, &new_name)
+                else {
+                    continue 'diagnostics;
+                };
+                // Family names that are also logic types (`Color`) lex as logic types; those are
+                // uses of the definition wherever the instruction does not expect a logic type
+                let query = Query::new(tree_sitter_ic10::language(), "(operand (logictype) @lt)").unwrap();
+                let mut cursor = QueryCursor::new();
+                for (capture, _) in cursor.captures(&query, tree.root_node(), document.content.as_bytes()) {
+                    let candidate = capture.captures[0].node;
+                    if candidate.utf8_text(document.content.as_bytes()) != Ok(name) {
+                        continue;
+                    }
+                    let Some(operand) = candidate.parent() else {
+                        continue;
+                    };
+                    let Some(instruction) = operand.parent() else {
+                        continue;
+                    };
+                    let mut operand_cursor = instruction.walk();
+                    let index = instruction
+                        .children_by_field_name("operand", &mut operand_cursor)
+                        .position(|o| o.id() == operand.id());
+                    let parameter = instruction
+                        .child_by_field_name("operation")
+                        .and_then(|op| op.utf8_text(document.content.as_bytes()).ok())
+                        .and_then(|op| instructions::INSTRUCTIONS.get(op))
+                        .zip(index)
+                        .and_then(|(signature, index)| signature.0.get(index));
+                    let expects_logic_type = parameter.is_some_and(|parameter| {
+                        parameter.match_type(instructions::DataType::LogicType)
+                            || parameter.match_type(instructions::DataType::SlotLogicType)
+                    });
+                    let range: tower_lsp::lsp_types::Range = Range::from(candidate.range()).into();
+                    if !expects_logic_type && !edits.iter().any(|edit| edit.range == range) {
+                        edits.push(TextEdit::new(range, new_name.clone()));
+                    }
+                }
+                ret.push(CodeActionOrCommand::CodeAction(CodeAction {
+                    title: format!("Rename {} to {}", kind, new_name),
+                    kind: Some(CodeActionKind::QUICKFIX),
+                    diagnostics: Some(vec![diagnostic.clone()]),
+                    edit: Some(WorkspaceEdit::new(HashMap::from([(uri.clone(), edits)]))),
+                    is_preferred: Some(true),
+                    ..Default::default()
+                }));
+            }
+            LINT_FUSIBLE_MOVE => {
+                // data: { remove: move line, replace: next instruction, text: fused instruction }
+                let fusion = diagnostic.data.as_ref().and_then(|data| {
+                    let remove = serde_json::from_value(data.get("remove")?.clone()).ok()?;
+                    let replace = serde_json::from_value(data.get("replace")?.clone()).ok()?;
+                    let text = data.get("text")?.as_str()?.to_string();
+                    Some((remove, replace, text))
+                });
+                if let Some((remove, replace, text)) = fusion {
+                    ret.push(CodeActionOrCommand::CodeAction(CodeAction {
+                        title: "Fold move into next instruction".to_string(),
+                        kind: Some(CodeActionKind::QUICKFIX),
+                        diagnostics: Some(vec![diagnostic.clone()]),
+                        edit: Some(WorkspaceEdit::new(HashMap::from([(
+                            uri.clone(),
+                            vec![
+                                TextEdit::new(remove, String::new()),
+                                TextEdit::new(replace, text),
+                            ],
+                        )]))),
+                        ..Default::default()
+                    }));
+                }
+            }
+            LINT_DEPRECATED_ENUM_FAMILY => {
+                // data holds the same member in the replacement family, when there is one
+                if let Some(replacement) = diagnostic.data.as_ref().and_then(|d| d.as_str()) {
+                    ret.push(CodeActionOrCommand::CodeAction(CodeAction {
+                        title: format!("Replace with {}", replacement),
+                        kind: Some(CodeActionKind::QUICKFIX),
+                        diagnostics: Some(vec![diagnostic.clone()]),
+                        edit: Some(WorkspaceEdit::new(HashMap::from([(
+                            uri.clone(),
+                            vec![TextEdit::new(diagnostic.range, replacement.to_string())],
+                        )]))),
+                        is_preferred: Some(true),
+                        ..Default::default()
+                    }));
+                }
+            }
+            LINT_ACCIDENTAL_DB_ACCESS => {
+                ret.push(CodeActionOrCommand::CodeAction(CodeAction {
+                    title: "Use device pin d0".to_string(),
+                    kind: Some(CodeActionKind::QUICKFIX),
+                    diagnostics: Some(vec![diagnostic.clone()]),
+                    edit: Some(WorkspaceEdit::new(HashMap::from([(
+                        uri.clone(),
+                        vec![TextEdit::new(diagnostic.range, "d0".to_string())],
+                    )]))),
+                    ..Default::default()
+                }));
+            }
+            LINT_INVALID_INSTRUCTION => {
+                // data holds the nearest valid mnemonic
+                if let Some(mnemonic) = diagnostic.data.as_ref().and_then(|d| d.as_str()) {
+                    ret.push(CodeActionOrCommand::CodeAction(CodeAction {
+                        title: format!("Change to {}", mnemonic),
+                        kind: Some(CodeActionKind::QUICKFIX),
+                        diagnostics: Some(vec![diagnostic.clone()]),
+                        edit: Some(WorkspaceEdit::new(HashMap::from([(
+                            uri.clone(),
+                            vec![TextEdit::new(diagnostic.range, mnemonic.to_string())],
+                        )]))),
+                        is_preferred: Some(true),
+                        ..Default::default()
+                    }));
+                }
+            }
+            LINT_MALFORMED_REGISTER => {
+                // data holds the canonical register name
+                if let Some(canonical) = diagnostic.data.as_ref().and_then(|d| d.as_str()) {
+                    ret.push(CodeActionOrCommand::CodeAction(CodeAction {
+                        title: format!("Normalize to {}", canonical),
+                        kind: Some(CodeActionKind::QUICKFIX),
+                        diagnostics: Some(vec![diagnostic.clone()]),
+                        edit: Some(WorkspaceEdit::new(HashMap::from([(
+                            uri.clone(),
+                            vec![TextEdit::new(diagnostic.range, canonical.to_string())],
+                        )]))),
+                        is_preferred: Some(true),
+                        ..Default::default()
+                    }));
+                }
+            }
+            LINT_POSSIBLE_MISSING_SPACE => {
+                // data holds the length of the define/alias name at the start of the operand
+                if let Some(split) = diagnostic.data.as_ref().and_then(|d| d.as_u64()) {
+                    let mut at = diagnostic.range.start;
+                    at.character += split as u32;
+                    ret.push(CodeActionOrCommand::CodeAction(CodeAction {
+                        title: "Insert space".to_string(),
+                        kind: Some(CodeActionKind::QUICKFIX),
+                        diagnostics: Some(vec![diagnostic.clone()]),
+                        edit: Some(WorkspaceEdit::new(HashMap::from([(
+                            uri.clone(),
+                            vec![TextEdit::new(
+                                tower_lsp::lsp_types::Range::new(at, at),
+                                " ".to_string(),
+                            )],
+                        )]))),
+                        is_preferred: Some(true),
+                        ..Default::default()
+                    }));
+                }
+            }
+            LINT_UNUSED_DEFINITION | LINT_UNUSED_LABEL | LINT_NO_OP_INSTRUCTION => {
+                // Delete the whole line; ignoring an unused name is offered below too. Numeric
+                // branch targets would shift, so with any of those the line is only blanked.
+                let line = diagnostic.range.start.line;
+                let end = if crate::lsp_diagnostics::absolute_jumps(tree, &document.content).is_empty() {
+                    tower_lsp::lsp_types::Position::new(line + 1, 0)
+                } else {
+                    let text = document.content.lines().nth(line as usize).unwrap_or_default();
+                    tower_lsp::lsp_types::Position::new(line, text.trim_end_matches('\r').len() as u32)
+                };
+                let title = match code.as_str() {
+                    LINT_UNUSED_LABEL => "Remove unused label",
+                    LINT_NO_OP_INSTRUCTION => "Remove no-op instruction",
+                    _ => "Remove unused definition",
+                };
+                ret.push(CodeActionOrCommand::CodeAction(CodeAction {
+                    title: title.to_string(),
+                    kind: Some(CodeActionKind::QUICKFIX),
+                    diagnostics: Some(vec![diagnostic.clone()]),
+                    edit: Some(WorkspaceEdit::new(HashMap::from([(
+                        uri.clone(),
+                        vec![TextEdit::new(
+                            tower_lsp::lsp_types::Range::new(
+                                tower_lsp::lsp_types::Position::new(line, 0),
+                                end,
+                            ),
+                            String::new(),
+                        )],
+                    )]))),
+                    is_preferred: Some(true),
+                    ..Default::default()
+                }));
+            }
+            _ => {}
+        }
+        match code.as_str() {
+            "register_assigned_not_read"
+            | LINT_UNUSED_DEFINITION
+            | LINT_UNUSED_LABEL
+            | "register_read_before_assign"
+            | "dead-device-read"
+            | "unused-comparison-result"
+            | "mismatched-logictype-roundtrip"
+            | "clobbered-across-call"
+            | "stack-imbalance" => {
                 // Extract register name from diagnostic data
                 if let Some(data) = &diagnostic.data {
                     if let Some(register_name) = data.as_str() {
@@ -730,7 +1436,7 @@ pub async fn handle_code_action(
             // Extract device name without quotes
             if let Some(device_name) = crate::hash_utils::extract_hash_argument(string_text) {
                 // Look up the hash value
-                if let Some(&hash_value) = crate::device_hashes::DEVICE_NAME_TO_HASH.get(device_name.as_str()) {
+                if let Some(hash_value) = device_hashes.get_device_hash(&device_name) {
                     // Offer to convert HASH("DeviceName") to hash number
                     let edit = TextEdit::new(
                         Range::from(hash_func_node.range()).into(),
@@ -758,15 +1464,9 @@ pub async fn handle_code_action(
         let number_text = node.utf8_text(document.content.as_bytes()).unwrap();
         if let Ok(hash_value) = number_text.parse::<i32>() {
             // Check if this is a known device hash by looking it up in the reverse map
-            if let Some(display_name) = crate::device_hashes::HASH_TO_DISPLAY_NAME.get(&hash_value) {
+            if let Some(display_name) = device_hashes.get_device_name_for_hash(hash_value) {
                 // Find the device name (key) that maps to this hash
-                let mut device_name_opt = None;
-                for device_name in crate::device_hashes::DEVICE_NAME_TO_HASH.keys() {
-                    if crate::device_hashes::DEVICE_NAME_TO_HASH[device_name] == hash_value {
-                        device_name_opt = Some(device_name);
-                        break;
-                    }
-                }
+                let device_name_opt = device_hashes.get_prefab_name_for_hash(hash_value);
                 
                 if let Some(device_name) = device_name_opt {
                     // Offer to convert hash number to HASH("DeviceName")
@@ -791,14 +1491,149 @@ pub async fn handle_code_action(
         }
     }
 
+    // Offer a matching pop for a push left open at the end of its straight-line block
+    let row = params.range.start.line;
+    if let Some(push) = crate::additional_features::unmatched_pushes(tree, &document.content)
+        .into_iter()
+        .find(|push| push.line == row)
+    {
+        let is_register = crate::additional_features::RegisterAnalyzer::is_register_name(&push.value)
+            || matches!(
+                file_data.type_data.aliases.get(&push.value).map(|alias| &alias.value),
+                Some(crate::AliasValue::Register(_))
+            );
+        if is_register {
+            let indent: String = document
+                .content
+                .lines()
+                .nth(row as usize)
+                .unwrap_or("")
+                .chars()
+                .take_while(|c| c.is_whitespace())
+                .collect();
+            let end = tree.root_node().end_position();
+            let edit = if push.block_end as usize == end.row && end.column > 0 {
+                // Last line has no newline to insert before
+                TextEdit::new(
+                    tower_lsp::lsp_types::Range::new(Position::from(end).into(), Position::from(end).into()),
+                    format!("\n{}pop {}", indent, push.value),
+                )
+            } else {
+                let at = tower_lsp::lsp_types::Position::new(push.block_end, 0);
+                TextEdit::new(
+                    tower_lsp::lsp_types::Range::new(at, at),
+                    format!("{}pop {}\n", indent, push.value),
+                )
+            };
+            ret.push(CodeActionOrCommand::CodeAction(CodeAction {
+                title: format!("Insert matching pop {}", push.value),
+                kind: Some(CodeActionKind::REFACTOR),
+                edit: Some(WorkspaceEdit::new(HashMap::from([(uri.clone(), vec![edit])]))),
+                ..Default::default()
+            }));
+        }
+    }
+
     Ok(Some(ret))
 }
 
+/// Header comment inserted above the grouped definitions block
+const DEFINITIONS_HEADER: &str = "# --- Definitions ---";
+
+/// Build an edit that groups the aliases and defines at the top of the file into one block:
+/// header comment, aliases, then defines. Comment and blank lines stay attached to the
+/// definition they precede. Returns `None` if nothing changes, or if the script branches to
+/// numeric line targets that the reshuffle would silently move.
+pub(crate) fn group_header_definitions(tree: &tree_sitter::Tree, content: &str) -> Option<TextEdit> {
+    if !crate::lsp_diagnostics::absolute_jumps(tree, content).is_empty() {
+        return None;
+    }
+
+    enum HeaderLine {
+        Alias,
+        Define,
+        Comment,
+        Blank,
+        Code,
+    }
+    fn classify(line: &str) -> HeaderLine {
+        let code = line.split('#').next().unwrap_or("").trim();
+        if code.is_empty() {
+            return if line.trim().is_empty() { HeaderLine::Blank } else { HeaderLine::Comment };
+        }
+        match code.split_whitespace().next() {
+            Some("alias") => HeaderLine::Alias,
+            Some("define") => HeaderLine::Define,
+            _ => HeaderLine::Code,
+        }
+    }
+
+    let lines: Vec<&str> = content.lines().collect();
+    let end = lines
+        .iter()
+        .position(|l| matches!(classify(l), HeaderLine::Code))
+        .unwrap_or(lines.len());
+    let first_definition = lines[..end]
+        .iter()
+        .position(|l| matches!(classify(l), HeaderLine::Alias | HeaderLine::Define))?;
+
+    let mut output: Vec<&str> = lines[..first_definition]
+        .iter()
+        .copied()
+        .filter(|l| l.trim() != DEFINITIONS_HEADER)
+        .collect();
+    output.push(DEFINITIONS_HEADER);
+
+    let mut aliases = Vec::new();
+    let mut defines = Vec::new();
+    let mut pending_lines = Vec::new();
+    for line in &lines[first_definition..end] {
+        match classify(line) {
+            HeaderLine::Alias => {
+                aliases.append(&mut pending_lines);
+                aliases.push(*line);
+            }
+            HeaderLine::Define => {
+                defines.append(&mut pending_lines);
+                defines.push(*line);
+            }
+            HeaderLine::Comment | HeaderLine::Blank if line.trim() != DEFINITIONS_HEADER => {
+                pending_lines.push(*line)
+            }
+            _ => {}
+        }
+    }
+    output.extend(aliases);
+    output.extend(defines);
+    output.extend(pending_lines);
+    if end < lines.len() && output.last().is_some_and(|l| !l.trim().is_empty()) {
+        output.push("");
+    }
+
+    let newline = if content.contains("\r\n") { "\r\n" } else { "\n" };
+    let mut new_text = output.join(newline);
+    new_text.push_str(newline);
+    let mut old_text = lines[..end].join(newline);
+    old_text.push_str(newline);
+    if new_text == old_text {
+        return None;
+    }
+
+    Some(TextEdit::new(
+        tower_lsp::lsp_types::Range::new(
+            tower_lsp::lsp_types::Position::new(0, 0),
+            tower_lsp::lsp_types::Position::new(end as u32, 0),
+        ),
+        new_text,
+    ))
+}
+
 /// Handle goto definition request for navigation
 pub async fn handle_goto_definition(
     backend: &Backend,
     params: GotoDefinitionParams,
 ) -> Result<Option<GotoDefinitionResponse>> {
+    let device_hashes = backend.device_hashes.read().await;
     let files = backend.files.read().await;
     let Some(file_data) = files.get(&params.text_document_position_params.text_document.uri)
     else {
@@ -811,6 +1646,13 @@ pub async fn handle_goto_definition(
 
     if let Some(tree) = document.tree.as_ref() {
         if let Some(node) = backend.node_at_position(position.into(), tree) {
+            // Devices have no source; their definition is a generated document
+            if let Some(prefab) = device_at(node, &document.content, &device_hashes) {
+                return Ok(Some(GotoDefinitionResponse::Scalar(Location::new(
+                    device_document_uri(prefab),
+                    tower_lsp::lsp_types::Range::default(),
+                ))));
+            }
             if node.kind() == "identifier" {
                 let name = node.utf8_text(document.content.as_bytes()).unwrap();
                 if let Some(range) = type_data.get_range(name) {
@@ -824,3 +1666,1089 @@ pub async fn handle_goto_definition(
     }
     Ok(None)
 }
+
+/// Handle rename for defines, aliases and labels
+pub async fn handle_rename(backend: &Backend, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+    let uri = params.text_document_position.text_document.uri;
+    let files = backend.files.read().await;
+    let Some(file_data) = files.get(&uri) else {
+        return Err(tower_lsp::jsonrpc::Error::internal_error());
+    };
+    let document = &file_data.document_data;
+    let Some(tree) = document.tree.as_ref() else {
+        return Ok(None);
+    };
+    let Some(node) = backend.node_at_position(params.text_document_position.position.into(), tree) else {
+        return Ok(None);
+    };
+    let edits = rename_edits(node, &document.content, &file_data.type_data, &params.new_name)?;
+    Ok(edits.map(|edits| WorkspaceEdit::new(HashMap::from([(uri, edits)]))))
+}
+
+/// Handle find-references for defines, aliases and labels
+pub async fn handle_references(backend: &Backend, params: ReferenceParams) -> Result<Option<Vec<Location>>> {
+    let uri = params.text_document_position.text_document.uri;
+    let files = backend.files.read().await;
+    let Some(file_data) = files.get(&uri) else {
+        return Err(tower_lsp::jsonrpc::Error::internal_error());
+    };
+    let document = &file_data.document_data;
+    let Some(tree) = document.tree.as_ref() else {
+        return Ok(None);
+    };
+    let Some(node) = backend.node_at_position(params.text_document_position.position.into(), tree) else {
+        return Ok(None);
+    };
+    let Some((_, definition, ranges)) = symbol_occurrences(node, &document.content, &file_data.type_data) else {
+        return Ok(None);
+    };
+    Ok(Some(
+        ranges
+            .into_iter()
+            .filter(|range| params.context.include_declaration || *range != definition)
+            .map(|range| Location::new(uri.clone(), range))
+            .collect(),
+    ))
+}
+
+/// Handle document highlight: every occurrence of the define, alias or label under the cursor
+pub async fn handle_document_highlight(
+    backend: &Backend,
+    params: DocumentHighlightParams,
+) -> Result<Option<Vec<DocumentHighlight>>> {
+    let uri = params.text_document_position_params.text_document.uri;
+    let files = backend.files.read().await;
+    let Some(file_data) = files.get(&uri) else {
+        return Err(tower_lsp::jsonrpc::Error::internal_error());
+    };
+    let document = &file_data.document_data;
+    let Some(tree) = document.tree.as_ref() else {
+        return Ok(None);
+    };
+    let Some(node) = backend.node_at_position(params.text_document_position_params.position.into(), tree) else {
+        return Ok(None);
+    };
+    Ok(document_highlights(node, &document.content, &file_data.type_data))
+}
+
+/// Highlights for the symbol under `node`: its definition as a write, every use as a read
+pub(crate) fn document_highlights(
+    node: tree_sitter::Node,
+    content: &str,
+    type_data: &crate::TypeData,
+) -> Option<Vec<DocumentHighlight>> {
+    let (_, definition, ranges) = symbol_occurrences(node, content, type_data)?;
+    Some(
+        ranges
+            .into_iter()
+            .map(|range| DocumentHighlight {
+                range,
+                kind: Some(if range == definition {
+                    DocumentHighlightKind::WRITE
+                } else {
+                    DocumentHighlightKind::READ
+                }),
+            })
+            .collect(),
+    )
+}
+
+/// The define, alias or label named by `node`, its definition range, and every occurrence in
+/// document order: the definition, each identifier with the same text, and the logic-type
+/// tokens that are really the symbol: any for a define or alias named like a logic type
+/// (`define Setting 5`), label operands of branches for a label. Text inside `HASH("...")`/`STR("...")` strings is
+/// not an identifier, so it never matches.
+fn symbol_occurrences<'a>(
+    node: tree_sitter::Node,
+    content: &'a str,
+    type_data: &crate::TypeData,
+) -> Option<(&'a str, tower_lsp::lsp_types::Range, Vec<tower_lsp::lsp_types::Range>)> {
+    if !matches!(node.kind(), "identifier" | "logictype") {
+        return None;
+    }
+    let name = node.utf8_text(content.as_bytes()).unwrap_or("");
+    let definition = type_data
+        .defines
+        .get(name)
+        .map(|d| d.range)
+        .or_else(|| type_data.aliases.get(name).map(|d| d.range))
+        .or_else(|| type_data.labels.get(name).map(|d| d.range))?
+        .0;
+    let is_label = type_data.labels.contains_key(name);
+    let is_value = type_data.defines.contains_key(name) || type_data.aliases.contains_key(name);
+
+    let mut ranges = vec![definition];
+    let mut root = node;
+    while let Some(parent) = root.parent() {
+        root = parent;
+    }
+    let query = Query::new(tree_sitter_ic10::language(), "[(identifier) (logictype)] @name").unwrap();
+    let mut cursor = QueryCursor::new();
+    for (capture, _) in cursor.captures(&query, root, content.as_bytes()) {
+        let candidate = capture.captures[0].node;
+        if candidate.utf8_text(content.as_bytes()) != Ok(name) {
+            continue;
+        }
+        if candidate.kind() == "logictype"
+            && !is_value
+            && !(is_label && is_branch_label_operand(candidate, content.as_bytes()))
+        {
+            continue;
+        }
+        let range = Range::from(candidate.range()).0;
+        if !ranges.contains(&range) {
+            ranges.push(range);
+        }
+    }
+    ranges.sort_by_key(|range| (range.start.line, range.start.character));
+    Some((name, definition, ranges))
+}
+
+/// Edits renaming the define, alias or label under `node` to `new_name`, one per occurrence.
+/// `None` when `node` names none of them; an error when `new_name` is not a valid name, reads
+/// as a register, pin, mnemonic or logic type, or is already taken.
+pub(crate) fn rename_edits(
+    node: tree_sitter::Node,
+    content: &str,
+    type_data: &crate::TypeData,
+    new_name: &str,
+) -> Result<Option<Vec<TextEdit>>> {
+    let Some((name, _, ranges)) = symbol_occurrences(node, content, type_data) else {
+        return Ok(None);
+    };
+
+    if let Some(invalid) = crate::lsp_diagnostics::invalid_symbol_name(Range(Default::default()), new_name) {
+        return Err(tower_lsp::jsonrpc::Error::invalid_params(invalid.message));
+    }
+    if let Some(kind) = crate::type_classification::reserved_name_kind(new_name) {
+        return Err(tower_lsp::jsonrpc::Error::invalid_params(format!(
+            "Cannot rename '{}' to '{}': that is a {} name",
+            name, new_name, kind
+        )));
+    }
+    if new_name == name {
+        return Ok(Some(Vec::new()));
+    }
+    let taken = [
+        ("define", type_data.defines.contains_key(new_name)),
+        ("alias", type_data.aliases.contains_key(new_name)),
+        ("label", type_data.labels.contains_key(new_name)),
+    ];
+    if let Some((kind, _)) = taken.iter().find(|(_, taken)| *taken) {
+        return Err(tower_lsp::jsonrpc::Error::invalid_params(format!(
+            "Cannot rename '{}' to '{}': a {} with that name already exists",
+            name, new_name, kind
+        )));
+    }
+
+    Ok(Some(
+        ranges
+            .into_iter()
+            .map(|range| TextEdit::new(range, new_name.to_string()))
+            .collect(),
+    ))
+}
+
+/// Suggest an `alias`/`define` header for a script that uses bare registers, device pins and
+/// raw device hashes. Registers and pins get names from the `naming` templates; known hashes
+/// get the device's display name. Returns the header text (empty if there is nothing to suggest).
+pub(crate) fn suggest_definitions(
+    tree: &tree_sitter::Tree,
+    content: &str,
+    type_data: &crate::TypeData,
+    naming: &crate::document::AliasNaming,
+    device_hashes: &DeviceHashes,
+) -> String {
+    use std::collections::BTreeSet;
+
+    let aliased: Vec<&str> = type_data
+        .aliases
+        .values()
+        .map(|alias| match &alias.value {
+            crate::AliasValue::Register(target) | crate::AliasValue::Device(target) => {
+                target.as_str()
+            }
+        })
+        .collect();
+    let defined: Vec<&str> = type_data
+        .defines
+        .values()
+        .filter_map(|define| match &define.value {
+            crate::DefineValue::Number(value) => Some(value.as_str()),
+            _ => None,
+        })
+        .collect();
+
+    let mut registers = BTreeSet::new();
+    let mut devices = BTreeSet::new();
+    let mut hashes = Vec::new();
+
+    let query = Query::new(tree_sitter_ic10::language(), "(instruction) @instruction").unwrap();
+    let mut cursor = QueryCursor::new();
+    for (capture, _) in cursor.captures(&query, tree.root_node(), content.as_bytes()) {
+        let instruction = capture.captures[0].node;
+        let op = instruction
+            .child_by_field_name("operation")
+            .and_then(|op| op.utf8_text(content.as_bytes()).ok())
+            .unwrap_or("");
+        if op == "alias" || op == "define" {
+            continue;
+        }
+        let mut operand_cursor = instruction.walk();
+        for operand in instruction.children_by_field_name("operand", &mut operand_cursor) {
+            let Some(value) = operand.child(0) else {
+                continue;
+            };
+            let text = value.utf8_text(content.as_bytes()).unwrap_or("");
+            match value.kind() {
+                "register" => {
+                    // rN only; sp/ra already have meaningful names
+                    if let Some(index) = text.strip_prefix('r').and_then(|n| n.parse::<u8>().ok()) {
+                        if !aliased.contains(&text) {
+                            registers.insert(index);
+                        }
+                    }
+                }
+                "device_spec" => {
+                    let pin = value.child(0).and_then(|d| d.utf8_text(content.as_bytes()).ok());
+                    if let Some(index) = pin
+                        .filter(|pin| !aliased.contains(pin))
+                        .and_then(|pin| pin.strip_prefix('d'))
+                        .and_then(|n| n.parse::<u8>().ok())
+                    {
+                        devices.insert(index);
+                    }
+                }
+                "number" => {
+                    if let Some(name) = text
+                        .parse::<i32>()
+                        .ok()
+                        .and_then(|hash| device_hashes.get_device_name_for_hash(hash))
+                    {
+                        if !defined.contains(&text) && !hashes.iter().any(|(_, h)| *h == text) {
+                            hashes.push((name, text));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut header = String::new();
+    for index in devices {
+        header.push_str(&format!("alias {} d{}\n", naming.device_name(index), index));
+    }
+    for index in registers {
+        header.push_str(&format!("alias {} r{}\n", naming.register_name(index), index));
+    }
+    let mut used_names: Vec<String> = Vec::new();
+    for (display_name, hash) in hashes {
+        let mut name: String = display_name.chars().filter(|c| c.is_ascii_alphanumeric()).collect();
+        if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+            name.insert_str(0, "Device");
+        }
+        let base = name.clone();
+        let mut suffix = 2;
+        while used_names.contains(&name) || type_data.defines.contains_key(&name) {
+            name = format!("{}{}", base, suffix);
+            suffix += 1;
+        }
+        header.push_str(&format!("define {} {}\n", name, hash));
+        used_names.push(name);
+    }
+    header
+}
+
+const SUMMARY_BEGIN: &str = "# --- Summary (generated) ---";
+const SUMMARY_END: &str = "# --- End summary ---";
+
+/// Numeric targets of absolute branches with the row they name, and whether inserting lines
+/// is safe: all other branch targets are labels or `ra` and there are no relative branches
+fn numeric_branch_targets<'a>(
+    tree: &'a tree_sitter::Tree,
+    content: &str,
+    type_data: &crate::TypeData,
+) -> (Vec<(tree_sitter::Node<'a>, usize)>, bool) {
+    let text = |node: tree_sitter::Node| node.utf8_text(content.as_bytes()).unwrap_or("");
+    let line_count = content.lines().count();
+    let mut numeric_targets = Vec::new();
+    let mut can_insert = true;
+    let query = Query::new(tree_sitter_ic10::language(), "(instruction) @x").unwrap();
+    let mut cursor = QueryCursor::new();
+    for (capture, _) in cursor.captures(&query, tree.root_node(), content.as_bytes()) {
+        let instruction = capture.captures[0].node;
+        let Some(operation) = instruction.child_by_field_name("operation") else {
+            continue;
+        };
+        let operation = text(operation).to_ascii_lowercase();
+        let relative = operation.starts_with("br") || operation == "jr";
+        let absolute = !relative && (operation.starts_with('b') || operation == "j" || operation == "jal");
+        if !(relative || absolute) || !instructions::INSTRUCTIONS.contains_key(operation.as_str()) {
+            continue;
+        }
+        let mut operand_cursor = instruction.walk();
+        let Some(target) = instruction.children_by_field_name("operand", &mut operand_cursor).last() else {
+            continue;
+        };
+        let row = crate::hash_utils::parse_ic10_number(text(target))
+            .and_then(|row| usize::try_from(row).ok())
+            .filter(|row| *row < line_count);
+        match row {
+            Some(row) if absolute => numeric_targets.push((target, row)),
+            _ if relative => can_insert = false,
+            _ => {
+                let name = text(target);
+                if name != "ra" && !type_data.labels.contains_key(name) {
+                    can_insert = false;
+                }
+            }
+        }
+    }
+    (numeric_targets, can_insert)
+}
+
+fn labels_by_row(type_data: &crate::TypeData) -> HashMap<usize, String> {
+    type_data
+        .labels
+        .iter()
+        .map(|(name, label)| (label.range.0.start.line as usize, name.clone()))
+        .collect()
+}
+
+/// `lineN`, or `lineN_2`, ... when that name is taken
+fn fresh_label_name(row: usize, type_data: &crate::TypeData) -> String {
+    let mut name = format!("line{}", row);
+    let mut suffix = 2;
+    while type_data.labels.contains_key(&name)
+        || type_data.defines.contains_key(&name)
+        || type_data.aliases.contains_key(&name)
+    {
+        name = format!("line{}_{}", row, suffix);
+        suffix += 1;
+    }
+    name
+}
+
+fn label_insertion(row: usize, name: &str, content: &str) -> TextEdit {
+    let line_break = if content.contains("\r\n") { "\r\n" } else { "\n" };
+    let at = tower_lsp::lsp_types::Position::new(row as u32, 0);
+    TextEdit::new(tower_lsp::lsp_types::Range::new(at, at), format!("{}:{}", name, line_break))
+}
+
+/// Edits for `ic10.convertBranchesToLabels`: every absolute branch whose numeric target is a
+/// labelled line is pointed at that label instead. With `insert_labels`, lines that are branched
+/// to but have no label get a `lineN:` label inserted above them. Inserting shifts every later
+/// line, so it is only done when all other branch targets are labels or `ra` and there are no
+/// relative branches; otherwise the unlabelled targets are left as numbers.
+pub(crate) fn branches_to_labels_edits(
+    tree: &tree_sitter::Tree,
+    content: &str,
+    type_data: &crate::TypeData,
+    insert_labels: bool,
+) -> Vec<TextEdit> {
+    let mut labels_by_row = labels_by_row(type_data);
+    let (numeric_targets, can_insert) = numeric_branch_targets(tree, content, type_data);
+
+    let mut edits = Vec::new();
+    if insert_labels && can_insert {
+        let mut rows: Vec<usize> = numeric_targets.iter().map(|(_, row)| *row).collect();
+        rows.sort_unstable();
+        rows.dedup();
+        for row in rows {
+            if labels_by_row.contains_key(&row) {
+                continue;
+            }
+            let name = fresh_label_name(row, type_data);
+            edits.push(label_insertion(row, &name, content));
+            labels_by_row.insert(row, name);
+        }
+    }
+    for (target, row) in numeric_targets {
+        if let Some(name) = labels_by_row.get(&row) {
+            edits.push(TextEdit::new(Range::from(target.range()).into(), name.clone()));
+        }
+    }
+    edits
+}
+
+/// Edits pointing one absolute branch at a label on its numeric target line: an existing
+/// label on that line is reused, otherwise a `lineN:` label is inserted above it. The label
+/// takes over row N, so the branch still lands on the same instruction. Rows below shift
+/// down, so insertion is refused when another numeric target or a relative branch would move.
+pub(crate) fn branch_target_label_edits(
+    tree: &tree_sitter::Tree,
+    content: &str,
+    type_data: &crate::TypeData,
+    instruction: tree_sitter::Node,
+) -> Option<(String, Vec<TextEdit>)> {
+    let (numeric_targets, can_insert) = numeric_branch_targets(tree, content, type_data);
+    let (target, row) = *numeric_targets
+        .iter()
+        .find(|(target, _)| target.parent() == Some(instruction))?;
+    let rewrite = |name: &str| TextEdit::new(Range::from(target.range()).into(), name.to_string());
+    if let Some(name) = labels_by_row(type_data).remove(&row) {
+        let edits = vec![rewrite(&name)];
+        return Some((name, edits));
+    }
+    let others_stay = numeric_targets
+        .iter()
+        .all(|(other, other_row)| *other == target || *other_row <= row);
+    if !(can_insert && others_stay) {
+        return None;
+    }
+    let name = fresh_label_name(row, type_data);
+    let edits = vec![label_insertion(row, &name, content), rewrite(&name)];
+    Some((name, edits))
+}
+
+/// URI scheme of the generated documents goto-definition opens for device hashes. The client
+/// fetches their content with the `ic10.deviceDocument` command.
+pub(crate) const DEVICE_DOCUMENT_SCHEME: &str = "ic10-device";
+
+/// Prefab name of the device a `HASH("...")` or a numeric prefab hash at `node` refers to
+fn device_at<'a>(node: tree_sitter::Node, content: &str, device_hashes: &'a DeviceHashes) -> Option<&'a str> {
+    let hash = match node.kind() {
+        "hash_function" | "hash_string" | "hash_keyword" => {
+            let function = if node.kind() == "hash_function" { node } else { node.parent()? };
+            if function.kind() != "hash_function" {
+                return None;
+            }
+            let name = crate::hash_utils::extract_hash_argument(function.utf8_text(content.as_bytes()).ok()?)?;
+            device_hashes.get_device_hash(&name)?
+        }
+        "number" => crate::hash_utils::hash_from_number_text(node.utf8_text(content.as_bytes()).ok()?)?,
+        _ => return None,
+    };
+    device_hashes.get_prefab_name_for_hash(hash)
+}
+
+pub(crate) fn device_document_uri(prefab: &str) -> Url {
+    Url::parse(&format!("{}:{}.md", DEVICE_DOCUMENT_SCHEME, prefab)).unwrap()
+}
+
+/// Content of a device document: hash, display name, description and the logic types the
+/// device exposes. `None` when the URI does not name a known device.
+pub(crate) fn device_document(uri: &Url, device_hashes: &DeviceHashes) -> Option<String> {
+    if uri.scheme() != DEVICE_DOCUMENT_SCHEME {
+        return None;
+    }
+    let prefab = uri.path().strip_suffix(".md")?;
+    let hash = device_hashes.get_device_hash(prefab)?;
+    let description = crate::descriptions::get_device_description(prefab);
+    let display_name = device_hashes.get_device_name_for_hash(hash)
+        .or(description.map(|(name, _)| name))
+        .unwrap_or(prefab);
+
+    let mut document = format!(
+        "# {}\n\n| Prefab | Hash |\n| --- | --- |\n| `{}` | `{}` |\n\n`HASH(\"{}\")` = {}\n",
+        display_name, prefab, hash, prefab, hash
+    );
+    if let Some((_, text)) = description.filter(|(_, text)| !text.is_empty()) {
+        document.push_str(&format!("\n{}\n", text));
+    }
+    if let Some(logic_types) = crate::descriptions::get_device_logic_types(prefab) {
+        document.push_str("\n## Logic types\n\n| Logic type | Read | Write |\n| --- | --- | --- |\n");
+        for (name, readable, writable) in logic_types.iter() {
+            let mark = |allowed: bool| if allowed { "yes" } else { "" };
+            document.push_str(&format!("| {} | {} | {} |\n", name, mark(*readable), mark(*writable)));
+        }
+    }
+    Some(document)
+}
+
+/// Build the edit for `ic10.insertSummaryHeader`: a comment block listing the device aliases,
+/// the device types reached by batch instructions and the instruction count. A block from an
+/// earlier run is replaced in place; otherwise the block goes at the top of the file. Returns
+/// `None` when the script branches to numeric line targets, which the edit would shift.
+pub(crate) fn summary_header_edit(
+    tree: &tree_sitter::Tree,
+    content: &str,
+    type_data: &crate::TypeData,
+    device_hashes: &DeviceHashes,
+) -> Option<TextEdit> {
+    if !crate::lsp_diagnostics::absolute_jumps(tree, content).is_empty() {
+        return None;
+    }
+
+    let mut aliases: Vec<(&str, &str)> = type_data
+        .aliases
+        .iter()
+        .filter_map(|(name, alias)| match &alias.value {
+            crate::AliasValue::Device(target) => Some((target.as_str(), name.as_str())),
+            crate::AliasValue::Register(_) => None,
+        })
+        .collect();
+    aliases.sort();
+
+    let query = Query::new(tree_sitter_ic10::language(), "(instruction) @instruction").unwrap();
+    let mut cursor = QueryCursor::new();
+    let instruction_count = cursor
+        .captures(&query, tree.root_node(), content.as_bytes())
+        .count();
+
+    let mut block = format!("{}\n", SUMMARY_BEGIN);
+    for (target, name) in aliases {
+        block.push_str(&format!("# {} {}\n", target, name));
+    }
+    let batch_devices = crate::additional_features::referenced_devices(
+        tree,
+        content,
+        &type_data.aliases,
+        &type_data.defines,
+        device_hashes,
+    );
+    for device in batch_devices.iter().filter(|device| device.batch) {
+        match &device.display_name {
+            Some(display_name) => block.push_str(&format!("# batch {}: {}\n", device.name, display_name)),
+            None => block.push_str(&format!("# batch {}\n", device.name)),
+        }
+    }
+    block.push_str(&format!("# instructions: {}\n", instruction_count));
+    block.push_str(&format!("{}\n", SUMMARY_END));
+
+    let lines: Vec<&str> = content.lines().collect();
+    let begin = lines.iter().position(|line| line.trim_end() == SUMMARY_BEGIN);
+    let end = begin.and_then(|begin| {
+        lines[begin..]
+            .iter()
+            .position(|line| line.trim_end() == SUMMARY_END)
+            .map(|offset| begin + offset)
+    });
+    let range = match (begin, end) {
+        (Some(begin), Some(end)) => tower_lsp::lsp_types::Range::new(
+            tower_lsp::lsp_types::Position::new(begin as u32, 0),
+            tower_lsp::lsp_types::Position::new(end as u32 + 1, 0),
+        ),
+        _ => tower_lsp::lsp_types::Range::default(),
+    };
+    Some(TextEdit::new(range, block))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn analyze_at_combines_hover_completion_and_signature() {
+        let content = "alias pump d0\ns pump On 1\n".to_string();
+        let mut parser = tree_sitter::Parser::new();
+        parser.set_language(tree_sitter_ic10::language()).unwrap();
+        let mut document = crate::DocumentData {
+            url: Url::parse("file:///test.ic10").unwrap(),
+            tree: parser.parse(&content, None),
+            content,
+            parser,
+        };
+        let type_data = crate::lsp_diagnostics::collect_type_data(
+            document.tree.as_ref().unwrap(),
+            &document.content,
+            &mut Vec::new(),
+        );
+        let config = crate::Configuration::default();
+        let position = tower_lsp::lsp_types::Position::new(1, 4);
+        let payload = analyze_at(&document, &type_data, position, &config, &DeviceHashes::default(), &CustomDocs::default()).unwrap();
+        assert!(payload["hover"]["contents"].to_string().contains("d0"));
+        assert!(payload["signature"]["signatures"][0]["label"].as_str().unwrap().starts_with("s "));
+        assert!(payload["completions"].as_array().unwrap().contains(&"pump".into()));
+
+        document.tree = None;
+        assert!(analyze_at(&document, &type_data, position, &config, &DeviceHashes::default(), &CustomDocs::default()).is_err());
+    }
+
+    #[test]
+    fn signature_parameters_describe_accepted_types() {
+        let content = "l r0 d0 Temperature\n".to_string();
+        let mut parser = tree_sitter::Parser::new();
+        parser.set_language(tree_sitter_ic10::language()).unwrap();
+        let document = crate::DocumentData {
+            url: Url::parse("file:///test.ic10").unwrap(),
+            tree: parser.parse(&content, None),
+            content,
+            parser,
+        };
+        let help = signature_help_at(&document, tower_lsp::lsp_types::Position::new(0, 6), &CustomDocs::default())
+            .unwrap()
+            .unwrap();
+        let accepted: Vec<_> = help.signatures[0]
+            .parameters
+            .iter()
+            .flatten()
+            .map(|parameter| match &parameter.documentation {
+                Some(Documentation::MarkupContent(markup)) => markup.value.clone(),
+                other => panic!("{:?}", other),
+            })
+            .collect();
+        assert_eq!(
+            accepted,
+            vec!["Accepts **register**", "Accepts **register | device | number**", "Accepts **logicType**"]
+        );
+    }
+
+    #[test]
+    fn device_hashes_resolve_to_a_device_document() {
+        let src = "lb r0 HASH(\"StructureVolumePump\") On Sum\nlb r0 -321403609 On Sum\nmove r0 5\n";
+        let mut parser = tree_sitter::Parser::new();
+        parser.set_language(tree_sitter_ic10::language()).unwrap();
+        let tree = parser.parse(src, None).unwrap();
+        let node_at = |line, column| {
+            let point = tree_sitter::Point::new(line, column);
+            tree.root_node().named_descendant_for_point_range(point, point).unwrap()
+        };
+        let device_hashes = DeviceHashes::default();
+        let device_at = |node| device_at(node, src, &device_hashes);
+        assert_eq!(device_at(node_at(0, 7)), Some("StructureVolumePump"));
+        assert_eq!(device_at(node_at(0, 15)), Some("StructureVolumePump"));
+        assert_eq!(device_at(node_at(1, 8)), Some("StructureVolumePump"));
+        assert_eq!(device_at(node_at(2, 8)), None);
+
+        let uri = device_document_uri("StructureVolumePump");
+        assert_eq!(uri.as_str(), "ic10-device:StructureVolumePump.md");
+        let document = device_document(&uri, &device_hashes).unwrap();
+        assert!(document.contains("`HASH(\"StructureVolumePump\")` = -321403609"), "{document}");
+        assert!(document.contains("## Logic types"), "{document}");
+        assert!(device_document(&device_document_uri("NotADevice"), &device_hashes).is_none());
+        assert!(device_document(&Url::parse("file:///StructureVolumePump.md").unwrap(), &device_hashes).is_none());
+    }
+
+    #[test]
+    fn rename_updates_every_reference() {
+        let src = "alias heater d0\nOn:\ns heater On 1\nbeqz r0 On\nj On\nalias fan d1\n";
+        let mut parser = tree_sitter::Parser::new();
+        parser.set_language(tree_sitter_ic10::language()).unwrap();
+        let tree = parser.parse(src, None).unwrap();
+        let range = |line, start, end| {
+            Range(tower_lsp::lsp_types::Range::new(
+                tower_lsp::lsp_types::Position::new(line, start),
+                tower_lsp::lsp_types::Position::new(line, end),
+            ))
+        };
+        let mut type_data = crate::TypeData::default();
+        type_data
+            .aliases
+            .insert("heater".to_string(), crate::DefinitionData::new(range(0, 6, 12), "d0".to_string().into()));
+        type_data
+            .aliases
+            .insert("fan".to_string(), crate::DefinitionData::new(range(5, 6, 9), "d1".to_string().into()));
+        type_data.labels.insert("On".to_string(), crate::DefinitionData::new(range(1, 0, 2), 1));
+        let node_at = |line, column| {
+            let point = tree_sitter::Point::new(line, column);
+            tree.root_node().named_descendant_for_point_range(point, point).unwrap()
+        };
+        let lines = |edits: Vec<TextEdit>| -> Vec<(u32, u32)> {
+            edits.iter().map(|e| (e.range.start.line, e.range.start.character)).collect()
+        };
+
+        let edits = rename_edits(node_at(2, 3), src, &type_data, "boiler").unwrap().unwrap();
+        assert_eq!(lines(edits), vec![(0, 6), (2, 2)]);
+        // The label's operands lex as logic types; only the branch targets are renamed
+        let edits = rename_edits(node_at(4, 2), src, &type_data, "loop").unwrap().unwrap();
+        assert_eq!(lines(edits), vec![(1, 0), (3, 8), (4, 2)]);
+        assert!(rename_edits(node_at(2, 3), src, &type_data, "fan").is_err());
+        assert!(rename_edits(node_at(2, 3), src, &type_data, "2fast").is_err());
+        for reserved in ["r1", "rr2", "sp", "d0", "db", "add", "Setting"] {
+            assert!(rename_edits(node_at(2, 3), src, &type_data, reserved).is_err(), "{reserved}");
+        }
+        assert!(rename_edits(node_at(3, 5), src, &type_data, "x").unwrap().is_none());
+    }
+
+    #[test]
+    fn references_skip_hash_strings() {
+        let src = "define Pump HASH(\"Pump\")\nlb r0 Pump On Sum\nsb STR(\"Pump\") On Pump\n";
+        let mut parser = tree_sitter::Parser::new();
+        parser.set_language(tree_sitter_ic10::language()).unwrap();
+        let tree = parser.parse(src, None).unwrap();
+        let definition = tower_lsp::lsp_types::Range::new(
+            tower_lsp::lsp_types::Position::new(0, 7),
+            tower_lsp::lsp_types::Position::new(0, 11),
+        );
+        let mut type_data = crate::TypeData::default();
+        type_data.defines.insert(
+            "Pump".to_string(),
+            crate::DefinitionData::new(Range(definition), "HASH(\"Pump\")".to_string().into()),
+        );
+        let point = tree_sitter::Point::new(1, 7);
+        let node = tree.root_node().named_descendant_for_point_range(point, point).unwrap();
+        let (_, found, ranges) = symbol_occurrences(node, src, &type_data).unwrap();
+        assert_eq!(found, definition);
+        let starts: Vec<_> = ranges.iter().map(|r| (r.start.line, r.start.character)).collect();
+        assert_eq!(starts, vec![(0, 7), (1, 6), (2, 18)]);
+    }
+
+    #[test]
+    fn defines_named_like_logic_types_are_found() {
+        let src = "define Setting 5\nadd r0 Setting 1\ns d0 On Setting\n";
+        let mut parser = tree_sitter::Parser::new();
+        parser.set_language(tree_sitter_ic10::language()).unwrap();
+        let tree = parser.parse(src, None).unwrap();
+        let definition = tower_lsp::lsp_types::Range::new(
+            tower_lsp::lsp_types::Position::new(0, 7),
+            tower_lsp::lsp_types::Position::new(0, 14),
+        );
+        let mut type_data = crate::TypeData::default();
+        type_data.defines.insert(
+            "Setting".to_string(),
+            crate::DefinitionData::new(Range(definition), "5".to_string().into()),
+        );
+        let point = tree_sitter::Point::new(1, 8);
+        let node = tree.root_node().named_descendant_for_point_range(point, point).unwrap();
+        assert_eq!(node.kind(), "logictype");
+        let (_, found, ranges) = symbol_occurrences(node, src, &type_data).unwrap();
+        assert_eq!(found, definition);
+        let starts: Vec<_> = ranges.iter().map(|r| (r.start.line, r.start.character)).collect();
+        assert_eq!(starts, vec![(0, 7), (1, 7), (2, 8)]);
+        let highlights = document_highlights(node, src, &type_data).unwrap();
+        assert_eq!(highlights.len(), 3);
+    }
+
+    #[test]
+    fn duplicates_and_moves_whole_lines() {
+        let apply = |src: &str, edit: TextEdit| {
+            let offset = |position: tower_lsp::lsp_types::Position| {
+                src.split_inclusive('\n').take(position.line as usize).map(str::len).sum::<usize>()
+                    + position.character as usize
+            };
+            let (start, end) = (offset(edit.range.start), offset(edit.range.end));
+            format!("{}{}{}", &src[..start], edit.new_text, &src[end..])
+        };
+        let mut parser = tree_sitter::Parser::new();
+        parser.set_language(tree_sitter_ic10::language()).unwrap();
+        let src = "start: # loop\n\tyield\nj start";
+        let tree = parser.parse(src, None).unwrap();
+
+        let edit = duplicate_line_edit(&tree, src, 0).unwrap();
+        assert_eq!(apply(src, edit), "start: # loop\nstart: # loop\n\tyield\nj start");
+        let edit = duplicate_line_edit(&tree, src, 2).unwrap();
+        assert_eq!(apply(src, edit), "start: # loop\n\tyield\nj start\nj start");
+
+        let edit = move_line_edit(&tree, src, 2, true).unwrap();
+        assert_eq!(apply(src, edit), "start: # loop\nj start\n\tyield");
+        let edit = move_line_edit(&tree, src, 0, false).unwrap();
+        assert_eq!(apply(src, edit), "\tyield\nstart: # loop\nj start");
+        assert!(move_line_edit(&tree, src, 0, true).is_none());
+        assert!(move_line_edit(&tree, src, 2, false).is_none());
+    }
+
+    fn group(src: &str) -> Option<TextEdit> {
+        let mut parser = tree_sitter::Parser::new();
+        parser.set_language(tree_sitter_ic10::language()).unwrap();
+        group_header_definitions(&parser.parse(src, None).unwrap(), src)
+    }
+
+    #[test]
+    fn groups_header_definitions() {
+        let src = "# Airlock\ndefine Target 100\n\n# inner door\nalias door d0\nalias r r0\n\nstart:\nyield\n";
+        let edit = group(src).unwrap();
+        assert_eq!(edit.range.end.line, 7);
+        assert_eq!(
+            edit.new_text,
+            "# Airlock\n# --- Definitions ---\n\n# inner door\nalias door d0\nalias r r0\ndefine Target 100\n\n"
+        );
+    }
+
+    #[test]
+    fn grouped_header_is_left_alone() {
+        let src = "# --- Definitions ---\nalias door d0\ndefine Target 100\n\nyield\n";
+        assert!(group(src).is_none());
+        assert!(group("yield\nalias door d0\n").is_none());
+    }
+
+    #[test]
+    fn grouping_is_refused_with_numeric_branch_targets() {
+        let src = "define Target 100\nalias door d0\nyield\nj 2\n";
+        assert!(group(src).is_none());
+        assert!(group("define Target 100\nalias door d0\nstart:\nj start\n").is_some());
+    }
+
+    #[test]
+    fn suggests_definitions_for_bare_operands() {
+        let src = "alias temp r1\nl r0 d0 Temperature\nlb r2 -1252983604 Temperature Average\nmove temp r0\ns db Setting r2\n";
+        let mut parser = tree_sitter::Parser::new();
+        parser.set_language(tree_sitter_ic10::language()).unwrap();
+        let tree = parser.parse(src, None).unwrap();
+        let mut type_data = crate::TypeData::default();
+        type_data.aliases.insert(
+            "temp".to_string(),
+            crate::DefinitionData::new(Range(tower_lsp::lsp_types::Range::default()), "r1".to_string().into()),
+        );
+        assert_eq!(
+            suggest_definitions(&tree, src, &type_data, &Default::default(), &DeviceHashes::default()),
+            "alias device0 d0\nalias value0 r0\nalias value2 r2\ndefine GasSensor -1252983604\n"
+        );
+
+        let mut naming = crate::document::AliasNaming::default();
+        let settings = serde_json::json!({ "register": "reg_{n}", "device": "Device{n}x{n}" });
+        assert_eq!(naming.update_from_json(settings.as_object().unwrap()), vec!["Device{n}x{n}"]);
+        assert!(!crate::document::AliasNaming::is_valid_template("1st{n}"));
+        for reserved in ["r{n}", "d{n}", "rr{n}", "atan{n}", "Channel{n}"] {
+            assert!(!crate::document::AliasNaming::is_valid_template(reserved), "{}", reserved);
+        }
+        assert_eq!(
+            suggest_definitions(&tree, src, &type_data, &naming, &DeviceHashes::default()),
+            "alias device0 d0\nalias reg_0 r0\nalias reg_2 r2\ndefine GasSensor -1252983604\n"
+        );
+    }
+
+    #[test]
+    fn indents_after_label_only_in_indented_files() {
+        let indented = "main:\n    yield\nloop:\n\n";
+        let edit = label_indent_edit(indented, 3).unwrap();
+        assert_eq!(edit.new_text, "    ");
+        assert_eq!(edit.range.end.character, 0);
+
+        assert!(label_indent_edit("main:\nyield\nloop:\n\n", 3).is_none());
+        assert!(label_indent_edit(indented, 2).is_none());
+        assert!(label_indent_edit("main:\n\tyield\nloop: # again\n\t\n", 3).is_none());
+    }
+
+    #[test]
+    fn summary_header_replaces_previous_block() {
+        let src = "alias GasSensor d0\nalias pump d1\nalias temp r0\nl temp GasSensor Temperature\ns pump On 1\nsb HASH(\"StructureGasSensor\") On 1\n";
+        let mut parser = tree_sitter::Parser::new();
+        parser.set_language(tree_sitter_ic10::language()).unwrap();
+        let mut type_data = crate::TypeData::default();
+        for (name, target) in [("GasSensor", "d0"), ("pump", "d1"), ("temp", "r0")] {
+            type_data.aliases.insert(
+                name.to_string(),
+                crate::DefinitionData::new(Range(tower_lsp::lsp_types::Range::default()), target.to_string().into()),
+            );
+        }
+        let tree = parser.parse(src, None).unwrap();
+        let edit = summary_header_edit(&tree, src, &type_data, &DeviceHashes::default()).unwrap();
+        assert_eq!(edit.range, tower_lsp::lsp_types::Range::default());
+        // Alias names are not matched against prefabs; batch hashes are resolved exactly
+        assert_eq!(
+            edit.new_text,
+            "# --- Summary (generated) ---\n# d0 GasSensor\n# d1 pump\n# batch StructureGasSensor: Gas Sensor\n# instructions: 6\n# --- End summary ---\n"
+        );
+
+        let rerun = format!("{}{}", edit.new_text, src);
+        let tree = parser.parse(&rerun, None).unwrap();
+        let edit = summary_header_edit(&tree, &rerun, &type_data, &DeviceHashes::default()).unwrap();
+        assert_eq!(edit.range.start.line, 0);
+        assert_eq!(edit.range.end.line, 6);
+
+        let jumps = "alias pump d1\ns pump On 1\nj 1\n";
+        let tree = parser.parse(jumps, None).unwrap();
+        assert!(summary_header_edit(&tree, jumps, &type_data, &DeviceHashes::default()).is_none());
+    }
+
+    #[test]
+    fn multi_line_tokens_are_split_per_line() {
+        let src = "move r0 1 # ünï\nyield\n\nj 0\n";
+        let mut parser = tree_sitter::Parser::new();
+        parser.set_language(tree_sitter_ic10::language()).unwrap();
+        let tree = parser.parse(src, None).unwrap();
+        let program = tree.root_node().child(0).unwrap();
+        assert!(program.end_position().row > program.start_position().row);
+
+        let mut tokens = SemanticTokenEncoder::new(src);
+        tokens.push(program.start_position(), program.end_position(), 0);
+        let segments: Vec<(u32, u32, u32)> =
+            tokens.data.iter().map(|t| (t.delta_line, t.delta_start, t.length)).collect();
+        assert_eq!(segments, vec![(0, 0, 15), (1, 0, 5), (2, 0, 3)]);
+
+        // Anything behind the previous token is dropped rather than underflowing the deltas
+        tokens.push(tree_sitter::Point::new(0, 0), tree_sitter::Point::new(0, 4), 0);
+        assert_eq!(tokens.data.len(), 3);
+        let mut row = 0;
+        for token in &tokens.data {
+            row += token.delta_line as usize;
+            assert!(token.length as usize <= src.lines().nth(row).unwrap().encode_utf16().count());
+        }
+    }
+
+    #[test]
+    fn formatting_normalizes_instruction_spacing() {
+        let mut parser = tree_sitter::Parser::new();
+        parser.set_language(tree_sitter_ic10::language()).unwrap();
+        let mut format = |src: &str| {
+            let tree = parser.parse(src, None).unwrap();
+            format_document_edit(&tree, src).map(|edit| edit.new_text)
+        };
+
+        assert_eq!(format("  add   r0    r1  r2   # sum\n").as_deref(), Some("  add r0 r1 r2 # sum\n"));
+        assert_eq!(
+            format("define  K   5  \nstart:   # top\n\n  \nyield   \r\nj   start").as_deref(),
+            Some("define  K   5  \nstart:   # top\n\n  \nyield\r\nj start")
+        );
+        assert_eq!(format("move r0 1 # already tidy\n"), None);
+    }
+
+    #[test]
+    fn numeric_branches_are_converted_to_labels() {
+        let mut parser = tree_sitter::Parser::new();
+        parser.set_language(tree_sitter_ic10::language()).unwrap();
+        let mut type_data = crate::TypeData::default();
+        type_data.labels.insert(
+            "start".to_string(),
+            crate::DefinitionData::new(
+                Range(tower_lsp::lsp_types::Range::new(
+                    tower_lsp::lsp_types::Position::new(0, 0),
+                    tower_lsp::lsp_types::Position::new(0, 5),
+                )),
+                0,
+            ),
+        );
+        let mut edits = |src: &str, insert_labels: bool| -> Vec<(u32, u32, String)> {
+            let tree = parser.parse(src, None).unwrap();
+            branches_to_labels_edits(&tree, src, &type_data, insert_labels)
+                .into_iter()
+                .map(|e| (e.range.start.line, e.range.start.character, e.new_text))
+                .collect()
+        };
+
+        let src = "start:\nyield\nbnez r0 1\nj 0\n";
+        assert_eq!(edits(src, false), vec![(3, 2, "start".to_string())]);
+        assert_eq!(
+            edits(src, true),
+            vec![
+                (1, 0, "line1:\n".to_string()),
+                (2, 8, "line1".to_string()),
+                (3, 2, "start".to_string())
+            ]
+        );
+        // A relative branch would be thrown off by the inserted line
+        let src = "start:\nyield\nbnez r0 1\nj 0\nbrnez r0 -1\n";
+        assert_eq!(edits(src, true), vec![(3, 2, "start".to_string())]);
+        // Hex and binary targets name lines too
+        assert_eq!(edits("start:\nj $0\nj %0\n", false).len(), 2);
+    }
+
+    #[test]
+    fn numeric_branch_target_becomes_a_label() {
+        let mut parser = tree_sitter::Parser::new();
+        parser.set_language(tree_sitter_ic10::language()).unwrap();
+        let mut fix = |src: &str, line: usize| {
+            let tree = parser.parse(src, None).unwrap();
+            let mut type_data = crate::TypeData::default();
+            for (row, text) in src.lines().enumerate() {
+                if let Some(name) = text.strip_suffix(':') {
+                    let at = tower_lsp::lsp_types::Position::new(row as u32, 0);
+                    type_data.labels.insert(
+                        name.to_string(),
+                        crate::DefinitionData::new(Range(tower_lsp::lsp_types::Range::new(at, at)), row as u8),
+                    );
+                }
+            }
+            let instruction = tree
+                .root_node()
+                .named_descendant_for_point_range(
+                    tree_sitter::Point::new(line, 0),
+                    tree_sitter::Point::new(line, 1),
+                )
+                .and_then(|node| node.find_parent("instruction"))
+                .unwrap();
+            branch_target_label_edits(&tree, src, &type_data, instruction).map(|(label, edits)| {
+                let edits: Vec<_> = edits
+                    .into_iter()
+                    .map(|e| (e.range.start.line, e.range.start.character, e.new_text))
+                    .collect();
+                (label, edits)
+            })
+        };
+
+        // `j 1` lands on `add`; the inserted label takes row 1 and `add` follows it
+        let (label, edits) = fix("yield\nadd r0 r0 1\nj 1\n", 2).unwrap();
+        assert_eq!(label, "line1");
+        assert_eq!(edits, vec![(1, 0, "line1:\n".to_string()), (2, 2, "line1".to_string())]);
+
+        // A label already on the target line is reused without inserting anything
+        let (label, edits) = fix("yield\nloop:\nj 1\n", 2).unwrap();
+        assert_eq!(label, "loop");
+        assert_eq!(edits, vec![(2, 2, "loop".to_string())]);
+
+        // `j 3` would move under a label inserted at row 1
+        assert!(fix("yield\nadd r0 r0 1\nj 1\nj 3\n", 2).is_none());
+        assert!(fix("yield\nadd r0 r0 1\nj 1\nbrnez r0 -1\n", 2).is_none());
+    }
+
+    #[test]
+    fn selection_expands_from_hash_string_to_line() {
+        let src = "yield\nlb r0 HASH(\"Foo Bar\") On Sum\n";
+        let mut parser = tree_sitter::Parser::new();
+        parser.set_language(tree_sitter_ic10::language()).unwrap();
+        let tree = parser.parse(src, None).unwrap();
+
+        let mut spans = Vec::new();
+        let mut selection = Some(selection_range_at(&tree, tree_sitter::Point::new(1, 14)));
+        while let Some(current) = selection {
+            let (start, end) = (current.range.start, current.range.end);
+            spans.push(((start.line, start.character), (end.line, end.character)));
+            selection = current.parent.map(|parent| *parent);
+        }
+        assert_eq!(
+            spans[..4],
+            [
+                ((1, 11), (1, 20)), // "Foo Bar"
+                ((1, 6), (1, 21)),  // HASH("Foo Bar"), which is also the operand
+                ((1, 0), (1, 28)),  // instruction
+                ((1, 0), (2, 0)),   // line
+            ]
+        );
+        assert_eq!(spans.last(), Some(&((0, 0), (2, 0))));
+    }
+
+    #[test]
+    fn workspace_symbols_search_every_document() {
+        let mut parser = tree_sitter::Parser::new();
+        parser.set_language(tree_sitter_ic10::language()).unwrap();
+        let pumps = (Url::parse("file:///scripts/pumps.ic10").unwrap(), "alias Pump d0\nmainLoop:\nj mainLoop\n");
+        let vents = (Url::parse("file:///scripts/vents.ic10").unwrap(), "define PUMP_RATE 5\nloop:\nj loop\n");
+        let trees: Vec<_> = [&vents, &pumps].iter().map(|(_, src)| parser.parse(src, None).unwrap()).collect();
+        let documents = || {
+            [&vents, &pumps]
+                .into_iter()
+                .zip(&trees)
+                .map(|((uri, src), tree)| (uri, tree, *src))
+        };
+
+        let found: Vec<_> = workspace_symbols(documents(), "pump")
+            .into_iter()
+            .map(|s| (s.name, s.location.uri.path().to_string(), s.location.range.start.line, s.container_name))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("Pump".to_string(), "/scripts/pumps.ic10".to_string(), 0, Some("pumps.ic10".to_string())),
+                ("PUMP_RATE".to_string(), "/scripts/vents.ic10".to_string(), 0, Some("vents.ic10".to_string())),
+            ]
+        );
+        assert_eq!(workspace_symbols(documents(), "LOOP").len(), 2);
+        assert_eq!(workspace_symbols(documents(), "").len(), 4);
+    }
+
+    #[test]
+    fn highlights_mark_the_label_definition_as_write() {
+        let src = "loop:\nyield\nbnez r0 loop\nj loop\n";
+        let mut parser = tree_sitter::Parser::new();
+        parser.set_language(tree_sitter_ic10::language()).unwrap();
+        let tree = parser.parse(src, None).unwrap();
+        let mut type_data = crate::TypeData::default();
+        type_data.labels.insert(
+            "loop".to_string(),
+            crate::DefinitionData::new(
+                Range(tower_lsp::lsp_types::Range::new(
+                    tower_lsp::lsp_types::Position::new(0, 0),
+                    tower_lsp::lsp_types::Position::new(0, 4),
+                )),
+                0,
+            ),
+        );
+        let point = tree_sitter::Point::new(3, 3);
+        let node = tree.root_node().named_descendant_for_point_range(point, point).unwrap();
+        let highlights: Vec<_> = document_highlights(node, src, &type_data)
+            .unwrap()
+            .into_iter()
+            .map(|h| (h.range.start.line, h.range.start.character, h.kind))
+            .collect();
+        assert_eq!(
+            highlights,
+            vec![
+                (0, 0, Some(DocumentHighlightKind::WRITE)),
+                (2, 8, Some(DocumentHighlightKind::READ)),
+                (3, 2, Some(DocumentHighlightKind::READ)),
+            ]
+        );
+    }
+}
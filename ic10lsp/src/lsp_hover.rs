@@ -17,7 +17,9 @@ use tree_sitter::{Query, QueryCursor};
 use ic10lsp::instructions;
 
 use crate::additional_features;
-use crate::document::AliasValue;
+use crate::document::{AliasValue, DefineValue, DefinitionData, DocumentData, TypeData};
+use crate::hash_utils::DeviceHashes;
+use crate::tooltip_documentation::CustomDocs;
 use crate::tree_utils::{get_current_parameter, NodeEx};
 use crate::types::{Position, Range};
 use crate::Backend;
@@ -85,6 +87,14 @@ fn query_identifier() -> &'static Query {
     })
 }
 
+/// Cached query for comments (`# log` debug annotations)
+fn query_comment() -> &'static Query {
+    static QUERY: OnceLock<Query> = OnceLock::new();
+    QUERY.get_or_init(|| {
+        Query::new(tree_sitter_ic10::language(), "(comment)@c").unwrap()
+    })
+}
+
 /// Warm up all cached queries at startup to eliminate first-request latency
 pub fn warmup_queries() {
     let _ = query_number();
@@ -94,6 +104,7 @@ pub fn warmup_queries() {
     let _ = query_instruction();
     let _ = query_str_function();
     let _ = query_identifier();
+    let _ = query_comment();
 }
 
 /// Handle hover requests - delegates to internal implementation
@@ -101,16 +112,44 @@ pub async fn handle_hover(backend: &Backend, params: HoverParams) -> Result<Opti
     let _timer = crate::performance::TimingGuard::new(&backend.perf_tracker, "lsp.server.hover");
     backend.perf_tracker.increment("lsp.server.hover.calls", 1);
     
+    let device_hashes = backend.device_hashes.read().await;
+    let custom_docs = backend.custom_docs.read().await;
     let files = backend.files.read().await;
     let Some(file_data) = files.get(&params.text_document_position_params.text_document.uri)
     else {
         return Err(tower_lsp::jsonrpc::Error::internal_error());
     };
-    let document = &file_data.document_data;
-    let type_data = file_data.type_data.clone();
+    hover_at(
+        &file_data.document_data,
+        &file_data.type_data,
+        params.text_document_position_params.position,
+        &device_hashes,
+        &custom_docs,
+    )
+}
 
-    let position = params.text_document_position_params.position;
+/// `KEYWORD("text") = crc32` for an arbitrary string, noting a device whose prefab name
+/// happens to hash to the same value
+fn string_hash_hover(keyword: &str, string: &str, device_hashes: &DeviceHashes) -> Vec<MarkedString> {
+    let hash = crate::hash_utils::compute_crc32(string);
+    let mut parts = vec![MarkedString::LanguageString(LanguageString {
+        language: "ic10".to_string(),
+        value: format!("{}(\"{}\") = {}", keyword, string, hash),
+    })];
+    if let Some(device) = device_hashes.get_device_name_for_hash(hash) {
+        parts.push(MarkedString::String(format!("Also the prefab hash of **{}**.", device)));
+    }
+    parts
+}
 
+/// Hover content at `position`; shared by the `hover` request and the `ic10.hoverAt` command
+pub(crate) fn hover_at(
+    document: &DocumentData,
+    type_data: &TypeData,
+    position: tower_lsp::lsp_types::Position,
+    device_hashes: &DeviceHashes,
+    custom_docs: &CustomDocs,
+) -> Result<Option<Hover>> {
     let Some(tree) = document.tree.as_ref() else {
         return Ok(None);
     };
@@ -173,7 +212,7 @@ pub async fn handle_hover(backend: &Backend, params: HoverParams) -> Result<Opti
                     }));
                 }
             }
-            if let Some(definition_data) = type_data.defines.get(name) {
+            if type_data.defines.contains_key(name) {
                 // Check if this is a HASH() function call
                 if let Some(parent) = node.parent() {
                     if parent.kind() == "function_call" {
@@ -183,7 +222,7 @@ pub async fn handle_hover(backend: &Backend, params: HoverParams) -> Result<Opti
                             crate::hash_utils::extract_hash_argument(parent_text)
                         {
                             if let Some(device_hash) =
-                                crate::hash_utils::get_device_hash(&device_name)
+                                device_hashes.get_device_hash(&device_name)
                             {
                                 let mut parts: Vec<MarkedString> = Vec::new();
                                 parts.push(MarkedString::LanguageString(LanguageString {
@@ -194,7 +233,7 @@ pub async fn handle_hover(backend: &Backend, params: HoverParams) -> Result<Opti
                                     ),
                                 }));
                                 if let Some(device_display_name) =
-                                    crate::hash_utils::get_device_name_for_hash(device_hash)
+                                    device_hashes.get_device_name_for_hash(device_hash)
                                 {
                                     parts.push(MarkedString::String(device_display_name.to_string()));
                                 }
@@ -206,158 +245,38 @@ pub async fn handle_hover(backend: &Backend, params: HoverParams) -> Result<Opti
                         }
                     }
                 }
-
-                // Handle defines - show resolved numeric hash if available
-                let device_hash_value = definition_data.value.resolved_numeric();
-                let device_display_name = device_hash_value
-                    .and_then(crate::hash_utils::get_device_name_for_hash);
-
-                if device_display_name.is_some() || device_hash_value.is_some() {
-                    let mut parts: Vec<MarkedString> = Vec::new();
-                    parts.push(MarkedString::LanguageString(LanguageString {
-                        language: "ic10".to_string(),
-                        value: format!("define {} {}", name, definition_data.value),
-                    }));
-                    if let Some(hash) = device_hash_value {
-                        parts.push(MarkedString::LanguageString(LanguageString {
-                            language: "ic10".to_string(),
-                            value: format!("// resolved hash = {}", hash),
-                        }));
-                    }
-                    if let Some(device_name) = device_display_name {
-                        parts.push(MarkedString::String(device_name.to_string()));
-                    }
-                    return Ok(Some(Hover {
-                        contents: HoverContents::Array(parts),
-                        range: Some(Range::from(node.range()).into()),
-                    }));
-                } else {
-                    return Ok(Some(Hover {
-                        contents: HoverContents::Array(vec![MarkedString::LanguageString(
-                            LanguageString {
-                                language: "ic10".to_string(),
-                                value: format!("define {} {}", name, definition_data.value),
-                            },
-                        )]),
-                        range: Some(Range::from(node.range()).into()),
-                    }));
-                }
             }
+            let mut interpretations = user_interpretations(name, document, type_data, device_hashes);
             // If an identifier text matches a known logic or slot type name, show its docs
-            if let Some(doc) = instructions::LOGIC_TYPE_DOCS.get(name) {
-                return Ok(Some(Hover {
-                    contents: HoverContents::Array(vec![MarkedString::String(format!(
+            if let Some(doc) = custom_docs.logic_type_doc(name) {
+                interpretations.push((
+                    "logic type",
+                    HoverContents::Array(vec![MarkedString::String(format!(
                         "# `{}` (`logicType`)\n{}",
                         name, doc
                     ))]),
-                    range: Some(Range::from(node.range()).into()),
-                }));
+                ));
             }
-            if let Some(doc) = instructions::SLOT_TYPE_DOCS.get(name) {
-                return Ok(Some(Hover {
-                    contents: HoverContents::Array(vec![MarkedString::String(format!(
+            if let Some(doc) = instructions::slot_type_doc(name) {
+                interpretations.push((
+                    "slot logic type",
+                    HoverContents::Array(vec![MarkedString::String(format!(
                         "# `{}` (`logicSlotType`)\n{}",
                         name, doc
                     ))]),
-                    range: Some(Range::from(node.range()).into()),
-                }));
+                ));
             }
-            if let Some(doc) = instructions::BATCH_MODE_DOCS.get(name) {
-                return Ok(Some(Hover {
-                    contents: HoverContents::Array(vec![MarkedString::String(format!(
+            if let Some(doc) = instructions::batch_mode_doc(name) {
+                interpretations.push((
+                    "batch mode",
+                    HoverContents::Array(vec![MarkedString::String(format!(
                         "# `{}` (`batchMode`)\n{}",
                         name, doc
                     ))]),
-                    range: Some(Range::from(node.range()).into()),
-                }));
+                ));
             }
-            if let Some(definition_data) = type_data.aliases.get(name) {
-                // Check if this is a register alias and provide value tracking info
-                if let AliasValue::Register(_) = &definition_data.value {
-                    // Perform register analysis to get current value information
-                    let mut register_analyzer = additional_features::RegisterAnalyzer::new();
-                    if let Some(tree) = document.tree.as_ref() {
-                        register_analyzer.analyze_register_usage(
-                            tree,
-                            &document.content,
-                            &type_data.aliases,
-                        );
-
-                        if let Some(register_info) = register_analyzer.get_register_info(name) {
-                            let register_name = definition_data.value.to_string();
-                            let mut hover_content =
-                                vec![MarkedString::LanguageString(LanguageString {
-                                    language: "ic10".to_string(),
-                                    value: format!("alias {} {}", name, definition_data.value),
-                                })];
-
-                            // Add register information with simple operation history
-                            let mut value_parts = vec![];
-
-                            value_parts
-                                .push(format!("**Register** {} ({})", name, register_name));
-
-                            // Add operation history if available
-                            if !register_info.operation_history.is_empty() {
-                                value_parts.push("**Operation history:**".to_string());
-                                let history_limit = 99; // Show up to 99 operations (tooltip is scrollable)
-                                let start_idx =
-                                    if register_info.operation_history.len() > history_limit {
-                                        register_info.operation_history.len() - history_limit
-                                    } else {
-                                        0
-                                    };
-
-                                for record in &register_info.operation_history[start_idx..] {
-                                    value_parts.push(format!(
-                                        "  • Line {}: {}",
-                                        record.line_number, record.operation
-                                    ));
-                                }
-
-                                if start_idx > 0 {
-                                    value_parts.push(format!(
-                                        "  • ... ({} earlier operations)",
-                                        start_idx
-                                    ));
-                                }
-                            } else {
-                                value_parts.push(
-                                    "**Operation history:** No operations found".to_string(),
-                                );
-                            }
-
-                            let value_info = value_parts.join("\n\n");
-
-                            hover_content.push(MarkedString::String(value_info));
-
-                            return Ok(Some(Hover {
-                                contents: HoverContents::Array(hover_content),
-                                range: Some(Range::from(node.range()).into()),
-                            }));
-                        }
-                    }
-                }
-
-                // Fallback to basic alias information
-                return Ok(Some(Hover {
-                    contents: HoverContents::Array(vec![MarkedString::LanguageString(
-                        LanguageString {
-                            language: "ic10".to_string(),
-                            value: format!("alias {} {}", name, definition_data.value),
-                        },
-                    )]),
-                    range: Some(Range::from(node.range()).into()),
-                }));
-            }
-            if let Some(definition_data) = type_data.labels.get(name) {
-                return Ok(Some(Hover {
-                    contents: HoverContents::Scalar(MarkedString::String(format!(
-                        "Label on line {}",
-                        definition_data.value + 1
-                    ))),
-                    range: Some(Range::from(node.range()).into()),
-                }));
+            if let Some(hover) = merge_interpretations(interpretations, node) {
+                return Ok(Some(hover));
             }
         }
         "operation" | "invalid_instruction" => {
@@ -389,6 +308,7 @@ pub async fn handle_hover(backend: &Backend, params: HoverParams) -> Result<Opti
                             instruction_node,
                             &document.content,
                             &register_analyzer,
+                            custom_docs,
                         ),
                     ),
                     range: Some(Range::from(node.range()).into()),
@@ -426,18 +346,18 @@ pub async fn handle_hover(backend: &Backend, params: HoverParams) -> Result<Opti
                 instructions::logictype_candidates(name)
             };
 
-            let strings: Vec<MarkedString> = candidates
+            let mut strings: Vec<MarkedString> = candidates
                 .iter()
                 .map(|typ| {
                     MarkedString::String(format!("# `{}` (`{}`)\n{}", name, typ, {
                         use instructions::DataType;
                         match typ {
-                            DataType::LogicType => instructions::LOGIC_TYPE_DOCS.get(name),
-                            DataType::SlotLogicType => instructions::SLOT_TYPE_DOCS.get(name),
-                            DataType::BatchMode => instructions::BATCH_MODE_DOCS.get(name),
+                            DataType::LogicType => custom_docs.logic_type_doc(name).map(|doc| doc.to_string()),
+                            DataType::SlotLogicType => instructions::slot_type_doc(name),
+                            DataType::BatchMode => instructions::batch_mode_doc(name),
                             _ => None,
                         }
-                        .unwrap_or(&"")
+                        .unwrap_or_default()
                     }))
                 })
                 .collect();
@@ -446,19 +366,19 @@ pub async fn handle_hover(backend: &Backend, params: HoverParams) -> Result<Opti
             if strings.is_empty() {
                 let mut fallback_parts: Vec<MarkedString> = Vec::new();
                 
-                if let Some(doc) = instructions::LOGIC_TYPE_DOCS.get(name) {
+                if let Some(doc) = custom_docs.logic_type_doc(name) {
                     fallback_parts.push(MarkedString::String(format!(
                         "# `{}` (`logicType`)\n{}",
                         name, doc
                     )));
                 }
-                if let Some(doc) = instructions::SLOT_TYPE_DOCS.get(name) {
+                if let Some(doc) = instructions::slot_type_doc(name) {
                     fallback_parts.push(MarkedString::String(format!(
                         "# `{}` (`logicSlotType`)\n{}",
                         name, doc
                     )));
                 }
-                if let Some(doc) = instructions::BATCH_MODE_DOCS.get(name) {
+                if let Some(doc) = instructions::batch_mode_doc(name) {
                     fallback_parts.push(MarkedString::String(format!(
                         "# `{}` (`batchMode`)\n{}",
                         name, doc
@@ -466,20 +386,36 @@ pub async fn handle_hover(backend: &Backend, params: HoverParams) -> Result<Opti
                 }
                 
                 if !fallback_parts.is_empty() {
-                    return Ok(Some(Hover {
-                        contents: HoverContents::Array(fallback_parts),
-                        range: Some(Range::from(node.range()).into()),
-                    }));
+                    strings = fallback_parts;
                 }
             }
 
+            // `define Setting 5` makes `Setting` a user name too; show both meanings
+            let mut interpretations = user_interpretations(name, document, type_data, device_hashes);
             if !strings.is_empty() {
+                interpretations.push(("logic type", HoverContents::Array(strings)));
+            }
+            if let Some(hover) = merge_interpretations(interpretations, node) {
+                return Ok(Some(hover));
+            }
+        }
+        "str_function" | "str_string" | "str_keyword" => {
+            let call_node = if node.kind() == "str_function" { Some(node) } else { node.parent() };
+            let call_text = call_node.and_then(|call| call.utf8_text(document.content.as_bytes()).ok());
+            if let Some(string) = call_text.and_then(crate::hash_utils::extract_str_argument) {
                 return Ok(Some(Hover {
-                    contents: HoverContents::Array(strings),
-                    range: Some(Range::from(node.range()).into()),
+                    contents: HoverContents::Array(string_hash_hover("STR", &string, device_hashes)),
+                    range: call_node.map(|call| Range::from(call.range()).into()),
                 }));
             }
         }
+        // A quoted string written straight as an operand, without HASH/STR around it
+        "hash_string" if node.parent().is_some_and(|parent| parent.kind() == "ERROR") => {
+            return Ok(Some(Hover {
+                contents: HoverContents::Array(string_hash_hover("STR", name.trim_matches('"'), device_hashes)),
+                range: Some(Range::from(node.range()).into()),
+            }));
+        }
         "hash_function" | "function_call" | "hash_string" | "hash_keyword" => {
             // For hash_string or hash_keyword, try to get the parent hash_function
             let hash_node = if matches!(node.kind(), "hash_string" | "hash_keyword") {
@@ -491,7 +427,7 @@ pub async fn handle_hover(backend: &Backend, params: HoverParams) -> Result<Opti
             if let Some(hash_node) = hash_node {
                 let text = hash_node.utf8_text(document.content.as_bytes()).unwrap();
                 if let Some(device_name) = crate::hash_utils::extract_hash_argument(text) {
-                    if let Some(device_hash) = crate::hash_utils::get_device_hash(&device_name) {
+                    if let Some(device_hash) = device_hashes.get_device_hash(&device_name) {
                         let mut parts: Vec<MarkedString> = Vec::new();
                         
                         // Show the hash function and value
@@ -510,17 +446,25 @@ pub async fn handle_hover(backend: &Backend, params: HoverParams) -> Result<Opti
                             }
                             parts.push(MarkedString::String(md_text));
                         } else if let Some(device_display_name) =
-                            crate::hash_utils::get_device_name_for_hash(device_hash)
+                            device_hashes.get_device_name_for_hash(device_hash)
                         {
                             // Fallback to display name only if no description available
                             parts.push(MarkedString::String(device_display_name.to_string()));
                         }
+                        let display_name = device_hashes.get_device_name_for_hash(device_hash)
+                            .unwrap_or(&device_name);
+                        parts.extend(batch_target_note(hash_node, &document.content, display_name));
                         
                         return Ok(Some(Hover {
                             contents: HoverContents::Array(parts),
                             range: Some(Range::from(node.range()).into()),
                         }));
                     }
+                    // Not a known device: the hash is still worth knowing for name hashes
+                    return Ok(Some(Hover {
+                        contents: HoverContents::Array(string_hash_hover("HASH", &device_name, device_hashes)),
+                        range: Some(Range::from(node.range()).into()),
+                    }));
                 }
             }
         }
@@ -603,18 +547,50 @@ pub async fn handle_hover(backend: &Backend, params: HoverParams) -> Result<Opti
                 }
             }
         }
+        "comment" => {
+            // `# log r0`: hovering the register shows its history up to the annotation
+            let Some((offset, register)) = additional_features::parse_log_directive(name) else {
+                return Ok(None);
+            };
+            let start = node.start_position().column + offset;
+            let column = position.character as usize;
+            if column < start || column >= start + register.len() {
+                return Ok(None);
+            }
+            let mut register_analyzer = additional_features::RegisterAnalyzer::new();
+            register_analyzer.analyze_register_usage(tree, &document.content, &type_data.aliases);
+            let Some(register_info) = register_analyzer.get_register_info(register) else {
+                return Ok(None);
+            };
+            let line_number = node.start_position().row as u32 + 1;
+            let mut value_parts = vec![format!("**Log** {} at line {}", register, line_number)];
+            let history: Vec<_> = register_info
+                .operation_history
+                .iter()
+                .filter(|record| record.line_number <= line_number)
+                .map(|record| format!("  • Line {}: {}", record.line_number, record.operation))
+                .collect();
+            if history.is_empty() {
+                value_parts.push("**Operation history:** No operations found".to_string());
+            } else {
+                value_parts.push("**Operation history:**".to_string());
+                value_parts.extend(history);
+            }
+            let range = tower_lsp::lsp_types::Range::new(
+                tower_lsp::lsp_types::Position::new(position.line, start as u32),
+                tower_lsp::lsp_types::Position::new(position.line, (start + register.len()) as u32),
+            );
+            return Ok(Some(Hover {
+                contents: HoverContents::Scalar(MarkedString::String(value_parts.join("\n\n"))),
+                range: Some(range),
+            }));
+        }
         "number" => {
             // Check if this number is a known device hash
             if let Ok(hash_value) = name.parse::<i32>() {
-                if let Some(device_display_name) = crate::hash_utils::get_device_name_for_hash(hash_value) {
+                if let Some(device_display_name) = device_hashes.get_device_name_for_hash(hash_value) {
                     // Try to find the prefab name from the hash value
-                    let mut prefab_name_opt = None;
-                    for (prefab, &hash) in crate::device_hashes::DEVICE_NAME_TO_HASH.entries() {
-                        if hash == hash_value {
-                            prefab_name_opt = Some(*prefab);
-                            break;
-                        }
-                    }
+                    let prefab_name_opt = device_hashes.get_prefab_name_for_hash(hash_value);
                     
                     let mut parts: Vec<MarkedString> = Vec::new();
                     parts.push(MarkedString::LanguageString(LanguageString {
@@ -647,12 +623,46 @@ pub async fn handle_hover(backend: &Backend, params: HoverParams) -> Result<Opti
                             device_display_name
                         )));
                     }
+                    parts.extend(batch_target_note(node, &document.content, device_display_name));
                     
                     return Ok(Some(Hover {
                         contents: HoverContents::Array(parts),
                         range: Some(Range::from(node.range()).into()),
                     }));
                 }
+
+                // Magic enum numbers: list members of the enum family this parameter expects
+                if let Some(instruction_node) = node.find_parent("instruction") {
+                    let (current_param, _) = get_current_parameter(
+                        instruction_node,
+                        position.character as usize,
+                        document.content.as_bytes(),
+                    );
+                    if let Some(family) =
+                        expected_enum_family(instruction_node, current_param, &document.content)
+                    {
+                        let matches: Vec<String> = instructions::all_enum_entries()
+                            .filter(|(f, _, _, value, _, _)| *f == family && *value == hash_value)
+                            .map(|(_, _, qualified, _, _, deprecated)| {
+                                if deprecated {
+                                    format!("`{}` (deprecated)", qualified)
+                                } else {
+                                    format!("`{}`", qualified)
+                                }
+                            })
+                            .collect();
+                        if !matches.is_empty() {
+                            return Ok(Some(Hover {
+                                contents: HoverContents::Scalar(MarkedString::String(format!(
+                                    "`{}` matches {}",
+                                    hash_value,
+                                    matches.join(", ")
+                                ))),
+                                range: Some(Range::from(node.range()).into()),
+                            }));
+                        }
+                    }
+                }
             }
         }
         _ => {}
@@ -660,12 +670,258 @@ pub async fn handle_hover(backend: &Backend, params: HoverParams) -> Result<Opti
     Ok(None)
 }
 
+/// For the device hash operand of a batch instruction: the instruction reaches every device of
+/// that prefab on the data network, and a load combines their values with its batch mode
+fn batch_target_note(hash_node: tree_sitter::Node, content: &str, device: &str) -> Option<MarkedString> {
+    let operand = hash_node.find_parent("operand")?;
+    let instruction = operand.parent()?;
+    let operation = instruction
+        .child_by_field_name("operation")?
+        .utf8_text(content.as_bytes())
+        .ok()?
+        .to_ascii_lowercase();
+    let (hash_index, load) = match operation.as_str() {
+        "lb" | "lbn" | "lbs" | "lbns" => (1, true),
+        "sb" | "sbn" | "sbs" => (0, false),
+        _ => return None,
+    };
+    let mut cursor = instruction.walk();
+    let operands: Vec<_> = instruction.children_by_field_name("operand", &mut cursor).collect();
+    if operands.get(hash_index) != Some(&operand) {
+        return None;
+    }
+
+    let scope = if operation.contains('n') {
+        format!("every {} whose name matches the name hash", device)
+    } else {
+        format!("every {}", device)
+    };
+    let mut note = if load {
+        format!("**Batch target:** reads {} on the data network, not a single device.", scope)
+    } else {
+        format!("**Batch target:** writes to {} on the data network at once.", scope)
+    };
+    if load {
+        // The mode is written by name (`Sum`, `LogicBatchMethod.Sum`) or by value (`1`)
+        let mode = operands.last().and_then(|mode| mode.utf8_text(content.as_bytes()).ok());
+        let mode = mode.and_then(|mode| match mode.parse::<i32>() {
+            Ok(value) => instructions::all_enum_entries()
+                .find(|(family, _, _, v, ..)| *family == "LogicBatchMethod" && *v == value)
+                .map(|(_, member, ..)| member),
+            Err(_) => mode.rsplit('.').next(),
+        });
+        if let Some((mode, aggregation)) =
+            mode.and_then(|mode| Some((mode, instructions::BATCH_MODE_NOTES.get(mode)?)))
+        {
+            note.push_str(&format!("\n\n**{}:** {}", mode, aggregation));
+        }
+    }
+    Some(MarkedString::String(note))
+}
+
+/// The user-declared meanings of `name` (define, alias, label), each with its hover content
+fn user_interpretations(
+    name: &str,
+    document: &DocumentData,
+    type_data: &TypeData,
+    device_hashes: &DeviceHashes,
+) -> Vec<(&'static str, HoverContents)> {
+    let mut interpretations = Vec::new();
+    if let Some(definition_data) = type_data.defines.get(name) {
+        interpretations.push(("define", define_hover_contents(name, definition_data, device_hashes)));
+    }
+    if let Some(definition_data) = type_data.aliases.get(name) {
+        interpretations.push(("alias", alias_hover_contents(name, definition_data, document, type_data)));
+    }
+    if let Some(definition_data) = type_data.labels.get(name) {
+        interpretations.push((
+            "label",
+            HoverContents::Scalar(MarkedString::String(format!(
+                "Label on line {}",
+                definition_data.value + 1
+            ))),
+        ));
+    }
+    interpretations
+}
+
+/// A name can mean several things at once (a define named `Setting` is also a logic type).
+/// A single meaning is shown as is; several are shown together under "As ...:" headings so the
+/// conflict is visible.
+fn merge_interpretations(
+    mut interpretations: Vec<(&'static str, HoverContents)>,
+    node: tree_sitter::Node,
+) -> Option<Hover> {
+    let range = Some(Range::from(node.range()).into());
+    if interpretations.len() <= 1 {
+        return interpretations.pop().map(|(_, contents)| Hover { contents, range });
+    }
+    let mut parts = Vec::new();
+    for (kind, contents) in interpretations {
+        parts.push(MarkedString::String(format!("**As {}:**", kind)));
+        match contents {
+            HoverContents::Array(items) => parts.extend(items),
+            HoverContents::Scalar(item) => parts.push(item),
+            HoverContents::Markup(markup) => parts.push(MarkedString::String(markup.value)),
+        }
+    }
+    Some(Hover {
+        contents: HoverContents::Array(parts),
+        range,
+    })
+}
+
+/// Hover for a define: its value, plus the resolved device hash and name when it has one
+fn define_hover_contents(
+    name: &str,
+    definition_data: &DefinitionData<DefineValue>,
+    device_hashes: &DeviceHashes,
+) -> HoverContents {
+    let mut parts: Vec<MarkedString> = vec![MarkedString::LanguageString(LanguageString {
+        language: "ic10".to_string(),
+        value: format!("define {} {}", name, definition_data.value),
+    })];
+    // A STR() define is a named string: show the string next to the hash it stands for
+    if let Some(string) = definition_data.value.string_constant() {
+        parts.push(MarkedString::LanguageString(LanguageString {
+            language: "ic10".to_string(),
+            value: format!(
+                "// string \"{}\" = {}",
+                string,
+                crate::hash_utils::compute_crc32(&string)
+            ),
+        }));
+        return HoverContents::Array(parts);
+    }
+    // A number in hex, binary or exponent notation: show it as a plain decimal
+    if let Some(value) = definition_data.value.resolved_value() {
+        if definition_data.value.to_string() != value.to_string() {
+            parts.push(MarkedString::LanguageString(LanguageString {
+                language: "ic10".to_string(),
+                value: format!("// value = {}", value),
+            }));
+        }
+    }
+    // Show the resolved numeric hash if available
+    let device_hash_value = definition_data.value.resolved_numeric();
+    if let (Some(hash), DefineValue::FunctionCall(_)) = (device_hash_value, &definition_data.value) {
+        parts.push(MarkedString::LanguageString(LanguageString {
+            language: "ic10".to_string(),
+            value: format!("// resolved hash = {}", hash),
+        }));
+    }
+    if let Some(device_name) = device_hash_value.and_then(|hash| device_hashes.get_device_name_for_hash(hash)) {
+        parts.push(MarkedString::String(device_name.to_string()));
+    }
+    HoverContents::Array(parts)
+}
+
+/// Hover for an alias: register aliases show their operation history, device aliases their pin
+fn alias_hover_contents(
+    name: &str,
+    definition_data: &DefinitionData<AliasValue>,
+    document: &DocumentData,
+    type_data: &TypeData,
+) -> HoverContents {
+    let mut hover_content = vec![MarkedString::LanguageString(LanguageString {
+        language: "ic10".to_string(),
+        value: format!("alias {} {}", name, definition_data.value),
+    })];
+
+    // Register alias: perform register analysis to get current value information
+    if let (AliasValue::Register(register_name), Some(tree)) =
+        (&definition_data.value, document.tree.as_ref())
+    {
+        let mut register_analyzer = additional_features::RegisterAnalyzer::new();
+        register_analyzer.analyze_register_usage(tree, &document.content, &type_data.aliases);
+
+        if let Some(register_info) = register_analyzer.get_register_info(name) {
+            // Add register information with simple operation history
+            let mut value_parts = vec![format!("**Register** {} ({})", name, register_name)];
+
+            if !register_info.operation_history.is_empty() {
+                value_parts.push("**Operation history:**".to_string());
+                let history_limit = 99; // Show up to 99 operations (tooltip is scrollable)
+                let start_idx = register_info.operation_history.len().saturating_sub(history_limit);
+
+                for record in &register_info.operation_history[start_idx..] {
+                    value_parts.push(format!("  • Line {}: {}", record.line_number, record.operation));
+                }
+
+                if start_idx > 0 {
+                    value_parts.push(format!("  • ... ({} earlier operations)", start_idx));
+                }
+            } else {
+                value_parts.push("**Operation history:** No operations found".to_string());
+            }
+
+            hover_content.push(MarkedString::String(value_parts.join("\n\n")));
+            return HoverContents::Array(hover_content);
+        }
+    }
+
+    // Connection-indexed device (e.g. d0:1)
+    if let AliasValue::Device(device) = &definition_data.value {
+        if let Some((pin, index)) = device.split_once(':') {
+            hover_content.push(MarkedString::String(format!(
+                "**Device** {} (network index {})",
+                pin, index
+            )));
+        }
+    }
+    HoverContents::Array(hover_content)
+}
+
+/// Work out which enum family a numeric operand stands in for.
+/// Typed parameters (logic type, slot type, batch/reagent mode) map to their enum directly;
+/// otherwise a logic type named like an enum family elsewhere in the instruction
+/// (e.g. `s d0 Color 6`) scopes the value to that family.
+fn expected_enum_family(
+    instruction_node: tree_sitter::Node,
+    param_index: usize,
+    content: &str,
+) -> Option<&'static str> {
+    use instructions::DataType;
+
+    let operation = instruction_node
+        .child_by_field_name("operation")?
+        .utf8_text(content.as_bytes())
+        .ok()?;
+    if let Some(param_type) = instructions::INSTRUCTIONS
+        .get(operation)
+        .and_then(|signature| signature.0.get(param_index))
+    {
+        for (data_type, family) in [
+            (DataType::LogicType, "LogicType"),
+            (DataType::SlotLogicType, "LogicSlotType"),
+            (DataType::BatchMode, "LogicBatchMethod"),
+            (DataType::ReagentMode, "LogicReagentMode"),
+        ] {
+            if param_type.match_type(data_type) {
+                return Some(family);
+            }
+        }
+    }
+
+    let mut cursor = instruction_node.walk();
+    let logic_types: Vec<&str> = instruction_node
+        .children_by_field_name("operand", &mut cursor)
+        .filter_map(|operand| operand.child(0))
+        .filter(|child| child.kind() == "logictype")
+        .filter_map(|child| child.utf8_text(content.as_bytes()).ok())
+        .collect();
+    instructions::all_enum_entries()
+        .map(|(family, ..)| family)
+        .find(|family| logic_types.contains(family))
+}
+
 /// Handle inlay hint requests
 pub async fn handle_inlay_hint(backend: &Backend, params: InlayHintParams) -> Result<Option<Vec<InlayHint>>> {
     let start_total = std::time::Instant::now();
     let mut ret = Vec::new();
 
     let start_lock = std::time::Instant::now();
+    let device_hashes = backend.device_hashes.read().await;
     let files = backend.files.read().await;
     eprintln!("[PERF] files.read() lock: {:?}", start_lock.elapsed());
     
@@ -697,9 +953,9 @@ pub async fn handle_inlay_hint(backend: &Backend, params: InlayHintParams) -> Re
 
         let text = node.utf8_text(document.content.as_bytes()).unwrap();
 
-        // Direct numeric device hash lookup
-        if let Ok(number) = text.parse::<i32>() {
-            if let Some(item_name) = crate::device_hashes::HASH_TO_DISPLAY_NAME.get(&number) {
+        // Direct numeric device hash lookup, in either its signed or unsigned form
+        if let Some(number) = crate::hash_utils::hash_from_number_text(text) {
+            if let Some(item_name) = device_hashes.get_device_name_for_hash(number) {
                 let Some(line_node) = node.find_parent("line") else {
                     continue;
                 };
@@ -749,11 +1005,9 @@ pub async fn handle_inlay_hint(backend: &Backend, params: InlayHintParams) -> Re
         }
         
         if let Some(device_name) = crate::hash_utils::extract_hash_argument(call_text) {
-            if let Some(hash_val) = crate::hash_utils::get_device_hash(&device_name) {
+            if let Some(hash_val) = device_hashes.get_device_hash(&device_name) {
                 // Look up the display name for this hash
-                let display_text = crate::device_hashes::HASH_TO_DISPLAY_NAME
-                    .get(&hash_val)
-                    .copied()
+                let display_text = device_hashes.get_device_name_for_hash(hash_val)
                     .unwrap_or("Unknown Device");
                 
                 let Some(line_node) = call_node.find_parent("line") else {
@@ -868,9 +1122,318 @@ pub async fn handle_inlay_hint(backend: &Backend, params: InlayHintParams) -> Re
     }
     eprintln!("[PERF] identifier queries: {:?}", start_ident.elapsed());
     
+    // `# log r0` annotations: show the register's last operation next to the directive
+    let mut cursor_comment = QueryCursor::new();
+    let mut register_analyzer = None;
+    for (cap, _) in cursor_comment.captures(query_comment(), tree.root_node(), document.content.as_bytes()) {
+        let comment_node = cap.captures[0].node;
+        let comment_text = comment_node.utf8_text(document.content.as_bytes()).unwrap();
+        let Some((_, register)) = additional_features::parse_log_directive(comment_text) else {
+            continue;
+        };
+        let analyzer = register_analyzer.get_or_insert_with(|| {
+            let mut analyzer = additional_features::RegisterAnalyzer::new();
+            analyzer.analyze_register_usage(tree, &document.content, &file_data.type_data.aliases);
+            analyzer
+        });
+        if analyzer.get_register_info(register).is_none() {
+            continue;
+        }
+        let line_number = comment_node.start_position().row as u32 + 1;
+        let label = match analyzer.last_operation_before(register, line_number) {
+            Some(record) => format!(" → line {}: {}", record.line_number, record.operation),
+            None => " → not set yet".to_string(),
+        };
+        ret.push(InlayHint {
+            position: Position::from(comment_node.range().end_point).into(),
+            label: InlayHintLabel::String(label),
+            kind: Some(InlayHintKind::TYPE),
+            text_edits: None,
+            tooltip: None,
+            padding_left: None,
+            padding_right: None,
+            data: None,
+        });
+    }
+
+    let (show_label_line_hints, show_cycle_hints) = {
+        let config = backend.config.read().await;
+        (config.show_label_line_hints, config.show_cycle_hints)
+    };
+    if show_label_line_hints {
+        ret.extend(label_line_hints(tree, &document.content, &file_data.type_data));
+    }
+    if show_cycle_hints {
+        ret.extend(label_block_size_hints(tree, &document.content, &file_data.type_data));
+    }
+
     // NOTE: Instruction parameter hints are handled client-side for instant display.
     // The LSP only provides device hash hints and enum value hints.
 
     eprintln!("[PERF] TOTAL inlay_hint: {:?} (hints: {})", start_total.elapsed(), ret.len());
     Ok(Some(ret))
 }
+
+/// ` [N lines]` at the end of each label's block: how many instructions run straight through
+/// from the label to the next one, against the 128 lines the IC executes per tick. Branch
+/// costs are not modeled.
+pub(crate) fn label_block_size_hints(tree: &tree_sitter::Tree, content: &str, type_data: &TypeData) -> Vec<InlayHint> {
+    let mut label_rows: Vec<u32> = type_data.labels.values().map(|label| label.range.0.start.line).collect();
+    label_rows.sort_unstable();
+    label_rows.dedup();
+
+    let mut cursor = QueryCursor::new();
+    let query = Query::new(tree_sitter_ic10::language(), "(instruction)@x").unwrap();
+    let instructions: Vec<_> = cursor
+        .captures(&query, tree.root_node(), content.as_bytes())
+        .map(|(capture, _)| capture.captures[0].node)
+        .filter(|instruction| crate::tree_utils::mnemonic_label(*instruction, content.as_bytes()).is_none())
+        .collect();
+
+    let mut hints = Vec::new();
+    for (index, start) in label_rows.iter().enumerate() {
+        let end = label_rows.get(index + 1).copied().unwrap_or(u32::MAX);
+        let block: Vec<_> = instructions
+            .iter()
+            .filter(|instruction| (*start..end).contains(&(instruction.start_position().row as u32)))
+            .collect();
+        let Some(last) = block.last() else {
+            continue;
+        };
+        let line_node = last.find_parent("line");
+        let position = match line_node.as_ref().and_then(|line| line.find_newline(content.as_bytes())) {
+            Some(newline) => Position::from(newline.range().start_point).into(),
+            None => Position::from(last.end_position()).into(),
+        };
+        let count = block.len();
+        hints.push(InlayHint {
+            position,
+            label: InlayHintLabel::String(format!(" [{} line{}]", count, if count == 1 { "" } else { "s" })),
+            kind: Some(InlayHintKind::TYPE),
+            text_edits: None,
+            tooltip: None,
+            padding_left: None,
+            padding_right: None,
+            data: None,
+        });
+    }
+    hints
+}
+
+/// ` (line N)` at the end of each label's line: the 1-based line the label sits on, which is
+/// also one more than the value a relative branch reads from it
+pub(crate) fn label_line_hints(tree: &tree_sitter::Tree, content: &str, type_data: &TypeData) -> Vec<InlayHint> {
+    let mut labels: Vec<_> = type_data.labels.values().map(|label| label.range).collect();
+    labels.sort_by_key(|range| (range.0.start.line, range.0.start.character));
+    labels
+        .into_iter()
+        .map(|range| {
+            let start = tree_sitter::Point::from(Position::from(range.0.start));
+            let line_node = tree
+                .root_node()
+                .named_descendant_for_point_range(start, start)
+                .and_then(|node| node.find_parent("line"));
+            let endpos = match line_node.as_ref().and_then(|line| line.find_newline(content.as_bytes())) {
+                Some(newline) => Position::from(newline.range().start_point).into(),
+                None => range.0.end,
+            };
+            InlayHint {
+                position: endpos,
+                label: InlayHintLabel::String(format!(" (line {})", range.0.start.line + 1)),
+                kind: Some(InlayHintKind::TYPE),
+                text_edits: None,
+                tooltip: None,
+                padding_left: None,
+                padding_right: None,
+                data: None,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn family_at(src: &str, param: usize) -> Option<&'static str> {
+        let mut parser = tree_sitter::Parser::new();
+        parser.set_language(tree_sitter_ic10::language()).unwrap();
+        let tree = parser.parse(src, None).unwrap();
+        let root = tree.root_node();
+        let instruction = root.query("(instruction)@x", src.as_bytes()).unwrap();
+        expected_enum_family(instruction, param, src)
+    }
+
+    /// The hover contents at `line`/`character` of `src`, as JSON text
+    fn hover_text(src: &str, type_data: &TypeData, line: u32, character: u32) -> Option<String> {
+        let mut parser = tree_sitter::Parser::new();
+        parser.set_language(tree_sitter_ic10::language()).unwrap();
+        let document = DocumentData {
+            url: tower_lsp::lsp_types::Url::parse("file:///test.ic10").unwrap(),
+            tree: parser.parse(src, None),
+            content: src.to_string(),
+            parser,
+        };
+        let position = tower_lsp::lsp_types::Position::new(line, character);
+        let hover = hover_at(&document, type_data, position, &DeviceHashes::default(), &CustomDocs::default());
+        hover.unwrap().map(|hover| serde_json::to_string(&hover.contents).unwrap())
+    }
+
+    #[test]
+    fn hover_at_without_a_server() {
+        let src = "move r0 5 # log r0\nlb r1 -1252983604 Temperature Average\n";
+        let type_data = TypeData::default();
+        assert!(hover_text(src, &type_data, 0, 17).unwrap().contains("Line 1: move r0 5"));
+        assert!(hover_text(src, &type_data, 1, 8).unwrap().contains("Gas Sensor"));
+        assert!(hover_text(src, &type_data, 0, 12).is_none());
+    }
+
+    #[test]
+    fn ambiguous_name_shows_every_meaning() {
+        let src = "define Setting 5\nmove r0 Setting\nmove r1 Temperature\n";
+        let mut type_data = TypeData::default();
+        type_data.defines.insert(
+            "Setting".to_string(),
+            DefinitionData::new(
+                Range(tower_lsp::lsp_types::Range::default()),
+                DefineValue::from("5".to_string()),
+            ),
+        );
+        let setting = hover_text(src, &type_data, 1, 10).unwrap();
+        assert!(setting.contains("**As define:**") && setting.contains("**As logic type:**"));
+        assert!(setting.find("define Setting 5") < setting.find("logicType"));
+        assert!(!hover_text(src, &type_data, 2, 10).unwrap().contains("**As "));
+    }
+
+    #[test]
+    fn string_define_shows_string_and_hash() {
+        let src = "define MSG STR(\"hello\")\nmove r0 MSG\n";
+        let mut type_data = TypeData::default();
+        type_data.defines.insert(
+            "MSG".to_string(),
+            DefinitionData::new(
+                Range(tower_lsp::lsp_types::Range::default()),
+                DefineValue::from("STR(\"hello\")".to_string()),
+            ),
+        );
+        let text = hover_text(src, &type_data, 1, 9).unwrap();
+        let hash = crate::hash_utils::compute_crc32("hello");
+        assert!(text.contains(&format!("// string \\\"hello\\\" = {}", hash)), "{}", text);
+        assert_eq!(type_data.defines["MSG"].value.resolved_numeric(), Some(hash));
+    }
+
+    #[test]
+    fn enum_family_from_context() {
+        assert_eq!(family_at("s d0 Color 6\n", 2), Some("Color"));
+        assert_eq!(family_at("lb r0 1 Power 1\n", 3), Some("LogicBatchMethod"));
+        assert_eq!(family_at("move r0 6\n", 1), None);
+    }
+
+    #[test]
+    fn labels_get_their_line_number() {
+        let src = "yield\nloop: # main\nj loop\nend:";
+        let mut parser = tree_sitter::Parser::new();
+        parser.set_language(tree_sitter_ic10::language()).unwrap();
+        let tree = parser.parse(src, None).unwrap();
+        let mut type_data = TypeData::default();
+        for (name, line, end) in [("loop", 1, 4), ("end", 3, 3)] {
+            let range = tower_lsp::lsp_types::Range::new(
+                tower_lsp::lsp_types::Position::new(line, 0),
+                tower_lsp::lsp_types::Position::new(line, end),
+            );
+            type_data.labels.insert(name.to_string(), DefinitionData::new(Range(range), line as u8));
+        }
+        let hints: Vec<_> = label_line_hints(&tree, src, &type_data)
+            .into_iter()
+            .map(|hint| match hint.label {
+                InlayHintLabel::String(label) => (hint.position.line, hint.position.character, label),
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(
+            hints,
+            vec![(1, 12, " (line 2)".to_string()), (3, 4, " (line 4)".to_string())]
+        );
+    }
+
+    #[test]
+    fn label_blocks_count_their_instructions() {
+        let src = "alias pump d0\nloop:\ns pump On 1\nyield # wait\nj loop\nidle:\nend:\nadd:\nmove r0 1\n";
+        let mut parser = tree_sitter::Parser::new();
+        parser.set_language(tree_sitter_ic10::language()).unwrap();
+        let tree = parser.parse(src, None).unwrap();
+        let mut diagnostics = Vec::new();
+        let type_data = crate::lsp_diagnostics::collect_type_data(&tree, src, &mut diagnostics);
+        let hints: Vec<_> = label_block_size_hints(&tree, src, &type_data)
+            .into_iter()
+            .map(|hint| match hint.label {
+                InlayHintLabel::String(label) => (hint.position.line, hint.position.character, label),
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(
+            hints,
+            vec![(4, 6, " [3 lines]".to_string()), (8, 9, " [1 line]".to_string())]
+        );
+    }
+
+    #[test]
+    fn batch_mode_hover_explains_aggregation() {
+        let src = "lb r0 -1252983604 Temperature Average\n";
+        let text = hover_text(src, &TypeData::default(), 0, 31).unwrap();
+        assert!(text.contains("mean of the value across all matching devices"), "{text}");
+    }
+
+    #[test]
+    fn batch_hash_hover_covers_every_device() {
+        let src = "lb r0 HASH(\"StructureVolumePump\") On 1\nsbn -321403609 HASH(\"Pump\") On 1\nlbn r0 1 HASH(\"StructureVolumePump\") On Sum\n";
+        let text = |line, character| hover_text(src, &TypeData::default(), line, character).unwrap();
+        let load = text(0, 14);
+        assert!(load.contains("**Batch target:** reads every"), "{load}");
+        assert!(load.contains("**Sum:** The values of all matching devices added together"), "{load}");
+        let store = text(1, 6);
+        assert!(store.contains("writes to every") && store.contains("whose name matches"), "{store}");
+        // A HASH in the name hash slot names devices, it doesn't pick a prefab
+        assert!(!text(2, 14).contains("Batch target"));
+    }
+
+    #[test]
+    fn sleep_hover_gives_its_unit() {
+        let text = hover_text("sleep 2\n", &TypeData::default(), 0, 2).unwrap();
+        assert!(text.contains("Duration in seconds"), "{text}");
+    }
+
+    #[test]
+    fn any_string_shows_its_hash() {
+        let src = "sbn 1 STR(\"Bar\") On 1\nlb r0 \"Foo\" On Sum\nlb r0 HASH(\"Not A Device\") On Sum\ndefine P STR(\"StructureVolumePump\")\n";
+        let text = |line, character| hover_text(src, &TypeData::default(), line, character).unwrap();
+        let crc = crate::hash_utils::compute_crc32;
+        assert!(text(0, 12).contains(&format!("STR(\\\"Bar\\\") = {}", crc("Bar"))));
+        assert!(text(0, 7).contains(&crc("Bar").to_string()));
+        assert!(text(1, 8).contains(&format!("STR(\\\"Foo\\\") = {}", crc("Foo"))));
+        assert!(text(2, 14).contains(&crc("Not A Device").to_string()));
+        let pump = text(3, 17);
+        assert!(pump.contains("-321403609"));
+        assert!(pump.contains("Also the prefab hash"));
+    }
+
+    #[test]
+    fn numeric_defines_show_their_decimal_value() {
+        let hover_text = |value: &str| {
+            let definition = DefinitionData::new(
+                Range(tower_lsp::lsp_types::Range::default()),
+                DefineValue::from(value.to_string()),
+            );
+            serde_json::to_string(&define_hover_contents("K", &definition, &DeviceHashes::default())).unwrap()
+        };
+        for (text, decimal) in [("$FF", 255.0), ("0x1_0", 16.0), ("%1010", 10.0), ("1.5e3", 1500.0), ("-$10", -16.0)] {
+            assert_eq!(DefineValue::from(text.to_string()).resolved_value(), Some(decimal), "{}", text);
+            assert!(hover_text(text).contains(&format!("// value = {}", decimal)), "{}", text);
+        }
+        assert_eq!(DefineValue::from("$FF".to_string()).resolved_numeric(), Some(255));
+        assert_eq!(DefineValue::from("0.5".to_string()).resolved_numeric(), None);
+        assert!(matches!(DefineValue::from("$G".to_string()), DefineValue::Identifier(_)));
+        // Already decimal: nothing to add
+        assert!(!hover_text("0.5").contains("// value"));
+    }
+}
@@ -89,6 +89,12 @@ mod lsp_hover;
 /// LSP handlers for semantic tokens, symbols, signature help, code actions, goto definition
 mod lsp_handlers;
 
+/// SARIF export of diagnostics for the CLI
+mod sarif;
+
+/// Symbol table export for the CLI
+mod symbols;
+
 // Re-export commonly used items
 use types::{Position, Range};
 use document::*;
@@ -103,6 +109,109 @@ const LINT_ABSOLUTE_JUMP: &str = "absolute-jump";
 /// Diagnostic code for relative branch to label (should use absolute branch)
 const LINT_RELATIVE_BRANCH_TO_LABEL: &str = "relative-branch-to-label";
 
+/// Diagnostic code for a single store to a device that is otherwise only read
+const LINT_LONE_DEVICE_WRITE: &str = "lone-device-write";
+
+/// Diagnostic code for a constant stack address outside the stack
+const LINT_STACK_ADDRESS_RANGE: &str = "stack-address-range";
+
+/// Diagnostic code for `jal` chains nested deeper than `max_call_depth`, or recursive
+const LINT_DEEP_CALL_NESTING: &str = "deep-call-nesting";
+
+/// Diagnostic code for the note that register and control-flow analysis were skipped on a file
+/// longer than `analysis_line_budget`
+const LINT_DEEP_ANALYSIS_SKIPPED: &str = "deep-analysis-skipped";
+
+/// Diagnostic code for an `alias`/`define` whose name is never used
+const LINT_UNUSED_DEFINITION: &str = "unused-definition";
+
+/// Diagnostic code for a label nothing branches to or references
+const LINT_UNUSED_LABEL: &str = "unused-label";
+
+/// Diagnostic code for instructions after an unconditional jump that no label makes reachable
+const LINT_UNREACHABLE_CODE: &str = "unreachable-code";
+
+/// Diagnostic code for device-control logic types read/written on the IC housing (`db`)
+const LINT_ACCIDENTAL_DB_ACCESS: &str = "accidental-db-access";
+
+/// Diagnostic code for an alias name used on a line above its declaration
+const LINT_ALIAS_USED_BEFORE_DECLARATION: &str = "alias-used-before-declaration";
+
+/// Diagnostic code for code placed above an `alias`/`define` it may expect to be in effect
+const LINT_DECLARATIONS_ARE_POSITIONAL: &str = "declarations-are-positional";
+
+/// Diagnostic code for a register written with leading zeros (`r07`)
+const LINT_MALFORMED_REGISTER: &str = "malformed-register";
+
+/// Diagnostic code for an enum member passed as a device operand
+const LINT_ENUM_WHERE_DEVICE_EXPECTED: &str = "enum-where-device-expected";
+
+/// Diagnostic code for a define (a number) passed as a device operand
+const LINT_NUMBER_DEFINE_AS_DEVICE: &str = "number-define-as-device";
+
+/// Diagnostic code for a device, logic type or other non-number as the name hash of `lbn`/`sbn`
+const LINT_INVALID_NAME_HASH: &str = "invalid-name-hash";
+
+/// Diagnostic code for a define or alias named like an enum family
+const LINT_SHADOWS_ENUM_FAMILY: &str = "shadows-enum-family";
+
+/// Diagnostic code for a define, alias or label name the game's identifier rules reject
+const LINT_INVALID_SYMBOL_NAME: &str = "invalid-symbol-name";
+
+/// Diagnostic code for an instruction missing from the `allowedInstructions` setting
+const LINT_INSTRUCTION_NOT_ALLOWED: &str = "instruction-not-allowed";
+
+/// Diagnostic code for a constant shift amount outside 0-63
+const LINT_SHIFT_AMOUNT_RANGE: &str = "shift-amount-range";
+
+/// Diagnostic code for a constant `ext`/`ins` bit field outside the 53 usable bits
+const LINT_BIT_FIELD_RANGE: &str = "bit-field-range";
+
+/// Diagnostic code for a constant `sleep` long enough to leave the script unresponsive
+const LINT_LONG_SLEEP: &str = "long-sleep";
+
+/// Diagnostic code for `s`/`ss` written value first, in load order
+const LINT_STORE_OPERAND_ORDER: &str = "store-operand-order";
+
+/// Diagnostic code for a define whose value is more than one token
+const LINT_MULTITOKEN_DEFINE: &str = "multitoken-define";
+
+/// Diagnostic code for an unknown mnemonic
+const LINT_INVALID_INSTRUCTION: &str = "invalid-instruction";
+
+/// Diagnostic code for a `define` whose HASH("...") names no known device
+const LINT_UNKNOWN_DEFINE_HASH: &str = "unknown-define-hash";
+
+/// Diagnostic code for a `# region` without its `# endregion`, or the reverse
+const LINT_UNBALANCED_REGION: &str = "unbalanced-region";
+
+/// Diagnostic code for an instruction that provably leaves its destination unchanged
+const LINT_NO_OP_INSTRUCTION: &str = "no-op-instruction";
+
+/// Diagnostic code for a number or device where an instruction writes its result
+const LINT_INVALID_DESTINATION: &str = "invalid-destination";
+
+/// Diagnostic code for an infix operator (`r0=5`) where IC10 needs an instruction
+const LINT_UNSUPPORTED_OPERATOR: &str = "unsupported-operator";
+
+/// Diagnostic code for a document that does not end with a newline
+const LINT_MISSING_TRAILING_NEWLINE: &str = "missing-trailing-newline";
+
+/// Diagnostic code for a label whose name is also an instruction mnemonic
+const LINT_LABEL_SHADOWS_INSTRUCTION: &str = "label-shadows-instruction";
+
+/// Diagnostic code for a `move` that can be folded into the arithmetic instruction after it
+const LINT_FUSIBLE_MOVE: &str = "fusible-move";
+
+/// Diagnostic code for a member of an enum family the game has deprecated as a whole
+const LINT_DEPRECATED_ENUM_FAMILY: &str = "deprecated-enum-family";
+
+/// Diagnostic code for an unknown identifier that looks like a define/alias name glued to a number
+const LINT_POSSIBLE_MISSING_SPACE: &str = "possible-missing-space";
+
+/// Diagnostic code for a script with nothing but declarations, labels and comments
+const LINT_NO_EXECUTABLE_INSTRUCTIONS: &str = "no-executable-instructions";
+
 /// Parameters that only accept Name (used in diagnostics)
 pub(crate) const NAME_ONLY: [instructions::DataType; 1] = [instructions::DataType::Name];
 
@@ -126,6 +235,16 @@ use tokio::{
     sync::RwLock,
 };
 use tower_lsp::{async_trait, jsonrpc::Result, lsp_types::*, Client};
+
+/// Sent when `setDiagnostics` changes the runtime flag, so the client can persist it and
+/// pass it back as the `diagnosticsEnabled` init option next session
+enum DiagnosticsStateChanged {}
+
+impl notification::Notification for DiagnosticsStateChanged {
+    type Params = Value;
+    const METHOD: &'static str = "ic10/diagnosticsStateChanged";
+}
+
 struct Backend {
     client: Client,
     files: Arc<RwLock<HashMap<Url, FileData>>>,
@@ -140,6 +259,12 @@ struct Backend {
     pending_diagnostics: Arc<tokio::sync::Mutex<HashMap<Url, tokio::task::JoinHandle<()>>>>,
     // Cache: Store diagnostics by content hash (DashMap is lock-free concurrent)
     diagnostic_cache: Arc<dashmap::DashMap<String, Vec<Diagnostic>>>,
+    // Device prefab names used in HASH("...") per open file, for completion ranking
+    device_usage: Arc<dashmap::DashMap<Url, std::collections::HashSet<String>>>,
+    // Device hash tables with the runtime and custom device overlays applied
+    device_hashes: Arc<RwLock<hash_utils::DeviceHashes>>,
+    // Docs from `customDocsPath`, shown ahead of the built-in ones
+    custom_docs: Arc<RwLock<tooltip_documentation::CustomDocs>>,
 }
 
 // Constants for performance tuning
@@ -157,6 +282,10 @@ impl LanguageServer for Backend {
         if let Some(init_options) = params.initialization_options {
             self.client.log_message(MessageType::INFO, format!("Init options: {}", serde_json::to_string_pretty(&init_options).unwrap_or_else(|_| "serialize failed".to_string()))).await;
             
+            if let Some(enabled) = init_options.get("diagnosticsEnabled").and_then(Value::as_bool) {
+                *self.diagnostics_enabled.write().await = enabled;
+            }
+
             let mut config = self.config.write().await;
             
             if let Some(warnings) = init_options.get("warnings").and_then(Value::as_object) {
@@ -197,14 +326,114 @@ impl LanguageServer for Backend {
                 .get("enableControlFlowAnalysis")
                 .and_then(Value::as_bool)
                 .unwrap_or(config.enable_control_flow_analysis);
+
+            config.max_call_depth = init_options
+                .get("maxCallDepth")
+                .and_then(Value::as_u64)
+                .map(|x| x as usize)
+                .unwrap_or(config.max_call_depth);
+
+            config.analysis_line_budget = init_options
+                .get("analysisLineBudget")
+                .and_then(Value::as_u64)
+                .map(|x| x as usize)
+                .unwrap_or(config.analysis_line_budget);
+
+            config.invalid_instruction_severity = match init_options
+                .get("invalidInstructionSeverity")
+                .and_then(Value::as_str)
+            {
+                Some("warning") => DiagnosticSeverity::WARNING,
+                Some("error") => DiagnosticSeverity::ERROR,
+                _ => config.invalid_instruction_severity,
+            };
+
+            config.show_label_line_hints = init_options
+                .get("showLabelLineHints")
+                .and_then(Value::as_bool)
+                .unwrap_or(config.show_label_line_hints);
+
+            config.show_cycle_hints = init_options
+                .get("showCycleHints")
+                .and_then(Value::as_bool)
+                .unwrap_or(config.show_cycle_hints);
             
             config.suppress_register_warnings = init_options
                 .get("suppressRegisterWarnings")
                 .and_then(Value::as_bool)
                 .unwrap_or(config.suppress_register_warnings);
+
+            config.warn_lone_device_write = init_options
+                .get("warnLoneDeviceWrite")
+                .and_then(Value::as_bool)
+                .unwrap_or(config.warn_lone_device_write);
+
+            config.warn_accidental_db_access = init_options
+                .get("warnAccidentalDbAccess")
+                .and_then(Value::as_bool)
+                .unwrap_or(config.warn_accidental_db_access);
+
+            config.warn_logictype_roundtrip = init_options
+                .get("warnLogicTypeRoundtrip")
+                .and_then(Value::as_bool)
+                .unwrap_or(config.warn_logictype_roundtrip);
+
+            config.diagnostics_on_save_only = init_options
+                .get("diagnosticsOnSaveOnly")
+                .and_then(Value::as_bool)
+                .unwrap_or(config.diagnostics_on_save_only);
+
+            config.verbose_completion_detail = init_options
+                .get("verboseCompletionDetail")
+                .and_then(Value::as_bool)
+                .unwrap_or(config.verbose_completion_detail);
+
+            config.enums_require_prefix = init_options
+                .get("enumsRequirePrefix")
+                .and_then(Value::as_bool)
+                .unwrap_or(config.enums_require_prefix);
+
+            if let Some(path) = init_options.get("customDocsPath").and_then(Value::as_str) {
+                config.custom_docs_path = (!path.is_empty()).then(|| path.to_string());
+            }
+
+            if let Some(devices) = init_options.get("customDevices").and_then(CustomDevice::list_from_json) {
+                config.custom_devices = devices;
+            }
+
+            if let Some(allowed) = init_options.get("allowedInstructions") {
+                config.allowed_instructions = allowed_instructions_from_json(allowed);
+            }
+
+            if let Some(categories) = init_options.get("completionCategories").and_then(Value::as_object) {
+                let current = &mut config.completion_categories;
+                for (key, flag) in [
+                    ("enums", &mut current.enums),
+                    ("defines", &mut current.defines),
+                    ("aliases", &mut current.aliases),
+                    ("labels", &mut current.labels),
+                    ("registers", &mut current.registers),
+                    ("devices", &mut current.devices),
+                ] {
+                    *flag = categories.get(key).and_then(Value::as_bool).unwrap_or(*flag);
+                }
+            }
+
+            if let Some(naming) = init_options.get("aliasNaming").and_then(Value::as_object) {
+                for template in config.alias_naming.update_from_json(naming) {
+                    self.client
+                        .log_message(
+                            MessageType::WARNING,
+                            format!("Ignoring alias naming template '{}': it needs {{n}} once and must form a valid name", template),
+                        )
+                        .await;
+                }
+            }
             
             self.client.log_message(MessageType::INFO, format!("Initial config - suppress_hash_diagnostics: {}", config.suppress_hash_diagnostics)).await;
         }
+        self.load_custom_docs().await;
+        self.load_custom_devices().await;
         
         let mut utf8_supported = false;
         if let Some(encodings) = params
@@ -236,13 +465,29 @@ impl LanguageServer for Backend {
             .await;
         Ok(InitializeResult {
             capabilities: ServerCapabilities {
-                text_document_sync: Some(TextDocumentSyncCapability::Kind(
-                    TextDocumentSyncKind::FULL,
+                text_document_sync: Some(TextDocumentSyncCapability::Options(
+                    TextDocumentSyncOptions {
+                        open_close: Some(true),
+                        change: Some(TextDocumentSyncKind::FULL),
+                        save: Some(TextDocumentSyncSaveOptions::Supported(true)),
+                        ..Default::default()
+                    },
                 )),
                 execute_command_provider: Some(ExecuteCommandOptions {
                     commands: vec![
                         "setDiagnostics".to_string(),
                         "ic10.setHashDiagnostics".to_string(),
+                        "ic10.validateSnippet".to_string(),
+                        "ic10.suggestDefinitions".to_string(),
+                        "ic10.insertSummaryHeader".to_string(),
+                        "ic10.hoverAt".to_string(),
+                        "ic10.analyzeAt".to_string(),
+                        "ic10.deviceDocument".to_string(),
+                        "ic10.controlFlowGraph".to_string(),
+                        "ic10.duplicateLine".to_string(),
+                        "ic10.moveLine".to_string(),
+                        "ic10.referencedDevices".to_string(),
+                        "ic10.convertBranchesToLabels".to_string(),
                     ],
                     work_done_progress_options: WorkDoneProgressOptions {
                         work_done_progress: None,
@@ -251,6 +496,9 @@ impl LanguageServer for Backend {
                 inlay_hint_provider: Some(OneOf::Left(true)),
                 hover_provider: Some(HoverProviderCapability::Simple(true)),
                 definition_provider: Some(OneOf::Left(true)),
+                rename_provider: Some(OneOf::Left(true)),
+                references_provider: Some(OneOf::Left(true)),
+                document_highlight_provider: Some(OneOf::Left(true)),
                 code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
                 signature_help_provider: Some(SignatureHelpOptions {
                     trigger_characters: Some(vec![" ".to_string()]),
@@ -259,6 +507,14 @@ impl LanguageServer for Backend {
                 }),
                 position_encoding: utf8_supported.then_some(PositionEncodingKind::UTF8),
                 document_symbol_provider: Some(OneOf::Left(true)),
+                workspace_symbol_provider: Some(OneOf::Left(true)),
+                document_formatting_provider: Some(OneOf::Left(true)),
+                selection_range_provider: Some(SelectionRangeProviderCapability::Simple(true)),
+                folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
+                document_on_type_formatting_provider: Some(DocumentOnTypeFormattingOptions {
+                    first_trigger_character: "\n".to_string(),
+                    more_trigger_character: None,
+                }),
                 completion_provider: Some(CompletionOptions {
                     resolve_provider: Some(false),
                     trigger_characters: Some(vec![" ".to_string(), "\"".to_string()]),
@@ -324,6 +580,11 @@ impl LanguageServer for Backend {
                         let mut flag = self.diagnostics_enabled.write().await;
                         *flag = enabled;
                     }
+                    self.client
+                        .send_notification::<DiagnosticsStateChanged>(
+                            serde_json::json!({ "enabled": enabled }),
+                        )
+                        .await;
                     // re-run or clear diagnostics for all open documents
                     let uris = {
                         let files = self.files.read().await;
@@ -365,6 +626,339 @@ impl LanguageServer for Backend {
                     self.client.show_message(MessageType::INFO, message).await;
                 }
             }
+            "ic10.validateSnippet" => {
+                // Validate a transient buffer (not an open textDocument) and return its diagnostics
+                let Some(source) = params.arguments.first().and_then(Value::as_str) else {
+                    return Err(tower_lsp::jsonrpc::Error::invalid_params(
+                        "ic10.validateSnippet expects the source text as its first argument",
+                    ));
+                };
+                let config = self.config.read().await.clone();
+                let device_hashes = self.device_hashes.read().await;
+                let diagnostics = lsp_diagnostics::compute_diagnostics_for_text_with_hashes(
+                    source,
+                    &config,
+                    &device_hashes,
+                );
+                return Ok(Some(serde_json::to_value(diagnostics).unwrap_or(Value::Null)));
+            }
+            "ic10.suggestDefinitions" => {
+                // Return a suggested alias/define header for the document; the client inserts it
+                let Some(uri) = params
+                    .arguments
+                    .first()
+                    .and_then(Value::as_str)
+                    .and_then(|uri| Url::parse(uri).ok())
+                else {
+                    return Err(tower_lsp::jsonrpc::Error::invalid_params(
+                        "ic10.suggestDefinitions expects a document URI as its first argument",
+                    ));
+                };
+                let naming = self.config.read().await.alias_naming.clone();
+                let device_hashes = self.device_hashes.read().await;
+                let files = self.files.read().await;
+                let Some(file_data) = files.get(&uri) else {
+                    return Ok(None);
+                };
+                let Some(ref tree) = file_data.document_data.tree else {
+                    return Ok(None);
+                };
+                let header = lsp_handlers::suggest_definitions(
+                    tree,
+                    &file_data.document_data.content,
+                    &file_data.type_data,
+                    &naming,
+                    &device_hashes,
+                );
+                return Ok(Some(Value::String(header)));
+            }
+            "ic10.hoverAt" => {
+                // Headless hover: same content the hover request would return, as JSON
+                let (Some(uri), Some(line), Some(character)) = (
+                    params
+                        .arguments
+                        .first()
+                        .and_then(Value::as_str)
+                        .and_then(|uri| Url::parse(uri).ok()),
+                    params.arguments.get(1).and_then(Value::as_u64),
+                    params.arguments.get(2).and_then(Value::as_u64),
+                ) else {
+                    return Err(tower_lsp::jsonrpc::Error::invalid_params(
+                        "ic10.hoverAt expects a document URI, a line and a character",
+                    ));
+                };
+                let device_hashes = self.device_hashes.read().await;
+                let custom_docs = self.custom_docs.read().await;
+                let files = self.files.read().await;
+                let Some(file_data) = files.get(&uri) else {
+                    return Ok(None);
+                };
+                let hover = lsp_hover::hover_at(
+                    &file_data.document_data,
+                    &file_data.type_data,
+                    tower_lsp::lsp_types::Position::new(line as u32, character as u32),
+                    &device_hashes,
+                    &custom_docs,
+                )?;
+                return Ok(hover.and_then(|hover| serde_json::to_value(hover).ok()));
+            }
+            "ic10.analyzeAt" => {
+                // Hover, completion labels and signature help in one round-trip
+                let (Some(uri), Some(line), Some(character)) = (
+                    params
+                        .arguments
+                        .first()
+                        .and_then(Value::as_str)
+                        .and_then(|uri| Url::parse(uri).ok()),
+                    params.arguments.get(1).and_then(Value::as_u64),
+                    params.arguments.get(2).and_then(Value::as_u64),
+                ) else {
+                    return Err(tower_lsp::jsonrpc::Error::invalid_params(
+                        "ic10.analyzeAt expects a document URI, a line and a character",
+                    ));
+                };
+                let config = self.config.read().await.clone();
+                let device_hashes = self.device_hashes.read().await;
+                let custom_docs = self.custom_docs.read().await;
+                let files = self.files.read().await;
+                let Some(file_data) = files.get(&uri) else {
+                    return Ok(None);
+                };
+                return lsp_handlers::analyze_at(
+                    &file_data.document_data,
+                    &file_data.type_data,
+                    tower_lsp::lsp_types::Position::new(line as u32, character as u32),
+                    &config,
+                    &device_hashes,
+                    &custom_docs,
+                )
+                .map(Some);
+            }
+            "ic10.deviceDocument" => {
+                // Content behind the `ic10-device:` URIs goto-definition returns for hashes
+                let Some(uri) = params
+                    .arguments
+                    .first()
+                    .and_then(Value::as_str)
+                    .and_then(|uri| Url::parse(uri).ok())
+                else {
+                    return Err(tower_lsp::jsonrpc::Error::invalid_params(
+                        "ic10.deviceDocument expects an ic10-device URI as its first argument",
+                    ));
+                };
+                let device_hashes = self.device_hashes.read().await;
+                return Ok(lsp_handlers::device_document(&uri, &device_hashes).map(Value::String));
+            }
+            "ic10.controlFlowGraph" => {
+                // Basic blocks and the edges between them, for an external viewer; lines are 0-based
+                let Some(uri) = params
+                    .arguments
+                    .first()
+                    .and_then(Value::as_str)
+                    .and_then(|uri| Url::parse(uri).ok())
+                else {
+                    return Err(tower_lsp::jsonrpc::Error::invalid_params(
+                        "ic10.controlFlowGraph expects a document URI as its first argument",
+                    ));
+                };
+                let files = self.files.read().await;
+                let Some(file_data) = files.get(&uri) else {
+                    return Ok(None);
+                };
+                let Some(ref tree) = file_data.document_data.tree else {
+                    return Ok(None);
+                };
+                let (blocks, edges) = additional_features::control_flow_graph(
+                    tree,
+                    &file_data.document_data.content,
+                    &file_data.type_data.labels,
+                );
+                let nodes: Vec<Value> = blocks
+                    .iter()
+                    .enumerate()
+                    .map(|(id, block)| {
+                        serde_json::json!({
+                            "id": id,
+                            "label": block.label,
+                            "startLine": block.start_line,
+                            "endLine": block.end_line,
+                            "terminator": block.terminator.as_ref().map(|(line, instruction)| {
+                                serde_json::json!({ "line": line, "instruction": instruction })
+                            }),
+                        })
+                    })
+                    .collect();
+                let edges: Vec<Value> = edges
+                    .iter()
+                    .map(|edge| serde_json::json!({ "from": edge.from, "to": edge.to, "kind": edge.kind }))
+                    .collect();
+                return Ok(Some(serde_json::json!({ "nodes": nodes, "edges": edges })));
+            }
+            "ic10.referencedDevices" => {
+                // What the script controls: each device it loads from or stores to
+                let Some(uri) = params
+                    .arguments
+                    .first()
+                    .and_then(Value::as_str)
+                    .and_then(|uri| Url::parse(uri).ok())
+                else {
+                    return Err(tower_lsp::jsonrpc::Error::invalid_params(
+                        "ic10.referencedDevices expects a document URI as its first argument",
+                    ));
+                };
+                let device_hashes = self.device_hashes.read().await;
+                let files = self.files.read().await;
+                let Some(file_data) = files.get(&uri) else {
+                    return Ok(None);
+                };
+                let Some(ref tree) = file_data.document_data.tree else {
+                    return Ok(None);
+                };
+                let devices: Vec<Value> = additional_features::referenced_devices(
+                    tree,
+                    &file_data.document_data.content,
+                    &file_data.type_data.aliases,
+                    &file_data.type_data.defines,
+                    &device_hashes,
+                )
+                .iter()
+                .map(|device| {
+                    let access = match (device.read, device.written) {
+                        (true, true) => "both",
+                        (false, true) => "write",
+                        _ => "read",
+                    };
+                    serde_json::json!({
+                        "name": device.name,
+                        "displayName": device.display_name,
+                        "access": access,
+                    })
+                })
+                .collect();
+                return Ok(Some(Value::Array(devices)));
+            }
+            "ic10.duplicateLine" | "ic10.moveLine" => {
+                // Whole-line editing on the parse tree's `line` nodes; the edit is returned, not applied
+                let (Some(uri), Some(line)) = (
+                    params
+                        .arguments
+                        .first()
+                        .and_then(Value::as_str)
+                        .and_then(|uri| Url::parse(uri).ok()),
+                    params.arguments.get(1).and_then(Value::as_u64),
+                ) else {
+                    return Err(tower_lsp::jsonrpc::Error::invalid_params(format!(
+                        "{} expects a document URI and a line",
+                        params.command
+                    )));
+                };
+                let up = match params.arguments.get(2).and_then(Value::as_str) {
+                    _ if params.command == "ic10.duplicateLine" => false,
+                    Some("up") => true,
+                    Some("down") => false,
+                    _ => {
+                        return Err(tower_lsp::jsonrpc::Error::invalid_params(
+                            "ic10.moveLine expects \"up\" or \"down\" as its third argument",
+                        ))
+                    }
+                };
+                let files = self.files.read().await;
+                let Some(file_data) = files.get(&uri) else {
+                    return Ok(None);
+                };
+                let Some(ref tree) = file_data.document_data.tree else {
+                    return Ok(None);
+                };
+                let content = &file_data.document_data.content;
+                let edit = if params.command == "ic10.duplicateLine" {
+                    lsp_handlers::duplicate_line_edit(tree, content, line as u32)
+                } else {
+                    lsp_handlers::move_line_edit(tree, content, line as u32, up)
+                };
+                let Some(edit) = edit else {
+                    return Ok(None);
+                };
+                let workspace_edit = tower_lsp::lsp_types::WorkspaceEdit {
+                    changes: Some(HashMap::from([(uri, vec![edit])])),
+                    ..Default::default()
+                };
+                return Ok(serde_json::to_value(workspace_edit).ok());
+            }
+            "ic10.convertBranchesToLabels" => {
+                // Point numeric branch targets at labels across the whole file; the edit is returned, not applied
+                let Some(uri) = params
+                    .arguments
+                    .first()
+                    .and_then(Value::as_str)
+                    .and_then(|uri| Url::parse(uri).ok())
+                else {
+                    return Err(tower_lsp::jsonrpc::Error::invalid_params(
+                        "ic10.convertBranchesToLabels expects a document URI as its first argument",
+                    ));
+                };
+                let insert_labels = params.arguments.get(1).and_then(Value::as_bool).unwrap_or(false);
+                let files = self.files.read().await;
+                let Some(file_data) = files.get(&uri) else {
+                    return Ok(None);
+                };
+                let Some(ref tree) = file_data.document_data.tree else {
+                    return Ok(None);
+                };
+                let edits = lsp_handlers::branches_to_labels_edits(
+                    tree,
+                    &file_data.document_data.content,
+                    &file_data.type_data,
+                    insert_labels,
+                );
+                let workspace_edit = tower_lsp::lsp_types::WorkspaceEdit {
+                    changes: Some(HashMap::from([(uri, edits)])),
+                    ..Default::default()
+                };
+                return Ok(serde_json::to_value(workspace_edit).ok());
+            }
+            "ic10.insertSummaryHeader" => {
+                // Insert (or refresh) the generated summary comment block at the top of the file
+                let Some(uri) = params
+                    .arguments
+                    .first()
+                    .and_then(Value::as_str)
+                    .and_then(|uri| Url::parse(uri).ok())
+                else {
+                    return Err(tower_lsp::jsonrpc::Error::invalid_params(
+                        "ic10.insertSummaryHeader expects a document URI as its first argument",
+                    ));
+                };
+                let edit = {
+                    let device_hashes = self.device_hashes.read().await;
+                    let files = self.files.read().await;
+                    let Some(file_data) = files.get(&uri) else {
+                        return Ok(None);
+                    };
+                    let Some(ref tree) = file_data.document_data.tree else {
+                        return Ok(None);
+                    };
+                    lsp_handlers::summary_header_edit(
+                        tree,
+                        &file_data.document_data.content,
+                        &file_data.type_data,
+                        &device_hashes,
+                    )
+                };
+                let Some(edit) = edit else {
+                    self.client
+                        .show_message(
+                            MessageType::WARNING,
+                            "Summary header not inserted: the script branches to line numbers, which the new lines would shift.",
+                        )
+                        .await;
+                    return Ok(None);
+                };
+                let workspace_edit = tower_lsp::lsp_types::WorkspaceEdit {
+                    changes: Some(HashMap::from([(uri, vec![edit])])),
+                    ..Default::default()
+                };
+                let _ = self.client.apply_edit(workspace_edit).await;
+            }
             "ic10.server.getBenchmarkReport" => {
                 let report = self.perf_tracker.generate_report();
                 self.client.log_message(MessageType::INFO, report.clone()).await;
@@ -516,6 +1110,11 @@ impl LanguageServer for Backend {
         
         // Request inlay hint refresh to show updated device hashes
         let _ = self.client.send_request::<tower_lsp::lsp_types::request::InlayHintRefreshRequest>(()).await;
+
+        // In save-only mode diagnostics wait for did_save
+        if self.config.read().await.diagnostics_on_save_only {
+            return;
+        }
         
         // Proper debouncing: Cancel any pending diagnostic task and schedule a new one
         // This ensures diagnostics run X ms after the LAST keystroke, not just throttling
@@ -553,6 +1152,9 @@ impl LanguageServer for Backend {
             perf_tracker: self.perf_tracker.clone(),
             pending_diagnostics: self.pending_diagnostics.clone(),
             diagnostic_cache: self.diagnostic_cache.clone(),
+            device_usage: self.device_usage.clone(),
+            device_hashes: self.device_hashes.clone(),
+            custom_docs: self.custom_docs.clone(),
         };
         
         let handle = tokio::spawn(async move {
@@ -566,6 +1168,13 @@ impl LanguageServer for Backend {
         self.pending_diagnostics.lock().await.insert(uri, handle);
     }
 
+    async fn did_save(&self, params: DidSaveTextDocumentParams) {
+        if !self.config.read().await.diagnostics_on_save_only {
+            return;
+        }
+        self.run_diagnostics(&params.text_document.uri).await;
+    }
+
     async fn did_change_configuration(&self, params: DidChangeConfigurationParams) {
         {
             let mut config = self.config.write().await;
@@ -615,13 +1224,113 @@ impl LanguageServer for Backend {
                 .and_then(Value::as_bool)
                 .unwrap_or(config.enable_control_flow_analysis);
 
+            config.max_call_depth = value
+                .get("maxCallDepth")
+                .and_then(Value::as_u64)
+                .map(|x| x as usize)
+                .unwrap_or(config.max_call_depth);
+
+            config.analysis_line_budget = value
+                .get("analysisLineBudget")
+                .and_then(Value::as_u64)
+                .map(|x| x as usize)
+                .unwrap_or(config.analysis_line_budget);
+
+            config.invalid_instruction_severity = match value
+                .get("invalidInstructionSeverity")
+                .and_then(Value::as_str)
+            {
+                Some("warning") => DiagnosticSeverity::WARNING,
+                Some("error") => DiagnosticSeverity::ERROR,
+                _ => config.invalid_instruction_severity,
+            };
+
+            config.show_label_line_hints = value
+                .get("showLabelLineHints")
+                .and_then(Value::as_bool)
+                .unwrap_or(config.show_label_line_hints);
+
+            config.show_cycle_hints = value
+                .get("showCycleHints")
+                .and_then(Value::as_bool)
+                .unwrap_or(config.show_cycle_hints);
+
             config.suppress_register_warnings = value
                 .get("suppressRegisterWarnings")
                 .and_then(Value::as_bool)
                 .unwrap_or(config.suppress_register_warnings);
 
+            config.warn_lone_device_write = value
+                .get("warnLoneDeviceWrite")
+                .and_then(Value::as_bool)
+                .unwrap_or(config.warn_lone_device_write);
+
+            config.warn_accidental_db_access = value
+                .get("warnAccidentalDbAccess")
+                .and_then(Value::as_bool)
+                .unwrap_or(config.warn_accidental_db_access);
+
+            config.warn_logictype_roundtrip = value
+                .get("warnLogicTypeRoundtrip")
+                .and_then(Value::as_bool)
+                .unwrap_or(config.warn_logictype_roundtrip);
+
+            config.diagnostics_on_save_only = value
+                .get("diagnosticsOnSaveOnly")
+                .and_then(Value::as_bool)
+                .unwrap_or(config.diagnostics_on_save_only);
+
+            config.verbose_completion_detail = value
+                .get("verboseCompletionDetail")
+                .and_then(Value::as_bool)
+                .unwrap_or(config.verbose_completion_detail);
+
+            config.enums_require_prefix = value
+                .get("enumsRequirePrefix")
+                .and_then(Value::as_bool)
+                .unwrap_or(config.enums_require_prefix);
+
+            if let Some(path) = value.get("customDocsPath").and_then(Value::as_str) {
+                config.custom_docs_path = (!path.is_empty()).then(|| path.to_string());
+            }
+
+            if let Some(devices) = value.get("customDevices").and_then(CustomDevice::list_from_json) {
+                config.custom_devices = devices;
+            }
+
+            if let Some(allowed) = value.get("allowedInstructions") {
+                config.allowed_instructions = allowed_instructions_from_json(allowed);
+            }
+
+            if let Some(categories) = value.get("completionCategories").and_then(Value::as_object) {
+                let current = &mut config.completion_categories;
+                for (key, flag) in [
+                    ("enums", &mut current.enums),
+                    ("defines", &mut current.defines),
+                    ("aliases", &mut current.aliases),
+                    ("labels", &mut current.labels),
+                    ("registers", &mut current.registers),
+                    ("devices", &mut current.devices),
+                ] {
+                    *flag = categories.get(key).and_then(Value::as_bool).unwrap_or(*flag);
+                }
+            }
+
+            if let Some(naming) = value.get("aliasNaming").and_then(Value::as_object) {
+                for template in config.alias_naming.update_from_json(naming) {
+                    self.client
+                        .log_message(
+                            MessageType::WARNING,
+                            format!("Ignoring alias naming template '{}': it needs {{n}} once and must form a valid name", template),
+                        )
+                        .await;
+                }
+            }
+
             self.client.log_message(MessageType::INFO, format!("suppress_hash_diagnostics set to: {}", config.suppress_hash_diagnostics)).await;
         }
+        self.load_custom_docs().await;
+        self.load_custom_devices().await;
 
         // Only re-run diagnostics on a limited set of files to avoid overwhelming the server
         // In large workspaces, we'll only refresh diagnostics for recently-edited files
@@ -671,10 +1380,46 @@ impl LanguageServer for Backend {
         lsp_handlers::handle_document_symbol(self, params).await
     }
 
+    async fn document_highlight(
+        &self,
+        params: DocumentHighlightParams,
+    ) -> Result<Option<Vec<DocumentHighlight>>> {
+        lsp_handlers::handle_document_highlight(self, params).await
+    }
+
+    async fn symbol(
+        &self,
+        params: WorkspaceSymbolParams,
+    ) -> Result<Option<Vec<SymbolInformation>>> {
+        lsp_handlers::handle_workspace_symbol(self, params).await
+    }
+
     async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
         lsp_completion::handle_completion(self, params).await
     }
 
+    async fn selection_range(
+        &self,
+        params: SelectionRangeParams,
+    ) -> Result<Option<Vec<SelectionRange>>> {
+        lsp_handlers::handle_selection_range(self, params).await
+    }
+
+    async fn folding_range(&self, params: FoldingRangeParams) -> Result<Option<Vec<FoldingRange>>> {
+        lsp_handlers::handle_folding_range(self, params).await
+    }
+
+    async fn formatting(&self, params: DocumentFormattingParams) -> Result<Option<Vec<TextEdit>>> {
+        lsp_handlers::handle_formatting(self, params).await
+    }
+
+    async fn on_type_formatting(
+        &self,
+        params: DocumentOnTypeFormattingParams,
+    ) -> Result<Option<Vec<TextEdit>>> {
+        lsp_handlers::handle_on_type_formatting(self, params).await
+    }
+
     async fn signature_help(&self, params: SignatureHelpParams) -> Result<Option<SignatureHelp>> {
         lsp_handlers::handle_signature_help(self, params).await
     }
@@ -696,9 +1441,86 @@ impl LanguageServer for Backend {
     async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
         lsp_hover::handle_hover(self, params).await
     }
+
+    async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+        lsp_handlers::handle_rename(self, params).await
+    }
+
+    async fn references(&self, params: ReferenceParams) -> Result<Option<Vec<Location>>> {
+        lsp_handlers::handle_references(self, params).await
+    }
 }
 
 impl Backend {
+    fn new(client: Client) -> Self {
+        Backend {
+            client,
+            files: Arc::new(RwLock::new(HashMap::new())),
+            config: Arc::new(RwLock::new(Configuration::default())),
+            diagnostics_enabled: Arc::new(RwLock::new(true)),
+            warned_about_file_count: Arc::new(tokio::sync::Mutex::new(false)),
+            perf_tracker: Arc::new(performance::PerformanceTracker::new()),
+            pending_diagnostics: Arc::new(tokio::sync::Mutex::new(HashMap::new())),
+            diagnostic_cache: Arc::new(dashmap::DashMap::new()),
+            device_usage: Arc::new(dashmap::DashMap::new()),
+            device_hashes: Arc::new(RwLock::new(hash_utils::DeviceHashes::default())),
+            custom_docs: Arc::new(RwLock::new(tooltip_documentation::CustomDocs::default())),
+        }
+    }
+
+    /// (Re)load the `customDocsPath` overlay; a bad file is logged and the built-in docs stay in use
+    async fn load_custom_docs(&self) {
+        let path = self.config.read().await.custom_docs_path.clone();
+        *self.custom_docs.write().await = Default::default();
+        let Some(path) = path else {
+            return;
+        };
+        match tooltip_documentation::CustomDocs::load(&path) {
+            Ok(docs) => {
+                let count = docs.len();
+                *self.custom_docs.write().await = docs;
+                self.client
+                    .log_message(MessageType::INFO, format!("Loaded {} custom doc entries from {}", count, path))
+                    .await;
+            }
+            Err(error) => {
+                self.client
+                    .log_message(MessageType::ERROR, format!("Ignoring custom docs: {}", error))
+                    .await;
+            }
+        }
+    }
+
+    /// Apply the `customDevices` setting to the device hash tables
+    async fn load_custom_devices(&self) {
+        let devices: Vec<(String, Option<String>)> = self
+            .config
+            .read()
+            .await
+            .custom_devices
+            .iter()
+            .map(|device| (device.name.clone(), device.display_name.clone()))
+            .collect();
+        let overridden = {
+            let mut device_hashes = self.device_hashes.write().await;
+            // Every configuration change lands here; only an edited list needs rebuilding
+            if device_hashes.custom_devices() == devices.as_slice() {
+                return;
+            }
+            device_hashes.set_custom_devices(&devices)
+        };
+        for name in overridden {
+            self.client
+                .log_message(
+                    MessageType::INFO,
+                    format!("Custom device '{}' overrides the built-in entry", name),
+                )
+                .await;
+        }
+        // Cached results were computed against the old tables
+        self.diagnostic_cache.clear();
+    }
+
     fn node_at_position<'a>(&'a self, position: Position, tree: &'a Tree) -> Option<Node<'a>> {
         self.node_at_range(
             tower_lsp::lsp_types::Range::new(position.into(), position.into()).into(),
@@ -715,12 +1537,13 @@ impl Backend {
         node
     }
 
-    async fn update_content(&self, uri: Url, mut text: String) {
+    async fn update_content(&self, uri: Url, text: String) {
         let mut files = self.files.write().await;
 
-        if !text.ends_with("\n") {
-            text.push('\n');
-        }
+        // Content is stored exactly as the editor has it. The grammar ends every line with a
+        // newline; for a last line without one, tree-sitter inserts a zero-width MISSING
+        // newline rather than an ERROR, and the missing newline is reported as a hint
+        // instead of being patched here.
         match files.entry(uri) {
             std::collections::hash_map::Entry::Vacant(entry) => {
                 let mut parser = Parser::new();
@@ -825,6 +1648,20 @@ impl Backend {
                             ));
                             continue;
                         } else {
+                            diagnostics.extend(lsp_diagnostics::shadows_enum_family(
+                                name_node.range().into(),
+                                name,
+                            ));
+                            diagnostics.extend(lsp_diagnostics::invalid_symbol_name(
+                                name_node.range().into(),
+                                name,
+                            ));
+                            if capture_idx == define_idx {
+                                diagnostics.extend(lsp_diagnostics::multitoken_define(
+                                    capture.captures[0].node,
+                                    document.content.as_bytes(),
+                                ));
+                            }
                             let mut cursor = capture.captures[0].node.walk();
                             let value_node = capture.captures[0]
                                 .node
@@ -877,6 +1714,10 @@ impl Backend {
                 } else if capture_idx == label_idx {
                     let name_node = capture.captures[0].node;
                     let name = name_node.utf8_text(document.content.as_bytes()).unwrap();
+                    if instructions::INSTRUCTIONS.contains_key(name) {
+                        diagnostics.push(lsp_diagnostics::label_shadows_instruction(name_node, name));
+                    }
+                    diagnostics.extend(lsp_diagnostics::invalid_symbol_name(name_node.range().into(), name));
                     if let Some(previous) = type_data.get_range(name) {
                         diagnostics.push(Diagnostic::new(
                             Range::from(name_node.range()).into(),
@@ -902,6 +1743,16 @@ impl Backend {
                 }
                 //println!("{:#?}", capture);
             }
+
+            // `add:` parses as an instruction plus a stray colon; still record it as a label
+            for name_node in tree_utils::mnemonic_labels(tree.root_node(), document.content.as_bytes()) {
+                let name = name_node.utf8_text(document.content.as_bytes()).unwrap();
+                diagnostics.push(lsp_diagnostics::label_shadows_instruction(name_node, name));
+                type_data.labels.entry(name.to_owned()).or_insert(DefinitionData {
+                    range: name_node.range().into(),
+                    value: name_node.start_position().row as u8,
+                });
+            }
             // println!("{:#?}", type_data.defines);
             // println!("{:#?}", type_data.aliases);
             // println!("{:#?}", type_data.labels);
@@ -912,6 +1763,41 @@ impl Backend {
     async fn run_diagnostics(&self, uri: &Url) {
         lsp_diagnostics::run_diagnostics(self, uri).await
     }
+
+    /// `ic10/reloadDeviceHashes` notification: `{ "hashes": { "PrefabName": hash, ... } }`
+    /// overlays the built-in device hash tables (an empty map restores them), then
+    /// re-runs diagnostics for every open file
+    async fn reload_device_hashes(&self, params: Value) {
+        let hashes: HashMap<String, i32> = match params
+            .get("hashes")
+            .cloned()
+            .map(serde_json::from_value)
+        {
+            Some(Ok(hashes)) => hashes,
+            _ => {
+                self.client
+                    .log_message(
+                        MessageType::WARNING,
+                        "ic10/reloadDeviceHashes: expected { \"hashes\": { name: hash } }",
+                    )
+                    .await;
+                return;
+            }
+        };
+        let count = hashes.len();
+        self.device_hashes.write().await.set_runtime_hashes(hashes);
+        self.client
+            .log_message(MessageType::INFO, format!("Loaded {} runtime device hashes", count))
+            .await;
+
+        // Cached results were computed against the old tables
+        self.diagnostic_cache.clear();
+        let uris: Vec<Url> = self.files.read().await.keys().cloned().collect();
+        for uri in &uris {
+            self.run_diagnostics(uri).await;
+        }
+        let _ = self.client.send_request::<tower_lsp::lsp_types::request::InlayHintRefreshRequest>(()).await;
+    }
 }
 
 /// Compute diagnostics for a single text buffer - delegates to lsp_diagnostics module
@@ -923,6 +1809,24 @@ fn compute_diagnostics_for_text(content: &str) -> Vec<Diagnostic> {
 mod tests {
     use super::*;
 
+    /// A server with `script` open as `file:///test.ic10` under `config`
+    async fn server_with(script: &str, config: Configuration) -> (LspService<Backend>, Url) {
+        let (service, _socket) = LspService::new(Backend::new);
+        *service.inner().config.write().await = config;
+        let uri = Url::parse("file:///test.ic10").unwrap();
+        service.inner().update_content(uri.clone(), script.to_string()).await;
+        (service, uri)
+    }
+
+    /// Diagnostics the server publishes for `script` opened with `config`
+    async fn server_diagnostics(script: &str, config: Configuration) -> Vec<Diagnostic> {
+        let (service, uri) = server_with(script, config).await;
+        let backend = service.inner();
+        backend.run_diagnostics(&uri).await;
+        let cached = backend.diagnostic_cache.iter().next().map(|entry| entry.value().clone());
+        cached.unwrap_or_default()
+    }
+
     #[test]
     fn hash_define_is_recognized() {
         let script = r#"define StartButton HASH("StructureLogicButton")
@@ -937,6 +1841,906 @@ sb StartButton Setting 34"#;
             diagnostics
         );
     }
+
+    #[test]
+    fn rand_and_sqrt_operand_counts() {
+        let diagnostics = compute_diagnostics_for_text("rand r0\nsqrt r1 r0\ns db Setting r1");
+        assert!(
+            diagnostics
+                .iter()
+                .all(|d| d.severity != Some(tower_lsp::lsp_types::DiagnosticSeverity::ERROR)),
+            "Unexpected errors: {:?}",
+            diagnostics
+        );
+
+        let diagnostics = compute_diagnostics_for_text("rand r0 r1\nsqrt r1");
+        assert!(diagnostics.iter().any(|d| d.message.contains("Superfluous argument")));
+        assert!(diagnostics.iter().any(|d| d.message == "Invalid number of arguments"));
+    }
+
+    #[test]
+    fn connection_indexed_device_alias() {
+        let alias = DefinitionData::new(Range(tower_lsp::lsp_types::Range::default()), AliasValue::from("d0:1".to_string()));
+        assert!(matches!(alias.value, AliasValue::Device(ref d) if d == "d0:1"));
+        assert_eq!(alias.get_type(), instructions::DataType::Device);
+
+        let script = "alias sensor d0:1
+alias pump dr2:0
+l r0 sensor Temperature
+s pump On r0
+l r1 d1:2 Pressure
+s db Setting r1";
+        let diagnostics = compute_diagnostics_for_text(script);
+        assert!(
+            diagnostics
+                .iter()
+                .all(|d| d.severity != Some(DiagnosticSeverity::ERROR)),
+            "Unexpected errors: {:?}",
+            diagnostics
+        );
+    }
+
+    #[test]
+    fn directive_hover_explains_directives() {
+        use crate::tooltip_documentation::create_enhanced_instruction_hover;
+        let text = |instruction| {
+            create_enhanced_instruction_hover(instruction, &Default::default())
+                .into_iter()
+                .filter_map(|part| match part {
+                    MarkedString::String(s) => Some(s),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        assert!(text("define").contains("Assembler directive"));
+        assert!(text("label").contains("Deprecated directive"));
+        assert!(!text("add").contains("directive"));
+    }
+
+    #[test]
+    fn parameter_names_from_syntax() {
+        use crate::tooltip_documentation::get_parameter_name;
+        assert_eq!(get_parameter_name("l", 0).as_deref(), Some("dest"));
+        assert_eq!(get_parameter_name("l", 2).as_deref(), Some("logicType"));
+        assert_eq!(get_parameter_name("lb", 3).as_deref(), Some("batchMode"));
+        assert_eq!(get_parameter_name("l", 3), None);
+    }
+
+    #[test]
+    fn custom_docs_overlay_builtins() {
+        use tooltip_documentation::CustomDocs;
+        let builtins = CustomDocs::default();
+        // Per-process name, so concurrent test runs never share the file
+        let path = std::env::temp_dir().join(format!("ic10lsp_custom_docs_{}.json", std::process::id()));
+        fs::write(
+            &path,
+            r#"{"instructions": {"add": "Custom add docs"}, "logicTypes": {"Temperature": "Kelvin, always"}}"#,
+        )
+        .unwrap();
+        let docs = CustomDocs::load(path.to_str().unwrap()).unwrap();
+        assert_eq!(docs.len(), 2);
+        assert_eq!(docs.instruction_doc("add"), Some("Custom add docs"));
+        assert_eq!(docs.logic_type_doc("Temperature"), Some("Kelvin, always"));
+        assert_eq!(docs.instruction_doc("yield"), builtins.instruction_doc("yield"));
+        assert_ne!(builtins.instruction_doc("add"), Some("Custom add docs"));
+
+        fs::write(&path, "{not json").unwrap();
+        assert!(CustomDocs::load(path.to_str().unwrap()).is_err());
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn custom_devices_extend_the_hash_tables() {
+        let devices = CustomDevice::list_from_json(&serde_json::json!([
+            { "name": "ModdedFusionReactor", "displayName": "Fusion Reactor" },
+            { "name": "StructureAutolathe", "displayName": "Modded Autolathe" },
+            { "displayName": "no name" },
+        ]))
+        .unwrap();
+        assert_eq!(devices.len(), 2);
+        let devices: Vec<_> = devices.into_iter().map(|d| (d.name, d.display_name)).collect();
+
+        let mut hashes = hash_utils::DeviceHashes::default();
+        let overridden = hashes.set_custom_devices(&devices);
+        assert_eq!(overridden, vec!["StructureAutolathe".to_string()]);
+        assert_eq!(hashes.custom_devices(), devices.as_slice());
+        let hash = hash_utils::compute_crc32("ModdedFusionReactor");
+        assert_eq!(hashes.get_device_hash("ModdedFusionReactor"), Some(hash));
+        assert_eq!(hashes.get_device_name_for_hash(hash), Some("Fusion Reactor"));
+        assert_eq!(hashes.get_prefab_name_for_hash(hash), Some("ModdedFusionReactor"));
+        assert_eq!(hashes.get_device_name_for_hash(336213101), Some("Modded Autolathe"));
+        assert!(hashes.device_hash_entries().contains(&("ModdedFusionReactor", hash)));
+
+        hashes.set_custom_devices(&[]);
+        assert_eq!(hashes.get_device_hash("ModdedFusionReactor"), None);
+        assert_ne!(hashes.get_device_name_for_hash(336213101), Some("Modded Autolathe"));
+    }
+
+    #[test]
+    fn device_hash_overlay_takes_precedence() {
+        let mut hashes = hash_utils::DeviceHashes::default();
+        let sensor = hashes.get_device_hash("StructureGasSensor").unwrap();
+        let display_name = hashes.get_device_name_for_hash(sensor).map(str::to_string);
+        hashes.set_runtime_hashes(HashMap::from([
+            ("StructureGasSensor".to_string(), 12345),
+            ("StructureBrandNew".to_string(), 777),
+        ]));
+        assert_eq!(hashes.get_device_hash("StructureGasSensor"), Some(12345));
+        assert_eq!(hashes.get_device_hash("StructureBrandNew"), Some(777));
+        // Display name carries over from the built-in entry, which no longer resolves
+        assert_eq!(hashes.get_device_name_for_hash(12345), display_name.as_deref());
+        assert_eq!(hashes.get_device_name_for_hash(sensor), None);
+        assert_eq!(hashes.get_prefab_name_for_hash(sensor), None);
+        assert_eq!(hashes.get_prefab_name_for_hash(777), Some("StructureBrandNew"));
+        assert!(hashes
+            .device_hash_entries()
+            .iter()
+            .all(|(name, hash)| *name != "StructureGasSensor" || *hash == 12345));
+
+        hashes.set_runtime_hashes(HashMap::new());
+        assert_eq!(hashes.get_device_hash("StructureGasSensor"), Some(sensor));
+        assert_eq!(hashes.get_device_name_for_hash(sensor), display_name.as_deref());
+        assert_eq!(hashes.get_device_hash("StructureBrandNew"), None);
+    }
+
+    #[test]
+    fn mnemonic_label_is_a_label_with_a_warning() {
+        let script = "add:\nyield\nj add\n";
+        let diagnostics = compute_diagnostics_for_text(script);
+        assert_eq!(diagnostics.len(), 1, "{:?}", diagnostics);
+        assert_eq!(
+            diagnostics[0].code,
+            Some(NumberOrString::String(LINT_LABEL_SHADOWS_INSTRUCTION.into()))
+        );
+        assert_eq!(diagnostics[0].range.end.character, 3);
+    }
+
+    #[test]
+    fn housing_control_access_is_noted() {
+        let script = "s db On 1\nl r0 db Open\ns db Setting r0\ns d0 On 1\n";
+        let is_db_access =
+            |d: &&Diagnostic| d.code == Some(NumberOrString::String(LINT_ACCIDENTAL_DB_ACCESS.into()));
+        let diagnostics = compute_diagnostics_for_text(script);
+        let lines: Vec<_> = diagnostics.iter().filter(is_db_access).map(|d| d.range.start.line).collect();
+        assert_eq!(lines, vec![0, 1]);
+
+        let config = Configuration {
+            warn_accidental_db_access: false,
+            ..Default::default()
+        };
+        let diagnostics = lsp_diagnostics::compute_diagnostics_for_text_with_config(script, &config);
+        assert!(!diagnostics.iter().any(|d| is_db_access(&d)));
+    }
+
+    #[test]
+    fn late_declaration_is_explained() {
+        let positional = |script| {
+            compute_diagnostics_for_text(script)
+                .into_iter()
+                .filter(|d| d.code == Some(NumberOrString::String(LINT_DECLARATIONS_ARE_POSITIONAL.into())))
+                .map(|d| (d.range.start.line, d.message))
+                .collect::<Vec<_>>()
+        };
+        let notes = positional("alias pump d0\nmove r0 1\nalias sensor d1\ndefine Max 5\n");
+        assert_eq!(notes.len(), 1);
+        assert_eq!(notes[0].0, 1);
+        assert!(notes[0].1.contains("alias on line 3"));
+        assert!(positional("alias pump d0\ndefine Max 5\nmove r0 Max\n").is_empty());
+    }
+
+    #[test]
+    fn declaration_only_scripts_get_a_hint() {
+        let hints = |script| {
+            compute_diagnostics_for_text(script)
+                .into_iter()
+                .filter(|d| d.code == Some(NumberOrString::String(LINT_NO_EXECUTABLE_INSTRUCTIONS.into())))
+                .map(|d| (d.severity, d.range.start.line))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            hints("alias pump d0\ndefine Max 5\nstart:\n# s d0 On 1\n"),
+            vec![(Some(DiagnosticSeverity::HINT), 0)]
+        );
+        assert_eq!(hints("# yield\n").len(), 1);
+        assert!(hints("define Max 5\nyield\n").is_empty());
+        assert!(hints("\n\n").is_empty());
+    }
+
+    #[test]
+    fn unused_definitions_and_labels_are_faded() {
+        let script = "alias sensor d0\nalias heater d1\ndefine Max 5\ndefine Min 1 # ignore Min\nstart:\nidle:\nl r0 sensor Temperature\nbgt r0 Max start\nj start\n";
+        let unused: Vec<_> = compute_diagnostics_for_text(script)
+            .into_iter()
+            .filter(|d| d.tags == Some(vec![DiagnosticTag::UNNECESSARY]))
+            .filter_map(|d| match d.code {
+                Some(NumberOrString::String(code)) => Some((d.range.start.line, code)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            unused,
+            vec![(1, LINT_UNUSED_DEFINITION.to_string()), (5, LINT_UNUSED_LABEL.to_string())]
+        );
+    }
+
+    #[test]
+    fn only_the_ignore_directive_hides_unused_names() {
+        let unused = |script: &str| {
+            compute_diagnostics_for_text(script)
+                .into_iter()
+                .filter(|d| d.code == Some(NumberOrString::String(LINT_UNUSED_DEFINITION.into())))
+                .count()
+        };
+        assert_eq!(unused("define Max 5 # ignored for now, Max\nyield\n"), 1);
+        assert_eq!(unused("define Max 5 # ignorelimits\nyield\n"), 1);
+        assert_eq!(unused("define Max 5 # ignore: Max\nyield\n"), 0);
+    }
+
+    #[tokio::test]
+    async fn removing_unused_definitions_keeps_numeric_targets() {
+        let removal = |script: &'static str| async move {
+            let (service, uri) = server_with(script, Configuration::default()).await;
+            let unused = compute_diagnostics_for_text(script)
+                .into_iter()
+                .find(|d| d.code == Some(NumberOrString::String(LINT_UNUSED_DEFINITION.into())))
+                .unwrap();
+            let params = CodeActionParams {
+                text_document: TextDocumentIdentifier::new(uri.clone()),
+                range: unused.range,
+                context: CodeActionContext { diagnostics: vec![unused], only: None, trigger_kind: None },
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            };
+            let actions = service.inner().code_action(params).await.unwrap().unwrap_or_default();
+            actions
+                .into_iter()
+                .find_map(|action| match action {
+                    CodeActionOrCommand::CodeAction(action) if action.title == "Remove unused definition" => {
+                        let edits = action.edit?.changes?.remove(&uri)?;
+                        Some((edits[0].range, edits[0].new_text.clone()))
+                    }
+                    _ => None,
+                })
+                .unwrap()
+        };
+        let range = |end_line, end_character| {
+            tower_lsp::lsp_types::Range::new(
+                tower_lsp::lsp_types::Position::new(0, 0),
+                tower_lsp::lsp_types::Position::new(end_line, end_character),
+            )
+        };
+        assert_eq!(
+            removal("define Max 5\nstart:\nyield\nj start\n").await,
+            (range(1, 0), String::new())
+        );
+        // `j 1` still has to land on `yield`, so the line stays as a blank one
+        assert_eq!(removal("define Max 5\nyield\nj 1\n").await, (range(0, 12), String::new()));
+    }
+
+    #[test]
+    fn large_files_skip_deep_analysis() {
+        let script = "l r0 d0 On\nl r0 d0 Setting\njal a\na:\npush ra\njal a\npop ra\nj ra\n";
+        let codes = |analysis_line_budget| {
+            let config = Configuration {
+                enable_control_flow_analysis: true,
+                analysis_line_budget,
+                ..Default::default()
+            };
+            lsp_diagnostics::compute_diagnostics_for_text_with_config(script, &config)
+                .into_iter()
+                .filter_map(|d| match d.code {
+                    Some(NumberOrString::String(code)) => Some(code),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+        let skipped = codes(4);
+        assert_eq!(skipped.iter().filter(|c| *c == LINT_DEEP_ANALYSIS_SKIPPED).count(), 1);
+        assert!(!skipped.iter().any(|c| c == LINT_DEEP_CALL_NESTING || c == "dead-device-read"));
+        for budget in [0, 8] {
+            let full = codes(budget);
+            assert!(!full.iter().any(|c| c == LINT_DEEP_ANALYSIS_SKIPPED));
+            assert!(full.iter().any(|c| c == LINT_DEEP_CALL_NESTING));
+            assert!(full.iter().any(|c| c == "dead-device-read"));
+        }
+    }
+
+    #[test]
+    fn half_typed_mnemonics_are_not_invalid() {
+        let invalid = |script: &str, config: &Configuration| {
+            lsp_diagnostics::compute_diagnostics_for_text_with_config(script, config)
+                .into_iter()
+                .filter(|d| d.message.starts_with("Invalid instruction"))
+                .map(|d| (d.range.start.line, d.severity))
+                .collect::<Vec<_>>()
+        };
+        let config = Configuration::default();
+        assert!(invalid("yield\nad\n", &config).is_empty());
+        assert_eq!(
+            invalid("ad r0 r1 r2\nfoo\n", &config),
+            vec![(0, Some(DiagnosticSeverity::ERROR)), (1, Some(DiagnosticSeverity::ERROR))]
+        );
+        let config = Configuration {
+            invalid_instruction_severity: DiagnosticSeverity::WARNING,
+            ..Default::default()
+        };
+        assert_eq!(invalid("foo\n", &config), vec![(0, Some(DiagnosticSeverity::WARNING))]);
+    }
+
+    #[tokio::test]
+    async fn published_invalid_instructions_follow_the_config() {
+        let invalid = |diagnostics: Vec<Diagnostic>| {
+            diagnostics
+                .into_iter()
+                .filter(|d| d.message.starts_with("Invalid instruction"))
+                .map(|d| (d.range.start.line, d.severity))
+                .collect::<Vec<_>>()
+        };
+        let config = Configuration {
+            invalid_instruction_severity: DiagnosticSeverity::WARNING,
+            ..Default::default()
+        };
+        assert!(invalid(server_diagnostics("yield\nad\n", config.clone()).await).is_empty());
+        assert_eq!(
+            invalid(server_diagnostics("ad r0 r1 r2\nfoo\n", config).await),
+            vec![(0, Some(DiagnosticSeverity::WARNING)), (1, Some(DiagnosticSeverity::WARNING))]
+        );
+    }
+
+    #[test]
+    fn instructions_outside_the_allowlist_are_errors() {
+        let script = "alias pump d0\ndefine Max 5\nl r0 pump On\nADD r0 r0 1\nmul r0 r0 2\nj 0\n";
+        let flagged = |config: &Configuration| {
+            lsp_diagnostics::compute_diagnostics_for_text_with_config(script, config)
+                .into_iter()
+                .filter(|d| d.code == Some(NumberOrString::String(LINT_INSTRUCTION_NOT_ALLOWED.into())))
+                .inspect(|d| assert_eq!(d.severity, Some(DiagnosticSeverity::ERROR)))
+                .map(|d| d.range.start.line)
+                .collect::<Vec<_>>()
+        };
+        assert!(flagged(&Configuration::default()).is_empty());
+        let allowed = allowed_instructions_from_json(&serde_json::json!(["l", "add", " J "]));
+        assert_eq!(allowed, Some(vec!["l".to_string(), "add".to_string(), "j".to_string()]));
+        let config = Configuration {
+            allowed_instructions: allowed,
+            ..Default::default()
+        };
+        assert_eq!(flagged(&config), vec![4]);
+        assert_eq!(allowed_instructions_from_json(&serde_json::json!([])), None);
+        assert_eq!(allowed_instructions_from_json(&Value::Null), None);
+    }
+
+    #[test]
+    fn code_after_an_unconditional_jump_is_unreachable() {
+        let config = Configuration {
+            enable_control_flow_analysis: true,
+            ..Default::default()
+        };
+        let unreachable = |script: &str| {
+            lsp_diagnostics::compute_diagnostics_for_text_with_config(script, &config)
+                .into_iter()
+                .filter(|d| d.code == Some(NumberOrString::String(LINT_UNREACHABLE_CODE.into())))
+                .map(|d| (d.range.start.line, d.range.end.line))
+                .collect::<Vec<_>>()
+        };
+        let script = "j main\nadd r0 r0 1\n# note\nmove r1 r0\nmain:\nyield\njal sub\nj main\nsub:\nj ra\n";
+        assert_eq!(unreachable(script), vec![(1, 3)]);
+        // A numeric branch target makes the line after it reachable again
+        assert_eq!(unreachable("j 3\nmove r0 1\nyield\nmove r0 2\nj 0\n"), vec![(1, 2)]);
+        // ...in any notation, or through a define
+        assert_eq!(unreachable("j $3\nmove r0 1\nyield\nmove r0 2\nj 0\n"), vec![(1, 2)]);
+        assert_eq!(unreachable("j 3.0\nmove r0 1\nyield\nmove r0 2\nj 0\n"), vec![(1, 2)]);
+        let script = "define Target 4\nj Target\nmove r0 1\nyield\nmove r0 2\nj 1\n";
+        assert_eq!(unreachable(script), vec![(2, 3)]);
+        // A jump through a register could land on any line
+        assert!(unreachable("j main\nmove r0 1\nmain:\nj r0\n").is_empty());
+        assert!(unreachable("j main\nmove r0 1\nmain:\njr r1\n").is_empty());
+        assert_eq!(unreachable("j main\nmove r0 1\nmain:\nj ra\n"), vec![(1, 1)]);
+    }
+
+    #[test]
+    fn deep_and_recursive_call_chains_are_noted() {
+        let config = Configuration {
+            enable_control_flow_analysis: true,
+            max_call_depth: 2,
+            ..Default::default()
+        };
+        let nesting = |script: &str| {
+            lsp_diagnostics::compute_diagnostics_for_text_with_config(script, &config)
+                .into_iter()
+                .filter(|d| d.code == Some(NumberOrString::String(LINT_DEEP_CALL_NESTING.into())))
+                .map(|d| (d.range.start.line, d.message))
+                .collect::<Vec<_>>()
+        };
+        let subs = "a:\npush ra\njal b\npop ra\nj ra\nb:\npush ra\njal c\npop ra\nj ra\nc:\nj ra\n";
+        let deep = nesting(&format!("jal a\nyield\n{}", subs));
+        assert_eq!(deep.len(), 1);
+        assert_eq!(deep[0].0, 0);
+        assert!(deep[0].1.contains("a → b → c"));
+        assert!(nesting(&format!("jal b\nyield\n{}", subs)).is_empty());
+
+        let recursive = nesting("jal a\nyield\na:\npush ra\njal a\npop ra\nj ra\n");
+        assert_eq!(recursive.len(), 1);
+        assert!(recursive[0].1.contains("a → a"));
+    }
+
+    #[test]
+    fn alias_used_above_its_declaration_is_flagged() {
+        let diagnostics = compute_diagnostics_for_text("move r0 2\nadd r1 sum 1\nalias sum r0\nadd r2 sum r0\n");
+        let early: Vec<_> = diagnostics
+            .iter()
+            .filter(|d| d.code == Some(NumberOrString::String(LINT_ALIAS_USED_BEFORE_DECLARATION.into())))
+            .collect();
+        assert_eq!(early.len(), 1);
+        assert_eq!(early[0].range.start.line, 1);
+        assert!(early[0].message.contains("line 3"));
+    }
+
+    #[tokio::test]
+    async fn deprecated_enum_family_is_flagged_with_a_fix() {
+        // No family in Enums.json is deprecated as a whole; stand one in
+        let family_deprecation =
+            |family: &str| (family == "TraderInstruction").then_some(Some("SorterInstruction"));
+        let script = "s d0 Mode TraderInstruction.FilterPrefabHashEquals\n\
+                      s d0 Mode TraderInstruction.FilterGasContains\n";
+        let mut parser = Parser::new();
+        parser.set_language(tree_sitter_ic10::language()).unwrap();
+        let tree = parser.parse(script, None).unwrap();
+        let diagnostic = |row| {
+            let point = tree_sitter::Point::new(row, 12);
+            let identifier = tree.root_node().named_descendant_for_point_range(point, point).unwrap();
+            let operand = identifier.parent().unwrap();
+            let canonical = operand.utf8_text(script.as_bytes()).unwrap();
+            lsp_diagnostics::deprecated_enum_family_diagnostic(operand, canonical, family_deprecation).unwrap()
+        };
+
+        let with_replacement = diagnostic(0);
+        assert_eq!(
+            with_replacement.code,
+            Some(NumberOrString::String(LINT_DEPRECATED_ENUM_FAMILY.into()))
+        );
+        assert!(with_replacement.message.contains("use 'SorterInstruction' instead"));
+        assert_eq!(
+            with_replacement.data,
+            Some(serde_json::json!("SorterInstruction.FilterPrefabHashEquals"))
+        );
+        // The replacement family has no such member, so there is nothing to swap in
+        assert_eq!(diagnostic(1).data, None);
+        let (service, uri) = server_with(script, Configuration::default()).await;
+        let fixes = |diagnostic: Diagnostic| {
+            let params = CodeActionParams {
+                text_document: TextDocumentIdentifier::new(uri.clone()),
+                range: diagnostic.range,
+                context: CodeActionContext { diagnostics: vec![diagnostic], only: None, trigger_kind: None },
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            };
+            let backend = service.inner();
+            let uri = uri.clone();
+            async move {
+                backend
+                    .code_action(params)
+                    .await
+                    .unwrap()
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter_map(|action| match action {
+                        CodeActionOrCommand::CodeAction(action) if action.title.starts_with("Replace with") => {
+                            let edits = action.edit?.changes?.remove(&uri)?;
+                            Some((action.title, edits[0].new_text.clone(), edits[0].range))
+                        }
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>()
+            }
+        };
+        assert_eq!(
+            fixes(with_replacement.clone()).await,
+            vec![(
+                "Replace with SorterInstruction.FilterPrefabHashEquals".to_string(),
+                "SorterInstruction.FilterPrefabHashEquals".to_string(),
+                with_replacement.range,
+            )]
+        );
+        assert!(fixes(diagnostic(1)).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn missing_trailing_newline_is_a_hint() {
+        let hints = |diagnostics: Vec<Diagnostic>| {
+            diagnostics
+                .into_iter()
+                .filter(|d| d.code == Some(NumberOrString::String(LINT_MISSING_TRAILING_NEWLINE.into())))
+                .map(|d| (d.range.start.line, d.range.start.character, d.severity))
+                .collect::<Vec<_>>()
+        };
+        let expected = vec![(1, 11, Some(DiagnosticSeverity::HINT))];
+        assert_eq!(hints(compute_diagnostics_for_text("move r0 1\nadd r0 r0 1")), expected);
+        assert_eq!(hints(server_diagnostics("move r0 1\nadd r0 r0 1", Configuration::default()).await), expected);
+        assert!(hints(compute_diagnostics_for_text("move r0 1\nadd r0 r0 1\n")).is_empty());
+        assert!(hints(server_diagnostics("move r0 1\nadd r0 r0 1\n", Configuration::default()).await).is_empty());
+        assert!(hints(compute_diagnostics_for_text("")).is_empty());
+    }
+
+    #[tokio::test]
+    async fn redeclared_alias_counts_from_its_first_declaration() {
+        let script = "alias x r1\nmove x 1\nadd r2 x 1\nalias x r2\nmove x 2\n";
+        let early = |diagnostics: Vec<Diagnostic>| {
+            diagnostics
+                .iter()
+                .any(|d| d.code == Some(NumberOrString::String(LINT_ALIAS_USED_BEFORE_DECLARATION.into())))
+        };
+        assert!(!early(compute_diagnostics_for_text(script)));
+        assert!(!early(server_diagnostics(script, Configuration::default()).await));
+    }
+
+    #[test]
+    fn enum_in_device_position_is_explained() {
+        let diagnostics = compute_diagnostics_for_text("l r0 Sound.Alarm2 On\ns d0 Mode Sound.Alarm2\n");
+        let enum_errors: Vec<_> = diagnostics
+            .iter()
+            .filter(|d| d.code == Some(NumberOrString::String(LINT_ENUM_WHERE_DEVICE_EXPECTED.into())))
+            .collect();
+        assert_eq!(enum_errors.len(), 1);
+        assert_eq!(enum_errors[0].range.start.line, 0);
+        assert!(!diagnostics
+            .iter()
+            .any(|d| d.range.start.line == 0 && d.message.starts_with("Type mismatch")));
+    }
+
+    #[test]
+    fn define_in_device_position_is_an_error() {
+        let script = "define DevHash HASH(\"StructureVolumePump\")\nlb r0 DevHash On Sum\nsb DevHash On 1\nl r1 DevHash On\n";
+        let diagnostics = compute_diagnostics_for_text(script);
+        let lines: Vec<_> = diagnostics
+            .iter()
+            .filter(|d| d.code == Some(NumberOrString::String(LINT_NUMBER_DEFINE_AS_DEVICE.into())))
+            .map(|d| d.range.start.line)
+            .collect();
+        assert_eq!(lines, vec![3]);
+        assert!(!diagnostics
+            .iter()
+            .any(|d| d.range.start.line < 3 && d.severity == Some(DiagnosticSeverity::ERROR)));
+    }
+
+    #[test]
+    fn name_hash_must_be_a_number() {
+        let script = "alias pump d0\ndefine Name HASH(\"Pump 1\")\n\
+                      lbn r0 HASH(\"X\") HASH(\"Y\") Setting Average\n\
+                      lbn r1 HASH(\"X\") Name Setting Average\n\
+                      sbn HASH(\"X\") STR(\"Y\") On r0\n\
+                      lbn r2 HASH(\"X\") pump Setting Average\n\
+                      sbn HASH(\"X\") Setting On 1\n";
+        let diagnostics = compute_diagnostics_for_text(script);
+        let lines: Vec<_> = diagnostics
+            .iter()
+            .filter(|d| d.code == Some(NumberOrString::String(LINT_INVALID_NAME_HASH.into())))
+            .map(|d| d.range.start.line)
+            .collect();
+        assert_eq!(lines, vec![5, 6]);
+        assert!(!diagnostics.iter().any(|d| d.message.starts_with("Type mismatch")));
+    }
+
+    #[test]
+    fn constant_shift_amounts_stay_within_range() {
+        let script = "define Wide 64\ndefine Bits 8\n\
+                      sll r0 r1 64\n\
+                      sra r0 r1 -1\n\
+                      srl r0 r1 Wide\n\
+                      sla r0 r1 Bits\n\
+                      sll r0 r1 63\n\
+                      sll r0 r1 r2\n";
+        let lines: Vec<_> = compute_diagnostics_for_text(script)
+            .iter()
+            .filter(|d| d.code == Some(NumberOrString::String(LINT_SHIFT_AMOUNT_RANGE.into())))
+            .map(|d| d.range.start.line)
+            .collect();
+        assert_eq!(lines, vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn store_in_load_order_is_reordered() {
+        let script = "alias pump d0\nalias level r5\n\
+                      s r0 d0 On\n\
+                      s level pump Setting\n\
+                      ss 1 d1 0 Open\n\
+                      s d0 On r0\n\
+                      s pump Setting 5\n";
+        let fixes: Vec<_> = compute_diagnostics_for_text(script)
+            .into_iter()
+            .filter(|d| d.code == Some(NumberOrString::String(LINT_STORE_OPERAND_ORDER.into())))
+            .map(|d| (d.range.start.line, d.data))
+            .collect();
+        assert_eq!(
+            fixes,
+            vec![
+                (2, Some(Value::from("s d0 On r0"))),
+                (3, Some(Value::from("s pump Setting level"))),
+                (4, Some(Value::from("ss d1 0 Open 1"))),
+            ]
+        );
+    }
+
+    #[test]
+    fn no_op_instructions_are_faded() {
+        let script = "alias level r5\nalias pump d0\n\
+                      move r0 r0\n\
+                      add level level 0\n\
+                      add r1 0 r1\n\
+                      sub r2 r2 0\n\
+                      mul r3 r3 1\n\
+                      div r4 r4 1.0\n\
+                      move r0 r1\n\
+                      add r1 r1 1\n\
+                      sub r2 0 r2\n\
+                      move level r5\n\
+                      define Zero 0\n\
+                      add r1 r1 Zero\n";
+        let lines: Vec<_> = compute_diagnostics_for_text(script)
+            .into_iter()
+            .filter(|d| d.code == Some(NumberOrString::String(LINT_NO_OP_INSTRUCTION.into())))
+            .inspect(|d| assert_eq!(d.tags, Some(vec![DiagnosticTag::UNNECESSARY])))
+            .map(|d| d.range.start.line)
+            .collect();
+        assert_eq!(lines, vec![2, 3, 4, 5, 6, 7]);
+    }
+
+    #[test]
+    fn constant_bit_fields_stay_within_range() {
+        let script = "define Offset 50\n\
+                      ext r0 r1 8 4\n\
+                      ins r0 r1 0 53\n\
+                      ext r0 r1 Offset 4\n\
+                      ins r0 r1 4 -1\n\
+                      ext r0 r1 53 1\n\
+                      ext r0 r1 r2 r3\n";
+        let flagged: Vec<_> = compute_diagnostics_for_text(script)
+            .into_iter()
+            .filter(|d| d.code == Some(NumberOrString::String(LINT_BIT_FIELD_RANGE.into())))
+            .map(|d| (d.range.start.line, d.range.start.character))
+            .collect();
+        assert_eq!(flagged, vec![(3, 17), (4, 12), (5, 10)]);
+    }
+
+    #[test]
+    fn long_constant_sleeps_are_noted() {
+        let script = "define Wait 600\nsleep 1\nsleep 60\nsleep 61\nsleep Wait\nsleep r0\n";
+        let flagged: Vec<_> = compute_diagnostics_for_text(script)
+            .into_iter()
+            .filter(|d| d.code == Some(NumberOrString::String(LINT_LONG_SLEEP.into())))
+            .inspect(|d| assert_eq!(d.severity, Some(DiagnosticSeverity::INFORMATION)))
+            .map(|d| d.range.start.line)
+            .collect();
+        assert_eq!(flagged, vec![3, 4]);
+    }
+
+    #[test]
+    fn define_values_must_be_one_token() {
+        let script = "define A 1 2\ndefine B HASH(\"Foo Bar\") # note\ndefine C STR(\"ab cd\")\ndefine D r0 + 1\ndefine E 5 # fine\n";
+        let flagged: Vec<_> = compute_diagnostics_for_text(script)
+            .into_iter()
+            .filter(|d| d.code == Some(NumberOrString::String(LINT_MULTITOKEN_DEFINE.into())))
+            .inspect(|d| assert_eq!(d.severity, Some(DiagnosticSeverity::WARNING)))
+            .map(|d| (d.range.start.line, d.range.start.character, d.range.end.character))
+            .collect();
+        assert_eq!(flagged, vec![(0, 9, 12), (3, 9, 15)]);
+    }
+
+    #[test]
+    fn misspelled_instructions_suggest_the_nearest_mnemonic() {
+        let script = "mvoe r0 1\nfrobnicate r0\n";
+        let flagged: Vec<_> = compute_diagnostics_for_text(script)
+            .into_iter()
+            .filter(|d| d.code == Some(NumberOrString::String(LINT_INVALID_INSTRUCTION.into())))
+            .map(|d| (d.range.start.line, d.message, d.data))
+            .collect();
+        assert_eq!(
+            flagged,
+            vec![
+                (0, "Invalid instruction, did you mean `move`?".to_string(), Some(Value::from("move"))),
+                (1, "Invalid instruction".to_string(), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_define_hashes_suggest_a_device() {
+        let script = "define Pump HASH(\"StructureVolumPump\")\ndefine Known HASH(\"StructureVolumePump\")\ndefine Odd HASH(\"Banana\")\n";
+        let flagged: Vec<_> = compute_diagnostics_for_text(script)
+            .into_iter()
+            .filter(|d| d.code == Some(NumberOrString::String(LINT_UNKNOWN_DEFINE_HASH.into())))
+            .inspect(|d| assert_eq!(d.severity, Some(DiagnosticSeverity::WARNING)))
+            .map(|d| (d.range.start.line, d.range.start.character, d.message))
+            .collect();
+        assert_eq!(
+            flagged,
+            vec![
+                (
+                    0,
+                    12,
+                    "Unknown device 'StructureVolumPump' in HASH(...). Did you mean 'StructureVolumePump'?"
+                        .to_string()
+                ),
+                (2, 11, "Unknown device 'Banana' in HASH(...).".to_string()),
+            ]
+        );
+
+        let config = Configuration { suppress_hash_diagnostics: true, ..Configuration::default() };
+        assert!(lsp_diagnostics::compute_diagnostics_for_text_with_config(script, &config)
+            .iter()
+            .all(|d| d.code != Some(NumberOrString::String(LINT_UNKNOWN_DEFINE_HASH.into()))));
+    }
+
+    #[test]
+    fn unbalanced_regions_are_flagged() {
+        let script = "# endregion\n# region Setup\nmove r0 1 # region not a marker\n#region Loop\n# Region 2 pumps\n# regional\nyield\n# endregion\n";
+        let flagged: Vec<_> = compute_diagnostics_for_text(script)
+            .into_iter()
+            .filter(|d| d.code == Some(NumberOrString::String(LINT_UNBALANCED_REGION.into())))
+            .inspect(|d| assert_eq!(d.severity, Some(DiagnosticSeverity::WARNING)))
+            .map(|d| d.range.start.line)
+            .collect();
+        assert_eq!(flagged, vec![0, 1]);
+
+        let mut parser = Parser::new();
+        parser.set_language(tree_sitter_ic10::language()).unwrap();
+        let tree = parser.parse(script, None).unwrap();
+        let uri = Url::parse("file:///script.ic10").unwrap();
+        let mut diagnostics = Vec::new();
+        lsp_diagnostics::check_unbalanced_regions(&tree, script, Some(&uri), &mut diagnostics);
+        let related = diagnostics[1].related_information.as_ref().unwrap();
+        assert_eq!(related[0].location.range.start, tower_lsp::lsp_types::Position::new(8, 0));
+
+        let folds = lsp_handlers::region_folding_ranges(&tree, script);
+        assert_eq!(folds.len(), 1);
+        assert_eq!((folds[0].start_line, folds[0].end_line), (3, 7));
+        assert_eq!(folds[0].kind, Some(FoldingRangeKind::Region));
+    }
+
+    #[test]
+    fn definitions_named_like_enum_families_are_flagged() {
+        let script = "define Color 5\nalias Sound d0\nalias pump d1\ndefine SortingClassic 1\ns pump On Color\n";
+        let flagged: Vec<_> = compute_diagnostics_for_text(script)
+            .into_iter()
+            .filter(|d| d.code == Some(NumberOrString::String(LINT_SHADOWS_ENUM_FAMILY.into())))
+            .map(|d| (d.range.start.line, d.data))
+            .collect();
+        assert_eq!(flagged, vec![(0, Some(Value::from("Color"))), (1, Some(Value::from("Sound")))]);
+    }
+
+    #[test]
+    fn names_outside_game_identifier_rules_are_errors() {
+        let script = "define Max.Speed 5\nalias pump_2 d0\nloop.start:\n_ok:\nj loop.start\n";
+        let flagged: Vec<_> = compute_diagnostics_for_text(script)
+            .into_iter()
+            .filter(|d| d.code == Some(NumberOrString::String(LINT_INVALID_SYMBOL_NAME.into())))
+            .inspect(|d| assert_eq!(d.severity, Some(DiagnosticSeverity::ERROR)))
+            .map(|d| (d.range.start.line, d.range.start.character))
+            .collect();
+        assert_eq!(flagged, vec![(0, 7), (2, 0)]);
+    }
+
+    #[test]
+    fn leading_zero_registers_are_flagged() {
+        let diagnostics = compute_diagnostics_for_text("move r07 5\nadd rr01 r00 1\nmove r1 r016\n");
+        let canonical: Vec<_> = diagnostics
+            .iter()
+            .filter(|d| d.code == Some(NumberOrString::String(LINT_MALFORMED_REGISTER.into())))
+            .filter_map(|d| d.data.as_ref().and_then(Value::as_str))
+            .collect();
+        assert_eq!(canonical, vec!["r7", "rr1", "r0"]);
+        assert_eq!(
+            diagnostics.iter().filter(|d| d.message == "Unknown identifier").count(),
+            1
+        );
+    }
+
+    #[test]
+    fn non_register_destination_is_reported_clearly() {
+        let diagnostics = compute_diagnostics_for_text("add 5 r0 1\nmul d0 r0 2\nadd r1 r0 1\ns d0 On 1\n");
+        let lines: Vec<_> = diagnostics
+            .iter()
+            .filter(|d| d.code == Some(NumberOrString::String(LINT_INVALID_DESTINATION.into())))
+            .map(|d| d.range.start.line)
+            .collect();
+        assert_eq!(lines, vec![0, 1]);
+        assert!(!diagnostics.iter().any(|d| d.message.starts_with("Type mismatch")));
+    }
+
+    #[test]
+    fn infix_operators_get_a_targeted_error() {
+        let operator_errors = |script| {
+            compute_diagnostics_for_text(script)
+                .into_iter()
+                .filter(|d| d.code == Some(NumberOrString::String(LINT_UNSUPPORTED_OPERATOR.into())))
+                .map(|d| d.message)
+                .collect::<Vec<_>>()
+        };
+        let errors = operator_errors("r0=5\n");
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("'='") && errors[0].contains("move"));
+        assert_eq!(operator_errors("move r0 r1+1\n").len(), 1);
+        assert!(operator_errors("add r0 r0 1 == 2\n")[0].contains("'=='"));
+        assert!(operator_errors("move r0 -5\n").is_empty());
+    }
+
+    #[test]
+    fn stack_addresses_are_range_checked() {
+        let script = "define Top 511\ndefine Past 512\npoke 0 1\npoke Top 1\npoke r0 1\nget r1 db 600\nput db Past r1\nputd 5 -1 r1\npoke 512 r1";
+        let lines: Vec<_> = compute_diagnostics_for_text(script)
+            .into_iter()
+            .filter(|d| d.code == Some(NumberOrString::String(LINT_STACK_ADDRESS_RANGE.into())))
+            .map(|d| d.range.start.line)
+            .collect();
+        assert_eq!(lines, vec![5, 6, 7, 8]);
+    }
+
+    #[test]
+    fn fusible_move_is_hinted() {
+        let script = "move r0 r1\nadd r0 r0 5\nmove r2 r1\nstart:\nadd r2 r2 1\nmove r3 r1\nmul r3 r3 r3\ns db Setting r0";
+        let hints: Vec<_> = compute_diagnostics_for_text(script)
+            .into_iter()
+            .filter(|d| d.code == Some(NumberOrString::String(LINT_FUSIBLE_MOVE.into())))
+            .collect();
+        assert_eq!(hints.len(), 1, "{:?}", hints);
+        assert_eq!(hints[0].range.start.line, 0);
+        assert_eq!(hints[0].data.as_ref().unwrap()["text"], "add r0 r1 5");
+        assert_eq!(hints[0].data.as_ref().unwrap()["remove"]["end"]["line"], 1);
+    }
+
+    #[test]
+    fn fusible_move_keeps_the_line_with_numeric_jumps() {
+        let script = "move r0 r1 # seed\nadd r0 r0 5\nj 0\n";
+        let hints: Vec<_> = compute_diagnostics_for_text(script)
+            .into_iter()
+            .filter(|d| d.code == Some(NumberOrString::String(LINT_FUSIBLE_MOVE.into())))
+            .collect();
+        assert_eq!(hints.len(), 1, "{:?}", hints);
+        let remove = &hints[0].data.as_ref().unwrap()["remove"];
+        assert_eq!(remove["start"], serde_json::json!({ "line": 0, "character": 0 }));
+        assert_eq!(remove["end"], serde_json::json!({ "line": 0, "character": 10 }));
+    }
+
+    #[test]
+    fn define_glued_to_number_suggests_space() {
+        let script = "define Pump 5\nadd r0 Pump1 r1\nadd r0 Pumpx1 r1";
+        let hints: Vec<_> = compute_diagnostics_for_text(script)
+            .into_iter()
+            .filter(|d| {
+                d.code == Some(NumberOrString::String(LINT_POSSIBLE_MISSING_SPACE.into()))
+            })
+            .collect();
+        assert_eq!(hints.len(), 1, "{:?}", hints);
+        assert_eq!(hints[0].range.start.line, 1);
+        assert_eq!(hints[0].severity, Some(DiagnosticSeverity::INFORMATION));
+    }
+
+    #[test]
+    fn lone_device_write_is_opt_in() {
+        let script = "alias sensor d0
+l r0 sensor Temperature
+l r1 sensor Pressure
+l r2 d0 TotalMoles
+s sensor On 1
+add r0 r0 r1
+add r0 r0 r2
+s db Setting r0";
+        let is_lone_write =
+            |d: &Diagnostic| d.code == Some(NumberOrString::String(LINT_LONE_DEVICE_WRITE.into()));
+
+        assert!(!compute_diagnostics_for_text(script).iter().any(is_lone_write));
+
+        let config = Configuration {
+            warn_lone_device_write: true,
+            ..Default::default()
+        };
+        let diagnostics = lsp_diagnostics::compute_diagnostics_for_text_with_config(script, &config);
+        let lone: Vec<_> = diagnostics.iter().filter(|d| is_lone_write(d)).collect();
+        assert_eq!(lone.len(), 1, "{:?}", diagnostics);
+        assert_eq!(lone[0].range.start.line, 4);
+        assert_eq!(lone[0].severity, Some(DiagnosticSeverity::HINT));
+    }
 }
 
 #[tokio::main]
@@ -944,14 +2748,138 @@ async fn main() {
     use clap::Parser as _;
     let cli = cli::Cli::parse();
 
+    // SARIF export mode: diagnose the given files and print one SARIF log for all of them.
+    // Unreadable files are reported in the log and make the exit code non-zero.
+    if !cli.diagnose_sarif.is_empty() {
+        let mut files: Vec<(String, Vec<Diagnostic>)> = Vec::new();
+        let mut unreadable: Vec<(String, String)> = Vec::new();
+        for path in &cli.diagnose_sarif {
+            match fs::read_to_string(path) {
+                Ok(content) => files.push((path.display().to_string(), compute_diagnostics_for_text(&content))),
+                Err(error) => unreadable.push((
+                    path.display().to_string(),
+                    format!("Could not read {}: {}", path.display(), error),
+                )),
+            }
+        }
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&sarif::to_sarif(&files, &unreadable)).unwrap_or_default()
+        );
+        std::process::exit(i32::from(!unreadable.is_empty()));
+    }
+
+    // Format mode: rewrite each file (or print it with --stdout); files with syntax errors
+    // are reported and left untouched, and make the exit code non-zero
+    if !cli.format.is_empty() {
+        let mut parser = Parser::new();
+        parser
+            .set_language(tree_sitter_ic10::language())
+            .expect("Failed to set language");
+        // A missing final newline shows up as a MISSING node; only real ERROR nodes count
+        let syntax_error = Query::new(tree_sitter_ic10::language(), "(ERROR) @error").unwrap();
+        let mut failed = false;
+        for path in &cli.format {
+            let Ok(content) = fs::read_to_string(path) else {
+                eprintln!("Could not read {}", path.display());
+                failed = true;
+                continue;
+            };
+            let Some(tree) = parser.parse(&content, None).filter(|tree| {
+                QueryCursor::new()
+                    .captures(&syntax_error, tree.root_node(), content.as_bytes())
+                    .next()
+                    .is_none()
+            }) else {
+                eprintln!("Could not parse {}; left unchanged", path.display());
+                failed = true;
+                continue;
+            };
+            let formatted = lsp_handlers::format_document(&tree, &content);
+            if cli.stdout {
+                print!("{}", formatted.as_deref().unwrap_or(&content));
+            } else if let Some(formatted) = formatted {
+                if let Err(error) = fs::write(path, formatted) {
+                    eprintln!("Could not write {}: {}", path.display(), error);
+                    failed = true;
+                }
+            }
+        }
+        std::process::exit(i32::from(failed));
+    }
+
+    // Symbol table mode: print the defines, aliases and labels of each file as JSON
+    if let Some(path) = &cli.symbols {
+        let Ok(content) = fs::read_to_string(path) else {
+            eprintln!("Could not read {}", path.display());
+            std::process::exit(1);
+        };
+        let mut parser = Parser::new();
+        parser
+            .set_language(tree_sitter_ic10::language())
+            .expect("Failed to set language");
+        let Some(tree) = parser.parse(&content, None) else {
+            eprintln!("Could not parse {}", path.display());
+            std::process::exit(1);
+        };
+        let type_data = lsp_diagnostics::collect_type_data(&tree, &content, &mut Vec::new());
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&symbols::symbol_table(&type_data)).unwrap_or_default()
+        );
+        return;
+    }
+
     // Diagnostic runner mode: if files provided with --diagnose, run the diagnostic logic
     // on each file and print the results to stdout, then exit.
     if !cli.diagnose.is_empty() {
+        let severity_label = |severity| match severity {
+            Some(DiagnosticSeverity::ERROR) => "ERROR",
+            Some(DiagnosticSeverity::WARNING) => "WARN",
+            Some(DiagnosticSeverity::INFORMATION) => "INFO",
+            Some(DiagnosticSeverity::HINT) => "HINT",
+            _ => "UNKNOWN",
+        };
+        // Unreadable files get an error entry (JSON) or a message on stderr, and make the
+        // exit code non-zero
+        let mut failed = false;
+        if cli.json {
+            let mut files = serde_json::Map::new();
+            for path in &cli.diagnose {
+                let content = match fs::read_to_string(path) {
+                    Ok(content) => content,
+                    Err(error) => {
+                        failed = true;
+                        files.insert(
+                            path.display().to_string(),
+                            serde_json::json!({ "error": format!("Could not read {}: {}", path.display(), error) }),
+                        );
+                        continue;
+                    }
+                };
+                let diagnostics = compute_diagnostics_for_text(&content)
+                    .into_iter()
+                    .map(|d| {
+                        serde_json::json!({
+                            "severity": severity_label(d.severity),
+                            "code": d.code,
+                            "range": d.range,
+                            "message": d.message,
+                        })
+                    })
+                    .collect();
+                files.insert(path.display().to_string(), Value::Array(diagnostics));
+            }
+            println!("{}", serde_json::to_string_pretty(&files).unwrap_or_default());
+            std::process::exit(i32::from(failed));
+        }
         for path in &cli.diagnose {
             let path_ref = Path::new(path);
             let content = match fs::read_to_string(path_ref) {
                 Ok(c) => c,
-                Err(_e) => {
+                Err(error) => {
+                    eprintln!("Could not read {}: {}", path_ref.display(), error);
+                    failed = true;
                     continue;
                 }
             };
@@ -963,13 +2891,7 @@ async fn main() {
                 println!("  (no diagnostics)");
             } else {
                 for d in diagnostics {
-                    let sev = match d.severity {
-                        Some(tower_lsp::lsp_types::DiagnosticSeverity::ERROR) => "ERROR",
-                        Some(tower_lsp::lsp_types::DiagnosticSeverity::WARNING) => "WARN",
-                        Some(tower_lsp::lsp_types::DiagnosticSeverity::INFORMATION) => "INFO",
-                        Some(tower_lsp::lsp_types::DiagnosticSeverity::HINT) => "HINT",
-                        _ => "UNKNOWN",
-                    };
+                    let sev = severity_label(d.severity);
                     // Print range start line/char and message
                     let range = d.range;
                     println!(
@@ -980,7 +2902,7 @@ async fn main() {
             }
             println!("");
         }
-        return;
+        std::process::exit(i32::from(failed));
     }
 
     let mut parser = Parser::new();
@@ -988,16 +2910,9 @@ async fn main() {
         .set_language(tree_sitter_ic10::language())
         .expect("Failed to set language");
 
-    let (service, socket) = LspService::new(|client| Backend {
-        client,
-        files: Arc::new(RwLock::new(HashMap::new())),
-        config: Arc::new(RwLock::new(Configuration::default())),
-        diagnostics_enabled: Arc::new(RwLock::new(true)),
-        warned_about_file_count: Arc::new(tokio::sync::Mutex::new(false)),
-        perf_tracker: Arc::new(performance::PerformanceTracker::new()),
-        pending_diagnostics: Arc::new(tokio::sync::Mutex::new(HashMap::new())),
-        diagnostic_cache: Arc::new(dashmap::DashMap::new()),
-    });
+    let (service, socket) = LspService::build(Backend::new)
+        .custom_method("ic10/reloadDeviceHashes", Backend::reload_device_hashes)
+        .finish();
 
     if !cli.listen && cli.host.is_none() {
         // stdin/stdout
@@ -0,0 +1,132 @@
+//! SARIF 2.1.0 export for CLI diagnostics
+//!
+//! Produces a minimal SARIF log so results can be uploaded to code-scanning dashboards
+//! (e.g. GitHub code scanning). One run, one result per diagnostic.
+
+use serde_json::{json, Value};
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, NumberOrString};
+
+/// Rule id used for diagnostics that carry no code (syntax errors, type errors, ...)
+const DEFAULT_RULE_ID: &str = "ic10";
+
+fn rule_id(diagnostic: &Diagnostic) -> String {
+    match &diagnostic.code {
+        Some(NumberOrString::String(code)) => code.clone(),
+        Some(NumberOrString::Number(code)) => code.to_string(),
+        None => DEFAULT_RULE_ID.to_string(),
+    }
+}
+
+fn level(severity: Option<DiagnosticSeverity>) -> &'static str {
+    match severity {
+        Some(DiagnosticSeverity::ERROR) => "error",
+        Some(DiagnosticSeverity::WARNING) => "warning",
+        _ => "note",
+    }
+}
+
+/// Build a SARIF log from `(artifact path, diagnostics)` pairs. Files that could not be read
+/// are `(artifact path, reason)` pairs in `unreadable`; they become error notifications on a
+/// failed invocation. LSP positions are 0-based; SARIF lines and columns are 1-based.
+pub(crate) fn to_sarif(files: &[(String, Vec<Diagnostic>)], unreadable: &[(String, String)]) -> Value {
+    let mut rules: Vec<String> = Vec::new();
+    let mut results = Vec::new();
+
+    for (path, diagnostics) in files {
+        let uri = path.replace('\\', "/");
+        for diagnostic in diagnostics {
+            let id = rule_id(diagnostic);
+            if !rules.contains(&id) {
+                rules.push(id.clone());
+            }
+            let range = diagnostic.range;
+            results.push(json!({
+                "ruleId": id,
+                "level": level(diagnostic.severity),
+                "message": { "text": diagnostic.message },
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": uri },
+                        "region": {
+                            "startLine": range.start.line + 1,
+                            "startColumn": range.start.character + 1,
+                            "endLine": range.end.line + 1,
+                            "endColumn": range.end.character + 1,
+                        }
+                    }
+                }]
+            }));
+        }
+    }
+
+    let notifications: Vec<Value> = unreadable
+        .iter()
+        .map(|(path, reason)| {
+            json!({
+                "level": "error",
+                "message": { "text": reason },
+                "locations": [{
+                    "physicalLocation": { "artifactLocation": { "uri": path.replace('\\', "/") } }
+                }]
+            })
+        })
+        .collect();
+
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "ic10lsp",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules.iter().map(|id| json!({ "id": id })).collect::<Vec<_>>(),
+                }
+            },
+            "invocations": [{
+                "executionSuccessful": unreadable.is_empty(),
+                "toolExecutionNotifications": notifications,
+            }],
+            "results": results,
+        }]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tower_lsp::lsp_types::{Position, Range};
+
+    #[test]
+    fn maps_code_severity_and_region() {
+        let diagnostic = Diagnostic {
+            range: Range::new(Position::new(2, 4), Position::new(2, 9)),
+            severity: Some(DiagnosticSeverity::HINT),
+            code: Some(NumberOrString::String("dead-device-read".to_string())),
+            message: "unused".to_string(),
+            ..Default::default()
+        };
+        let log = to_sarif(&[("scripts\\airlock.ic10".to_string(), vec![diagnostic])], &[]);
+        let result = &log["runs"][0]["results"][0];
+        assert_eq!(log["version"], "2.1.0");
+        assert_eq!(result["ruleId"], "dead-device-read");
+        assert_eq!(result["level"], "note");
+        let location = &result["locations"][0]["physicalLocation"];
+        assert_eq!(location["artifactLocation"]["uri"], "scripts/airlock.ic10");
+        assert_eq!(location["region"]["startLine"], 3);
+        assert_eq!(location["region"]["startColumn"], 5);
+        assert_eq!(log["runs"][0]["tool"]["driver"]["rules"][0]["id"], "dead-device-read");
+        assert_eq!(log["runs"][0]["invocations"][0]["executionSuccessful"], true);
+    }
+
+    #[test]
+    fn unreadable_files_fail_the_invocation() {
+        let unreadable = [("missing.ic10".to_string(), "Could not read missing.ic10".to_string())];
+        let log = to_sarif(&[], &unreadable);
+        let invocation = &log["runs"][0]["invocations"][0];
+        assert_eq!(invocation["executionSuccessful"], false);
+        let notification = &invocation["toolExecutionNotifications"][0];
+        assert_eq!(notification["level"], "error");
+        assert_eq!(notification["locations"][0]["physicalLocation"]["artifactLocation"]["uri"], "missing.ic10");
+    }
+}
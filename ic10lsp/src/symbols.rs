@@ -0,0 +1,71 @@
+//! Machine-readable symbol table for the CLI
+//!
+//! Lists every define, alias and label of a script with its value and source range, so
+//! build tools can index IC10 projects without an LSP client.
+
+use serde_json::{json, Value};
+use tower_lsp::lsp_types::Range as LspRange;
+
+use crate::document::TypeData;
+
+fn symbol(name: &str, kind: &str, value: String, resolved: Value, range: LspRange) -> Value {
+    json!({
+        "name": name,
+        "kind": kind,
+        "value": value,
+        "resolved": resolved,
+        "range": range,
+    })
+}
+
+/// One entry per definition, in source order. `resolved` is the numeric value of a define
+/// (hashes included), the register or pin of an alias, and the line index a label jumps to;
+/// `null` when it cannot be known before the script runs.
+pub(crate) fn symbol_table(type_data: &TypeData) -> Value {
+    let mut symbols = Vec::new();
+    for (name, define) in &type_data.defines {
+        let resolved = define
+            .value
+            .resolved_value()
+            .or_else(|| define.value.resolved_numeric().map(f64::from))
+            .map_or(Value::Null, Value::from);
+        symbols.push(symbol(name, "define", define.value.to_string(), resolved, define.range.0));
+    }
+    for (name, alias) in &type_data.aliases {
+        let target = alias.value.to_string();
+        symbols.push(symbol(name, "alias", target.clone(), Value::from(target), alias.range.0));
+    }
+    for (name, label) in &type_data.labels {
+        let line = label.range.0.start.line;
+        symbols.push(symbol(name, "label", line.to_string(), Value::from(line), label.range.0));
+    }
+    symbols.sort_by_key(|symbol| {
+        let start = &symbol["range"]["start"];
+        (start["line"].as_u64(), start["character"].as_u64())
+    });
+    Value::Array(symbols)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_definitions_in_source_order() {
+        let content = "define Max $10\nalias pump d0\nstart:\ndefine Pump HASH(\"StructureVolumePump\")\nj start\n";
+        let mut parser = tree_sitter::Parser::new();
+        parser.set_language(tree_sitter_ic10::language()).unwrap();
+        let tree = parser.parse(content, None).unwrap();
+        let type_data = crate::lsp_diagnostics::collect_type_data(&tree, content, &mut Vec::new());
+        let table = symbol_table(&type_data);
+        let names: Vec<_> = table.as_array().unwrap().iter().map(|s| s["name"].clone()).collect();
+        assert_eq!(names, vec!["Max", "pump", "start", "Pump"]);
+        assert_eq!(table[0]["value"], "$10");
+        assert_eq!(table[0]["resolved"], 16.0);
+        assert_eq!(table[1]["resolved"], "d0");
+        assert_eq!(table[2]["kind"], "label");
+        assert_eq!(table[2]["resolved"], 2);
+        assert_eq!(table[3]["resolved"], -321403609.0);
+        assert_eq!(table[3]["range"]["start"]["line"], 3);
+    }
+}
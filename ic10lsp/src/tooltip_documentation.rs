@@ -1,4 +1,5 @@
 use phf::phf_map;
+use std::collections::HashMap;
 
 /// Enhanced documentation for instruction hover tooltips
 /// This module provides examples, categories, and related instruction mappings
@@ -43,6 +44,7 @@ pub(crate) const INSTRUCTION_EXAMPLES: phf::Map<&'static str, &'static str> = ph
     "sleep" => "sleep 1           # Wait 1 second between readings\nsleep 3           # Wait before checking vents again",
     "yield" => "yield             # Allow other scripts to run\nyield             # Pause execution for 1 tick",
     "sqrt" => "sqrt r0 r1        # Simple: r0 = square root of r1\nsqrt r2 r3         # Calculate square root\nsqrt distance r1  # distance = sqrt(r1)",
+    "rand" => "rand r0           # Simple: r0 = random value in [0, 1)\nrand r1           # Fresh roll each tick\nrand chance       # chance = random value in [0, 1)",
     "abs" => "abs r0 r1         # Simple: r0 = absolute value of r1\nabs r2 temperature # Get absolute temperature\nabs magnitude velocity",
     "sin" => "sin r0 r1         # Simple: r0 = sine of r1 (radians)\nsin r2 angle       # Calculate sine\nsin y_pos angle",
     "cos" => "cos r0 r1         # Simple: r0 = cosine of r1 (radians)\ncos r2 angle       # Calculate cosine\ncos x_pos angle",
@@ -168,11 +170,98 @@ pub(crate) const RELATED_INSTRUCTIONS: phf::Map<&'static str, &'static [&'static
     "bdnvs" => &["bdse", "bdns", "brdse", "brdns", "s"],
 };
 
+/// Extra hover notes for assembler directives, which are resolved before the program runs
+/// and take no instruction time of their own
+pub(crate) const DIRECTIVE_NOTES: phf::Map<&'static str, &'static str> = phf_map! {
+    "define" => "**Assembler directive.** `define NAME value` introduces a named constant. Every use of `NAME` is replaced by `value`; no register is used. The value can be a number or `HASH(\"...\")`.",
+    "alias" => "**Assembler directive.** `alias NAME r?|d?` gives a register or device pin a readable name. Device aliases also label the screw on the IC housing. An alias can be re-pointed later in the script, and later lines see the new target.",
+    "label" => "**Deprecated directive.** `label NAME r?|d?` is an old spelling of `alias`; use `alias` instead. Jump targets are declared with `name:` on a line of their own, not with `label`.",
+};
+
+/// Hover notes for instructions whose timing is commonly misread
+pub(crate) const TIMING_NOTES: phf::Map<&'static str, &'static str> = phf_map! {
+    "sleep" => "**Duration in seconds.** `sleep 2` waits two real seconds (about four game ticks), not two ticks or lines. The operand can be a number or a register. The chip blocks for the whole time, so it reads, writes and branches nothing until it wakes; use `yield` to wait a single tick.",
+};
+
+/// Bit diagrams for the bit field instructions, shown in their hover
+pub(crate) const BIT_FIELD_DIAGRAMS: phf::Map<&'static str, &'static str> = phf_map! {
+    "ext" => "ext r0 r1 8 4\n\nr1:  ... b12 [b11 b10 b9 b8] b7 ... b0\n              |  width 4  | offset 8\nr0:  0 ... 0  b11 b10 b9 b8",
+    "ins" => "ins r0 r1 8 4\n\nr1:  ... b4 [b3 b2 b1 b0]\n            |  width 4  |\nr0:  ... b12 [b3 b2 b1 b0] b7 ... b0\n                          offset 8, other bits kept",
+};
+
+/// Docs loaded from `customDocsPath`, consulted before the built-in tables
+#[derive(Debug, Default)]
+pub(crate) struct CustomDocs {
+    instructions: HashMap<String, String>,
+    logic_types: HashMap<String, String>,
+}
+
+impl CustomDocs {
+    /// Load `{"instructions": {name: markdown}, "logicTypes": {name: markdown}}` from `path`
+    pub(crate) fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+        let json: serde_json::Value =
+            serde_json::from_str(&text).map_err(|e| format!("invalid JSON in {}: {}", path, e))?;
+        let section = |key: &str| -> Result<HashMap<String, String>, String> {
+            let Some(entries) = json.get(key) else {
+                return Ok(HashMap::new());
+            };
+            let entries = entries
+                .as_object()
+                .ok_or_else(|| format!("'{}' in {} must be an object", key, path))?;
+            entries
+                .iter()
+                .map(|(name, doc)| {
+                    let doc = doc
+                        .as_str()
+                        .ok_or_else(|| format!("'{}.{}' in {} must be a string", key, name, path))?;
+                    Ok((name.clone(), doc.to_string()))
+                })
+                .collect()
+        };
+        Ok(CustomDocs {
+            instructions: section("instructions")?,
+            logic_types: section("logicTypes")?,
+        })
+    }
+
+    /// Number of entries across both sections
+    pub(crate) fn len(&self) -> usize {
+        self.instructions.len() + self.logic_types.len()
+    }
+
+    pub(crate) fn instruction_doc<'a>(&'a self, instruction: &str) -> Option<&'a str> {
+        self.instructions
+            .get(instruction)
+            .map(String::as_str)
+            .or_else(|| crate::instructions::INSTRUCTION_DOCS.get(instruction).copied())
+    }
+
+    pub(crate) fn logic_type_doc<'a>(&'a self, logic_type: &str) -> Option<&'a str> {
+        self.logic_types
+            .get(logic_type)
+            .map(String::as_str)
+            .or_else(|| crate::instructions::LOGIC_TYPE_DOCS.get(logic_type).copied())
+    }
+}
+
 /// Helper functions for enhanced hover documentation
 pub(crate) fn get_instruction_examples(instruction: &str) -> Option<&'static str> {
     INSTRUCTION_EXAMPLES.get(instruction).copied()
 }
 
+pub(crate) fn get_directive_note(instruction: &str) -> Option<&'static str> {
+    DIRECTIVE_NOTES.get(instruction).copied()
+}
+
+pub(crate) fn get_timing_note(instruction: &str) -> Option<&'static str> {
+    TIMING_NOTES.get(instruction).copied()
+}
+
+pub(crate) fn get_bit_field_diagram(instruction: &str) -> Option<&'static str> {
+    BIT_FIELD_DIAGRAMS.get(instruction).copied()
+}
+
 pub(crate) fn get_instruction_category(instruction: &str) -> Option<&'static str> {
     INSTRUCTION_CATEGORIES.get(instruction).copied()
 }
@@ -520,9 +609,22 @@ pub(crate) fn get_instruction_syntax(instruction: &str) -> String {
     }
 }
 
+/// Role name of one operand slot (e.g. "logicType", "device"), taken from the syntax line
+pub(crate) fn get_parameter_name(instruction: &str, index: usize) -> Option<String> {
+    let syntax = get_instruction_syntax(instruction);
+    let token = syntax.split_whitespace().nth(index + 1)?;
+    let name = token.split('(').next().unwrap_or(token);
+    if name.is_empty() || name.contains('?') {
+        // Bare register tokens like "r?" are unlabelled destinations
+        return Some("dest".to_string());
+    }
+    Some(name.to_string())
+}
+
 /// Create enhanced hover content for instructions with examples, syntax, and related commands
 pub(crate) fn create_enhanced_instruction_hover(
     instruction: &str,
+    custom_docs: &CustomDocs,
 ) -> Vec<tower_lsp::lsp_types::MarkedString> {
     use tower_lsp::lsp_types::{LanguageString, MarkedString};
 
@@ -539,7 +641,7 @@ pub(crate) fn create_enhanced_instruction_hover(
     let mut markdown_parts = Vec::new();
 
     // Add instruction title and description
-    if let Some(doc) = crate::instructions::INSTRUCTION_DOCS.get(instruction) {
+    if let Some(doc) = custom_docs.instruction_doc(instruction) {
         markdown_parts.push(format!("**{}**\n\n{}", instruction, doc));
     } else {
         markdown_parts.push(format!("**{}**", instruction));
@@ -549,6 +651,18 @@ pub(crate) fn create_enhanced_instruction_hover(
     let initial_content = markdown_parts.join("\n\n");
     hover_content.push(MarkedString::String(initial_content));
 
+    if let Some(note) = get_directive_note(instruction) {
+        hover_content.push(MarkedString::String(note.to_string()));
+    }
+
+    if let Some(note) = get_timing_note(instruction) {
+        hover_content.push(MarkedString::String(note.to_string()));
+    }
+
+    if let Some(diagram) = get_bit_field_diagram(instruction) {
+        hover_content.push(MarkedString::String(format!("```\n{}\n```", diagram)));
+    }
+
     // Add examples immediately after description (restore original order)
     if let Some(examples) = get_instruction_examples(instruction) {
         hover_content.push(MarkedString::String("**Examples:**".to_string()));
@@ -622,11 +736,12 @@ pub(crate) fn create_enhanced_instruction_hover_with_history(
     instruction_node: tree_sitter::Node,
     content: &str,
     register_analyzer: &crate::additional_features::RegisterAnalyzer,
+    custom_docs: &CustomDocs,
 ) -> Vec<tower_lsp::lsp_types::MarkedString> {
     use tower_lsp::lsp_types::MarkedString;
 
     // Start with the base instruction hover content
-    let mut hover_content = create_enhanced_instruction_hover(instruction);
+    let mut hover_content = create_enhanced_instruction_hover(instruction, custom_docs);
 
     // Try to find registers in this instruction and add their operation history
     let mut register_histories = Vec::new();
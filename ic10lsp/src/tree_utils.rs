@@ -32,6 +32,96 @@ pub fn warmup_queries() {
     let _ = cached_query_instruction();
 }
 
+/// A label named like a mnemonic (`add:`) parses as a bare instruction followed by an
+/// `ERROR` node holding the colon. Returns the operation node (the label name) for that shape.
+pub fn mnemonic_label<'a>(instruction: Node<'a>, content: &[u8]) -> Option<Node<'a>> {
+    if instruction.kind() != "instruction" || instruction.named_child_count() != 1 {
+        return None;
+    }
+    let colon = instruction.next_sibling()?;
+    if colon.kind() != "ERROR"
+        || colon.start_byte() != instruction.end_byte()
+        || colon.utf8_text(content).ok()? != ":"
+    {
+        return None;
+    }
+    instruction.child_by_field_name("operation")
+}
+
+/// Whether an `ERROR` node is just the colon of a mnemonic-named label (see [`mnemonic_label`])
+pub fn is_mnemonic_label_colon(error: Node, content: &[u8]) -> bool {
+    error
+        .prev_sibling()
+        .is_some_and(|instruction| mnemonic_label(instruction, content).is_some())
+}
+
+/// All mnemonic-named labels in the tree, as their name nodes
+pub fn mnemonic_labels<'a>(root: Node<'a>, content: &[u8]) -> Vec<Node<'a>> {
+    let query = Query::new(tree_sitter_ic10::language(), "(instruction)@x").unwrap();
+    let mut cursor = QueryCursor::new();
+    cursor
+        .captures(&query, root, content)
+        .filter_map(|(capture, _)| mnemonic_label(capture.captures[0].node, content))
+        .collect()
+}
+
+/// `# region` / `# endregion` comments in a script, paired innermost first. Folding and the
+/// unbalanced-region lint both read this, so they agree on what counts as a marker.
+pub struct RegionMarkers<'a> {
+    /// Matched (opener, closer) pairs
+    pub regions: Vec<(Node<'a>, Node<'a>)>,
+    /// `# endregion` comments with no open region
+    pub unmatched_ends: Vec<Node<'a>>,
+    /// `# region` comments still open at the end of the file
+    pub unclosed: Vec<Node<'a>>,
+}
+
+/// A region marker is a comment alone on its line whose text after `#` starts with the
+/// lowercase keyword `region` or `endregion` as a whole word, the same rule as the folding
+/// markers in the extension's language configuration. Returns `true` for an opener.
+fn region_marker(comment: Node, content: &str) -> Option<bool> {
+    let line = content.lines().nth(comment.start_position().row)?;
+    if !line[..comment.start_position().column].trim().is_empty() {
+        return None;
+    }
+    let text = comment.utf8_text(content.as_bytes()).ok()?;
+    let text = text.strip_prefix('#')?.trim_start();
+    let is_keyword = |keyword: &str| {
+        text.strip_prefix(keyword)
+            .is_some_and(|rest| !rest.starts_with(|c: char| c.is_alphanumeric() || c == '_'))
+    };
+    if is_keyword("region") {
+        Some(true)
+    } else if is_keyword("endregion") {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// Scan every comment under `root` for region markers and pair them up
+pub fn region_markers<'a>(root: Node<'a>, content: &str) -> RegionMarkers<'a> {
+    let query = Query::new(tree_sitter_ic10::language(), "(comment)@x").unwrap();
+    let mut cursor = QueryCursor::new();
+    let mut markers = RegionMarkers {
+        regions: Vec::new(),
+        unmatched_ends: Vec::new(),
+        unclosed: Vec::new(),
+    };
+    for (capture, _) in cursor.captures(&query, root, content.as_bytes()) {
+        let node = capture.captures[0].node;
+        match region_marker(node, content) {
+            Some(true) => markers.unclosed.push(node),
+            Some(false) => match markers.unclosed.pop() {
+                Some(opener) => markers.regions.push((opener, node)),
+                None => markers.unmatched_ends.push(node),
+            },
+            None => {}
+        }
+    }
+    markers
+}
+
 /// Extension trait for tree-sitter Node providing convenience methods
 pub trait NodeEx: Sized {
     /// Find the nearest parent node of a given kind
@@ -117,6 +207,12 @@ pub fn get_current_parameter<'a>(
         .children_by_field_name("operand", &mut cursor)
         .enumerate()
     {
+        // Stop at the operand the cursor is inside, or the first one after it; only operands
+        // fully before the cursor count
+        if operand.end_byte() > cursor_byte {
+            break;
+        }
+
         // Skip empty operands (whitespace-only nodes that tree-sitter creates)
         let operand_text = operand.utf8_text(content).unwrap_or("");
         let is_empty = operand_text.trim().is_empty();
@@ -125,12 +221,6 @@ pub fn get_current_parameter<'a>(
         if !is_empty {
             ret += 1;
         }
-
-        // Break if this operand extends past cursor position
-        // This means we're either inside it or haven't typed the next parameter yet
-        if operand.end_byte() > cursor_byte {
-            break;
-        }
     }
 
     let operand = instruction_node
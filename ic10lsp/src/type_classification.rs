@@ -39,6 +39,27 @@ pub fn classify_exact_keyword(ident: &str) -> KeywordFlags {
     )
 }
 
+/// What `name` already means to the game when used as a define, alias or label name: a
+/// register (`r0`, `rr1`, `sp`), a device pin (`d0`, `db`, `dr1`), an instruction mnemonic or
+/// a logic type. Uses of the symbol would read as that token instead.
+pub fn reserved_name_kind(name: &str) -> Option<&'static str> {
+    let register_index = |rest: &str| (0..=15).any(|n| rest == n.to_string());
+    let indirect = |rest: &str| rest.starts_with('r') && register_index(rest.trim_start_matches('r'));
+    if matches!(name, "ra" | "sp") || name.strip_prefix('r').is_some_and(|rest| register_index(rest) || indirect(rest)) {
+        return Some("register");
+    }
+    if name.strip_prefix('d').is_some_and(|rest| matches!(rest, "b" | "0" | "1" | "2" | "3" | "4" | "5") || indirect(rest)) {
+        return Some("device pin");
+    }
+    if instructions::INSTRUCTIONS.contains_key(name) {
+        return Some("instruction");
+    }
+    if instructions::LOGIC_TYPES.contains(name) || instructions::SLOT_LOGIC_TYPES.contains(name) {
+        return Some("logic type");
+    }
+    None
+}
+
 /// Classify an identifier with case-insensitive matching
 pub fn classify_ci_keyword(ident: &str) -> KeywordFlags {
     KeywordFlags::from_bools(